@@ -0,0 +1,139 @@
+//! Derive macro for the [`uapi-config`](https://docs.rs/uapi-config) crate. Use it through the `derive` feature of that crate
+//! rather than depending on this crate directly.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+/// Generates an implementation of `uapi_config::MergeFrom` that parses each file and overrides the fields that are set in it,
+/// and `load()` and `load_from()` functions that search for the files and merge them into `Self::default()`.
+///
+/// See `uapi_config::UapiConfig` for the attributes.
+#[proc_macro_derive(UapiConfig, attributes(uapi_config))]
+pub fn derive_uapi_config(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let input = syn::parse_macro_input!(input as syn::DeriveInput);
+	match expand(&input) {
+		Ok(output) => output.into(),
+		Err(err) => err.into_compile_error().into(),
+	}
+}
+
+struct Attributes {
+	project: syn::LitStr,
+	suffix: syn::LitStr,
+	format: Format,
+}
+
+enum Format {
+	Json,
+	Toml,
+}
+
+fn expand(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+	let Attributes { project, suffix, format } = parse_attributes(input)?;
+
+	let syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) = &input.data else {
+		return Err(syn::Error::new_spanned(input, "UapiConfig can only be derived for structs with named fields"));
+	};
+
+	let mut field_merges = vec![];
+	for field in &fields.named {
+		let ident = field.ident.as_ref().expect("named fields have identifiers");
+		let mut key = syn::LitStr::new(&ident.to_string(), ident.span());
+		for attr in &field.attrs {
+			if !attr.path().is_ident("uapi_config") {
+				continue;
+			}
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("rename") {
+					key = meta.value()?.parse()?;
+					Ok(())
+				}
+				else {
+					Err(meta.error("unsupported field attribute, expected `rename`"))
+				}
+			})?;
+		}
+
+		field_merges.push(quote! {
+			if let ::core::option::Option::Some(value) = table.take(#key)? {
+				self.#ident = value;
+			}
+		});
+	}
+
+	let parse = match format {
+		Format::Json => quote! { ::uapi_config::__private::Table::json(reader)? },
+		Format::Toml => quote! { ::uapi_config::__private::Table::toml(reader)? },
+	};
+
+	let ident = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	Ok(quote! {
+		impl #impl_generics ::uapi_config::MergeFrom for #ident #ty_generics #where_clause {
+			type Error = ::uapi_config::FormatError;
+
+			fn merge_from<R>(&mut self, _path: &::std::path::Path, reader: R) -> ::core::result::Result<(), Self::Error> where R: ::std::io::Read {
+				let mut table = #parse;
+				#(#field_merges)*
+				table.finish()
+			}
+		}
+
+		impl #impl_generics #ident #ty_generics #where_clause {
+			/// Searches for the config files of the project in the modern system search directories
+			/// and merges them into the default value.
+			pub fn load() -> ::std::io::Result<Self> where Self: ::core::default::Default {
+				Self::load_from(::uapi_config::SearchDirectories::modern_system())
+			}
+
+			/// Searches for the config files of the project in the given search directories
+			/// and merges them into the default value.
+			pub fn load_from(search_directories: ::uapi_config::SearchDirectories<'_>) -> ::std::io::Result<Self> where Self: ::core::default::Default {
+				::uapi_config::__private::load(search_directories, #project, #suffix)
+			}
+		}
+	})
+}
+
+fn parse_attributes(input: &syn::DeriveInput) -> syn::Result<Attributes> {
+	let mut project = None;
+	let mut suffix = None;
+	let mut format = None;
+
+	for attr in &input.attrs {
+		if !attr.path().is_ident("uapi_config") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("project") {
+				project = Some(meta.value()?.parse()?);
+			}
+			else if meta.path.is_ident("suffix") {
+				suffix = Some(meta.value()?.parse()?);
+			}
+			else if meta.path.is_ident("format") {
+				let value: syn::LitStr = meta.value()?.parse()?;
+				format = Some(match &*value.value() {
+					"json" => Format::Json,
+					"toml" => Format::Toml,
+					_ => return Err(syn::Error::new_spanned(value, r#"unsupported format, expected "json" or "toml""#)),
+				});
+			}
+			else {
+				return Err(meta.error("unsupported attribute, expected `project`, `suffix` or `format`"));
+			}
+			Ok(())
+		})?;
+	}
+
+	let Some(project) = project else {
+		return Err(syn::Error::new(Span::call_site(), r#"missing `#[uapi_config(project = "...")]` attribute"#));
+	};
+	let suffix = suffix.unwrap_or_else(|| syn::LitStr::new(".conf", Span::call_site()));
+	let Some(format) = format else {
+		return Err(syn::Error::new(Span::call_site(), r#"missing `#[uapi_config(format = "...")]` attribute"#));
+	};
+
+	Ok(Attributes { project, suffix, format })
+}
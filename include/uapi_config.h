@@ -0,0 +1,89 @@
+#ifndef UAPI_CONFIG_H
+#define UAPI_CONFIG_H
+
+/* This file is generated by `make capi-header`. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The paths of the files found by `uapi_config_find`, in lowest-precedence-first order. Free it with `uapi_config_paths_free`.
+typedef struct uapi_config_paths uapi_config_paths;
+
+// A list of search directories. Free it with `uapi_config_search_directories_free`.
+typedef struct uapi_config_search_directories uapi_config_search_directories;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns a new empty list of search directories.
+uapi_config_search_directories *uapi_config_search_directories_new(void);
+
+// Returns a new list of the default search directories for a system application on a classic Linux distribution,
+// ie `/usr/lib`, `/var/run`, `/etc`.
+uapi_config_search_directories *uapi_config_search_directories_classic_system(void);
+
+// Returns a new list of the default search directories for a system application on a modern Linux distribution,
+// ie `/usr/etc`, `/run`, `/etc`.
+uapi_config_search_directories *uapi_config_search_directories_modern_system(void);
+
+// Appends `path` to the list of search directories. Files found in this directory will override files found in earlier directories.
+//
+// Returns `-EINVAL` if `path` does not start with `/` or if it contains `..`.
+//
+// # Safety
+//
+// `search_directories` must have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+// `path` must be a valid NUL-terminated string.
+int uapi_config_search_directories_push(uapi_config_search_directories *search_directories,
+                                        const char *path);
+
+// Frees a list of search directories. Does nothing if `search_directories` is `NULL`.
+//
+// # Safety
+//
+// `search_directories` must be `NULL` or have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+void uapi_config_search_directories_free(uapi_config_search_directories *search_directories);
+
+// Searches for config files under the search directories, and sets `*paths` to the paths of the files that were found.
+//
+// `project`, `file_name` and `dropin_suffix` may each be `NULL`. The search is performed like the `find_files` methods
+// of the Rust API, depending on which of `project` and `file_name` are set. If only `project` is set, `dropin_suffix` must be set too.
+//
+// Returns `-EINVAL` if neither `project` nor `file_name` are set, or if only `project` is set but `dropin_suffix` is not.
+// Returns a negated `errno` value if the search fails, or `-EIO` if the error does not correspond to an `errno` value.
+//
+// # Safety
+//
+// `search_directories` must have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+// `project`, `file_name` and `dropin_suffix` must each be `NULL` or a valid NUL-terminated string. `paths` must be valid for writes.
+int uapi_config_find(const uapi_config_search_directories *search_directories,
+                     const char *project,
+                     const char *file_name,
+                     const char *dropin_suffix,
+                     uapi_config_paths **paths);
+
+// Returns the next path in `paths`, or `NULL` if there are no more paths.
+//
+// The returned string is owned by `paths` and remains valid until `paths` is freed.
+//
+// # Safety
+//
+// `paths` must have been returned by `uapi_config_find` and not freed yet.
+const char *uapi_config_paths_next(uapi_config_paths *paths);
+
+// Frees the paths returned by `uapi_config_find`, including the strings returned by `uapi_config_paths_next`.
+// Does nothing if `paths` is `NULL`.
+//
+// # Safety
+//
+// `paths` must be `NULL` or have been returned by `uapi_config_find` and not freed yet.
+void uapi_config_paths_free(uapi_config_paths *paths);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* UAPI_CONFIG_H */
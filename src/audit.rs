@@ -0,0 +1,117 @@
+use std::{
+	fs,
+	io,
+	os::unix::fs::MetadataExt as _,
+	path::{Path, PathBuf},
+};
+
+use crate::{Entries, Entry};
+
+/// A report of the properties of a config file that are relevant to its security, returned by [`Entry::audit`] and [`Entries::audit`].
+#[derive(Clone, Debug)]
+pub struct Audit {
+	path: PathBuf,
+	uid: u32,
+	gid: u32,
+	mode: u32,
+	writable_ancestor: Option<PathBuf>,
+	via_symlink: bool,
+}
+
+impl Audit {
+	/// The path of the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The uid of the owner of the file.
+	pub fn uid(&self) -> u32 {
+		self.uid
+	}
+
+	/// The gid of the group of the file.
+	pub fn gid(&self) -> u32 {
+		self.gid
+	}
+
+	/// The mode of the file, including the file type bits, like [`MetadataExt::mode`](std::os::unix::fs::MetadataExt::mode).
+	pub fn mode(&self) -> u32 {
+		self.mode
+	}
+
+	/// The closest ancestor directory of the file that is writable by a user other than root, if any.
+	///
+	/// A directory is considered writable by a user other than root if it is owned by a user other than root,
+	/// if it is group-writable and its group is not root, or if it is world-writable.
+	/// Such a user could replace the file, even if the file itself is only writable by root.
+	pub fn writable_ancestor(&self) -> Option<&Path> {
+		self.writable_ancestor.as_deref()
+	}
+
+	/// Whether the file is a symlink in the directory it was found in, so that the properties are those of the target of the symlink.
+	pub fn via_symlink(&self) -> bool {
+		self.via_symlink
+	}
+}
+
+impl Entry {
+	/// Returns a report of the properties of this file that are relevant to its security.
+	///
+	/// The owner and mode are those of the file as of when it was found by the search. The ancestor directories and whether the file
+	/// is a symlink are looked up when this is called.
+	///
+	/// # Errors
+	///
+	/// Returns any I/O error from looking up the file and its ancestor directories, including if the file no longer exists.
+	pub fn audit(&self) -> io::Result<Audit> {
+		let mut scratch = PathBuf::new();
+		let via_symlink = self.dir.metadata(&self.file_name, false, &mut scratch)?.is_symlink();
+
+		let mut writable_ancestor = None;
+		for ancestor in self.path.ancestors().skip(1) {
+			let metadata = match self.dir.root() {
+				Some(root) if ancestor.starts_with(root.path()) => root.metadata(ancestor, true)?,
+				_ => fs::metadata(ancestor)?,
+			};
+			if metadata.uid() != 0 || (metadata.mode() & 0o020 != 0 && metadata.gid() != 0) || metadata.mode() & 0o002 != 0 {
+				writable_ancestor = Some(ancestor.to_owned());
+				break;
+			}
+		}
+
+		Ok(Audit {
+			path: self.path.clone(),
+			uid: self.metadata.uid(),
+			gid: self.metadata.gid(),
+			mode: self.metadata.mode(),
+			writable_ancestor,
+			via_symlink,
+		})
+	}
+}
+
+impl Entries {
+	/// Returns a report of the properties of each of the remaining entries that are relevant to their security, in the same order as the entries.
+	///
+	/// # Errors
+	///
+	/// See [`Entry::audit`].
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let entries =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .find_entries(".conf")
+	///     .unwrap();
+	/// for audit in entries.audit().unwrap() {
+	///     if audit.uid() != 0 || audit.writable_ancestor().is_some() {
+	///         eprintln!("{} could be modified by a user other than root", audit.path().display());
+	///     }
+	/// }
+	/// ```
+	pub fn audit(self) -> io::Result<Vec<Audit>> {
+		self.map(|entry| entry.audit()).collect()
+	}
+}
@@ -0,0 +1,112 @@
+//! Command-line tool for inspecting config files found according to the UAPI Configuration Files Specification.
+//!
+//! ```text
+//! uapi-config diff --root / --root /mnt/new-image --project foobar [--file-name foobar.conf] [--dropin-suffix .conf] [--search-directory DIR]...
+//! ```
+//!
+//! `diff` runs the same search under both roots and prints the effective files that differ, with the paths relative to the roots.
+//! It exits with 0 if there are no differences, 1 if there are differences, and 2 on error, like `diff(1)`.
+
+use std::{
+	ffi::OsString,
+	io::Write as _,
+	path::{Path, PathBuf},
+	process::ExitCode,
+};
+
+fn main() -> ExitCode {
+	match run(std::env::args_os().skip(1)) {
+		Ok(true) => ExitCode::SUCCESS,
+		Ok(false) => ExitCode::from(1),
+		Err(err) => {
+			eprintln!("uapi-config: {err}");
+			ExitCode::from(2)
+		},
+	}
+}
+
+const USAGE: &str = "\
+usage: uapi-config diff --root ROOT --root ROOT [--project PROJECT] [--file-name FILE_NAME] [--dropin-suffix SUFFIX] [--search-directory DIR]...
+
+The search directories default to those of a modern system, ie /usr/etc, /run and /etc. The dropin suffix defaults to .conf";
+
+struct DiffArgs {
+	roots: Vec<PathBuf>,
+	project: Option<OsString>,
+	file_name: Option<OsString>,
+	dropin_suffix: uapi_config::DropinSuffix,
+	search_directories: Vec<PathBuf>,
+}
+
+/// Returns whether the command succeeded without differences.
+fn run<I>(args: I) -> Result<bool, Box<dyn std::error::Error>> where I: IntoIterator<Item = OsString> {
+	let mut args = args.into_iter();
+
+	match args.next() {
+		Some(command) if command == "diff" => (),
+		Some(command) if command == "--help" || command == "-h" => {
+			println!("{USAGE}");
+			return Ok(true);
+		},
+		_ => return Err(USAGE.into()),
+	}
+
+	let mut diff_args = DiffArgs {
+		roots: vec![],
+		project: None,
+		file_name: None,
+		dropin_suffix: uapi_config::DropinSuffix::raw(".conf"),
+		search_directories: vec![],
+	};
+
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or_else(|| format!("{} requires a value\n\n{USAGE}", arg.to_string_lossy()));
+		match arg.to_str() {
+			Some("--root") => diff_args.roots.push(value()?.into()),
+			Some("--project") => diff_args.project = Some(value()?),
+			Some("--file-name") => diff_args.file_name = Some(value()?),
+			Some("--dropin-suffix") => diff_args.dropin_suffix = uapi_config::DropinSuffix::new(value()?).map_err(|err| format!("--dropin-suffix: {err}"))?,
+			Some("--search-directory") => diff_args.search_directories.push(value()?.into()),
+			_ => return Err(format!("unexpected argument {}\n\n{USAGE}", arg.to_string_lossy()).into()),
+		}
+	}
+
+	let [old_root, new_root] = &diff_args.roots[..] else {
+		return Err(format!("exactly two roots are required\n\n{USAGE}").into());
+	};
+
+	let old = snapshot(&diff_args, old_root)?;
+	let new = snapshot(&diff_args, new_root)?;
+	let diff = old.diff(&new);
+
+	let mut stdout = std::io::stdout().lock();
+	write!(stdout, "{diff}")?;
+	stdout.flush()?;
+
+	Ok(diff.is_empty())
+}
+
+fn snapshot(args: &DiffArgs, root: &Path) -> Result<uapi_config::Snapshot, Box<dyn std::error::Error>> {
+	let search_directories =
+		if args.search_directories.is_empty() {
+			uapi_config::SearchDirectories::modern_system()
+		}
+		else {
+			let mut search_directories = uapi_config::SearchDirectories::empty();
+			for path in &args.search_directories {
+				search_directories.push(path.into()).map_err(|err| format!("{}: {err}", path.display()))?;
+			}
+			search_directories
+		};
+	let search_directories = search_directories.chroot(root).map_err(|err| format!("{}: {err}", root.display()))?;
+
+	let dropin_suffix = &args.dropin_suffix;
+	let entries = match (&args.project, &args.file_name) {
+		(Some(project), None) => search_directories.with_project(project).find_entries(dropin_suffix)?,
+		(None, Some(file_name)) => search_directories.with_file_name(file_name).find_entries(Some(dropin_suffix))?,
+		(Some(project), Some(file_name)) => search_directories.with_project(project).with_file_name(file_name).find_entries(Some(dropin_suffix))?,
+		(None, None) => return Err(format!("at least one of --project and --file-name is required\n\n{USAGE}").into()),
+	};
+
+	Ok(entries.snapshot_with_digests()?.relative_to(root))
+}
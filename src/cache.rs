@@ -0,0 +1,220 @@
+//! Caching the results of searches in a file, so that the next start of the process does not need to read the directories again.
+
+use std::{
+	ffi::OsString,
+	fs,
+	io,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use crate::{platform, Entries, SearchPlan, Snapshot};
+
+/// The version of the format of the cache file. Cache files with a different version are ignored.
+const VERSION: u32 = 1;
+
+/// A file that records the results of searches, so that a later process can reuse them instead of searching again,
+/// such as to speed up resolving the config of hundreds of services at boot.
+///
+/// Each search is recorded under a key chosen by the caller, together with the [`SearchPlan`] of the search,
+/// a [`Snapshot`] of the files that it found, and the modification times and inode numbers of the paths in the plan.
+/// [`resolve`](Self::resolve) reuses the recorded result if the plan is the same and none of these have changed,
+/// which only needs one `stat(2)` per path rather than reading every search directory and dropin directory.
+/// Otherwise it searches again and records the new result.
+///
+/// Modification times have a limited granularity, so a change to a dropin directory in the same tick as the search that was recorded
+/// may go unnoticed. The cache is therefore meant for speeding up starts of processes, not for detecting changes in a running process.
+///
+/// Requires the `cache` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let mut cache = uapi_config::SearchCache::load("/run/foobar/search-cache.json").unwrap();
+///
+/// let search = uapi_config::SearchDirectories::modern_system().with_file_name("foobar.conf");
+/// let snapshot = cache.resolve("foobar.conf", &search.clone().describe(Some(".conf")), || search.find_entries(Some(".conf"))).unwrap();
+/// for path in snapshot.paths() {
+///     // Parse the file at `path`
+/// }
+///
+/// cache.save().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SearchCache {
+	path: PathBuf,
+	searches: Vec<CachedSearch>,
+	modified: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct CacheFile<T> {
+	version: u32,
+	searches: T,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct CachedSearch {
+	key: String,
+	main_files: Vec<PathBuf>,
+	dropin_directories: Vec<PathBuf>,
+	dropin_suffix: Option<OsString>,
+	/// The stamps of the paths of [`SearchPlan::candidate_paths`], in the same order.
+	stamps: Vec<Option<Stamp>>,
+	snapshot: Snapshot,
+}
+
+/// The metadata of a path that changes when a file is added to, removed from or renamed within a directory,
+/// or when a file is replaced.
+#[derive(Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+struct Stamp {
+	is_dir: bool,
+	modified: Option<SystemTime>,
+	file_id: Option<(u64, u64)>,
+}
+
+impl SearchCache {
+	/// Loads the cache from the file at `path`. The file does not need to exist, and is not created until [`save`](Self::save) is called.
+	///
+	/// If the file cannot be parsed, such as because it was written by a different version of this crate, the cache starts out empty.
+	///
+	/// # Errors
+	///
+	/// Any I/O errors from reading the file, other than the file not existing, are propagated.
+	pub fn load<P>(path: P) -> io::Result<Self> where P: Into<PathBuf> {
+		let path = path.into();
+
+		let searches = match fs::read(&path) {
+			Ok(contents) => match serde_json::from_slice::<CacheFile<Vec<CachedSearch>>>(&contents) {
+				Ok(file) if file.version == VERSION => file.searches,
+				_ => vec![],
+			},
+			Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+			Err(err) => return Err(err),
+		};
+
+		Ok(Self {
+			path,
+			searches,
+			modified: false,
+		})
+	}
+
+	/// Returns the [`Snapshot`] of the files that the search identified by `key` found, reusing the recorded result if it is still valid.
+	///
+	/// `plan` must be the plan of the search that `search` performs, such as from the `describe` method of the same search directories.
+	/// The result is only reused if the recorded plan is the same as `plan`, so changing the search directories or the file name
+	/// invalidates it. The options of the search are not part of the plan, so searches with different [`FindOptions`](crate::FindOptions)
+	/// must use different keys.
+	///
+	/// Otherwise `search` is called to search again, and its result replaces the recorded one.
+	///
+	/// # Errors
+	///
+	/// Any errors from `search` and from looking up the files of the recorded result, other than the files not existing, are propagated.
+	pub fn resolve<F>(&mut self, key: &str, plan: &SearchPlan, search: F) -> io::Result<Snapshot>
+	where
+		F: FnOnce() -> io::Result<Entries>,
+	{
+		let index = self.searches.iter().position(|cached| cached.key == key);
+
+		if let Some(cached) = index.map(|index| &self.searches[index]) {
+			if cached.is_valid(plan)? {
+				return Ok(cached.snapshot.clone());
+			}
+		}
+
+		// Record the stamps before searching, so that a change made during the search leaves the stamps outdated
+		// and the search is repeated next time.
+		let stamps = plan.candidate_paths().map(stamp).collect::<io::Result<_>>()?;
+		let snapshot = search()?.snapshot();
+
+		let cached = CachedSearch {
+			key: key.to_owned(),
+			main_files: plan.main_files().map(ToOwned::to_owned).collect(),
+			dropin_directories: plan.dropin_directories().map(ToOwned::to_owned).collect(),
+			dropin_suffix: plan.dropin_suffix().map(ToOwned::to_owned),
+			stamps,
+			snapshot: snapshot.clone(),
+		};
+		match index {
+			Some(index) => self.searches[index] = cached,
+			None => self.searches.push(cached),
+		}
+		self.modified = true;
+
+		Ok(snapshot)
+	}
+
+	/// Removes the recorded results of all searches, so that the next [`resolve`](Self::resolve) of every key searches again.
+	pub fn clear(&mut self) {
+		if !self.searches.is_empty() {
+			self.searches.clear();
+			self.modified = true;
+		}
+	}
+
+	/// Writes the cache to its file, if any results have changed since it was loaded or last saved.
+	///
+	/// The parent directory of the file is created if it does not exist. The file is replaced atomically,
+	/// so other processes that load the cache at the same time see either the old or the new contents.
+	///
+	/// # Errors
+	///
+	/// Any I/O errors from writing the file are propagated. Returns an error of kind [`io::ErrorKind::InvalidData`]
+	/// if any of the recorded paths are not valid UTF-8, since they cannot be represented in the file.
+	pub fn save(&mut self) -> io::Result<()> {
+		if !self.modified {
+			return Ok(());
+		}
+
+		let contents =
+			serde_json::to_vec(&CacheFile { version: VERSION, searches: &self.searches })
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		let mut temp_path = self.path.clone().into_os_string();
+		temp_path.push(format!(".{}.tmp", std::process::id()));
+		fs::write(&temp_path, contents)?;
+		fs::rename(&temp_path, &self.path)?;
+
+		self.modified = false;
+		Ok(())
+	}
+}
+
+impl CachedSearch {
+	fn is_valid(&self, plan: &SearchPlan) -> io::Result<bool> {
+		if
+			!self.main_files.iter().map(PathBuf::as_path).eq(plan.main_files()) ||
+			!self.dropin_directories.iter().map(PathBuf::as_path).eq(plan.dropin_directories()) ||
+			self.dropin_suffix.as_deref() != plan.dropin_suffix()
+		{
+			return Ok(false);
+		}
+
+		for (path, recorded) in plan.candidate_paths().zip(&self.stamps) {
+			if stamp(path)? != *recorded {
+				return Ok(false);
+			}
+		}
+
+		self.snapshot.is_stale().map(|is_stale| !is_stale)
+	}
+}
+
+/// Returns the stamp of the path, or `None` if it does not exist.
+fn stamp(path: &Path) -> io::Result<Option<Stamp>> {
+	match fs::metadata(path) {
+		Ok(metadata) => Ok(Some(Stamp {
+			is_dir: metadata.is_dir(),
+			modified: metadata.modified().ok(),
+			file_id: platform::file_id(&metadata),
+		})),
+		Err(err) if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => Ok(None),
+		Err(err) => Err(err),
+	}
+}
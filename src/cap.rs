@@ -0,0 +1,269 @@
+use std::{
+	ffi::{OsStr, OsString},
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use cap_std::fs::{Dir, File, OpenOptionsExt as _};
+
+use crate::{
+	dir::{EntryKind, SearchDir},
+	file_system::SearchFileSystem,
+	find_dropins,
+	find_main_file,
+	platform,
+	EntryMetadata,
+	FindOptions,
+	SearchContext,
+};
+
+/// A list of search directories given as [`cap_std::fs::Dir`] handles, rather than as absolute paths.
+///
+/// All lookups are performed relative to the handles, so this can be used by sandboxed applications that hold
+/// pre-opened directory capabilities. It also means that files are always contained within the search directories,
+/// even if there are symlinks that point outside them.
+///
+/// The search directories are considered in the order that they appear in the list, like with [`SearchDirectories`](crate::SearchDirectories).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use cap_std::{ambient_authority, fs::Dir};
+///
+/// let usr_etc = Dir::open_ambient_dir("/usr/etc", ambient_authority()).unwrap();
+/// let etc = Dir::open_ambient_dir("/etc", ambient_authority()).unwrap();
+///
+/// let files =
+///     uapi_config::CapSearchDirectories::new([&usr_etc, &etc])
+///     .with_project("foobar")
+///     .find_files(Some(".conf"))
+///     .unwrap();
+/// for (search_directory_index, path, file) in files {
+///     // `path` is relative to the search directory at `search_directory_index`
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CapSearchDirectories<'a> {
+	inner: Vec<&'a Dir>,
+	project: Option<OsString>,
+	file_name: Option<OsString>,
+}
+
+impl<'a> CapSearchDirectories<'a> {
+	/// Start with the given search directories.
+	pub fn new<I>(search_directories: I) -> Self where I: IntoIterator<Item = &'a Dir> {
+		Self {
+			inner: search_directories.into_iter().collect(),
+			project: None,
+			file_name: None,
+		}
+	}
+
+	/// Search for configuration files for the given project name.
+	///
+	/// The project name is usually the name of your application.
+	#[must_use]
+	pub fn with_project<TProject>(mut self, project: TProject) -> Self where TProject: AsRef<OsStr> {
+		self.project = Some(project.as_ref().to_owned());
+		self
+	}
+
+	/// Search for configuration files with the given config file name.
+	#[must_use]
+	pub fn with_file_name<TFileName>(mut self, file_name: TFileName) -> Self where TFileName: AsRef<OsStr> {
+		self.file_name = Some(file_name.as_ref().to_owned());
+		self
+	}
+
+	/// Returns an [`Iterator`] of `(search_directory_index, `[`PathBuf`]`, `[`cap_std::fs::File`]`)`s for all the files found in the search directories.
+	/// The path is relative to the search directory at `search_directory_index`.
+	///
+	/// The files are located the same way as the `find_files` methods of [`SearchDirectoriesForProject`](crate::SearchDirectoriesForProject),
+	/// [`SearchDirectoriesForFileName`](crate::SearchDirectoriesForFileName) and
+	/// [`SearchDirectoriesForProjectAndFileName`](crate::SearchDirectoriesForProjectAndFileName), depending on whether
+	/// [`with_project`](Self::with_project) and [`with_file_name`](Self::with_file_name) were called. If neither was called, no files are found.
+	///
+	/// If only the project name was set, `dropin_suffix` must be provided for any files to be found.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<CapFiles>
+	where
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// The [`FindOptions::open_options`], [`FindOptions::symlink_policy`], [`FindOptions::dropin_directory_symlink_policy`],
+	/// [`FindOptions::canonicalize_paths`], [`FindOptions::dedupe_canonical_paths`] and [`FindOptions::dedupe_inodes`] options are not used,
+	/// since files are opened through `cap-std` and are always contained within the search directories.
+	/// The [`FindOptions::template_dropins`], [`FindOptions::type_dropins`], [`FindOptions::prefix_dropins`],
+	/// [`FindOptions::conditional_dropin_subdirectories`], [`FindOptions::require_main_file`]
+	/// and [`FindOptions::require_files`] options are not used either. The paths given to [`FindOptions::skip_diagnostics`]
+	/// are relative to the search directory that contains them.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<CapFiles>
+	where
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let (main_file, dropin_directory) = match (&self.project, &self.file_name) {
+			(None, None) => return Ok(CapFiles { inner: vec![].into_iter() }),
+
+			(Some(project), None) => (None, append_dropin_directory_suffix(PathBuf::new(), project)),
+
+			(None, Some(file_name)) => (Some((PathBuf::new(), &**file_name)), append_dropin_directory_suffix(PathBuf::new(), file_name)),
+
+			(Some(project), Some(file_name)) => {
+				let project = PathBuf::from(project);
+				(Some((project.clone(), &**file_name)), append_dropin_directory_suffix(project, file_name))
+			},
+		};
+
+		let file_system = CapFileSystem { search_directories: &self.inner };
+		let context = SearchContext::with_file_system(options, &file_system);
+		let dirs = |path: &Path| -> Vec<_> { (0..self.inner.len()).map(|index| Arc::new(CapDir { index, path: path.to_owned() })).collect() };
+
+		let main_file =
+			if let Some((main_file_directory, file_name)) = main_file {
+				find_main_file(&[file_name], &context, &dirs(&main_file_directory))?
+			}
+			else {
+				None
+			};
+
+		let dropins =
+			if let Some(dropin_suffix) = dropin_suffix {
+				find_dropins(dropin_suffix.as_ref(), &context, &dirs(&dropin_directory))?
+			}
+			else {
+				vec![]
+			};
+
+		let mut result = vec![];
+		for found_file in main_file.into_iter().chain(dropins) {
+			let path = found_file.dir.path.join(&found_file.file_name);
+			if let Some(file) = open_if_file(self.inner[found_file.dir.index], &path)? {
+				result.push((found_file.dir.index, path, file));
+			}
+		}
+
+		Ok(CapFiles {
+			inner: result.into_iter(),
+		})
+	}
+}
+
+fn append_dropin_directory_suffix(mut path: PathBuf, name: &OsStr) -> PathBuf {
+	let mut name = name.to_owned();
+	name.push(".d");
+	path.push(name);
+	path
+}
+
+/// Opens the file at `path` that the search found, or returns `None` if it has since been removed or replaced with a file that is not a regular file.
+fn open_if_file(search_directory: &Dir, path: &Path) -> io::Result<Option<File>> {
+	// With the `rustix` feature, open without blocking in case the file has been replaced with a FIFO since it was found.
+	let mut open_options = cap_std::fs::OpenOptions::new();
+	open_options.read(true).custom_flags(platform::NONBLOCKING_OPEN_FLAGS);
+	let file = match search_directory.open_with(path, &open_options) {
+		Ok(file) => file,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(err) => return Err(err),
+	};
+	if !file.metadata()?.is_file() {
+		return Ok(None);
+	}
+	platform::set_blocking(&file)?;
+
+	Ok(Some(file))
+}
+
+/// The search directories of a [`CapSearchDirectories`] as a filesystem for the search.
+struct CapFileSystem<'a> {
+	search_directories: &'a [&'a Dir],
+}
+
+/// A directory at `path` relative to the search directory at `index`.
+struct CapDir {
+	index: usize,
+	path: PathBuf,
+}
+
+impl SearchDir for CapDir {
+	fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl SearchFileSystem for CapFileSystem<'_> {
+	type Dir = CapDir;
+
+	type FileNames = std::iter::Map<cap_std::fs::ReadDir, fn(io::Result<cap_std::fs::DirEntry>) -> io::Result<(OsString, EntryKind)>>;
+
+	fn file_names(&self, dir: &CapDir) -> io::Result<Self::FileNames> {
+		Ok(self.search_directories[dir.index].read_dir(&dir.path)?.map(|entry| {
+			let entry = entry?;
+			let kind = match entry.file_type() {
+				Ok(file_type) if file_type.is_file() => EntryKind::File,
+				// Symlinks are followed within the search directory, since `FindOptions::symlink_policy` is not used.
+				Ok(file_type) if file_type.is_symlink() => EntryKind::Unknown,
+				Ok(_) => EntryKind::Other,
+				// The entry has been removed since the directory was read, which the lookup of its metadata reports.
+				Err(err) if err.kind() == io::ErrorKind::NotFound => EntryKind::Unknown,
+				Err(err) => return Err(err),
+			};
+			Ok((entry.file_name(), kind))
+		}))
+	}
+
+	fn metadata(&self, dir: &CapDir, file_name: &OsStr, _follow_symlinks: bool, scratch: &mut PathBuf) -> io::Result<EntryMetadata> {
+		// Symlinks are always followed, since `FindOptions::symlink_policy` is not used.
+		Ok((&self.search_directories[dir.index].metadata(dir.path_of(file_name, scratch))?).into())
+	}
+}
+
+/// The iterator of files returned by [`CapSearchDirectories::find_files`].
+///
+/// Files are yielded in lowest-precedence-first order, ie the main file (if any) followed by the dropins.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct CapFiles {
+	inner: std::vec::IntoIter<(usize, PathBuf, File)>,
+}
+
+impl Iterator for CapFiles {
+	type Item = (usize, PathBuf, File);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for CapFiles {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.inner.next_back()
+	}
+}
+
+impl ExactSizeIterator for CapFiles {}
+
+impl std::iter::FusedIterator for CapFiles {}
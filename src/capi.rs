@@ -0,0 +1,197 @@
+//! A C API for searching for config files, for use by programs that are not written in Rust.
+//!
+//! The header for this API is `include/uapi_config.h`, generated with `make capi-header`.
+//! Build the library with `cargo rustc --release --features capi --crate-type cdylib` (or `staticlib`).
+//!
+//! Functions that can fail return `0` on success and a negated `errno` value on failure, like systemd's C APIs.
+
+use std::{
+	ffi::{c_char, c_int, CStr, CString, OsStr},
+	io,
+	path::Path,
+	ptr,
+};
+
+use crate::{
+	platform::{OsStrExt as _, OsStringExt as _},
+	Entries,
+	SearchDirectories,
+};
+
+/// A list of search directories. Free it with `uapi_config_search_directories_free`.
+pub struct UapiConfigSearchDirectories(SearchDirectories<'static>);
+
+/// The paths of the files found by `uapi_config_find`, in lowest-precedence-first order. Free it with `uapi_config_paths_free`.
+pub struct UapiConfigPaths {
+	inner: Vec<CString>,
+	next: usize,
+}
+
+/// Returns a new empty list of search directories.
+#[no_mangle]
+pub extern "C" fn uapi_config_search_directories_new() -> *mut UapiConfigSearchDirectories {
+	Box::into_raw(Box::new(UapiConfigSearchDirectories(SearchDirectories::empty())))
+}
+
+/// Returns a new list of the default search directories for a system application on a classic Linux distribution,
+/// ie `/usr/lib`, `/var/run`, `/etc`.
+#[no_mangle]
+pub extern "C" fn uapi_config_search_directories_classic_system() -> *mut UapiConfigSearchDirectories {
+	Box::into_raw(Box::new(UapiConfigSearchDirectories(SearchDirectories::classic_system())))
+}
+
+/// Returns a new list of the default search directories for a system application on a modern Linux distribution,
+/// ie `/usr/etc`, `/run`, `/etc`.
+#[no_mangle]
+pub extern "C" fn uapi_config_search_directories_modern_system() -> *mut UapiConfigSearchDirectories {
+	Box::into_raw(Box::new(UapiConfigSearchDirectories(SearchDirectories::modern_system())))
+}
+
+/// Appends `path` to the list of search directories. Files found in this directory will override files found in earlier directories.
+///
+/// Returns `-EINVAL` if `path` does not start with `/` or if it contains `..`.
+///
+/// # Safety
+///
+/// `search_directories` must have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn uapi_config_search_directories_push(search_directories: *mut UapiConfigSearchDirectories, path: *const c_char) -> c_int {
+	let (Some(search_directories), Some(path)) = (unsafe { search_directories.as_mut() }, unsafe { c_path(path) }) else {
+		return -libc::EINVAL;
+	};
+
+	match search_directories.0.push(path.to_owned().into()) {
+		Ok(()) => 0,
+		Err(_) => -libc::EINVAL,
+	}
+}
+
+/// Frees a list of search directories. Does nothing if `search_directories` is `NULL`.
+///
+/// # Safety
+///
+/// `search_directories` must be `NULL` or have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn uapi_config_search_directories_free(search_directories: *mut UapiConfigSearchDirectories) {
+	if !search_directories.is_null() {
+		drop(unsafe { Box::from_raw(search_directories) });
+	}
+}
+
+/// Searches for config files under the search directories, and sets `*paths` to the paths of the files that were found.
+///
+/// `project`, `file_name` and `dropin_suffix` may each be `NULL`. The search is performed like the `find_files` methods
+/// of the Rust API, depending on which of `project` and `file_name` are set. If only `project` is set, `dropin_suffix` must be set too.
+///
+/// Returns `-EINVAL` if neither `project` nor `file_name` are set, or if only `project` is set but `dropin_suffix` is not.
+/// Returns a negated `errno` value if the search fails, or `-EIO` if the error does not correspond to an `errno` value.
+///
+/// # Safety
+///
+/// `search_directories` must have been returned by one of the `uapi_config_search_directories_*` constructors and not freed yet.
+/// `project`, `file_name` and `dropin_suffix` must each be `NULL` or a valid NUL-terminated string. `paths` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn uapi_config_find(
+	search_directories: *const UapiConfigSearchDirectories,
+	project: *const c_char,
+	file_name: *const c_char,
+	dropin_suffix: *const c_char,
+	paths: *mut *mut UapiConfigPaths,
+) -> c_int {
+	let Some(search_directories) = (unsafe { search_directories.as_ref() }) else {
+		return -libc::EINVAL;
+	};
+	if paths.is_null() {
+		return -libc::EINVAL;
+	}
+
+	let search_directories = search_directories.0.clone();
+	let project = unsafe { c_os_str(project) };
+	let file_name = unsafe { c_os_str(file_name) };
+	let dropin_suffix = unsafe { c_os_str(dropin_suffix) };
+
+	let entries = match (project, file_name) {
+		(None, None) => return -libc::EINVAL,
+		(Some(project), None) => {
+			let Some(dropin_suffix) = dropin_suffix else {
+				return -libc::EINVAL;
+			};
+			search_directories.with_project(project).find_entries(dropin_suffix)
+		},
+		(None, Some(file_name)) => search_directories.with_file_name(file_name).find_entries(dropin_suffix),
+		(Some(project), Some(file_name)) => search_directories.with_project(project).with_file_name(file_name).find_entries(dropin_suffix),
+	};
+
+	match entries {
+		Ok(entries) => {
+			let result = UapiConfigPaths {
+				inner: c_paths(entries),
+				next: 0,
+			};
+			unsafe { paths.write(Box::into_raw(Box::new(result))); }
+			0
+		},
+		Err(err) => -errno(&err),
+	}
+}
+
+/// Returns the next path in `paths`, or `NULL` if there are no more paths.
+///
+/// The returned string is owned by `paths` and remains valid until `paths` is freed.
+///
+/// # Safety
+///
+/// `paths` must have been returned by `uapi_config_find` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn uapi_config_paths_next(paths: *mut UapiConfigPaths) -> *const c_char {
+	let Some(paths) = (unsafe { paths.as_mut() }) else {
+		return ptr::null();
+	};
+
+	let Some(path) = paths.inner.get(paths.next) else {
+		return ptr::null();
+	};
+	paths.next += 1;
+	path.as_ptr()
+}
+
+/// Frees the paths returned by `uapi_config_find`, including the strings returned by `uapi_config_paths_next`.
+/// Does nothing if `paths` is `NULL`.
+///
+/// # Safety
+///
+/// `paths` must be `NULL` or have been returned by `uapi_config_find` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn uapi_config_paths_free(paths: *mut UapiConfigPaths) {
+	if !paths.is_null() {
+		drop(unsafe { Box::from_raw(paths) });
+	}
+}
+
+/// # Safety
+///
+/// `s` must be `NULL` or a valid NUL-terminated string.
+unsafe fn c_os_str<'a>(s: *const c_char) -> Option<&'a OsStr> {
+	if s.is_null() {
+		return None;
+	}
+	Some(OsStr::from_bytes(unsafe { CStr::from_ptr(s) }.to_bytes()))
+}
+
+/// # Safety
+///
+/// `s` must be `NULL` or a valid NUL-terminated string.
+unsafe fn c_path<'a>(s: *const c_char) -> Option<&'a Path> {
+	unsafe { c_os_str(s) }.map(Path::new)
+}
+
+fn c_paths(entries: Entries) -> Vec<CString> {
+	entries
+	.map(|entry| CString::new(entry.into_path().into_os_string().into_vec()).expect("paths cannot contain NUL"))
+	.collect()
+}
+
+fn errno(err: &io::Error) -> c_int {
+	err.raw_os_error().unwrap_or(libc::EIO)
+}
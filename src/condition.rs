@@ -0,0 +1,76 @@
+//! Conditions for dropin subdirectories that are only used on some machines.
+
+use std::{
+	ffi::OsStr,
+	sync::Arc,
+};
+
+/// Decides which subdirectories of dropin directories are also dropin directories, such as per-architecture subdirectories
+/// like `foo.d/x86-64/`.
+///
+/// Set with [`FindOptions::conditional_dropin_subdirectories`](crate::FindOptions::conditional_dropin_subdirectories).
+///
+/// # Examples
+///
+/// ```rust
+/// let options =
+///     uapi_config::FindOptions::new()
+///     .conditional_dropin_subdirectories(uapi_config::SubdirectoryCondition::architecture());
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &options)
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SubdirectoryCondition(Arc<dyn Fn(&OsStr) -> bool + Send + Sync>);
+
+impl SubdirectoryCondition {
+	/// Uses the subdirectories for whose names `f` returns `true`.
+	pub fn new<F>(f: F) -> Self where F: Fn(&OsStr) -> bool + Send + Sync + 'static {
+		Self(Arc::new(f))
+	}
+
+	/// Uses the subdirectory whose name is the architecture of the program, using the names of systemd's `ConditionArchitecture=`
+	/// like `x86-64`, `arm64` and `riscv64`. No subdirectories are used if the architecture is not known to systemd.
+	///
+	/// This is the architecture that the program was compiled for, which may differ from that of the kernel,
+	/// such as for a 32-bit program on a 64-bit kernel.
+	pub fn architecture() -> Self {
+		let architecture = architecture();
+		Self::new(move |name| architecture.is_some_and(|architecture| name == architecture))
+	}
+
+	pub(crate) fn matches(&self, name: &OsStr) -> bool {
+		(self.0)(name)
+	}
+}
+
+impl std::fmt::Debug for SubdirectoryCondition {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("SubdirectoryCondition").finish_non_exhaustive()
+	}
+}
+
+/// The name of the architecture of the program as used by systemd, if systemd knows it.
+fn architecture() -> Option<&'static str> {
+	let little_endian = cfg!(target_endian = "little");
+	Some(match std::env::consts::ARCH {
+		"x86_64" => "x86-64",
+		"x86" => "x86",
+		"aarch64" => if little_endian { "arm64" } else { "arm64-be" },
+		"arm" => if little_endian { "arm" } else { "arm-be" },
+		"loongarch64" => "loongarch64",
+		"m68k" => "m68k",
+		"mips" => if little_endian { "mips-le" } else { "mips" },
+		"mips64" => if little_endian { "mips64-le" } else { "mips64" },
+		"powerpc" => if little_endian { "ppc-le" } else { "ppc" },
+		"powerpc64" => if little_endian { "ppc64-le" } else { "ppc64" },
+		"riscv32" => "riscv32",
+		"riscv64" => "riscv64",
+		"s390x" => "s390x",
+		"sparc" => "sparc",
+		"sparc64" => "sparc64",
+		_ => return None,
+	})
+}
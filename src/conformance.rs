@@ -0,0 +1,132 @@
+//! Conformance tests that compare the files found by this crate with the files that systemd finds for the same directory trees.
+//!
+//! Every scenario is checked against a known-good ordering. The tests that also run `systemd-analyze cat-config` are ignored by default,
+//! since they need systemd to be installed. Run them with `cargo test -- --ignored conformance`.
+
+use std::path::{Path, PathBuf};
+
+use crate::test_support::Fixture;
+
+/// The search directories that `systemd-analyze cat-config` uses, in lowest-precedence-first order.
+const SYSTEMD_SEARCH_DIRECTORIES: &[&str] = &["/usr/lib", "/usr/local/lib", "/run", "/etc"];
+
+struct Scenario {
+	name: &'static str,
+	files: &'static [&'static str],
+	expected: &'static [&'static str],
+}
+
+const SCENARIOS: &[Scenario] = &[
+	Scenario {
+		name: "main file only in vendor directory",
+		files: &[
+			"/usr/lib/systemd/foo.conf",
+		],
+		expected: &[
+			"/usr/lib/systemd/foo.conf",
+		],
+	},
+	Scenario {
+		name: "main file overridden by sysadmin",
+		files: &[
+			"/usr/lib/systemd/foo.conf",
+			"/usr/local/lib/systemd/foo.conf",
+			"/etc/systemd/foo.conf",
+		],
+		expected: &[
+			"/etc/systemd/foo.conf",
+		],
+	},
+	Scenario {
+		name: "dropins are ordered by file name across directories",
+		files: &[
+			"/usr/lib/systemd/foo.conf",
+			"/usr/lib/systemd/foo.conf.d/50-vendor.conf",
+			"/run/systemd/foo.conf.d/10-runtime.conf",
+			"/etc/systemd/foo.conf.d/90-admin.conf",
+			"/usr/local/lib/systemd/foo.conf.d/20-local.conf",
+		],
+		expected: &[
+			"/usr/lib/systemd/foo.conf",
+			"/run/systemd/foo.conf.d/10-runtime.conf",
+			"/usr/local/lib/systemd/foo.conf.d/20-local.conf",
+			"/usr/lib/systemd/foo.conf.d/50-vendor.conf",
+			"/etc/systemd/foo.conf.d/90-admin.conf",
+		],
+	},
+	Scenario {
+		name: "dropins with the same name are overridden",
+		files: &[
+			"/usr/lib/systemd/foo.conf",
+			"/usr/lib/systemd/foo.conf.d/10-a.conf",
+			"/run/systemd/foo.conf.d/10-a.conf",
+			"/etc/systemd/foo.conf.d/10-a.conf",
+			"/usr/lib/systemd/foo.conf.d/20-b.conf",
+			"/run/systemd/foo.conf.d/20-b.conf",
+		],
+		expected: &[
+			"/usr/lib/systemd/foo.conf",
+			"/etc/systemd/foo.conf.d/10-a.conf",
+			"/run/systemd/foo.conf.d/20-b.conf",
+		],
+	},
+	Scenario {
+		name: "dropins without the suffix are ignored",
+		files: &[
+			"/usr/lib/systemd/foo.conf",
+			"/etc/systemd/foo.conf.d/10-a.conf",
+			"/etc/systemd/foo.conf.d/20-b.conf.disabled",
+			"/etc/systemd/foo.conf.d/30-c",
+		],
+		expected: &[
+			"/usr/lib/systemd/foo.conf",
+			"/etc/systemd/foo.conf.d/10-a.conf",
+		],
+	},
+	Scenario {
+		name: "dropins without a main file",
+		files: &[
+			"/usr/lib/systemd/foo.conf.d/10-a.conf",
+			"/etc/systemd/foo.conf.d/20-b.conf",
+		],
+		expected: &[
+			"/usr/lib/systemd/foo.conf.d/10-a.conf",
+			"/etc/systemd/foo.conf.d/20-b.conf",
+		],
+	},
+];
+
+fn fixture(scenario: &Scenario) -> Fixture {
+	let mut fixture = Fixture::new();
+	for path in scenario.files {
+		fixture = fixture.file(path, format!("{path}\n"));
+	}
+	fixture
+}
+
+fn find_files(fixture: &Fixture) -> Vec<PathBuf> {
+	fixture.search_directories(SYSTEMD_SEARCH_DIRECTORIES)
+	.with_project("systemd")
+	.with_file_name("foo.conf")
+	.find_files(Some(".conf"))
+	.unwrap()
+	.map(|(path, _)| fixture.relative_path(&path).into_owned())
+	.collect()
+}
+
+#[test]
+fn known_good() {
+	for scenario in SCENARIOS {
+		let fixture = fixture(scenario);
+		assert_eq!(find_files(&fixture), scenario.expected.iter().map(Path::new).collect::<Vec<_>>(), "{}", scenario.name);
+	}
+}
+
+#[test]
+#[ignore = "requires systemd-analyze"]
+fn systemd_analyze_cat_config() {
+	for scenario in SCENARIOS {
+		let fixture = fixture(scenario);
+		assert_eq!(find_files(&fixture), fixture.systemd_cat_config("systemd/foo.conf").unwrap(), "{}", scenario.name);
+	}
+}
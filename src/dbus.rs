@@ -0,0 +1,103 @@
+//! Exposing the search over D-Bus, for components that are not written in Rust.
+
+use crate::SearchDirectories;
+
+/// A D-Bus object that looks up config files in the given search directories on behalf of other processes,
+/// so that every component on the system finds the same files.
+///
+/// The object implements the `dev.arnavion.UapiConfig1` interface:
+///
+/// - `ListFiles(s project, s suffix) -> as`: The paths of the dropins that are found for `project` with the given dropin suffix,
+///   like [`SearchDirectoriesForProject::find_files`](crate::SearchDirectoriesForProject::find_files).
+///
+/// - `CatConfig(s project, s suffix) -> s`: The contents of the same files, each preceded by a `# /path/to/file` header,
+///   like [`Files::write_cat_config`](crate::Files::write_cat_config).
+///
+/// - `Changed(s project)`: A signal that the files for `project` may have changed. This crate does not watch the files,
+///   so the application emits this signal with [`ConfigService::changed`] when it detects a change,
+///   such as with [`Snapshot::is_stale`](crate::Snapshot::is_stale).
+///
+/// Paths and contents that are not valid UTF-8 cannot be sent as D-Bus strings, so the methods fail for them.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// zbus::block_on(async {
+///     let service = uapi_config::ConfigService::new(uapi_config::SearchDirectories::modern_system());
+///     let connection =
+///         zbus::connection::Builder::system().unwrap()
+///         .name("dev.arnavion.UapiConfig").unwrap()
+///         .serve_at("/dev/arnavion/UapiConfig", service).unwrap()
+///         .build().await
+///         .unwrap();
+///
+///     // Later, when the files for the project have changed...
+///
+///     let emitter = zbus::object_server::SignalEmitter::new(&connection, "/dev/arnavion/UapiConfig").unwrap();
+///     uapi_config::ConfigService::changed(&emitter, "foobar").await.unwrap();
+/// });
+/// ```
+#[derive(Debug)]
+pub struct ConfigService {
+	search_directories: SearchDirectories<'static>,
+}
+
+impl ConfigService {
+	/// Creates a service that searches `search_directories`.
+	pub fn new(search_directories: SearchDirectories<'static>) -> Self {
+		ConfigService {
+			search_directories,
+		}
+	}
+}
+
+#[zbus::interface(name = "dev.arnavion.UapiConfig1")]
+impl ConfigService {
+	/// The paths of the dropins that are found for `project` with the dropin suffix `suffix`, in order.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `project` is empty, if the search fails, or if a path is not valid UTF-8.
+	pub fn list_files(&self, project: &str, suffix: &str) -> zbus::fdo::Result<Vec<String>> {
+		let entries = self.with_project(project)?.find_entries(suffix).map_err(io_error)?;
+		entries
+			.map(|entry| entry.path().to_str().map(ToOwned::to_owned).ok_or_else(|| non_utf8(entry.path())))
+			.collect()
+	}
+
+	/// The contents of the dropins that are found for `project` with the dropin suffix `suffix`,
+	/// each preceded by a `# /path/to/file` header.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `project` is empty, if the search fails, if a file cannot be read, or if the result is not valid UTF-8.
+	pub fn cat_config(&self, project: &str, suffix: &str) -> zbus::fdo::Result<String> {
+		let files = self.with_project(project)?.find_files(suffix).map_err(io_error)?;
+		let mut result = vec![];
+		files.write_cat_config(&mut result).map_err(io_error)?;
+		String::from_utf8(result).map_err(|_| zbus::fdo::Error::Failed(format!("the files of {project} are not valid UTF-8")))
+	}
+
+	/// Emits the `Changed` signal for `project`.
+	#[zbus(signal)]
+	pub async fn changed(emitter: &zbus::object_server::SignalEmitter<'_>, project: &str) -> zbus::Result<()>;
+}
+
+impl ConfigService {
+	fn with_project<'a>(&self, project: &'a str) -> zbus::fdo::Result<crate::SearchDirectoriesForProject<'a, &'a str>> {
+		if project.is_empty() {
+			return Err(zbus::fdo::Error::InvalidArgs("project must not be empty".to_owned()));
+		}
+
+		Ok(self.search_directories.clone().with_project(project))
+	}
+}
+
+#[allow(clippy::needless_pass_by_value)] // Used with `map_err`
+fn io_error(err: std::io::Error) -> zbus::fdo::Error {
+	zbus::fdo::Error::IOError(err.to_string())
+}
+
+fn non_utf8(path: &std::path::Path) -> zbus::fdo::Error {
+	zbus::fdo::Error::Failed(format!("{} is not valid UTF-8", path.display()))
+}
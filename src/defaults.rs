@@ -0,0 +1,203 @@
+//! Built-in defaults that are merged before the files that were found.
+
+use std::{
+	borrow::Cow,
+	fs::File,
+	io::{self, Read},
+	path::PathBuf,
+};
+
+use crate::{Files, MergeError, MergeFrom};
+
+impl Files {
+	/// Returns an [`Iterator`] that yields `contents` as a file at `path` before all the files that were found,
+	/// so that the files that were found override it.
+	///
+	/// This is for programs that compile their default config in, such as with [`include_bytes!`], so that the defaults
+	/// are parsed and merged the same way as the files. `path` is only used to identify the defaults, such as in errors,
+	/// so it does not need to exist.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// use std::io::Read as _;
+	///
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	///     .with_defaults("<built-in defaults>", b"log_level=info\n".as_slice());
+	/// for (path, mut reader) in files {
+	///     let mut contents = String::new();
+	///     reader.read_to_string(&mut contents).unwrap();
+	///     // Parse `contents` and merge into the config
+	/// }
+	/// ```
+	pub fn with_defaults<P, C>(self, path: P, contents: C) -> FilesWithDefaults where P: Into<PathBuf>, C: Into<Cow<'static, [u8]>> {
+		FilesWithDefaults {
+			defaults: Some((path.into(), contents.into())),
+			files: self,
+		}
+	}
+
+	/// Same as [`with_defaults`](Self::with_defaults), but with defaults that were embedded with [`embedded_default!`](crate::embedded_default).
+	pub fn with_embedded_default(self, default: EmbeddedDefault) -> FilesWithDefaults {
+		self.with_defaults(default.path, default.contents)
+	}
+}
+
+/// Built-in defaults that were embedded into the program at compile time, created with [`embedded_default!`](crate::embedded_default).
+///
+/// The path identifies where the defaults came from, such as the path of the file in the source tree, so that
+/// [`FilesWithDefaults::write_cat_config`] and errors can show it.
+#[derive(Clone, Copy, Debug)]
+pub struct EmbeddedDefault {
+	path: &'static str,
+	contents: &'static [u8],
+}
+
+impl EmbeddedDefault {
+	/// Creates built-in defaults with the given path and contents. Use [`embedded_default!`](crate::embedded_default)
+	/// to embed the contents of a file.
+	pub const fn new(path: &'static str, contents: &'static [u8]) -> Self {
+		Self { path, contents }
+	}
+
+	/// The path that identifies where the defaults came from.
+	pub const fn path(&self) -> &'static str {
+		self.path
+	}
+
+	/// The contents of the defaults.
+	pub const fn contents(&self) -> &'static [u8] {
+		self.contents
+	}
+}
+
+/// Embeds the file at the given path as an [`EmbeddedDefault`], using [`include_bytes!`].
+///
+/// Like with [`include_bytes!`], the path is relative to the file that the macro is used in. The path is also used to identify
+/// the defaults, unless a different label is given as the first argument.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// const DEFAULTS: uapi_config::EmbeddedDefault = uapi_config::embedded_default!("/usr/share/foobar/foobar.conf", "../foobar.conf");
+///
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .find_files(Some(".conf"))
+///     .unwrap()
+///     .with_embedded_default(DEFAULTS);
+/// ```
+#[macro_export]
+macro_rules! embedded_default {
+	($path:literal) => {
+		$crate::EmbeddedDefault::new($path, ::core::include_bytes!($path))
+	};
+
+	($label:expr, $path:literal) => {
+		$crate::EmbeddedDefault::new($label, ::core::include_bytes!($path))
+	};
+}
+
+/// The iterator returned by [`Files::with_defaults`].
+///
+/// The built-in defaults are yielded first, followed by the files that were found in lowest-precedence-first order.
+#[derive(Debug)]
+pub struct FilesWithDefaults {
+	defaults: Option<(PathBuf, Cow<'static, [u8]>)>,
+	files: Files,
+}
+
+impl FilesWithDefaults {
+	/// Merges the built-in defaults and then all the files in order into a default-constructed `T`.
+	/// See [`Files::load_merged`].
+	///
+	/// # Errors
+	///
+	/// Returns `Err(MergeError)` for the first file that could not be merged, which may be the built-in defaults.
+	/// The error contains the path of that file.
+	pub fn load_merged<T>(self) -> Result<T, MergeError<T::Error>> where T: MergeFrom + Default {
+		let mut result = T::default();
+		self.load_merged_into(&mut result)?;
+		Ok(result)
+	}
+
+	/// Same as [`load_merged`](Self::load_merged), but merges into an existing value.
+	///
+	/// # Errors
+	///
+	/// See [`load_merged`](Self::load_merged).
+	pub fn load_merged_into<T>(self, value: &mut T) -> Result<(), MergeError<T::Error>> where T: MergeFrom {
+		crate::merge::merge_into(self, value)
+	}
+
+	/// Writes the contents of the built-in defaults and all the files to `writer` like [`Files::write_cat_config`].
+	/// The header of the built-in defaults labels them as such, like `# /usr/share/foobar/foobar.conf (built-in default)`.
+	///
+	/// # Errors
+	///
+	/// See [`Files::write_cat_config`].
+	pub fn write_cat_config<W>(mut self, writer: &mut W) -> io::Result<()> where W: io::Write + ?Sized {
+		let observer = self.files.observer.take();
+		crate::read::write_cat_config(self.map(|(path, file)| {
+			let annotation = matches!(file, DefaultsOrFile::Defaults(_)).then_some("built-in default");
+			(path, file, annotation)
+		}), observer.as_ref(), writer)
+	}
+}
+
+impl Iterator for FilesWithDefaults {
+	type Item = (PathBuf, DefaultsOrFile);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some((path, contents)) = self.defaults.take() {
+			return Some((path, DefaultsOrFile::Defaults(io::Cursor::new(contents))));
+		}
+
+		let (path, file) = self.files.next()?;
+		Some((path, DefaultsOrFile::File(file)))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = usize::from(self.defaults.is_some()) + self.files.len();
+		(len, Some(len))
+	}
+}
+
+impl DoubleEndedIterator for FilesWithDefaults {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		if let Some((path, file)) = self.files.next_back() {
+			return Some((path, DefaultsOrFile::File(file)));
+		}
+
+		let (path, contents) = self.defaults.take()?;
+		Some((path, DefaultsOrFile::Defaults(io::Cursor::new(contents))))
+	}
+}
+
+impl ExactSizeIterator for FilesWithDefaults {}
+
+impl std::iter::FusedIterator for FilesWithDefaults {}
+
+/// The reader of a file yielded by [`FilesWithDefaults`], which is either the built-in defaults or a file that was found.
+#[derive(Debug)]
+pub enum DefaultsOrFile {
+	/// The built-in defaults.
+	Defaults(io::Cursor<Cow<'static, [u8]>>),
+
+	/// A file that was found by the search.
+	File(File),
+}
+
+impl Read for DefaultsOrFile {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self {
+			Self::Defaults(contents) => contents.read(buf),
+			Self::File(file) => file.read(buf),
+		}
+	}
+}
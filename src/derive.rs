@@ -0,0 +1,267 @@
+//! Support for `#[derive(UapiConfig)]`, which generates the code to search for, parse and merge the config files of a project.
+
+use std::{io, ops::Range};
+
+/// Generates an implementation of [`MergeFrom`](crate::MergeFrom) that parses each file and overrides the fields of the struct
+/// that are set in it, and functions to search for the files of a project and merge them.
+///
+/// The struct must have named fields, and each field must implement `serde::Deserialize`. The generated functions are:
+///
+/// - `load() -> std::io::Result<Self>`: Searches for the files in [`SearchDirectories::modern_system`](crate::SearchDirectories::modern_system)
+///   and merges them into `Self::default()`.
+///
+/// - `load_from(search_directories: SearchDirectories<'_>) -> std::io::Result<Self>`: Same as `load()`, but searches in the given
+///   search directories, such as ones that were created with `chroot`.
+///
+/// The files are the main file `{project}{suffix}` and the dropins `{project}.d/*{suffix}`, as found by
+/// [`SearchDirectoriesForProject::with_main_file`](crate::SearchDirectoriesForProject::with_main_file).
+/// Each file is a table of settings. A setting in a file overrides the field of the same name, and fields whose settings are not in
+/// any file keep their default values. A setting that does not correspond to any field is an error, to catch typos.
+///
+/// If a file cannot be parsed, the error is of kind [`io::ErrorKind::InvalidData`] and contains a
+/// [`MergeError`](crate::MergeError)`<`[`FormatError`]`>` with the path of the file.
+///
+/// # Attributes
+///
+/// On the struct:
+///
+/// - `#[uapi_config(project = "...")]`: The name of the project. Required.
+///
+/// - `#[uapi_config(suffix = "...")]`: The suffix of the files. Defaults to `".conf"`.
+///
+/// - `#[uapi_config(format = "...")]`: The format of the files. Required. `"json"` requires the `serde_json` feature,
+///   and `"toml"` requires the `toml` feature.
+///
+/// On a field:
+///
+/// - `#[uapi_config(rename = "...")]`: The name of the setting of the field, if it differs from the name of the field.
+///
+/// Requires the `derive` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "toml")] {
+/// #[derive(Debug, Default, uapi_config::UapiConfig)]
+/// #[uapi_config(project = "foobar", format = "toml")]
+/// struct Config {
+///     log_level: Option<String>,
+///     #[uapi_config(rename = "max-connections")]
+///     max_connections: u32,
+/// }
+///
+/// let config = Config::load().unwrap();
+/// # }
+/// ```
+pub use uapi_config_derive::UapiConfig;
+
+/// Error from parsing a file with the [`MergeFrom`](crate::MergeFrom) implementation that `#[derive(UapiConfig)]` generates.
+#[derive(Debug)]
+pub struct FormatError(FormatErrorKind);
+
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "serde_json", feature = "toml")), allow(dead_code))]
+enum FormatErrorKind {
+	Read(io::Error),
+	Parse { inner: Box<dyn std::error::Error + Send + Sync>, contents: String, span: Option<Range<usize>> },
+	InvalidSetting { key: String, inner: Box<dyn std::error::Error + Send + Sync> },
+	UnknownSetting { key: String },
+}
+
+impl FormatError {
+	/// The name of the setting that has an invalid value or does not correspond to any field, if the error is about a particular setting.
+	pub fn key(&self) -> Option<&str> {
+		match &self.0 {
+			FormatErrorKind::Read(_) | FormatErrorKind::Parse { .. } => None,
+			FormatErrorKind::InvalidSetting { key, .. } | FormatErrorKind::UnknownSetting { key } => Some(key),
+		}
+	}
+
+	/// The range of bytes in the file at which it could not be parsed, if the error is a syntax error and the parser reported its location.
+	pub fn span(&self) -> Option<Range<usize>> {
+		match &self.0 {
+			FormatErrorKind::Parse { span, .. } => span.clone(),
+			_ => None,
+		}
+	}
+
+	/// The contents of the file, if the error is a syntax error, such as to show the lines around [`span`](Self::span).
+	pub fn contents(&self) -> Option<&str> {
+		match &self.0 {
+			FormatErrorKind::Parse { contents, .. } => Some(contents),
+			_ => None,
+		}
+	}
+}
+
+impl std::fmt::Display for FormatError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.0 {
+			FormatErrorKind::Read(_) => f.write_str("could not read file"),
+			FormatErrorKind::Parse { .. } => f.write_str("could not parse file"),
+			FormatErrorKind::InvalidSetting { key, .. } => write!(f, "invalid value for setting {key:?}"),
+			FormatErrorKind::UnknownSetting { key } => write!(f, "unknown setting {key:?}"),
+		}
+	}
+}
+
+impl std::error::Error for FormatError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match &self.0 {
+			FormatErrorKind::Read(err) => Some(err),
+			FormatErrorKind::Parse { inner: err, .. } | FormatErrorKind::InvalidSetting { inner: err, .. } => Some(&**err),
+			FormatErrorKind::UnknownSetting { .. } => None,
+		}
+	}
+}
+
+/// Syntax errors have the contents of the file as their source code, with a label at the location of the error.
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for FormatError {
+	fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		Some(Box::new(match &self.0 {
+			FormatErrorKind::Read(_) => "uapi_config::format::read",
+			FormatErrorKind::Parse { .. } => "uapi_config::format::parse",
+			FormatErrorKind::InvalidSetting { .. } => "uapi_config::format::invalid_setting",
+			FormatErrorKind::UnknownSetting { .. } => "uapi_config::format::unknown_setting",
+		}))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+		match &self.0 {
+			FormatErrorKind::UnknownSetting { .. } => Some(Box::new("check the spelling of the setting, or remove it")),
+			_ => None,
+		}
+	}
+
+	fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+		match &self.0 {
+			FormatErrorKind::Parse { contents, .. } => Some(contents),
+			_ => None,
+		}
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+		match &self.0 {
+			FormatErrorKind::Parse { inner, span: Some(span), .. } =>
+				Some(Box::new(std::iter::once(miette::LabeledSpan::at(span.clone(), inner.to_string())))),
+			_ => None,
+		}
+	}
+}
+
+/// Used by the code that `#[derive(UapiConfig)]` generates. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+	use std::io;
+
+	use super::FormatError;
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	use super::FormatErrorKind;
+	use crate::{MergeFrom, SearchDirectories};
+
+	/// The settings of a file that have not been merged into a field yet.
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	pub enum Table {
+		#[cfg(feature = "serde_json")]
+		Json(serde_json::Map<String, serde_json::Value>),
+
+		#[cfg(feature = "toml")]
+		Toml(toml::Table),
+	}
+
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	impl Table {
+		#[cfg(feature = "serde_json")]
+		pub fn json<R>(reader: R) -> Result<Self, FormatError> where R: io::Read {
+			let contents = read_to_string(reader)?;
+			match serde_json::from_str(&contents) {
+				Ok(table) => Ok(Self::Json(table)),
+				Err(err) => {
+					// serde_json reports the 1-based line and column of the error. Convert them to a byte offset.
+					let span = (err.line() > 0).then(|| {
+						let line_start: usize = contents.split_inclusive('\n').take(err.line() - 1).map(str::len).sum();
+						let offset = (line_start + err.column().saturating_sub(1)).min(contents.len());
+						offset..offset
+					});
+					Err(parse_error(err.into(), contents, span))
+				},
+			}
+		}
+
+		#[cfg(feature = "toml")]
+		pub fn toml<R>(reader: R) -> Result<Self, FormatError> where R: io::Read {
+			let contents = read_to_string(reader)?;
+			match contents.parse() {
+				Ok(table) => Ok(Self::Toml(table)),
+				Err(err) => {
+					let err: toml::de::Error = err;
+					let span = err.span();
+					Err(parse_error(err.into(), contents, span))
+				},
+			}
+		}
+
+		/// Removes the setting `key` from the table and deserializes it, if it is set.
+		pub fn take<T>(&mut self, key: &str) -> Result<Option<T>, FormatError> where T: serde::de::DeserializeOwned {
+			match self {
+				#[cfg(feature = "serde_json")]
+				Self::Json(table) =>
+					table.remove(key).map(serde_json::from_value).transpose().map_err(|err| invalid_setting(key, err.into())),
+
+				#[cfg(feature = "toml")]
+				Self::Toml(table) =>
+					table.remove(key).map(toml::Value::try_into).transpose().map_err(|err| invalid_setting(key, err.into())),
+			}
+		}
+
+		/// Returns an error if any settings are left in the table, since they do not correspond to any field.
+		pub fn finish(self) -> Result<(), FormatError> {
+			match self {
+				#[cfg(feature = "serde_json")]
+				Self::Json(table) => unknown_setting(table.into_iter().next().map(|(key, _)| key)),
+
+				#[cfg(feature = "toml")]
+				Self::Toml(table) => unknown_setting(table.into_iter().next().map(|(key, _)| key)),
+			}
+		}
+	}
+
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	fn read_to_string<R>(mut reader: R) -> Result<String, FormatError> where R: io::Read {
+		let mut contents = String::new();
+		reader.read_to_string(&mut contents).map_err(|err| FormatError(FormatErrorKind::Read(err)))?;
+		Ok(contents)
+	}
+
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	fn parse_error(inner: Box<dyn std::error::Error + Send + Sync>, contents: String, span: Option<std::ops::Range<usize>>) -> FormatError {
+		FormatError(FormatErrorKind::Parse { inner, contents, span })
+	}
+
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	fn invalid_setting(key: &str, inner: Box<dyn std::error::Error + Send + Sync>) -> FormatError {
+		FormatError(FormatErrorKind::InvalidSetting { key: key.to_owned(), inner })
+	}
+
+	#[cfg(any(feature = "serde_json", feature = "toml"))]
+	fn unknown_setting(key: Option<String>) -> Result<(), FormatError> {
+		match key {
+			Some(key) => Err(FormatError(FormatErrorKind::UnknownSetting { key })),
+			None => Ok(()),
+		}
+	}
+
+	/// Searches for the main file and dropins of `project` and merges them into the default value.
+	pub fn load<T>(search_directories: SearchDirectories<'_>, project: &str, suffix: &str) -> io::Result<T>
+	where
+		T: MergeFrom<Error = FormatError> + Default,
+	{
+		search_directories
+			.with_project(project)
+			.with_main_file()
+			.find_files(suffix)?
+			.load_merged()
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+	}
+}
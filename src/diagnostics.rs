@@ -0,0 +1,164 @@
+//! Recording why the files that a search looked at were not returned.
+
+use std::{
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, PoisonError},
+};
+
+/// Collects the files that searches looked at but did not return, together with the reason they were skipped.
+///
+/// Set with [`FindOptions::skip_diagnostics`](crate::FindOptions::skip_diagnostics). Clones share the same collection,
+/// so the skipped files can be read from the original after the search.
+///
+/// # Examples
+///
+/// ```rust
+/// let diagnostics = uapi_config::SkipDiagnostics::new();
+/// let options = uapi_config::FindOptions::new().skip_diagnostics(diagnostics.clone());
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &options)
+///     .unwrap();
+/// for (path, reason) in diagnostics.take() {
+///     eprintln!("{} was skipped: {reason:?}", path.display());
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SkipDiagnostics(Arc<Mutex<Vec<(PathBuf, SkipReason)>>>);
+
+impl SkipDiagnostics {
+	/// Start with no skipped files.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the skipped files in the order that they were looked at, and removes them from the collection.
+	pub fn take(&self) -> Vec<(PathBuf, SkipReason)> {
+		std::mem::take(&mut *self.lock())
+	}
+
+	/// Same as [`take`](Self::take), but returns the skipped files as [`SkipWarning`]s that can be shown to the user.
+	pub fn take_warnings(&self) -> Vec<SkipWarning> {
+		self.take().into_iter().map(|(path, reason)| SkipWarning { path, reason }).collect()
+	}
+
+	pub(crate) fn to_vec(&self) -> Vec<(PathBuf, SkipReason)> {
+		self.lock().clone()
+	}
+
+	/// Returns the number of skipped files that have been recorded and not yet [`take`](Self::take)n.
+	pub fn len(&self) -> usize {
+		self.lock().len()
+	}
+
+	/// Returns `true` if no skipped files have been recorded since the last [`take`](Self::take).
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub(crate) fn record(&self, path: PathBuf, reason: SkipReason) {
+		self.lock().push((path, reason));
+	}
+
+	fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(PathBuf, SkipReason)>> {
+		// The list is always in a consistent state, so it is fine to keep using it even if another thread panicked while holding the lock.
+		self.0.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// The reason that a file was not returned by a search, recorded in [`SkipDiagnostics`].
+///
+/// Apart from [`Duplicate`](Self::Duplicate), [`Filesystem`](Self::Filesystem) and [`TimedOut`](Self::TimedOut), these are only recorded
+/// for the files in dropin directories, since the main file is looked up by name rather than by reading the search directories.
+/// The latter two are recorded for search directories rather than files.
+///
+/// Files that could not be read are not skipped. The search fails with the I/O error instead.
+///
+/// If the `serde` feature is enabled, this implements `serde::Serialize` and `serde::Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[non_exhaustive]
+pub enum SkipReason {
+	/// The name of the file does not match the dropin suffix.
+	SuffixMismatch,
+
+	/// The name of the file starts with `.`, and [`FindOptions::skip_hidden_files`](crate::FindOptions::skip_hidden_files) is set.
+	Hidden,
+
+	/// The file looks like a backup, and [`FindOptions::skip_backup_files`](crate::FindOptions::skip_backup_files) is set.
+	Backup,
+
+	/// The name of the file does not match the regular expression set with `FindOptions::dropin_regex`.
+	RegexMismatch,
+
+	/// The file is not a regular file, such as a directory.
+	NotRegularFile,
+
+	/// The file is a symlink that is not allowed by [`FindOptions::symlink_policy`](crate::FindOptions::symlink_policy).
+	Symlink,
+
+	/// The file no longer exists, or is a symlink whose target does not exist.
+	NotFound,
+
+	/// The file is not executable, and [`FindOptions::executables_only`](crate::FindOptions::executables_only) is set.
+	NotExecutable,
+
+	/// A dropin with the same name in a dropin directory with higher precedence overrides this one.
+	Shadowed,
+
+	/// The file is the same as a file with higher precedence, and was removed by
+	/// [`FindOptions::dedupe_canonical_paths`](crate::FindOptions::dedupe_canonical_paths) or
+	/// [`FindOptions::dedupe_inodes`](crate::FindOptions::dedupe_inodes).
+	Duplicate,
+
+	/// The search directory is on a filesystem that does not match the `FindOptions::filesystem_condition`.
+	Filesystem,
+
+	/// The search directory did not respond within the [`FindOptions::search_directory_timeout`](crate::FindOptions::search_directory_timeout).
+	TimedOut,
+}
+
+/// A file that a search looked at but did not return, returned by [`SkipDiagnostics::take_warnings`].
+///
+/// The [`Display`](std::fmt::Display) impl describes why the file was skipped, so that it can be shown to the user,
+/// such as to point out a dropin that was ignored because of a typo in its suffix.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkipWarning {
+	path: PathBuf,
+	reason: SkipReason,
+}
+
+impl SkipWarning {
+	/// The path of the file, or of the search directory for [`SkipReason::Filesystem`] and [`SkipReason::TimedOut`].
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The reason that the file was skipped.
+	pub fn reason(&self) -> SkipReason {
+		self.reason
+	}
+}
+
+impl std::fmt::Display for SkipWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let path = self.path.display();
+		match self.reason {
+			SkipReason::SuffixMismatch => write!(f, "{path} was skipped because its name does not end with the dropin suffix"),
+			SkipReason::Hidden => write!(f, "{path} was skipped because it is hidden"),
+			SkipReason::Backup => write!(f, "{path} was skipped because it looks like a backup file"),
+			SkipReason::RegexMismatch => write!(f, "{path} was skipped because its name does not match the dropin regex"),
+			SkipReason::NotRegularFile => write!(f, "{path} was skipped because it is not a regular file"),
+			SkipReason::Symlink => write!(f, "{path} was skipped because it is a symlink that is not allowed"),
+			SkipReason::NotFound => write!(f, "{path} was skipped because it no longer exists"),
+			SkipReason::NotExecutable => write!(f, "{path} was skipped because it is not executable"),
+			SkipReason::Shadowed => write!(f, "{path} was skipped because a dropin with the same name overrides it"),
+			SkipReason::Duplicate => write!(f, "{path} was skipped because it is the same file as one with higher precedence"),
+			SkipReason::Filesystem => write!(f, "{path} was skipped because of the type of its filesystem"),
+			SkipReason::TimedOut => write!(f, "{path} was skipped because it did not respond in time"),
+		}
+	}
+}
+
+impl std::error::Error for SkipWarning {}
@@ -0,0 +1,270 @@
+//! Directories that are opened once, so that the files in them are looked up relative to the directory
+//! instead of re-resolving the full path of every file.
+//!
+//! With the `rustix` feature on Linux, this holds a file descriptor for the directory and uses `openat` relative to it,
+//! so a directory that is renamed or replaced in the middle of the search cannot cause files from two different directories
+//! to be mixed up. Otherwise it falls back to using full paths.
+
+use std::{
+	ffi::{OsStr, OsString},
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use crate::{EntryMetadata, Root};
+
+#[derive(Debug)]
+pub(crate) struct Dir {
+	path: PathBuf,
+
+	/// The root that path resolution is contained in, if any.
+	root: Option<Arc<Root>>,
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	fd: std::os::fd::OwnedFd,
+}
+
+impl Dir {
+	/// Opens the directory at `path`.
+	///
+	/// If `path` is not under `root`, such as for the credentials directory of the current process, it is opened without being contained in `root`.
+	#[cfg_attr(not(all(feature = "rustix", target_os = "linux")), allow(clippy::unnecessary_wraps))] // Only fallible with a file descriptor.
+	pub(crate) fn open(path: PathBuf, root: Option<&Arc<Root>>) -> io::Result<Self> {
+		let root = root.filter(|root| path.starts_with(root.path()));
+
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		{
+			use rustix::fs::{Mode, OFlags};
+
+			// `O_PATH` is enough to look up files relative to the directory, so a directory that is only searchable
+			// and not readable, like `/etc/foo` with mode `0711`, can still be used for finding main files.
+			let oflags = OFlags::PATH | OFlags::DIRECTORY;
+			let fd = match root {
+				Some(root) => root.resolve(&path, oflags)?,
+				None => rustix::fs::open(&path, oflags | OFlags::CLOEXEC, Mode::empty())?,
+			};
+			Ok(Self { path, root: root.cloned(), fd })
+		}
+
+		// Without a file descriptor, any errors are only reported when the directory is used.
+		#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+		{
+			Ok(Self { path, root: root.cloned() })
+		}
+	}
+
+	/// Opens the directory at `relative_path` under this directory.
+	///
+	/// With a file descriptor, the directory is opened relative to it, so `relative_path` is resolved within this directory
+	/// even if this directory has been renamed since it was opened.
+	pub(crate) fn open_dir(&self, relative_path: &Path) -> io::Result<Self> {
+		let path = self.path.join(relative_path);
+
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		if self.root.is_none() {
+			use rustix::fs::{Mode, OFlags};
+
+			let fd = rustix::fs::openat(&self.fd, relative_path, OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC, Mode::empty())?;
+			return Ok(Self { path, root: None, fd });
+		}
+
+		Self::open(path, self.root.as_ref())
+	}
+
+	pub(crate) fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The root that path resolution is contained in, if any.
+	pub(crate) fn root(&self) -> Option<&Root> {
+		self.root.as_deref()
+	}
+
+	/// Whether path resolution is contained in a root, so files must be opened with `openat2` rather than `openat`.
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
+	pub(crate) fn is_contained(&self) -> bool {
+		self.root.is_some()
+	}
+
+	/// The file descriptor of this directory, if it has one.
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
+	#[allow(clippy::unnecessary_wraps, clippy::unused_self)] // Depends on whether the `rustix` feature is enabled.
+	pub(crate) fn fd(&self) -> Option<std::os::fd::BorrowedFd<'_>> {
+		#[cfg(feature = "rustix")]
+		{
+			use std::os::fd::AsFd as _;
+
+			Some(self.fd.as_fd())
+		}
+
+		#[cfg(not(feature = "rustix"))]
+		{
+			None
+		}
+	}
+
+	/// Returns the file names and types of the entries of this directory, excluding `.` and `..`.
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the directory does not exist.
+	pub(crate) fn file_names(&self) -> io::Result<FileNames> {
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		{
+			use rustix::fs::{Mode, OFlags};
+
+			// The directory is only opened with `O_PATH`, so it needs to be opened again for reading to list it.
+			let fd = rustix::fs::openat(&self.fd, ".", OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC, Mode::empty())?;
+			Ok(FileNames(rustix::fs::Dir::new(fd)?))
+		}
+
+		#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+		{
+			Ok(FileNames(fs::read_dir(&self.path)?))
+		}
+	}
+
+	/// Like [`fs::metadata`] of the file named `file_name` in this directory if `follow_symlinks` is `true`,
+	/// else like [`fs::symlink_metadata`].
+	///
+	/// With a file descriptor, this is a single `fstatat` relative to it. `scratch` is used as a buffer for building the path
+	/// of the file, if necessary.
+	pub(crate) fn metadata(&self, file_name: &OsStr, follow_symlinks: bool, scratch: &mut PathBuf) -> io::Result<EntryMetadata> {
+		// Symlinks in a contained root must be resolved relative to the root, not relative to this directory.
+		if let Some(root) = &self.root {
+			return Ok((&root.metadata(self.path_of(file_name, scratch), follow_symlinks)?).into());
+		}
+
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		{
+			use rustix::fs::AtFlags;
+
+			let flags = if follow_symlinks { AtFlags::empty() } else { AtFlags::SYMLINK_NOFOLLOW };
+			Ok(rustix::fs::statat(&self.fd, file_name, flags)?.into())
+		}
+
+		#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+		{
+			let path = self.path_of(file_name, scratch);
+			Ok((&if follow_symlinks { fs::metadata(path)? } else { fs::symlink_metadata(path)? }).into())
+		}
+	}
+
+	/// Opens the file named `file_name` in this directory for reading.
+	///
+	/// The file is opened like [`open_nonblocking`](crate::platform::open_nonblocking) so that opening a FIFO does not block. The caller must verify that
+	/// the file is a regular file and then clear the flag with [`set_blocking`](crate::platform::set_blocking).
+	///
+	/// If `open_options` is set and path resolution is not contained in a root, the file is opened by its full path with those options instead.
+	/// Such files are opened without [`NONBLOCKING_OPEN_FLAGS`](crate::platform::NONBLOCKING_OPEN_FLAGS), since they cannot be added to the caller's options.
+	pub(crate) fn open_file(&self, file_name: &OsStr, open_options: Option<&fs::OpenOptions>) -> io::Result<File> {
+		if let Some(root) = &self.root {
+			return root.open_file(&self.path.join(file_name));
+		}
+
+		if let Some(open_options) = open_options {
+			return open_options.open(self.path.join(file_name));
+		}
+
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		{
+			use rustix::fs::{Mode, OFlags};
+
+			Ok(rustix::fs::openat(&self.fd, file_name, OFlags::RDONLY | OFlags::NONBLOCK | OFlags::CLOEXEC, Mode::empty())?.into())
+		}
+
+		#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+		{
+			crate::platform::open_nonblocking(&self.path.join(file_name))
+		}
+	}
+}
+
+/// A directory that the search has opened on some filesystem, identified by its path.
+pub(crate) trait SearchDir {
+	fn path(&self) -> &Path;
+
+	/// Returns the full path of the file named `file_name` in this directory, using `scratch` as the buffer.
+	fn path_of<'a>(&self, file_name: &OsStr, scratch: &'a mut PathBuf) -> &'a Path {
+		scratch.clear();
+		scratch.push(self.path());
+		scratch.push(file_name);
+		scratch
+	}
+}
+
+impl SearchDir for Dir {
+	fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+/// The type of a directory entry, as reported by the directory listing itself without a separate `stat`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum EntryKind {
+	File,
+	Symlink,
+
+	/// A directory, socket, FIFO, device, etc.
+	Other,
+
+	/// The filesystem does not report the type of entries in directory listings.
+	Unknown,
+}
+
+/// The file names and types of the entries of a directory, returned by [`Dir::file_names`].
+pub(crate) struct FileNames(
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	rustix::fs::Dir,
+
+	#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+	fs::ReadDir,
+);
+
+impl Iterator for FileNames {
+	type Item = io::Result<(OsString, EntryKind)>;
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	fn next(&mut self) -> Option<Self::Item> {
+		use std::os::unix::ffi::OsStrExt as _;
+
+		use rustix::fs::FileType;
+
+		loop {
+			let entry = match self.0.next()? {
+				Ok(entry) => entry,
+				Err(err) => return Some(Err(err.into())),
+			};
+			let file_name = entry.file_name().to_bytes();
+			if file_name == b"." || file_name == b".." {
+				continue;
+			}
+
+			let kind = match entry.file_type() {
+				FileType::RegularFile => EntryKind::File,
+				FileType::Symlink => EntryKind::Symlink,
+				FileType::Unknown => EntryKind::Unknown,
+				_ => EntryKind::Other,
+			};
+			return Some(Ok((OsStr::from_bytes(file_name).to_owned(), kind)));
+		}
+	}
+
+	#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+	fn next(&mut self) -> Option<Self::Item> {
+		let entry = match self.0.next()? {
+			Ok(entry) => entry,
+			Err(err) => return Some(Err(err)),
+		};
+
+		// `DirEntry::file_type` only needs a separate `lstat` if the filesystem does not report the type in the directory listing.
+		// If that fails, leave it to the caller's own `stat` to report the error.
+		let kind = match entry.file_type() {
+			Ok(file_type) if file_type.is_file() => EntryKind::File,
+			Ok(file_type) if file_type.is_symlink() => EntryKind::Symlink,
+			Ok(_) => EntryKind::Other,
+			Err(_) => EntryKind::Unknown,
+		};
+		Some(Ok((entry.file_name(), kind)))
+	}
+}
@@ -0,0 +1,79 @@
+use std::{
+	fmt::Write as _,
+	path::Path,
+};
+
+use crate::{Entries, Entry};
+
+impl Entries {
+	/// Renders the remaining entries as a graph in the Graphviz DOT language, such as for embedding in documentation
+	/// that is generated from the live system.
+	///
+	/// Each directory that a file was found in is drawn as a cluster containing the files that were found in it.
+	/// The files are connected in lowest-precedence-first order by edges labeled `overridden by`, ie each file overrides
+	/// the settings of the files before it.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let entries =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_entries(Some(".conf"))
+	///     .unwrap();
+	/// // Render with `dot -Tsvg`
+	/// println!("{}", entries.to_dot());
+	/// ```
+	pub fn to_dot(&self) -> String {
+		let entries: Vec<&Entry> = self.main_file.iter().chain(self.dropins.as_slice()).collect();
+
+		// Directories in the order that they were first seen, with the indices of the entries in them.
+		let mut directories: Vec<(&Path, Vec<usize>)> = vec![];
+		for (i, entry) in entries.iter().enumerate() {
+			let directory = entry.dir.path();
+			match directories.iter_mut().find(|(path, _)| *path == directory) {
+				Some((_, indices)) => indices.push(i),
+				None => directories.push((directory, vec![i])),
+			}
+		}
+
+		let mut result = String::new();
+		result.push_str("digraph config {\n");
+		result.push_str("\trankdir=LR;\n");
+		result.push_str("\tnode [shape=box];\n");
+
+		for (i, (directory, indices)) in directories.iter().enumerate() {
+			_ = writeln!(result, "\tsubgraph cluster_{i} {{");
+			_ = writeln!(result, "\t\tlabel={};", quote(&directory.to_string_lossy()));
+			for &index in indices {
+				_ = writeln!(result, "\t\tfile_{index} [label={}];", quote(&entries[index].file_name.to_string_lossy()));
+			}
+			result.push_str("\t}\n");
+		}
+
+		for i in 1..entries.len() {
+			_ = writeln!(result, "\tfile_{} -> file_{i} [label=\"overridden by\"];", i - 1);
+		}
+
+		result.push_str("}\n");
+		result
+	}
+}
+
+/// Quotes `s` as a DOT string.
+fn quote(s: &str) -> String {
+	let mut result = String::with_capacity(s.len() + 2);
+	result.push('"');
+	for c in s.chars() {
+		match c {
+			'"' | '\\' => {
+				result.push('\\');
+				result.push(c);
+			},
+			'\n' => result.push_str("\\n"),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+	result
+}
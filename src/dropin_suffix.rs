@@ -0,0 +1,68 @@
+//! Validated dropin suffixes.
+
+use std::ffi::{OsStr, OsString};
+
+use crate::platform::OsStrExt as _;
+
+/// A dropin suffix that has been checked to be a file extension like `.conf`.
+///
+/// The `find_files` methods accept any [`AsRef<OsStr>`] as the dropin suffix and match it with [`SuffixMatch::EndsWith`](crate::SuffixMatch::EndsWith)
+/// by default, so passing `"conf"` instead of `".conf"` silently also finds files like `fooconf`. This type can be passed instead to catch that mistake.
+///
+/// # Examples
+///
+/// ```rust
+/// let dropin_suffix = uapi_config::DropinSuffix::new(".conf").unwrap();
+/// assert!(uapi_config::DropinSuffix::new("conf").is_err());
+///
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files(&dropin_suffix)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DropinSuffix(OsString);
+
+impl DropinSuffix {
+	/// Validates that `suffix` starts with `.` and is followed by at least one more character.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(InvalidDropinSuffixError)` if `suffix` does not start with `.` or if it is only `.`.
+	pub fn new<S>(suffix: S) -> Result<Self, InvalidDropinSuffixError> where S: AsRef<OsStr> {
+		let suffix = suffix.as_ref();
+		match suffix.as_bytes() {
+			[b'.', _, ..] => Ok(Self(suffix.to_owned())),
+			_ => Err(InvalidDropinSuffixError),
+		}
+	}
+
+	/// Creates a dropin suffix without validating it, for suffixes that are not file extensions on purpose, like `-local`.
+	pub fn raw<S>(suffix: S) -> Self where S: Into<OsString> {
+		Self(suffix.into())
+	}
+
+	/// The suffix.
+	pub fn as_os_str(&self) -> &OsStr {
+		&self.0
+	}
+}
+
+impl AsRef<OsStr> for DropinSuffix {
+	fn as_ref(&self) -> &OsStr {
+		&self.0
+	}
+}
+
+/// Error returned by [`DropinSuffix::new`] when the suffix does not start with `.` or is only `.`.
+#[derive(Debug)]
+pub struct InvalidDropinSuffixError;
+
+impl std::fmt::Display for InvalidDropinSuffixError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("dropin suffix must start with '.' followed by at least one character")
+	}
+}
+
+impl std::error::Error for InvalidDropinSuffixError {}
@@ -0,0 +1,388 @@
+use std::{
+	ffi::OsString,
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::SystemTime,
+};
+
+use crate::{platform, Dir, Files, IncludeContext};
+
+/// A file that was found by the search, but has not been opened yet.
+///
+/// Returned by the `find_entries` methods.
+#[derive(Debug)]
+pub struct Entry {
+	pub(crate) path: PathBuf,
+	pub(crate) metadata: EntryMetadata,
+
+	/// The directory that the file was found in, and the name of the file in it.
+	/// The file is opened relative to this directory so that it cannot be swapped by renaming the directory.
+	pub(crate) dir: Arc<Dir>,
+	pub(crate) file_name: OsString,
+
+	pub(crate) open_options: Option<fs::OpenOptions>,
+
+	/// Whether the opened file must be verified to be the same file as the one that was found,
+	/// so that a file cannot be swapped for a symlink after the symlink policy has been applied.
+	pub(crate) verify_identity: bool,
+}
+
+impl Entry {
+	/// The path of the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Consumes this entry and returns the path of the file.
+	pub fn into_path(self) -> PathBuf {
+		self.path
+	}
+
+	/// The metadata of the file, as of when it was found by the search.
+	pub fn metadata(&self) -> &EntryMetadata {
+		&self.metadata
+	}
+
+	/// Opens the file for reading, using the [`FindOptions::open_options`](crate::FindOptions::open_options) that the search was performed with.
+	/// If the search directories were created with `SearchDirectories::chroot_contained`, the file is opened read-only within the root instead.
+	///
+	/// Unless custom [`FindOptions::open_options`](crate::FindOptions::open_options) are set, the file is opened with `O_NONBLOCK`, which is
+	/// cleared once the file is verified to be a regular file. So if the file has been replaced with a FIFO since the search,
+	/// this returns an error instead of blocking until something writes to the FIFO.
+	///
+	/// # Errors
+	///
+	/// Returns any I/O error from opening the file, including if the file no longer exists.
+	/// Returns an error of kind [`io::ErrorKind::InvalidData`] if the path no longer refers to a regular file.
+	///
+	/// If the search was performed with a [`SymlinkPolicy`](crate::SymlinkPolicy) other than `Follow`, then this also returns an error of kind
+	/// [`io::ErrorKind::InvalidData`] if the path now refers to a different file than the one that was found by the search.
+	pub fn open(&self) -> io::Result<File> {
+		let (file, _) = self.open_with_metadata()?;
+		Ok(file)
+	}
+
+	/// Opens the file like [`Entry::open`], and also returns the metadata of the opened file.
+	///
+	/// Unlike [`Entry::metadata`], the metadata is as of when the file was opened, so it reflects changes to the file since the search,
+	/// such as for comparing its modification time with the previous reload.
+	///
+	/// # Errors
+	///
+	/// See [`Entry::open`].
+	pub fn open_with_metadata(&self) -> io::Result<(File, fs::Metadata)> {
+		let file = self.open_file()?;
+		let metadata = file.metadata()?;
+		if !metadata.file_type().is_file() {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is no longer a regular file", self.path.display())));
+		}
+		self.verify_identity(&metadata)?;
+		platform::set_blocking(&file)?;
+		Ok((file, metadata))
+	}
+
+	fn open_file(&self) -> io::Result<File> {
+		self.dir.open_file(&self.file_name, self.open_options.as_ref())
+	}
+
+	fn verify_identity(&self, metadata: &fs::Metadata) -> io::Result<()> {
+		match platform::file_id(metadata) {
+			Some((dev, ino)) => self.verify_identity_of(dev, ino),
+
+			// The identity of the file cannot be verified if the platform cannot identify files.
+			None => Ok(()),
+		}
+	}
+
+	pub(crate) fn verify_identity_of(&self, dev: u64, ino: u64) -> io::Result<()> {
+		if self.verify_identity && self.metadata.file_id().is_some_and(|file_id| file_id != (dev, ino)) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} was replaced after it was found", self.path.display())));
+		}
+		Ok(())
+	}
+
+	/// Opens the file like [`Entry::open`], except that the file not existing any more or not being a regular file any more
+	/// are treated as if the file had never been found by the search, to match the non-deferred `find_files` methods.
+	fn open_if_still_a_file(self) -> io::Result<Option<(PathBuf, File, fs::Metadata)>> {
+		let file = self.open_file_if_still_a_file()?;
+		Ok(file.map(|(file, metadata)| (self.path, file, metadata)))
+	}
+
+	pub(crate) fn open_file_if_still_a_file(&self) -> io::Result<Option<(File, fs::Metadata)>> {
+		let file = match self.open_file() {
+			Ok(file) => file,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			// Without the `rustix` feature, a file that is not a regular file any more is rejected before it is opened.
+			#[cfg(not(all(unix, feature = "rustix")))]
+			Err(err) if err.kind() == io::ErrorKind::InvalidData => return Ok(None),
+			Err(err) => return Err(err),
+		};
+
+		let metadata = file.metadata()?;
+		if !metadata.file_type().is_file() {
+			return Ok(None);
+		}
+		self.verify_identity(&metadata)?;
+		platform::set_blocking(&file)?;
+
+		Ok(Some((file, metadata)))
+	}
+}
+
+/// The metadata of a file that was found by the search, as of when it was found.
+///
+/// Unlike [`fs::Metadata`], this can be looked up relative to the directory of the file with a single `fstatat`,
+/// so it only has the properties of the file that the search uses. Use [`Entry::open_with_metadata`] to get
+/// the [`fs::Metadata`] of the opened file.
+#[derive(Clone, Debug)]
+pub struct EntryMetadata {
+	file_type: FileType,
+	size: u64,
+	modified: Option<SystemTime>,
+	file_id: Option<(u64, u64)>,
+
+	/// The uid, gid and mode of the file.
+	#[cfg(unix)]
+	owner_and_mode: (u32, u32, u32),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FileType {
+	File,
+	Dir,
+	Symlink,
+	Other,
+}
+
+impl EntryMetadata {
+	/// Whether the file is a regular file.
+	pub fn is_file(&self) -> bool {
+		self.file_type == FileType::File
+	}
+
+	/// Whether the file is a directory.
+	pub fn is_dir(&self) -> bool {
+		self.file_type == FileType::Dir
+	}
+
+	/// Whether the file is a symlink. This is only possible for the metadata of files that were looked up without following symlinks.
+	pub fn is_symlink(&self) -> bool {
+		self.file_type == FileType::Symlink
+	}
+
+	/// The size of the file in bytes.
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+
+	/// The modification time of the file, if the platform provides it.
+	pub fn modified(&self) -> Option<SystemTime> {
+		self.modified
+	}
+
+	/// The uid of the owner of the file.
+	#[cfg(unix)]
+	pub fn uid(&self) -> u32 {
+		self.owner_and_mode.0
+	}
+
+	/// The gid of the group of the file.
+	#[cfg(unix)]
+	pub fn gid(&self) -> u32 {
+		self.owner_and_mode.1
+	}
+
+	/// The mode of the file, including the file type bits, like [`MetadataExt::mode`](std::os::unix::fs::MetadataExt::mode).
+	#[cfg(unix)]
+	pub fn mode(&self) -> u32 {
+		self.owner_and_mode.2
+	}
+
+	/// The device and inode numbers of the file, which together identify it, or `None` if the platform does not provide them.
+	pub(crate) fn file_id(&self) -> Option<(u64, u64)> {
+		self.file_id
+	}
+
+	/// Returns the uid of the owner and the mode of the file.
+	///
+	/// Returns an error of kind [`io::ErrorKind::Unsupported`] if the platform does not have file owners and modes.
+	#[cfg_attr(unix, allow(clippy::unnecessary_wraps))] // Only fallible on platforms without file owners and modes.
+	#[cfg_attr(not(unix), allow(clippy::unused_self))]
+	pub(crate) fn owner_and_mode(&self) -> io::Result<(u32, u32)> {
+		#[cfg(unix)]
+		{
+			Ok((self.owner_and_mode.0, self.owner_and_mode.2))
+		}
+
+		#[cfg(not(unix))]
+		{
+			Err(io::Error::new(io::ErrorKind::Unsupported, "file owners and modes are not supported on this platform"))
+		}
+	}
+}
+
+impl From<&fs::Metadata> for EntryMetadata {
+	fn from(metadata: &fs::Metadata) -> Self {
+		let file_type = metadata.file_type();
+		let file_type =
+			if file_type.is_file() { FileType::File }
+			else if file_type.is_dir() { FileType::Dir }
+			else if file_type.is_symlink() { FileType::Symlink }
+			else { FileType::Other };
+
+		Self {
+			file_type,
+			size: metadata.len(),
+			modified: metadata.modified().ok(),
+			file_id: platform::file_id(metadata),
+			#[cfg(unix)]
+			owner_and_mode: {
+				use std::os::unix::fs::MetadataExt as _;
+
+				(metadata.uid(), metadata.gid(), metadata.mode())
+			},
+		}
+	}
+}
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+impl From<rustix::fs::Stat> for EntryMetadata {
+	// The types of the fields differ between architectures.
+	#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss, clippy::unnecessary_cast)]
+	fn from(stat: rustix::fs::Stat) -> Self {
+		let file_type = match rustix::fs::FileType::from_raw_mode(stat.st_mode as _) {
+			rustix::fs::FileType::RegularFile => FileType::File,
+			rustix::fs::FileType::Directory => FileType::Dir,
+			rustix::fs::FileType::Symlink => FileType::Symlink,
+			_ => FileType::Other,
+		};
+
+		let mtime = stat.st_mtime as i64;
+		let mtime_nsec = std::time::Duration::from_nanos(stat.st_mtime_nsec as u64);
+		let modified =
+			if mtime >= 0 {
+				SystemTime::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(mtime as u64))
+			}
+			else {
+				SystemTime::UNIX_EPOCH.checked_sub(std::time::Duration::from_secs(mtime.unsigned_abs()))
+			}
+			.and_then(|modified| modified.checked_add(mtime_nsec));
+
+		Self {
+			file_type,
+			size: stat.st_size as u64,
+			modified,
+			file_id: Some((stat.st_dev as u64, stat.st_ino as u64)),
+			owner_and_mode: (stat.st_uid as u32, stat.st_gid as u32, stat.st_mode as u32),
+		}
+	}
+}
+
+impl From<crate::FileSystemMetadata> for EntryMetadata {
+	// The other properties are only used by options that are not supported when searching a `FileSystem`.
+	fn from(metadata: crate::FileSystemMetadata) -> Self {
+		Self {
+			file_type: if metadata.is_file() { FileType::File } else { FileType::Other },
+			size: metadata.size(),
+			modified: None,
+			file_id: None,
+			#[cfg(unix)]
+			owner_and_mode: (0, 0, 0),
+		}
+	}
+}
+
+#[cfg(feature = "cap-std")]
+impl From<&cap_std::fs::Metadata> for EntryMetadata {
+	fn from(metadata: &cap_std::fs::Metadata) -> Self {
+		use cap_std::fs::MetadataExt as _;
+
+		let file_type = metadata.file_type();
+		let file_type =
+			if file_type.is_file() { FileType::File }
+			else if file_type.is_dir() { FileType::Dir }
+			else if file_type.is_symlink() { FileType::Symlink }
+			else { FileType::Other };
+
+		Self {
+			file_type,
+			size: metadata.len(),
+			modified: metadata.modified().ok().map(cap_std::time::SystemTime::into_std),
+			file_id: Some((metadata.dev(), metadata.ino())),
+			owner_and_mode: (metadata.uid(), metadata.gid(), metadata.mode()),
+		}
+	}
+}
+
+/// The iterator of entries returned by the `find_entries` methods.
+///
+/// This is like [`Files`], except that the files are not opened until [`Entry::open`] is called.
+/// This avoids holding a file descriptor open for every file at the same time,
+/// which can otherwise exhaust the file descriptor limit of the process when there are a large number of dropins.
+///
+/// Entries are yielded in lowest-precedence-first order, ie the main file (if any) followed by the dropins.
+#[derive(Debug)]
+pub struct Entries {
+	pub(crate) main_file: Option<Entry>,
+	pub(crate) dropins: std::vec::IntoIter<Entry>,
+
+	/// The parts of the search that also apply to the files that these files include.
+	pub(crate) includes: Arc<IncludeContext>,
+}
+
+impl Entries {
+	/// Returns the number of entries remaining in this iterator.
+	pub fn len(&self) -> usize {
+		usize::from(self.main_file.is_some()) + self.dropins.len()
+	}
+
+	/// Returns `true` if there are no entries remaining in this iterator.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Opens all the remaining entries.
+	pub(crate) fn open_all(self) -> io::Result<Files> {
+		let main_file = match self.main_file {
+			Some(main_file) => main_file.open_if_still_a_file()?,
+			None => None,
+		};
+
+		let dropins: Vec<_> =
+			self.dropins
+			.filter_map(|dropin| dropin.open_if_still_a_file().transpose())
+			.collect::<io::Result<_>>()?;
+
+		Ok(Files {
+			main_file,
+			dropins: dropins.into_iter(),
+			observer: None,
+			includes: self.includes,
+		})
+	}
+}
+
+impl Iterator for Entries {
+	type Item = Entry;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.main_file.take().or_else(|| self.dropins.next())
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.len();
+		(len, Some(len))
+	}
+}
+
+impl DoubleEndedIterator for Entries {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.dropins.next_back().or_else(|| self.main_file.take())
+	}
+}
+
+impl ExactSizeIterator for Entries {}
+
+impl std::iter::FusedIterator for Entries {}
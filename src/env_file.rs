@@ -0,0 +1,230 @@
+//! Parsing environment files like systemd's `EnvironmentFile=`.
+
+use std::{
+	collections::HashMap,
+	ffi::OsString,
+};
+
+use crate::{platform::OsStringExt as _, Files, ReadError};
+
+impl Files {
+	/// Reads all the files as environment files and merges their variables, with variables in later files overriding
+	/// variables with the same name in earlier files.
+	///
+	/// The files are parsed like systemd parses the files of `EnvironmentFile=`:
+	///
+	/// - Each assignment is of the form `KEY=VALUE`. Whitespace around the key and around an unquoted value is ignored.
+	///   Lines that are empty or whose first non-whitespace character is `#` or `;` are comments. Lines without a `=`,
+	///   and assignments whose key is not a valid variable name, are ignored.
+	///
+	/// - A value that starts with `'` continues until the next `'`, and its contents are used literally.
+	///
+	/// - A value that starts with `"` continues until the next unescaped `"`. Within it, `\` followed by one of `"`, `\`, `` ` `` or `$`
+	///   is replaced by that character, and `\` followed by a newline is removed. Any other `\` is used literally.
+	///
+	/// - In an unquoted value, and after the closing quote of a quoted value, `\` followed by a newline is removed,
+	///   and `\` followed by any other character is replaced by that character.
+	///
+	/// Quoted values may span multiple lines.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(ReadError)` for the first file that could not be read. The error contains the path of that file.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let environment =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .with_file_name("foobar.env")
+	///     .find_files(Some(".env"))
+	///     .unwrap()
+	///     .parse_environment()
+	///     .unwrap();
+	/// if let Some(log_level) = environment.get(std::ffi::OsStr::new("LOG_LEVEL")) {
+	///     // ...
+	/// }
+	/// ```
+	pub fn parse_environment(self) -> Result<HashMap<OsString, OsString>, ReadError> {
+		let mut result = HashMap::new();
+
+		for (_, contents) in self.read_all()? {
+			result.extend(parse(&contents).into_iter().map(|(key, value)| (OsString::from_vec(key), OsString::from_vec(value))));
+		}
+
+		Ok(result)
+	}
+}
+
+#[derive(Clone, Copy)]
+enum State {
+	PreKey,
+	Key,
+	PreValue,
+	Value,
+	ValueEscape,
+	SingleQuoteValue,
+	DoubleQuoteValue,
+	DoubleQuoteValueEscape,
+	Comment,
+	CommentEscape,
+}
+
+/// Returns the assignments in `contents`, in order.
+fn parse(contents: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+	let mut result = vec![];
+
+	let mut state = State::PreKey;
+	let mut key = vec![];
+	let mut value = vec![];
+	// The length of `value` without the trailing whitespace that is not quoted or escaped.
+	let mut value_len = 0;
+
+	let mut push = |key: &mut Vec<u8>, value: &mut Vec<u8>, value_len: usize| {
+		let key = std::mem::take(key);
+		let mut value = std::mem::take(value);
+		value.truncate(value_len);
+		let key_len = key.len() - key.iter().rev().take_while(|b| b.is_ascii_whitespace()).count();
+		let key = &key[..key_len];
+		if is_valid_key(key) {
+			result.push((key.to_owned(), value));
+		}
+	};
+
+	for &b in contents {
+		state = match (state, b) {
+			(State::PreKey, b'#' | b';') => State::Comment,
+			(State::PreKey, b) if b.is_ascii_whitespace() => State::PreKey,
+			(State::PreKey | State::Key, b) => {
+				if b == b'\n' {
+					// A line without a `=`
+					key.clear();
+					State::PreKey
+				}
+				else if b == b'=' {
+					State::PreValue
+				}
+				else {
+					key.push(b);
+					State::Key
+				}
+			},
+
+			(State::PreValue, b'\n') => {
+				push(&mut key, &mut value, 0);
+				State::PreKey
+			},
+			(State::PreValue, b'\'') => State::SingleQuoteValue,
+			(State::PreValue, b'"') => State::DoubleQuoteValue,
+			(State::PreValue, b'\\') => State::ValueEscape,
+			(State::PreValue, b) if b.is_ascii_whitespace() => State::PreValue,
+			(State::PreValue | State::Value, b) => {
+				if b == b'\n' {
+					push(&mut key, &mut value, value_len);
+					value_len = 0;
+					State::PreKey
+				}
+				else if b == b'\\' {
+					State::ValueEscape
+				}
+				else {
+					value.push(b);
+					if !b.is_ascii_whitespace() {
+						value_len = value.len();
+					}
+					State::Value
+				}
+			},
+			(State::ValueEscape, b) => {
+				if b != b'\n' {
+					value.push(b);
+					value_len = value.len();
+				}
+				State::Value
+			},
+
+			(State::SingleQuoteValue, b'\'') |
+			(State::DoubleQuoteValue, b'"') => {
+				value_len = value.len();
+				State::Value
+			},
+			(State::SingleQuoteValue, b) => {
+				value.push(b);
+				State::SingleQuoteValue
+			},
+
+			(State::DoubleQuoteValue, b'\\') => State::DoubleQuoteValueEscape,
+			(State::DoubleQuoteValue, b) => {
+				value.push(b);
+				State::DoubleQuoteValue
+			},
+			(State::DoubleQuoteValueEscape, b) => {
+				match b {
+					b'"' | b'\\' | b'`' | b'$' => value.push(b),
+					b'\n' => (),
+					b => value.extend_from_slice(&[b'\\', b]),
+				}
+				State::DoubleQuoteValue
+			},
+
+			(State::Comment, b'\\') => State::CommentEscape,
+			(State::Comment, b'\n') => State::PreKey,
+			(State::Comment | State::CommentEscape, _) => State::Comment,
+		};
+	}
+
+	// An unterminated quoted value extends to the end of the file.
+	if matches!(state, State::SingleQuoteValue | State::DoubleQuoteValue | State::DoubleQuoteValueEscape) {
+		value_len = value.len();
+	}
+	if !matches!(state, State::PreKey | State::Key | State::Comment | State::CommentEscape) {
+		push(&mut key, &mut value, value_len);
+	}
+
+	result
+}
+
+fn is_valid_key(key: &[u8]) -> bool {
+	match key.split_first() {
+		Some((first, rest)) => (first.is_ascii_alphabetic() || *first == b'_') && rest.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_'),
+		None => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn parse() {
+		let contents = b"\
+# comment that continues \\
+X=on the next line
+A=1
+  B = two words  \n\
+;another comment
+C='single \"quoted\" $value'
+D=\"double \\\"quoted\\\" \\$value \\n\"
+E=\"multi
+line\"
+F=unquoted\\ escape\\
+continued
+G=
+invalid line
+1H=invalid key
+I=\"a\"b
+J='unterminated";
+
+		let result: Vec<_> = super::parse(contents).into_iter().map(|(key, value)| (String::from_utf8(key).unwrap(), String::from_utf8(value).unwrap())).collect();
+		assert_eq!(result, [
+			("A".to_owned(), "1".to_owned()),
+			("B".to_owned(), "two words".to_owned()),
+			("C".to_owned(), "single \"quoted\" $value".to_owned()),
+			("D".to_owned(), "double \"quoted\" $value \\n".to_owned()),
+			("E".to_owned(), "multi\nline".to_owned()),
+			("F".to_owned(), "unquoted escapecontinued".to_owned()),
+			("G".to_owned(), String::new()),
+			("I".to_owned(), "ab".to_owned()),
+			("J".to_owned(), "unterminated".to_owned()),
+		]);
+	}
+}
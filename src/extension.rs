@@ -0,0 +1,201 @@
+//! Finding which system extension or configuration extension image a config file originates from.
+
+use std::{
+	collections::BTreeMap,
+	ffi::{OsStr, OsString},
+	fs,
+	io,
+	path::{Path, PathBuf},
+};
+
+use crate::platform::OsStrExt as _;
+
+/// The extension images that `systemd-sysext` or `systemd-confext` has merged into a hierarchy, such as `/etc`,
+/// used to find which image a file in the hierarchy originates from.
+///
+/// When configuration extensions are in use, `/etc` is a read-only overlay of the host's `/etc` and the `etc/` directory of every image.
+/// The search does not need to know about this, since it only sees the merged hierarchy, so the existing presets like
+/// [`SearchDirectories::modern_system`](crate::SearchDirectories::modern_system) find the same files as the programs that read them.
+/// But the merged hierarchy does not record which image each file came from, so that is looked up in the images themselves.
+///
+/// Only images that are directories can be introspected. Disk images like `foo.raw` are skipped, so files that come from them
+/// are reported as originating from the host.
+///
+/// # Examples
+///
+/// ```rust
+/// let extensions = uapi_config::Extensions::confext().unwrap();
+///
+/// let entries =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .find_entries(Some(".conf"))
+///     .unwrap();
+/// for entry in entries {
+///     match extensions.origin(entry.path()).unwrap() {
+///         Some(image) => println!("{} from extension {}", entry.path().display(), image.display()),
+///         None => println!("{}", entry.path().display()),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Extensions {
+	hierarchy: PathBuf,
+	/// Lowest-precedence-first, like the layers of the overlay.
+	images: Vec<Image>,
+}
+
+#[derive(Clone, Debug)]
+struct Image {
+	name: OsString,
+	/// The image's copy of the hierarchy.
+	hierarchy: PathBuf,
+}
+
+/// The directories that `systemd-confext` looks for images in, highest-precedence-first. From `man systemd-sysext`.
+const CONFEXT_IMAGE_DIRECTORIES: &[&str] = &["run/confexts", "var/lib/confexts", "usr/lib/confexts", "usr/local/lib/confexts"];
+
+/// The directories that `systemd-sysext` looks for images in, highest-precedence-first. From `man systemd-sysext`.
+const SYSEXT_IMAGE_DIRECTORIES: &[&str] = &["etc/extensions", "run/extensions", "var/lib/extensions", "usr/lib/extensions", "usr/local/lib/extensions"];
+
+impl Extensions {
+	/// Start with no images for the hierarchy at the given path.
+	///
+	/// Use this with [`image`](Self::image) for extension schemes other than the ones of `systemd-sysext` and `systemd-confext`.
+	pub fn new<P>(hierarchy: P) -> Self where P: Into<PathBuf> {
+		Self {
+			hierarchy: hierarchy.into(),
+			images: vec![],
+		}
+	}
+
+	/// Appends an image with the given name, whose copy of the hierarchy is at the given path.
+	/// Files in this image override files in earlier images.
+	#[must_use]
+	pub fn image<N, P>(mut self, name: N, hierarchy: P) -> Self where N: Into<OsString>, P: Into<PathBuf> {
+		self.images.push(Image {
+			name: name.into(),
+			hierarchy: hierarchy.into(),
+		});
+		self
+	}
+
+	/// Discovers the configuration extension images that are merged into `/etc`.
+	///
+	/// # Errors
+	///
+	/// Any errors from non-existing image directories are ignored. Apart from that, any I/O errors from reading the image directories are propagated.
+	pub fn confext() -> io::Result<Self> {
+		Self::confext_in(Path::new("/"))
+	}
+
+	/// Same as [`confext`](Self::confext), but for the OS tree at `root`, such as a container image.
+	///
+	/// # Errors
+	///
+	/// See [`confext`](Self::confext).
+	pub fn confext_in(root: &Path) -> io::Result<Self> {
+		Self::discover(root, Path::new("etc"), CONFEXT_IMAGE_DIRECTORIES, Path::new("extension-release.d"))
+	}
+
+	/// Discovers the system extension images that are merged into `/usr`.
+	///
+	/// `systemd-sysext` also merges images into `/opt`. Use [`Extensions::new`] to look up files there.
+	///
+	/// # Errors
+	///
+	/// See [`confext`](Self::confext).
+	pub fn sysext() -> io::Result<Self> {
+		Self::sysext_in(Path::new("/"))
+	}
+
+	/// Same as [`sysext`](Self::sysext), but for the OS tree at `root`, such as a container image.
+	///
+	/// # Errors
+	///
+	/// See [`confext`](Self::confext).
+	pub fn sysext_in(root: &Path) -> io::Result<Self> {
+		Self::discover(root, Path::new("usr"), SYSEXT_IMAGE_DIRECTORIES, Path::new("lib/extension-release.d"))
+	}
+
+	/// Images are merged in the order of their names, and an image in an earlier image directory hides images with the same name in later ones.
+	///
+	/// Every image contains an `extension-release.<name>` file under `release_directory`, so the image is only merged
+	/// if that file is visible in the merged hierarchy.
+	fn discover(root: &Path, hierarchy: &Path, image_directories: &[&str], release_directory: &Path) -> io::Result<Self> {
+		let merged_hierarchy = root.join(hierarchy);
+
+		let mut images: BTreeMap<OsString, PathBuf> = Default::default();
+
+		for image_directory in image_directories {
+			let image_directory = root.join(image_directory);
+			let entries = match fs::read_dir(&image_directory) {
+				Ok(entries) => entries,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err),
+			};
+			for entry in entries {
+				let name = entry?.file_name();
+				if name.as_bytes().starts_with(b".") || images.contains_key(&name) {
+					continue;
+				}
+
+				let path = image_directory.join(&name);
+				match fs::metadata(&path) {
+					Ok(metadata) if metadata.is_dir() => (),
+					Ok(_) => continue,
+					Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+					Err(err) => return Err(err),
+				}
+
+				images.insert(name, path);
+			}
+		}
+
+		let mut result = Self::new(&merged_hierarchy);
+
+		for (name, path) in images {
+			let mut release_file_name = OsString::from("extension-release.");
+			release_file_name.push(&name);
+			let is_merged = match fs::symlink_metadata(merged_hierarchy.join(release_directory).join(release_file_name)) {
+				Ok(_) => true,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+				Err(err) => return Err(err),
+			};
+			if is_merged {
+				result = result.image(name, path.join(hierarchy));
+			}
+		}
+
+		Ok(result)
+	}
+
+	/// The names of the images, lowest-precedence-first.
+	pub fn images(&self) -> impl DoubleEndedIterator<Item = &OsStr> + ExactSizeIterator {
+		self.images.iter().map(|image| &*image.name)
+	}
+
+	/// Returns the name of the image that the file at `path` in the merged hierarchy originates from, which is the image
+	/// with the highest precedence that contains it.
+	///
+	/// Returns `None` if no image contains the file, such as when it originates from the host, or if `path` is not in the hierarchy.
+	///
+	/// # Errors
+	///
+	/// Any I/O errors from looking up the file in the images, other than the file not existing, are propagated.
+	pub fn origin(&self, path: &Path) -> io::Result<Option<&OsStr>> {
+		let Ok(relative_path) = path.strip_prefix(&self.hierarchy) else {
+			return Ok(None);
+		};
+
+		for image in self.images.iter().rev() {
+			match fs::symlink_metadata(image.hierarchy.join(relative_path)) {
+				Ok(_) => return Ok(Some(&image.name)),
+				Err(err) if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => (),
+				Err(err) => return Err(err),
+			}
+		}
+
+		Ok(None)
+	}
+}
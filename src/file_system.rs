@@ -0,0 +1,375 @@
+//! Searching for config files through a [`FileSystem`] other than the one that the process has access to,
+//! such as a virtual, remote or overlay filesystem.
+
+use std::{
+	ffi::{OsStr, OsString},
+	fs,
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use crate::{
+	dir::{self, Dir, EntryKind, SearchDir},
+	platform,
+	EntryMetadata,
+	FindOptions,
+	FoundFile,
+	FoundFiles,
+	SearchContext,
+	SearchDirectoriesForFileName,
+	SearchDirectoriesForProject,
+	SearchDirectoriesForProjectAndFileName,
+	SymlinkPolicy,
+};
+
+/// A filesystem that config files can be searched for in, with the `find_files_in` methods.
+///
+/// [`StdFileSystem`] is the filesystem that the process has access to. Implement this trait to search virtual filesystems,
+/// remote filesystems, overlays of multiple filesystems, etc, with the same precedence rules as the regular search.
+pub trait FileSystem {
+	/// The type of an opened file.
+	type File;
+
+	/// The iterator of file names returned by [`read_dir`](Self::read_dir).
+	type ReadDir: Iterator<Item = io::Result<OsString>>;
+
+	/// Returns the names of the entries of the directory at `path`, excluding `.` and `..`, in any order.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the directory does not exist, which the search ignores.
+	/// Any other error is propagated by the search.
+	fn read_dir(&self, path: &Path) -> io::Result<Self::ReadDir>;
+
+	/// Returns the metadata of the file at `path`, following symlinks.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the file does not exist, which the search ignores.
+	/// Any other error is propagated by the search.
+	fn metadata(&self, path: &Path) -> io::Result<FileSystemMetadata>;
+
+	/// Opens the file at `path` for reading.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the file does not exist, which the search ignores.
+	/// Any other error is propagated by the search.
+	fn open(&self, path: &Path) -> io::Result<Self::File>;
+}
+
+/// The metadata of a file in a [`FileSystem`] that the search needs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileSystemMetadata {
+	is_file: bool,
+	size: u64,
+}
+
+impl FileSystemMetadata {
+	/// The metadata of a regular file with the given size in bytes.
+	pub fn file(size: u64) -> Self {
+		Self { is_file: true, size }
+	}
+
+	/// The metadata of a directory or any other file that is not a regular file. The search ignores such files.
+	pub fn other() -> Self {
+		Self { is_file: false, size: 0 }
+	}
+
+	/// Whether the file is a regular file.
+	pub fn is_file(&self) -> bool {
+		self.is_file
+	}
+
+	/// The size of the file in bytes.
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+}
+
+impl From<&fs::Metadata> for FileSystemMetadata {
+	fn from(metadata: &fs::Metadata) -> Self {
+		if metadata.is_file() { Self::file(metadata.len()) } else { Self::other() }
+	}
+}
+
+/// The filesystem that the process has access to, using the APIs in [`std::fs`].
+///
+/// The `find_files_in` methods with this filesystem find the same files as the `find_files` methods.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+	type File = fs::File;
+
+	type ReadDir = std::iter::Map<fs::ReadDir, fn(io::Result<fs::DirEntry>) -> io::Result<OsString>>;
+
+	fn read_dir(&self, path: &Path) -> io::Result<Self::ReadDir> {
+		Ok(fs::read_dir(path)?.map(|entry| Ok(entry?.file_name())))
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<FileSystemMetadata> {
+		Ok((&fs::metadata(path)?).into())
+	}
+
+	/// Opens the file at `path` for reading without blocking if it is a FIFO.
+	///
+	/// Returns an error of kind [`io::ErrorKind::InvalidData`] if the file is not a regular file.
+	fn open(&self, path: &Path) -> io::Result<Self::File> {
+		platform::open_regular_file(path)
+	}
+}
+
+/// The iterator of files returned by the `find_files_in` methods.
+///
+/// Files are yielded in lowest-precedence-first order, ie the main file (if any) followed by the dropins.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct FileSystemFiles<T> {
+	inner: std::vec::IntoIter<(PathBuf, T)>,
+}
+
+impl<T> Iterator for FileSystemFiles<T> {
+	type Item = (PathBuf, T);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl<T> DoubleEndedIterator for FileSystemFiles<T> {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.inner.next_back()
+	}
+}
+
+impl<T> ExactSizeIterator for FileSystemFiles<T> {}
+
+impl<T> std::iter::FusedIterator for FileSystemFiles<T> {}
+
+impl<TProject> SearchDirectoriesForProject<'_, TProject> {
+	/// Same as [`find_files_with`](Self::find_files_with), but searches the given [`FileSystem`].
+	///
+	/// Only the options that select, order and limit dropins and files by their names and sizes are supported, ie
+	/// [`FindOptions::skip_backup_files`], [`FindOptions::skip_hidden_files`], [`FindOptions::dropin_suffix_match`], [`FindOptions::ordering`],
+	/// [`FindOptions::template_dropins`], [`FindOptions::type_dropins`], [`FindOptions::prefix_dropins`], [`FindOptions::max_dropins`],
+	/// [`FindOptions::max_file_size`] and `FindOptions::dropin_regex`. The others depend on how the filesystem resolves paths and identifies files,
+	/// which the search does not know.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::Unsupported`] if any other option is set.
+	/// Apart from that, see [`find_files`](Self::find_files).
+	pub fn find_files_in<F, TDropinSuffix>(
+		self,
+		file_system: &F,
+		dropin_suffix: TDropinSuffix,
+		options: &FindOptions,
+	) -> io::Result<FileSystemFiles<F::File>>
+	where
+		F: FileSystem,
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		check_options(options)?;
+
+		let file_system = PathFileSystem(file_system);
+		let context = SearchContext::with_file_system(options, &file_system);
+		let paths: Vec<&Path> = self.inner.iter().map(AsRef::as_ref).collect();
+		open_all(file_system.0, self.find_in(&context, &paths, dropin_suffix.as_ref())?)
+	}
+}
+
+impl<TFileName> SearchDirectoriesForFileName<'_, TFileName> {
+	/// Same as [`find_files_with`](Self::find_files_with), but searches the given [`FileSystem`].
+	///
+	/// See [`SearchDirectoriesForProject::find_files_in`] for the options that are supported.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::Unsupported`] if an option that is not supported is set.
+	/// Apart from that, see [`find_files`](Self::find_files).
+	pub fn find_files_in<F, TDropinSuffix>(
+		self,
+		file_system: &F,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<FileSystemFiles<F::File>>
+	where
+		F: FileSystem,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		check_options(options)?;
+
+		let file_system = PathFileSystem(file_system);
+		let context = SearchContext::with_file_system(options, &file_system);
+		let paths: Vec<&Path> = self.inner.iter().map(AsRef::as_ref).collect();
+		open_all(file_system.0, self.find_in(&context, &paths, dropin_suffix)?)
+	}
+}
+
+impl<TProject, TFileName> SearchDirectoriesForProjectAndFileName<'_, TProject, TFileName> {
+	/// Same as [`find_files_with`](Self::find_files_with), but searches the given [`FileSystem`].
+	///
+	/// See [`SearchDirectoriesForProject::find_files_in`] for the options that are supported.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::Unsupported`] if an option that is not supported is set.
+	/// Apart from that, see [`find_files`](Self::find_files).
+	pub fn find_files_in<F, TDropinSuffix>(
+		self,
+		file_system: &F,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<FileSystemFiles<F::File>>
+	where
+		F: FileSystem,
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		check_options(options)?;
+
+		let file_system = PathFileSystem(file_system);
+		let context = SearchContext::with_file_system(options, &file_system);
+		let paths: Vec<&Path> = self.inner.iter().map(AsRef::as_ref).collect();
+		open_all(file_system.0, self.find_in(&context, &paths, dropin_suffix)?)
+	}
+}
+
+/// Returns an error of kind [`io::ErrorKind::Unsupported`] if any of the options that the `find_files_in` methods do not support is set,
+/// rather than silently finding different files than the `find_files_with` methods would.
+fn check_options(options: &FindOptions) -> io::Result<()> {
+	let unsupported = [
+		("open_options", options.open_options.is_some()),
+		("symlink_policy", options.symlink_policy != SymlinkPolicy::Follow),
+		("dropin_directory_symlink_policy", options.dropin_directory_symlink_policy != SymlinkPolicy::Follow),
+		("canonicalize_paths", options.canonicalize_paths),
+		("dedupe_canonical_paths", options.dedupe_canonical_paths),
+		("dedupe_inodes", options.dedupe_inodes),
+		("dedupe_search_directories", options.dedupe_search_directories),
+		("require_main_file", options.require_main_file),
+		("require_files", options.require_files),
+		("permission_policy", options.permission_policy.is_some()),
+		("executables_only", options.executables_only),
+		("conditional_dropin_subdirectories", options.conditional_dropin_subdirectories.is_some()),
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		("filesystem_condition", options.filesystem_condition.is_some()),
+		("search_directory_timeout", options.search_directory_timeout.is_some()),
+		("skip_diagnostics", options.skip_diagnostics.is_some()),
+		("observer", options.observer.is_some()),
+		#[cfg(all(feature = "io-uring", target_os = "linux"))]
+		("io_uring", options.io_uring),
+	];
+	match unsupported.into_iter().find(|&(_, is_set)| is_set) {
+		Some((name, _)) => Err(io::Error::new(io::ErrorKind::Unsupported, format!("FindOptions::{name} is not supported when searching a FileSystem"))),
+		None => Ok(()),
+	}
+}
+
+/// Opens the files that the search found through `file_system`, skipping any that have been removed since they were found.
+fn open_all<F>(file_system: &F, (main_file, dropins): FoundFiles<PathBuf>) -> io::Result<FileSystemFiles<F::File>> where F: FileSystem {
+	let mut result = vec![];
+	for FoundFile { dir, file_name, .. } in main_file.into_iter().chain(dropins) {
+		let path = dir.join(file_name);
+		match file_system.open(&path) {
+			Ok(file) => result.push((path, file)),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => return Err(err),
+		}
+	}
+
+	Ok(FileSystemFiles {
+		inner: result.into_iter(),
+	})
+}
+
+/// The operations that the search performs on a filesystem, with [`Dir`](Self::Dir) as the handle of an opened directory.
+///
+/// This is implemented by [`StdFileSystem`] with [`dir::Dir`] as its handle, by [`PathFileSystem`] for any other [`FileSystem`],
+/// and by the filesystem of `CapSearchDirectories`.
+pub(crate) trait SearchFileSystem {
+	type Dir: SearchDir;
+
+	type FileNames: Iterator<Item = io::Result<(OsString, EntryKind)>>;
+
+	/// Returns the file names and types of the entries of `dir`, excluding `.` and `..`.
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the directory does not exist.
+	fn file_names(&self, dir: &Self::Dir) -> io::Result<Self::FileNames>;
+
+	/// Returns the metadata of the file named `file_name` in `dir`, following symlinks if `follow_symlinks` is `true`.
+	///
+	/// `scratch` is used as a buffer for building the path of the file, if necessary.
+	fn metadata(&self, dir: &Self::Dir, file_name: &OsStr, follow_symlinks: bool, scratch: &mut PathBuf) -> io::Result<EntryMetadata>;
+}
+
+/// A [`SearchFileSystem`] whose directories can be opened by their paths.
+pub(crate) trait OpenDir: SearchFileSystem + Sized {
+	/// Opens the directory at `path`.
+	///
+	/// Returns an error of kind [`io::ErrorKind::NotFound`] if the directory does not exist.
+	fn open_dir(context: &SearchContext<'_, Self>, path: PathBuf) -> io::Result<Arc<Self::Dir>>;
+}
+
+impl SearchFileSystem for StdFileSystem {
+	type Dir = Dir;
+
+	type FileNames = dir::FileNames;
+
+	fn file_names(&self, dir: &Dir) -> io::Result<Self::FileNames> {
+		dir.file_names()
+	}
+
+	fn metadata(&self, dir: &Dir, file_name: &OsStr, follow_symlinks: bool, scratch: &mut PathBuf) -> io::Result<EntryMetadata> {
+		dir.metadata(file_name, follow_symlinks, scratch)
+	}
+}
+
+impl OpenDir for StdFileSystem {
+	fn open_dir(context: &SearchContext<'_, Self>, path: PathBuf) -> io::Result<Arc<Dir>> {
+		match context.finder_cache.as_ref().and_then(|finder_cache| finder_cache.open_dir(&path, context.root.as_ref())) {
+			Some(dir) => dir,
+			None => Dir::open(path, context.root.as_ref()).map(Arc::new),
+		}
+	}
+}
+
+/// Searches a [`FileSystem`] through paths, since it has no handles for directories.
+struct PathFileSystem<'a, F>(&'a F);
+
+impl SearchDir for PathBuf {
+	fn path(&self) -> &Path {
+		self
+	}
+}
+
+impl<F> SearchFileSystem for PathFileSystem<'_, F> where F: FileSystem {
+	type Dir = PathBuf;
+
+	type FileNames = std::iter::Map<F::ReadDir, fn(io::Result<OsString>) -> io::Result<(OsString, EntryKind)>>;
+
+	fn file_names(&self, dir: &PathBuf) -> io::Result<Self::FileNames> {
+		// The filesystem does not report the types of entries, so they are all looked up.
+		Ok(self.0.read_dir(dir)?.map(|file_name| Ok((file_name?, EntryKind::Unknown))))
+	}
+
+	fn metadata(&self, dir: &PathBuf, file_name: &OsStr, _follow_symlinks: bool, scratch: &mut PathBuf) -> io::Result<EntryMetadata> {
+		// Symlinks are always followed, since `check_options` only allows `SymlinkPolicy::Follow`.
+		Ok(self.0.metadata(dir.path_of(file_name, scratch))?.into())
+	}
+}
+
+impl<F> OpenDir for PathFileSystem<'_, F> where F: FileSystem {
+	// Directories that do not exist are skipped when they are read.
+	fn open_dir(_context: &SearchContext<'_, Self>, path: PathBuf) -> io::Result<Arc<PathBuf>> {
+		Ok(Arc::new(path))
+	}
+}
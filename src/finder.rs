@@ -0,0 +1,186 @@
+//! A long-lived set of search directories for resolving many names, with caching of the directories that do not exist
+//! and of the search directories that were opened.
+
+use std::{
+	collections::{HashMap, HashSet},
+	ffi::OsStr,
+	io,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{Dir, Root, SearchDirectories, SearchDirectoriesForFileName, SearchDirectoriesForFileNameGlob, SearchDirectoriesForFileNameSuffix, SearchDirectoriesForProject};
+
+/// A list of search directories that is used to resolve many project names or file names, such as all the units of a service manager.
+///
+/// Most `<name>.d` directories usually do not exist, so every search through a `Finder` remembers which directories were not found
+/// and does not look them up again in later searches. Call [`invalidate`](Self::invalidate) or
+/// [`invalidate_directory`](Self::invalidate_directory) when directories may have been created, such as when reloading configuration.
+///
+/// With the `rustix` feature on Linux, the search directories are also opened once and kept open for the lifetime of the `Finder`,
+/// such as for a service that resolves config on every client connection. The files in them and the dropin directories under them
+/// are looked up relative to these file descriptors with `openat`, so every search does not resolve the full paths of the search directories
+/// again, and renaming a search directory or a directory above it in the middle of a search cannot cause files to be read from two different places.
+/// Call [`invalidate`](Self::invalidate) if the search directories themselves may have been replaced, since the `Finder` keeps using the old ones
+/// until then. Search directories created with [`SearchDirectories::chroot_contained`] are not kept open, since every lookup must be contained
+/// in the root.
+///
+/// Without the `rustix` feature, directories are only opened when they are read, so only missing dropin directories are remembered.
+///
+/// # Examples
+///
+/// ```rust
+/// let finder = uapi_config::Finder::new(uapi_config::SearchDirectories::modern_system());
+///
+/// for project in ["foo", "bar", "baz"] {
+///     let files = finder.with_project(project).find_files(".conf").unwrap();
+///     // Parse and merge `files` for `project`
+/// }
+///
+/// // Some directories may have been created since, so look them up again.
+/// finder.invalidate();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Finder<'a> {
+	search_directories: SearchDirectories<'a>,
+}
+
+impl<'a> Finder<'a> {
+	/// Start with the given search directories.
+	pub fn new(mut search_directories: SearchDirectories<'a>) -> Self {
+		let finder_cache = FinderCache::new(search_directories.inner.iter().map(|path| path.clone().into_owned()).collect());
+		search_directories.finder_cache = Some(Arc::new(finder_cache));
+		Self { search_directories }
+	}
+
+	/// Search for configuration files for the given project name, like [`SearchDirectories::with_project`].
+	pub fn with_project<TProject>(&self, project: TProject) -> SearchDirectoriesForProject<'a, TProject> {
+		self.search_directories.clone().with_project(project)
+	}
+
+	/// Search for configuration files with the given config file name, like [`SearchDirectories::with_file_name`].
+	pub fn with_file_name<TFileName>(&self, file_name: TFileName) -> SearchDirectoriesForFileName<'a, TFileName> where TFileName: AsRef<OsStr> {
+		self.search_directories.clone().with_file_name(file_name)
+	}
+
+	/// Search for configuration files whose names match the given glob pattern, like [`SearchDirectories::with_file_name_glob`].
+	pub fn with_file_name_glob<TPattern>(&self, pattern: TPattern) -> SearchDirectoriesForFileNameGlob<'a, TPattern> {
+		self.search_directories.clone().with_file_name_glob(pattern)
+	}
+
+	/// Search for configuration files whose names end with the given suffix, like [`SearchDirectories::with_file_name_suffix`].
+	pub fn with_file_name_suffix<TSuffix>(&self, suffix: TSuffix) -> SearchDirectoriesForFileNameSuffix<'a, TSuffix> {
+		self.search_directories.clone().with_file_name_suffix(suffix)
+	}
+
+	/// Forgets all the directories that were not found and closes the search directories that were kept open,
+	/// so that later searches look them up again.
+	///
+	/// Searches that are in progress are not affected.
+	pub fn invalidate(&self) {
+		if let Some(finder_cache) = &self.search_directories.finder_cache {
+			finder_cache.clear();
+		}
+	}
+
+	/// Forgets that the directory at `path` and all directories under it were not found, and closes any search directories
+	/// at or under `path` that were kept open, so that later searches look them up again.
+	///
+	/// `path` is the full path of the directory, including any root that the search directories were [`chroot`](SearchDirectories::chroot)ed to.
+	pub fn invalidate_directory(&self, path: &Path) {
+		if let Some(finder_cache) = &self.search_directories.finder_cache {
+			finder_cache.remove(path);
+		}
+	}
+}
+
+impl<'a> From<SearchDirectories<'a>> for Finder<'a> {
+	fn from(search_directories: SearchDirectories<'a>) -> Self {
+		Self::new(search_directories)
+	}
+}
+
+/// The state that is shared by all the searches made through a [`Finder`].
+#[derive(Debug, Default)]
+pub(crate) struct FinderCache {
+	/// The directories that were not found.
+	missing_directories: Mutex<HashSet<PathBuf>>,
+
+	/// The paths of the search directories of the `Finder`.
+	search_directory_paths: Vec<PathBuf>,
+
+	/// The search directories that have been opened, by path.
+	open_search_directories: Mutex<HashMap<PathBuf, Arc<Dir>>>,
+}
+
+impl FinderCache {
+	fn new(search_directory_paths: Vec<PathBuf>) -> Self {
+		Self {
+			search_directory_paths,
+			..Default::default()
+		}
+	}
+
+	pub(crate) fn contains(&self, path: &Path) -> bool {
+		lock(&self.missing_directories).contains(path)
+	}
+
+	pub(crate) fn insert(&self, path: PathBuf) {
+		lock(&self.missing_directories).insert(path);
+	}
+
+	/// Opens the directory at `path`, reusing the search directory at `path` or opening the directory relative to the search directory
+	/// that contains it. The search directories are opened and kept open the first time they are needed.
+	///
+	/// Returns `None` if the directory must be opened by the caller instead, because it is not under any search directory
+	/// or because path resolution is contained in a root.
+	pub(crate) fn open_dir(&self, path: &Path, root: Option<&Arc<Root>>) -> Option<io::Result<Arc<Dir>>> {
+		if root.is_some() {
+			return None;
+		}
+
+		// A search directory may be nested in another one, so use the one that is the closest ancestor of `path`.
+		let search_directory_path =
+			self.search_directory_paths.iter()
+			.filter(|search_directory_path| path.starts_with(search_directory_path))
+			.max_by_key(|search_directory_path| search_directory_path.components().count())?;
+
+		let mut open_search_directories = lock(&self.open_search_directories);
+		let search_directory =
+			if let Some(search_directory) = open_search_directories.get(search_directory_path) {
+				search_directory.clone()
+			}
+			else {
+				let search_directory = match Dir::open(search_directory_path.clone(), None) {
+					Ok(search_directory) => Arc::new(search_directory),
+					Err(err) => return Some(Err(err)),
+				};
+				open_search_directories.insert(search_directory_path.clone(), search_directory.clone());
+				search_directory
+			};
+		drop(open_search_directories);
+
+		let relative_path = path.strip_prefix(search_directory_path).ok()?;
+		if relative_path.as_os_str().is_empty() {
+			Some(Ok(search_directory))
+		}
+		else {
+			Some(search_directory.open_dir(relative_path).map(Arc::new))
+		}
+	}
+
+	fn remove(&self, path: &Path) {
+		lock(&self.missing_directories).retain(|missing| !missing.starts_with(path));
+		lock(&self.open_search_directories).retain(|search_directory_path, _| !search_directory_path.starts_with(path));
+	}
+
+	fn clear(&self) {
+		lock(&self.missing_directories).clear();
+		lock(&self.open_search_directories).clear();
+	}
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+	// The state is always consistent, so it is fine to keep using it even if another thread panicked while holding the lock.
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,210 @@
+//! Skipping search directories that are on network filesystems or that do not respond, so that a hung mount
+//! cannot block the search forever.
+
+use std::{
+	collections::BTreeSet,
+	io,
+	path::{Path, PathBuf},
+	sync::{mpsc, Mutex, PoisonError},
+};
+
+use crate::{FindOptions, SkipReason};
+
+/// The type of a filesystem, as reported by `statfs(2)` in `f_type`.
+///
+/// Passed to the function of a [`FilesystemCondition`]. The associated constants are the types of network filesystems,
+/// and of FUSE filesystems, which are often backed by the network. Other types can be created from their magic numbers,
+/// as listed in `man 2 statfs`.
+///
+/// Requires the `rustix` feature.
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FilesystemType(u32);
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+impl FilesystemType {
+	/// AFS.
+	pub const AFS: Self = Self(0x5346_414f);
+
+	/// Ceph.
+	pub const CEPH: Self = Self(0x00c3_6400);
+
+	/// CIFS.
+	pub const CIFS: Self = Self(0xff53_4d42);
+
+	/// Coda.
+	pub const CODA: Self = Self(0x7375_7245);
+
+	/// FUSE.
+	pub const FUSE: Self = Self(0x6573_5546);
+
+	/// NCP.
+	pub const NCP: Self = Self(0x564c);
+
+	/// NFS.
+	pub const NFS: Self = Self(0x6969);
+
+	/// SMB.
+	pub const SMB: Self = Self(0x517b);
+
+	/// SMB2 and SMB3.
+	pub const SMB2: Self = Self(0xfe53_4d42);
+
+	/// 9P, such as the shared folders of virtual machines.
+	pub const V9FS: Self = Self(0x0102_1997);
+
+	/// The network filesystems among the associated constants, ie all of them other than [`FUSE`](Self::FUSE).
+	const NETWORK: &'static [Self] = &[Self::AFS, Self::CEPH, Self::CIFS, Self::CODA, Self::NCP, Self::NFS, Self::SMB, Self::SMB2, Self::V9FS];
+
+	/// The filesystem type with the given magic number.
+	pub const fn from_magic(magic: u32) -> Self {
+		Self(magic)
+	}
+
+	/// The magic number of this filesystem type.
+	pub const fn magic(self) -> u32 {
+		self.0
+	}
+
+	/// Returns `true` if this is one of the network filesystems among the associated constants.
+	pub fn is_network(self) -> bool {
+		Self::NETWORK.contains(&self)
+	}
+}
+
+/// Decides which search directories are searched, based on the type of the filesystem that they are on,
+/// such as to skip search directories on network filesystems.
+///
+/// Set with [`FindOptions::filesystem_condition`](crate::FindOptions::filesystem_condition).
+///
+/// Requires the `rustix` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// let options = uapi_config::FindOptions::new().filesystem_condition(uapi_config::FilesystemCondition::local_only());
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_user_directory()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &options)
+///     .unwrap();
+/// ```
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+#[derive(Clone)]
+pub struct FilesystemCondition(std::sync::Arc<FilesystemConditionFn>);
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+type FilesystemConditionFn = dyn Fn(&Path, FilesystemType) -> bool + Send + Sync;
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+impl FilesystemCondition {
+	/// Searches the search directories for which `f` returns `true`, given the path of the search directory and the type of
+	/// the filesystem that it is on.
+	pub fn new<F>(f: F) -> Self where F: Fn(&Path, FilesystemType) -> bool + Send + Sync + 'static {
+		Self(std::sync::Arc::new(f))
+	}
+
+	/// Skips the search directories that are on any of the given filesystem types.
+	pub fn skip_types<I>(filesystem_types: I) -> Self where I: IntoIterator<Item = FilesystemType> {
+		let filesystem_types: Vec<_> = filesystem_types.into_iter().collect();
+		Self::new(move |_, filesystem_type| !filesystem_types.contains(&filesystem_type))
+	}
+
+	/// Skips the search directories that are on network filesystems or on FUSE filesystems.
+	pub fn local_only() -> Self {
+		Self::new(|_, filesystem_type| filesystem_type != FilesystemType::FUSE && !filesystem_type.is_network())
+	}
+
+	fn matches(&self, path: &Path, filesystem_type: FilesystemType) -> bool {
+		(self.0)(path, filesystem_type)
+	}
+}
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+impl std::fmt::Debug for FilesystemCondition {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("FilesystemCondition").finish_non_exhaustive()
+	}
+}
+
+/// The search directories whose lookups have been started by [`search_directory_skip_reason`] and have not finished yet.
+static PENDING: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Returns why the search directory at `path` must be skipped according to [`FindOptions::filesystem_condition`]
+/// and [`FindOptions::search_directory_timeout`], or `None` if it is searched.
+///
+/// A search directory that does not exist is searched, since the search skips it anyway.
+pub(crate) fn search_directory_skip_reason(path: &Path, options: &FindOptions) -> io::Result<Option<SkipReason>> {
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	let filesystem_condition = options.filesystem_condition.clone();
+
+	let check = {
+		let path = path.to_owned();
+		move || -> io::Result<Option<SkipReason>> {
+			#[cfg(all(feature = "rustix", target_os = "linux"))]
+			if let Some(filesystem_condition) = filesystem_condition {
+				return match statfs(&path) {
+					Ok(filesystem_type) if filesystem_condition.matches(&path, filesystem_type) => Ok(None),
+					Ok(_) => Ok(Some(SkipReason::Filesystem)),
+					Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+					Err(err) => Err(err),
+				};
+			}
+
+			// Only the timeout is set, so access the directory to find out whether it responds.
+			match std::fs::metadata(&path) {
+				Ok(_) => Ok(None),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+				Err(err) => Err(err),
+			}
+		}
+	};
+
+	let Some(timeout) = options.search_directory_timeout else {
+		return check();
+	};
+
+	// If the directory does not respond, the thread stays blocked in the kernel. There is nothing that can be done about that,
+	// so the thread is left behind and its result is dropped if it ever finishes. Until then, later searches skip the directory
+	// immediately instead of leaving another thread behind.
+	if !PENDING.lock().unwrap_or_else(PoisonError::into_inner).insert(path.to_owned()) {
+		return Ok(Some(SkipReason::TimedOut));
+	}
+
+	let (sender, receiver) = mpsc::sync_channel(1);
+	let spawned = {
+		let path = path.to_owned();
+		std::thread::Builder::new()
+			.name("uapi-config-search-directory".to_owned())
+			.spawn(move || {
+				let result = check();
+				PENDING.lock().unwrap_or_else(PoisonError::into_inner).remove(&path);
+				_ = sender.send(result);
+			})
+	};
+	if let Err(err) = spawned {
+		PENDING.lock().unwrap_or_else(PoisonError::into_inner).remove(path);
+		return Err(err);
+	}
+
+	match receiver.recv_timeout(timeout) {
+		Ok(result) => result,
+		Err(mpsc::RecvTimeoutError::Timeout) => Ok(Some(SkipReason::TimedOut)),
+		Err(mpsc::RecvTimeoutError::Disconnected) => {
+			PENDING.lock().unwrap_or_else(PoisonError::into_inner).remove(path);
+			Err(io::Error::other(format!("checking {} panicked", path.display())))
+		},
+	}
+}
+
+/// Returns the type of the filesystem that `path` is on.
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+fn statfs(path: &Path) -> io::Result<FilesystemType> {
+	let statfs = rustix::fs::statfs(path)?;
+
+	// The type of `f_type` differs between architectures and libcs, but the magic numbers are all 32-bit.
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::unnecessary_cast)]
+	let magic = statfs.f_type as u32;
+	Ok(FilesystemType(magic))
+}
@@ -0,0 +1,147 @@
+/// Matches `name` against the shell-style glob `pattern`.
+///
+/// Supports `*` (any sequence of bytes), `?` (any single byte), bracket expressions like `[abc]`, `[a-z]` and `[!abc]` / `[^abc]`,
+/// and `\` to escape the next byte. Since this is only used to match file names, there is no special handling of `/` or leading `.`.
+pub(crate) fn matches(pattern: &[u8], name: &[u8]) -> bool {
+	let mut p = 0;
+	let mut n = 0;
+
+	// The position in the pattern right after the most recent `*`, and the position in the name that it was tried against.
+	let mut backtrack = None;
+
+	loop {
+		if p < pattern.len() {
+			match pattern[p] {
+				b'*' => {
+					p += 1;
+					backtrack = Some((p, n));
+					continue;
+				},
+
+				b'?' if n < name.len() => {
+					p += 1;
+					n += 1;
+					continue;
+				},
+
+				b'[' if n < name.len() => if let Some((matched, len)) = match_bracket(&pattern[p..], name[n]) {
+					if matched {
+						p += len;
+						n += 1;
+						continue;
+					}
+				}
+				else if name[n] == b'[' {
+					// Unterminated bracket expression, so match it literally.
+					p += 1;
+					n += 1;
+					continue;
+				},
+
+				b'\\' if p + 1 < pattern.len() => if n < name.len() && name[n] == pattern[p + 1] {
+					p += 2;
+					n += 1;
+					continue;
+				},
+
+				c => if n < name.len() && name[n] == c {
+					p += 1;
+					n += 1;
+					continue;
+				},
+			}
+		}
+		else if n == name.len() {
+			return true;
+		}
+
+		// Mismatch. Let the most recent `*` consume one more byte of the name and retry.
+		match backtrack {
+			Some((backtrack_p, backtrack_n)) if backtrack_n < name.len() => {
+				p = backtrack_p;
+				n = backtrack_n + 1;
+				backtrack = Some((backtrack_p, n));
+			},
+
+			_ => return false,
+		}
+	}
+}
+
+/// Matches `c` against the bracket expression at the start of `pattern`.
+///
+/// Returns whether `c` matched and the length of the bracket expression,
+/// or `None` if the bracket expression is not terminated.
+fn match_bracket(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+	let mut i = 1;
+
+	let negated = matches!(pattern.get(i), Some(b'!' | b'^'));
+	if negated {
+		i += 1;
+	}
+
+	let mut matched = false;
+	let mut first = true;
+
+	loop {
+		let mut start = *pattern.get(i)?;
+		if start == b']' && !first {
+			return Some((matched != negated, i + 1));
+		}
+		first = false;
+
+		if start == b'\\' {
+			i += 1;
+			start = *pattern.get(i)?;
+		}
+		i += 1;
+
+		let end =
+			if pattern.get(i) == Some(&b'-') && pattern.get(i + 1).is_some_and(|&end| end != b']') {
+				let mut end = pattern[i + 1];
+				i += 2;
+				if end == b'\\' {
+					end = *pattern.get(i)?;
+					i += 1;
+				}
+				end
+			}
+			else {
+				start
+			};
+
+		if (start..=end).contains(&c) {
+			matched = true;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn matches() {
+		for (pattern, name, expected) in [
+			("*.network", "a.network", true),
+			("*.network", ".network", true),
+			("*.network", "a.network.d", false),
+			("*.network", "a.netdev", false),
+			("a?c", "abc", true),
+			("a?c", "ac", false),
+			("*a*b*", "xaxxbx", true),
+			("*a*b*", "xbxxax", false),
+			("[0-9][0-9]-*", "10-foo", true),
+			("[0-9][0-9]-*", "1-foo", false),
+			("[!a]*", "abc", false),
+			("[^a]*", "bcd", true),
+			("[]]", "]", true),
+			("[a-]", "-", true),
+			(r"\*", "*", true),
+			(r"\*", "a", false),
+			("[abc", "[abc", true),
+			("", "", true),
+			("", "a", false),
+		] {
+			assert_eq!(super::matches(pattern.as_bytes(), name.as_bytes()), expected, "{pattern:?} {name:?}");
+		}
+	}
+}
@@ -0,0 +1,211 @@
+//! Fetching config from URLs as a layer of [`Layers`](crate::Layers).
+
+use std::{
+	fmt::Write as _,
+	fs,
+	io::{self, Read},
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use crate::LayerProvider;
+
+/// A [`LayerProvider`] whose entries are fetched from URLs, such as an override that a fleet manager serves to all machines.
+///
+/// Each response is saved to a cache directory together with its `ETag`, so that later fetches of the same URL send `If-None-Match`
+/// and reuse the saved response if the server replies with `304 Not Modified`. If the server cannot be reached or replies with an error,
+/// the saved response is used instead, so a machine keeps its last known config while it is offline.
+/// A `404 Not Found` or `410 Gone` response means that the URL has no config, like a file that does not exist,
+/// so the URL produces no entry and its saved response is deleted.
+///
+/// Entries are named by their URL and are in the order that the URLs were added, so later URLs have higher precedence.
+/// Add the layer to [`Layers`](crate::Layers) at the precedence that it should have relative to the search directories,
+/// such as after the layer of the files in `/usr/etc` and before the layer of the files in `/etc` so that local admins can still override it.
+///
+/// Requires the `http` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let vendor = uapi_config::SearchDirectories::try_from(vec!["/usr/etc".into()]).unwrap().with_file_name("foobar.conf");
+/// let admin = uapi_config::SearchDirectories::try_from(vec!["/etc".into()]).unwrap().with_file_name("foobar.conf");
+/// let layers =
+///     uapi_config::Layers::new()
+///     .layer(uapi_config::SearchLayer::new("vendor", move || vendor.clone().find_files(Some(".conf"))))
+///     .layer(
+///         uapi_config::HttpLayer::new("fleet", "/var/cache/foobar/fleet")
+///         .url("https://config.example.com/foobar.conf")
+///     )
+///     .layer(uapi_config::SearchLayer::new("admin", move || admin.clone().find_files(Some(".conf"))));
+/// for (path, entry) in layers.entries().unwrap() {
+///     // Parse the entry
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct HttpLayer {
+	name: String,
+	cache_directory: PathBuf,
+	urls: Vec<String>,
+	timeout: Option<Duration>,
+}
+
+impl HttpLayer {
+	/// Creates a layer with the given name and no URLs, that saves responses to `cache_directory`.
+	///
+	/// The cache directory is created when the first response is saved. It should not be shared with other layers,
+	/// and should be somewhere that persists across reboots, such as `/var/cache`, so that the saved responses can be used
+	/// if the server cannot be reached at boot.
+	pub fn new<N, P>(name: N, cache_directory: P) -> Self where N: Into<String>, P: Into<PathBuf> {
+		Self {
+			name: name.into(),
+			cache_directory: cache_directory.into(),
+			urls: vec![],
+			timeout: None,
+		}
+	}
+
+	/// Appends a URL to fetch. URLs added later have higher precedence.
+	#[must_use]
+	pub fn url<U>(mut self, url: U) -> Self where U: Into<String> {
+		self.urls.push(url.into());
+		self
+	}
+
+	/// Limits the time to fetch each URL, after which the saved response is used as if the server could not be reached.
+	///
+	/// Defaults to no limit.
+	#[must_use]
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	fn fetch(&self, agent: &ureq::Agent, url: &str) -> io::Result<Option<Vec<u8>>> {
+		let cache_path = self.cache_directory.join(cache_file_name(url));
+		let mut etag_path = cache_path.clone().into_os_string();
+		etag_path.push(".etag");
+		let etag_path = PathBuf::from(etag_path);
+
+		let cached = match fs::read(&cache_path) {
+			Ok(contents) => Some(contents),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+			Err(err) => return Err(err),
+		};
+
+		let mut request = agent.get(url);
+		if cached.is_some() {
+			match fs::read_to_string(&etag_path) {
+				Ok(etag) => request = request.header("If-None-Match", etag),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+				Err(err) => return Err(err),
+			}
+		}
+
+		let mut response = match request.call() {
+			Ok(response) => response,
+			Err(err) => return cached.map(Some).ok_or_else(|| err.into_io()),
+		};
+
+		match response.status().as_u16() {
+			200 => {
+				let contents = response.body_mut().read_to_vec().map_err(ureq::Error::into_io)?;
+				let etag = response.headers().get("etag").and_then(|etag| etag.to_str().ok());
+				fs::create_dir_all(&self.cache_directory)?;
+				write_atomic(&cache_path, &contents)?;
+				match etag {
+					Some(etag) => write_atomic(&etag_path, etag.as_bytes())?,
+					None => remove_if_exists(&etag_path)?,
+				}
+				Ok(Some(contents))
+			},
+
+			304 if cached.is_some() => Ok(cached),
+
+			404 | 410 => {
+				remove_if_exists(&cache_path)?;
+				remove_if_exists(&etag_path)?;
+				Ok(None)
+			},
+
+			status => cached.map(Some).ok_or_else(|| io::Error::other(format!("{url} returned HTTP status {status}"))),
+		}
+	}
+}
+
+impl LayerProvider for HttpLayer {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Fetches every URL in order.
+	///
+	/// # Errors
+	///
+	/// Returns an error if a URL cannot be fetched and has no saved response, or if the cache directory cannot be read or written.
+	fn entries(&self) -> io::Result<Vec<(PathBuf, Box<dyn Read + Send>)>> {
+		let agent: ureq::Agent =
+			ureq::Agent::config_builder()
+			.http_status_as_error(false)
+			.timeout_global(self.timeout)
+			.build()
+			.into();
+
+		let mut entries = vec![];
+		for url in &self.urls {
+			if let Some(contents) = self.fetch(&agent, url)? {
+				entries.push((PathBuf::from(url), Box::new(io::Cursor::new(contents)) as _));
+			}
+		}
+		Ok(entries)
+	}
+}
+
+/// The longest name of a file in the cache directory for a URL. This leaves room under the 255-byte limit of most filesystems
+/// for the `.etag` suffix and the suffix of the temporary files of [`write_atomic`].
+const MAX_CACHE_FILE_NAME_LEN: usize = 200;
+
+/// Returns the name of the file in the cache directory for the given URL.
+///
+/// Every byte other than ASCII alphanumerics, `-` and `_` is percent-encoded, so that different URLs get different files.
+/// Since `.` is encoded too, the suffixes that are appended to the name for the `ETag` file and the temporary files cannot make it
+/// collide with the file of another URL. If the encoded name is too long, it is truncated and the SHA-256 digest of the URL
+/// is appended after a `.` instead.
+pub(crate) fn cache_file_name(url: &str) -> String {
+	let mut result = String::with_capacity(url.len());
+	for &b in url.as_bytes() {
+		if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_') {
+			result.push(char::from(b));
+		}
+		else {
+			_ = write!(result, "%{b:02X}");
+		}
+	}
+
+	if result.len() > MAX_CACHE_FILE_NAME_LEN {
+		use sha2::Digest as _;
+
+		let digest = sha2::Sha256::digest(url.as_bytes());
+		result.truncate(MAX_CACHE_FILE_NAME_LEN - 1 - 2 * digest.len());
+		result.push('.');
+		for b in digest {
+			_ = write!(result, "{b:02x}");
+		}
+	}
+
+	result
+}
+
+/// Replaces the file at `path` atomically, so that a concurrent reader sees either the old or the new contents.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+	let mut temp_path = path.to_owned().into_os_string();
+	temp_path.push(format!(".{}.tmp", std::process::id()));
+	fs::write(&temp_path, contents)?;
+	fs::rename(&temp_path, path)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+	match fs::remove_file(path) {
+		Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+		_ => Ok(()),
+	}
+}
@@ -0,0 +1,390 @@
+//! Expanding include directives in the files that were found, for legacy config formats that support them.
+
+use std::{
+	ffi::OsStr,
+	fs,
+	io::{self, Read as _},
+	path::{Component, Path, PathBuf},
+	sync::Arc,
+};
+
+use crate::{platform::{self, OsStrExt as _}, Dir, FileTooLargeError, Files, ReadError, Root, SymlinkPolicy};
+
+/// Options for expanding include directives with [`Files::read_all_with_includes`].
+///
+/// A directive is a line that starts with the directive name, followed by whitespace and the path of the file or directory to include.
+/// Relative paths are resolved relative to the directory of the file that contains the directive. Absolute paths are resolved under the root
+/// that the search directories were [`chroot`](crate::SearchDirectories::chroot)ed to, if the file is under one, so that a file in a container
+/// that includes `/etc/foo.conf` gets the container's file rather than the host's.
+///
+/// Included files are subject to the [`symlink_policy`](crate::FindOptions::symlink_policy) and [`max_file_size`](crate::FindOptions::max_file_size)
+/// of the search like the files that the search found, and are opened within the root of [`chroot_contained`](crate::SearchDirectories::chroot_contained)
+/// if the search directories were created with it.
+#[derive(Clone, Debug)]
+pub struct IncludeOptions {
+	include_directive: Vec<u8>,
+	include_dir_directive: Vec<u8>,
+	max_depth: usize,
+}
+
+impl IncludeOptions {
+	/// Start with the default options, which expand `.include <file>` and `.include-dir <directory>` up to a depth of 16 files.
+	pub fn new() -> Self {
+		Self {
+			include_directive: b".include".to_vec(),
+			include_dir_directive: b".include-dir".to_vec(),
+			max_depth: 16,
+		}
+	}
+
+	/// Set the name of the directive that includes a single file. Defaults to `.include`.
+	#[must_use]
+	pub fn include_directive<D>(mut self, include_directive: D) -> Self where D: Into<Vec<u8>> {
+		self.include_directive = include_directive.into();
+		self
+	}
+
+	/// Set the name of the directive that includes all the files in a directory, in lexicographic order of file name.
+	/// Hidden files whose names start with `.` and files that are not regular files are skipped. Defaults to `.include-dir`.
+	#[must_use]
+	pub fn include_dir_directive<D>(mut self, include_dir_directive: D) -> Self where D: Into<Vec<u8>> {
+		self.include_dir_directive = include_dir_directive.into();
+		self
+	}
+
+	/// Set the maximum number of files in a chain of includes, including the file that was found by the search. Defaults to 16.
+	#[must_use]
+	pub fn max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
+
+	/// Returns the path in `line` and whether it is a directory, if `line` is a directive.
+	fn directive<'a>(&self, line: &'a [u8]) -> Option<(&'a [u8], bool)> {
+		// The longer name is checked first, in case the other name is a prefix of it.
+		let mut directives = [(&self.include_directive, false), (&self.include_dir_directive, true)];
+		directives.sort_by_key(|(directive, _)| std::cmp::Reverse(directive.len()));
+
+		for (directive, is_dir) in directives {
+			let Some(rest) = line.strip_prefix(&directive[..]) else {
+				continue;
+			};
+			if !rest.first().is_some_and(u8::is_ascii_whitespace) {
+				continue;
+			}
+			let path = rest.trim_ascii();
+			if path.is_empty() {
+				continue;
+			}
+			return Some((path, is_dir));
+		}
+
+		None
+	}
+}
+
+impl Default for IncludeOptions {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Error returned by [`Files::read_all_with_includes`] when the includes cannot be expanded.
+///
+/// This is the [inner error](ReadError::io_error) of a [`ReadError`] for the file that contains the directive,
+/// wrapped in an [`io::Error`] of kind [`io::ErrorKind::InvalidData`].
+#[derive(Debug)]
+pub enum IncludeError {
+	/// The file at `path` includes itself, directly or indirectly.
+	Cycle {
+		/// The path of the included file, as written in the directive.
+		path: PathBuf,
+	},
+
+	/// The file at `path` is a symlink that the [`FindOptions::symlink_policy`](crate::FindOptions::symlink_policy) of the search does not allow.
+	///
+	/// Such files are skipped when they are in a directory included with [`IncludeOptions::include_dir_directive`].
+	Symlink {
+		/// The path of the included file, as written in the directive.
+		path: PathBuf,
+	},
+
+	/// Including the file at `path` would exceed the [`IncludeOptions::max_depth`] limit.
+	TooDeep {
+		/// The path of the included file, as written in the directive.
+		path: PathBuf,
+
+		/// The limit that was exceeded.
+		max_depth: usize,
+	},
+}
+
+impl std::fmt::Display for IncludeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Cycle { path } => write!(f, "{} is included recursively", path.display()),
+			Self::Symlink { path } => write!(f, "{} is a symlink that the symlink policy does not allow", path.display()),
+			Self::TooDeep { path, max_depth } => write!(f, "including {} exceeds the limit of {max_depth} nested includes", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for IncludeError {}
+
+impl Files {
+	/// Reads the contents of all the files, expanding the include directives in them recursively.
+	///
+	/// Each file is yielded in segments, so that the settings in included files override the settings before the directive
+	/// and are overridden by the settings after it. That is, a file `a` with the lines `x`, `.include b`, `y` is yielded as
+	/// `(a, "x\n")`, `(b, ...)`, `(a, "y\n")`. The directive lines are removed, so a file that only contains directives is not yielded at all.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(ReadError)` for the first file that could not be read, including included files.
+	/// If a file includes itself, the includes are nested too deeply or an included file is a symlink that the search does not allow,
+	/// the error is for the file that contains the directive and its inner error wraps an [`IncludeError`].
+	/// If an included file is larger than the [`FindOptions::max_file_size`](crate::FindOptions::max_file_size) limit,
+	/// the inner error wraps a [`FileTooLargeError`].
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	///     .read_all_with_includes(&Default::default())
+	///     .unwrap();
+	/// for (path, contents) in files {
+	///     // Parse `contents` and merge into the config
+	/// }
+	/// ```
+	pub fn read_all_with_includes(self, options: &IncludeOptions) -> Result<Vec<(PathBuf, Vec<u8>)>, ReadError> {
+		let context = self.includes.clone();
+		let mut result = vec![];
+
+		for (path, contents) in self.read_all()? {
+			let canonical_path = context.canonicalize(&path).map_err(|inner| ReadError { path: path.clone(), inner })?;
+			let mut stack = vec![canonical_path];
+			expand(path, &contents, options, &context, &mut stack, &mut result)?;
+		}
+
+		Ok(result)
+	}
+}
+
+/// The parts of the search that found the files that also apply to the files that they include.
+#[derive(Debug, Default)]
+pub(crate) struct IncludeContext {
+	/// The roots that the search directories were chrooted to.
+	pub(crate) chroots: Vec<PathBuf>,
+
+	/// The root that path resolution is contained in, if the search directories were created with
+	/// [`SearchDirectories::chroot_contained`](crate::SearchDirectories::chroot_contained).
+	pub(crate) root: Option<Arc<Root>>,
+
+	pub(crate) symlink_policy: SymlinkPolicy,
+
+	/// The canonicalized search directories, if the symlink policy is [`SymlinkPolicy::WithinSearchDirectories`].
+	pub(crate) canonical_search_directories: Vec<PathBuf>,
+
+	pub(crate) max_file_size: Option<u64>,
+}
+
+impl IncludeContext {
+	/// Resolves the `target` of a directive in the file at `path`.
+	///
+	/// An absolute target is resolved under the innermost root that `path` is under. `..` components cannot go above that root,
+	/// like in a real chroot.
+	fn resolve(&self, path: &Path, target: &Path) -> PathBuf {
+		let Ok(target) = target.strip_prefix("/") else {
+			return path.parent().unwrap_or(Path::new("/")).join(target);
+		};
+
+		let root =
+			self.chroots.iter()
+			.filter(|root| path.starts_with(root))
+			.max_by_key(|root| root.components().count())
+			.map_or(Path::new("/"), AsRef::as_ref);
+		let mut result = root.to_owned();
+		for component in target.components() {
+			match component {
+				Component::Normal(component) => result.push(component),
+				Component::ParentDir => if result != root {
+					result.pop();
+				},
+				Component::Prefix(_) |
+				Component::RootDir |
+				Component::CurDir => (),
+			}
+		}
+		result
+	}
+
+	/// The contained root that `path` is resolved in, if any.
+	fn root(&self, path: &Path) -> Option<&Root> {
+		self.root.as_deref().filter(|root| path.starts_with(root.path()))
+	}
+
+	fn metadata(&self, path: &Path, follow_symlinks: bool) -> io::Result<fs::Metadata> {
+		match self.root(path) {
+			Some(root) => root.metadata(path, follow_symlinks),
+			None if follow_symlinks => fs::metadata(path),
+			None => fs::symlink_metadata(path),
+		}
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		match self.root(path) {
+			Some(root) => root.canonicalize(path),
+			None => fs::canonicalize(path),
+		}
+	}
+
+	/// Returns whether the file at `path` may be included according to the symlink policy of the search.
+	fn is_allowed(&self, path: &Path) -> io::Result<bool> {
+		if self.symlink_policy == SymlinkPolicy::Follow || !self.metadata(path, false)?.is_symlink() {
+			return Ok(true);
+		}
+
+		if self.symlink_policy == SymlinkPolicy::Refuse {
+			return Ok(false);
+		}
+
+		let target = self.canonicalize(path)?;
+		Ok(self.canonical_search_directories.iter().any(|search_directory| target.starts_with(search_directory)))
+	}
+
+	/// Opens the regular file at `path` and reads it, if it is not larger than the limit of the search.
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		let mut file = match self.root(path) {
+			Some(root) => {
+				let file = root.open_file(path)?;
+				if !file.metadata()?.is_file() {
+					return Err(platform::not_a_regular_file(path));
+				}
+				platform::set_blocking(&file)?;
+				file
+			},
+
+			None => platform::open_regular_file(path)?,
+		};
+
+		// The size is checked on the opened file, so that it cannot be replaced by a larger one after the check.
+		let size = file.metadata()?.len();
+		if let Some(max_file_size) = self.max_file_size {
+			if size > max_file_size {
+				return Err(io::Error::other(FileTooLargeError { path: path.to_owned(), size, max_file_size }));
+			}
+		}
+
+		let mut contents = vec![];
+		file.read_to_end(&mut contents)?;
+		Ok(contents)
+	}
+
+	/// Returns the paths of the regular files in `dir` that are not hidden and that the symlink policy allows,
+	/// in lexicographic order of file name.
+	fn dir_files(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+		let mut result = vec![];
+		for entry in Dir::open(dir.to_owned(), self.root.as_ref())?.file_names()? {
+			let (file_name, _) = entry?;
+			if file_name.as_bytes().starts_with(b".") {
+				continue;
+			}
+			let path = dir.join(file_name);
+			if self.metadata(&path, true)?.is_file() && self.is_allowed(&path)? {
+				result.push(path);
+			}
+		}
+		result.sort();
+		Ok(result)
+	}
+}
+
+/// Appends the segments of `contents` and of the files that it includes to `result`.
+///
+/// `stack` contains the canonical paths of `path` and the files that include it.
+fn expand(
+	path: PathBuf,
+	contents: &[u8],
+	options: &IncludeOptions,
+	context: &IncludeContext,
+	stack: &mut Vec<PathBuf>,
+	result: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), ReadError> {
+	let mut segment = vec![];
+	let mut has_directives = false;
+
+	for line in contents.split_inclusive(|&b| b == b'\n') {
+		let Some((target, is_dir)) = options.directive(line) else {
+			segment.extend_from_slice(line);
+			continue;
+		};
+
+		has_directives = true;
+		if !segment.is_empty() {
+			result.push((path.clone(), std::mem::take(&mut segment)));
+		}
+
+		let target = context.resolve(&path, Path::new(OsStr::from_bytes(target)));
+		if is_dir {
+			for target in context.dir_files(&target).map_err(|inner| ReadError { path: target, inner })? {
+				include(&path, target, options, context, stack, result)?;
+			}
+		}
+		else {
+			include(&path, target, options, context, stack, result)?;
+		}
+	}
+
+	// An empty file is still yielded, like when includes are not expanded.
+	if !segment.is_empty() || !has_directives {
+		result.push((path, segment));
+	}
+
+	Ok(())
+}
+
+fn include(
+	parent: &Path,
+	path: PathBuf,
+	options: &IncludeOptions,
+	context: &IncludeContext,
+	stack: &mut Vec<PathBuf>,
+	result: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), ReadError> {
+	let include_error = |err| ReadError {
+		path: parent.to_owned(),
+		inner: io::Error::new(io::ErrorKind::InvalidData, err),
+	};
+
+	match context.is_allowed(&path) {
+		Ok(true) => (),
+		Ok(false) => return Err(include_error(IncludeError::Symlink { path })),
+		Err(inner) => return Err(ReadError { path, inner }),
+	}
+
+	let canonical_path = match context.canonicalize(&path) {
+		Ok(canonical_path) => canonical_path,
+		Err(inner) => return Err(ReadError { path, inner }),
+	};
+	if stack.contains(&canonical_path) {
+		return Err(include_error(IncludeError::Cycle { path }));
+	}
+	if stack.len() >= options.max_depth {
+		return Err(include_error(IncludeError::TooDeep { path, max_depth: options.max_depth }));
+	}
+
+	let contents = match context.read(&path) {
+		Ok(contents) => contents,
+		Err(inner) => return Err(ReadError { path, inner }),
+	};
+
+	stack.push(canonical_path);
+	expand(path, &contents, options, context, stack, result)?;
+	stack.pop();
+
+	Ok(())
+}
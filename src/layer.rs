@@ -0,0 +1,298 @@
+//! Stacking the files found by searches with config from other sources, such as the environment or the kernel command line.
+
+use std::{
+	borrow::Cow,
+	io::{self, Read},
+	path::PathBuf,
+	sync::Arc,
+};
+
+use crate::{Files, MergeError, MergeFrom};
+
+/// A source of config entries that can be stacked with other sources in [`Layers`], such as the files found by a search,
+/// the environment, the kernel command line or a remote server.
+///
+/// Each entry is named by a path, like the files found by a search. For entries that are not files, the path only identifies the entry,
+/// such as in errors and in [`LayeredEntries::write_cat_config`], so it does not need to exist.
+///
+/// [`SearchLayer`] is the implementation for the files found by a search, and [`StaticLayer`] is the implementation for entries
+/// whose contents are already known.
+///
+/// # Examples
+///
+/// ```rust
+/// struct Environment;
+///
+/// impl uapi_config::LayerProvider for Environment {
+///     fn name(&self) -> &str {
+///         "environment"
+///     }
+///
+///     fn entries(&self) -> std::io::Result<Vec<(std::path::PathBuf, Box<dyn std::io::Read + Send>)>> {
+///         let Ok(log_level) = std::env::var("FOOBAR_LOG_LEVEL") else {
+///             return Ok(vec![]);
+///         };
+///         let contents = format!("log_level={log_level}\n");
+///         Ok(vec![("$FOOBAR_LOG_LEVEL".into(), Box::new(std::io::Cursor::new(contents)))])
+///     }
+/// }
+/// ```
+pub trait LayerProvider: Send + Sync {
+	/// The name of the layer, such as `"environment"`, to identify where its entries came from.
+	fn name(&self) -> &str;
+
+	/// Returns the paths and readers of the entries of the layer, in lowest-precedence-first order.
+	///
+	/// This is called every time the layers are read with [`Layers::entries`], so the entries can change between calls.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the entries cannot be produced, such as because the search for files failed.
+	fn entries(&self) -> io::Result<Vec<(PathBuf, Box<dyn Read + Send>)>>;
+}
+
+/// The [`LayerProvider`] for the files found by a search, which is performed again every time the layers are read.
+///
+/// # Examples
+///
+/// ```rust
+/// let search = uapi_config::SearchDirectories::modern_system().with_file_name("foobar.conf");
+/// let layer = uapi_config::SearchLayer::new("files", move || search.clone().find_files(Some(".conf")));
+/// ```
+pub struct SearchLayer<F> {
+	name: String,
+	search: F,
+}
+
+impl<F> SearchLayer<F> where F: Fn() -> io::Result<Files> + Send + Sync {
+	/// Creates a layer with the given name whose entries are the files that `search` finds.
+	pub fn new<N>(name: N, search: F) -> Self where N: Into<String> {
+		Self {
+			name: name.into(),
+			search,
+		}
+	}
+}
+
+impl<F> LayerProvider for SearchLayer<F> where F: Fn() -> io::Result<Files> + Send + Sync {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn entries(&self) -> io::Result<Vec<(PathBuf, Box<dyn Read + Send>)>> {
+		Ok((self.search)()?.map(|(path, file)| (path, Box::new(file) as _)).collect())
+	}
+}
+
+impl<F> std::fmt::Debug for SearchLayer<F> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SearchLayer").field("name", &self.name).finish_non_exhaustive()
+	}
+}
+
+/// A [`LayerProvider`] with entries whose contents are known in advance, such as settings that were parsed from the command line.
+///
+/// # Examples
+///
+/// ```rust
+/// let layer =
+///     uapi_config::StaticLayer::new("command line")
+///     .entry("--log-level", b"log_level=debug\n".as_slice());
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticLayer {
+	name: String,
+	entries: Vec<(PathBuf, Cow<'static, [u8]>)>,
+}
+
+impl StaticLayer {
+	/// Creates a layer with the given name and no entries.
+	pub fn new<N>(name: N) -> Self where N: Into<String> {
+		Self {
+			name: name.into(),
+			entries: vec![],
+		}
+	}
+
+	/// Appends an entry with the given path and contents. Entries added later have higher precedence.
+	#[must_use]
+	pub fn entry<P, C>(mut self, path: P, contents: C) -> Self where P: Into<PathBuf>, C: Into<Cow<'static, [u8]>> {
+		self.entries.push((path.into(), contents.into()));
+		self
+	}
+}
+
+impl LayerProvider for StaticLayer {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn entries(&self) -> io::Result<Vec<(PathBuf, Box<dyn Read + Send>)>> {
+		Ok(self.entries.iter().map(|(path, contents)| (path.clone(), Box::new(io::Cursor::new(contents.clone())) as _)).collect())
+	}
+}
+
+/// A stack of [`LayerProvider`]s, in lowest-precedence-first order, whose entries are merged like the files found by a single search.
+///
+/// # Examples
+///
+/// ```rust
+/// let search = uapi_config::SearchDirectories::modern_system().with_file_name("foobar.conf");
+/// let layers =
+///     uapi_config::Layers::new()
+///     .layer(uapi_config::StaticLayer::new("defaults").entry("<built-in defaults>", b"log_level=info\n".as_slice()))
+///     .layer(uapi_config::SearchLayer::new("files", move || search.clone().find_files(Some(".conf"))))
+///     .layer(uapi_config::StaticLayer::new("command line").entry("--log-level", b"log_level=debug\n".as_slice()));
+/// for (path, entry) in layers.entries().unwrap() {
+///     println!("{} from the {} layer", path.display(), entry.layer());
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Layers {
+	layers: Vec<Arc<dyn LayerProvider>>,
+}
+
+impl Layers {
+	/// Start with no layers.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Appends a layer, whose entries override the entries of all the layers before it.
+	#[must_use]
+	pub fn layer<L>(mut self, layer: L) -> Self where L: LayerProvider + 'static {
+		self.layers.push(Arc::new(layer));
+		self
+	}
+
+	/// Inserts a layer at position `index`, so that its entries override the entries of the layers before it
+	/// and are overridden by the entries of the layers after it.
+	///
+	/// # Panics
+	///
+	/// Panics if `index > len`.
+	pub fn insert<L>(&mut self, index: usize, layer: L) where L: LayerProvider + 'static {
+		self.layers.insert(index, Arc::new(layer));
+	}
+
+	/// Returns the names of the layers, in lowest-precedence-first order.
+	pub fn names(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+		self.layers.iter().map(|layer| layer.name())
+	}
+
+	/// Returns the number of layers.
+	pub fn len(&self) -> usize {
+		self.layers.len()
+	}
+
+	/// Returns `true` if there are no layers.
+	pub fn is_empty(&self) -> bool {
+		self.layers.is_empty()
+	}
+
+	/// Returns the entries of all the layers, in lowest-precedence-first order.
+	///
+	/// # Errors
+	///
+	/// Returns the first error from [`LayerProvider::entries`].
+	pub fn entries(&self) -> io::Result<LayeredEntries> {
+		let mut entries = vec![];
+		for layer in &self.layers {
+			let name: Arc<str> = layer.name().into();
+			entries.extend(layer.entries()?.into_iter().map(|(path, reader)| (path, LayerEntry { layer: name.clone(), reader })));
+		}
+		Ok(LayeredEntries(entries.into_iter()))
+	}
+}
+
+impl std::fmt::Debug for Layers {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_list().entries(self.names()).finish()
+	}
+}
+
+/// The iterator of the entries of [`Layers`], returned by [`Layers::entries`], in lowest-precedence-first order.
+#[derive(Debug)]
+pub struct LayeredEntries(std::vec::IntoIter<(PathBuf, LayerEntry)>);
+
+impl LayeredEntries {
+	/// Merges all the entries in order into a default-constructed `T`. See [`Files::load_merged`].
+	///
+	/// # Errors
+	///
+	/// Returns `Err(MergeError)` for the first entry that could not be merged. The error contains the path of that entry.
+	pub fn load_merged<T>(self) -> Result<T, MergeError<T::Error>> where T: MergeFrom + Default {
+		let mut result = T::default();
+		self.load_merged_into(&mut result)?;
+		Ok(result)
+	}
+
+	/// Same as [`load_merged`](Self::load_merged), but merges into an existing value.
+	///
+	/// # Errors
+	///
+	/// See [`load_merged`](Self::load_merged).
+	pub fn load_merged_into<T>(self, value: &mut T) -> Result<(), MergeError<T::Error>> where T: MergeFrom {
+		crate::merge::merge_into(self, value)
+	}
+
+	/// Writes the contents of all the entries to `writer` like [`Files::write_cat_config`].
+	/// The header of each entry names the layer that it came from, like `# /etc/foobar.conf (files)`.
+	///
+	/// # Errors
+	///
+	/// See [`Files::write_cat_config`].
+	pub fn write_cat_config<W>(self, writer: &mut W) -> io::Result<()> where W: io::Write + ?Sized {
+		crate::read::write_cat_config(self.map(|(path, entry)| {
+			let layer = entry.layer.clone();
+			(path, entry, Some(layer))
+		}), None, writer)
+	}
+}
+
+impl Iterator for LayeredEntries {
+	type Item = (PathBuf, LayerEntry);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.0.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.0.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for LayeredEntries {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.0.next_back()
+	}
+}
+
+impl ExactSizeIterator for LayeredEntries {}
+
+impl std::iter::FusedIterator for LayeredEntries {}
+
+/// The reader of an entry yielded by [`LayeredEntries`].
+pub struct LayerEntry {
+	layer: Arc<str>,
+	reader: Box<dyn Read + Send>,
+}
+
+impl LayerEntry {
+	/// The name of the layer that the entry came from.
+	pub fn layer(&self) -> &str {
+		&self.layer
+	}
+}
+
+impl Read for LayerEntry {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.reader.read(buf)
+	}
+}
+
+impl std::fmt::Debug for LayerEntry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("LayerEntry").field("layer", &self.layer).finish_non_exhaustive()
+	}
+}
@@ -25,21 +25,183 @@
 //!     .unwrap();
 //! ```
 
+// The code generated by `#[derive(UapiConfig)]` refers to this crate by name, including in the tests of this crate.
+#[cfg(all(test, feature = "derive", feature = "toml"))]
+extern crate self as uapi_config;
+
 use std::{
 	borrow::Cow,
-	collections::BTreeMap,
+	collections::{BTreeMap, BTreeSet},
 	ffi::{OsStr, OsString},
 	fs::{self, File},
 	io,
-	ops::Deref,
-	os::unix::ffi::{OsStrExt as _, OsStringExt as _},
 	path::{Component, Path, PathBuf},
+	sync::Arc,
 };
 
+#[cfg(unix)]
+mod audit;
+#[cfg(unix)]
+pub use audit::Audit;
+
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use cache::SearchCache;
+
+#[cfg(feature = "cap-std")]
+mod cap;
+#[cfg(feature = "cap-std")]
+pub use cap::{CapFiles, CapSearchDirectories};
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+mod condition;
+pub use condition::SubdirectoryCondition;
+
+#[cfg(test)]
+mod conformance;
+
+#[cfg(feature = "zbus")]
+mod dbus;
+#[cfg(feature = "zbus")]
+pub use dbus::ConfigService;
+
+mod defaults;
+pub use defaults::{DefaultsOrFile, EmbeddedDefault, FilesWithDefaults};
+
+#[cfg(feature = "derive")]
+mod derive;
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub use derive::__private;
+#[cfg(feature = "derive")]
+pub use derive::{FormatError, UapiConfig};
+
+mod diagnostics;
+pub use diagnostics::{SkipDiagnostics, SkipReason, SkipWarning};
+
+mod dir;
+use dir::{Dir, EntryKind, SearchDir as _};
+
+mod dot;
+
+mod dropin_suffix;
+pub use dropin_suffix::{DropinSuffix, InvalidDropinSuffixError};
+
+mod entry;
+pub use entry::{Entries, Entry, EntryMetadata};
+
+mod env_file;
+
+mod extension;
+pub use extension::Extensions;
+
+mod file_system;
+pub use file_system::{FileSystem, FileSystemFiles, FileSystemMetadata, StdFileSystem};
+use file_system::{OpenDir, SearchFileSystem};
+
+mod fs_type;
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+pub use fs_type::{FilesystemCondition, FilesystemType};
+
+mod finder;
+pub use finder::Finder;
+use finder::FinderCache;
+
+mod glob;
+
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http")]
+pub use http::HttpLayer;
+
+mod include;
+use include::IncludeContext;
+pub use include::{IncludeError, IncludeOptions};
+
+mod layer;
+pub use layer::{LayerEntry, LayerProvider, Layers, LayeredEntries, SearchLayer, StaticLayer};
+
+mod memory_fs;
+pub use memory_fs::InMemoryFs;
+
+mod merge;
+pub use merge::{MergeError, MergeFrom};
+
+#[cfg(feature = "memmap2")]
+mod mmap;
+
+mod observer;
+use observer::{MetricsRecorder, Observer};
+pub use observer::{SearchMetrics, SearchObserver};
+
+mod outcome;
+pub use outcome::SearchOutcome;
+
+mod permission;
+pub use permission::{PermissionError, PermissionPolicy, PermissionViolation};
+
+mod platform;
+use platform::{OsStrExt as _, OsStringExt as _};
+
+mod plan;
+pub use plan::SearchPlan;
+
+mod read;
+pub use read::{ReadError, Readers};
+
+mod reload;
+#[cfg(all(feature = "signal", target_os = "linux"))]
+pub use reload::ReloadSignal;
+pub use reload::{ReloadError, ReloadTrigger, Reloader, ReloadingConfig};
+
+#[cfg(feature = "miette")]
+mod report;
+
+mod root;
+use root::Root;
+
+mod sandbox;
+pub use sandbox::Sandbox;
+
+mod snapshot;
+pub use snapshot::{Snapshot, SnapshotDiff, SnapshotFile};
+
+mod strverscmp;
+
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+
 /// A list of search directories that the config files will be searched under.
+///
+/// On WASI, paths are resolved against the directories that were preopened for the module, so a search directory must be
+/// at or under the guest path of a preopened directory. For example, with the host's `/etc` preopened as `/etc`,
+/// [`SearchDirectories::modern_system`] finds files in it and ignores `/usr/etc` and `/run` as non-existent.
+/// To search a whole host root that is preopened under a different guest path, [`chroot`](Self::chroot) to that path.
+/// Since WASI has no file owners, modes or inode numbers, [`FindOptions::permission_policy`] is not supported and
+/// [`FindOptions::dedupe_inodes`] and [`FindOptions::dedupe_search_directories`] have no effect.
 #[derive(Clone, Debug)]
 pub struct SearchDirectories<'a> {
 	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+
+	/// The roots that the search directories were [`chroot`](SearchDirectories::chroot)ed to, that absolute include targets are resolved under.
+	chroots: Vec<PathBuf>,
+
+	/// The search directories that are not searched, from [`SearchDirectories::set_enabled`].
+	disabled: BTreeSet<PathBuf>,
+
+	/// The labels of the search directories, from [`SearchDirectories::set_label`].
+	labels: BTreeMap<PathBuf, String>,
+
+	/// The file names that the search directories from [`SearchDirectories::with_credentials_directory_for`] are searched for.
+	file_name_filters: BTreeMap<PathBuf, BTreeSet<OsString>>,
 }
 
 impl<'a> SearchDirectories<'a> {
@@ -47,6 +209,12 @@ impl<'a> SearchDirectories<'a> {
 	pub const fn empty() -> Self {
 		Self {
 			inner: vec![],
+			root: None,
+			finder_cache: None,
+			chroots: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
 		}
 	}
 
@@ -61,6 +229,12 @@ impl<'a> SearchDirectories<'a> {
 				Path::new("/var/run").into(),
 				Path::new("/etc").into(),
 			],
+			root: None,
+			finder_cache: None,
+			chroots: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
 		}
 	}
 
@@ -68,6 +242,9 @@ impl<'a> SearchDirectories<'a> {
 	///
 	/// The OS vendor ships configuration in `/usr/etc`, ephemeral configuration is defined in `/run`,
 	/// and the sysadmin places overrides in `/etc`.
+	///
+	/// On systems that use `systemd-sysext` or `systemd-confext`, `/usr` and `/etc` are overlays that also contain the files
+	/// of extension images, which are found like any other files. Use [`Extensions`] to find which image a file originates from.
 	pub fn modern_system() -> Self {
 		Self {
 			inner: vec![
@@ -75,6 +252,54 @@ impl<'a> SearchDirectories<'a> {
 				Path::new("/run").into(),
 				Path::new("/etc").into(),
 			],
+			root: None,
+			finder_cache: None,
+			chroots: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
+		}
+	}
+
+	/// Start with the unit load path that the systemd system manager uses for system units, as documented in `man systemd.unit`.
+	///
+	/// This includes the directories of the early, normal and late generators, the directories of portable services,
+	/// and the directories of transient units and units created with `systemctl set-property`, in lowest-precedence-first order.
+	///
+	/// Unlike [`classic_system`](Self::classic_system) and [`modern_system`](Self::modern_system), these are not search directory roots.
+	/// Use [`with_file_name`](Self::with_file_name) with the unit name to find a unit file and its dropins.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::systemd_system_units()
+	///     .with_file_name("foo.service")
+	///     .find_files(Some(".conf"))
+	///     .unwrap();
+	/// ```
+	pub fn systemd_system_units() -> Self {
+		Self {
+			inner: vec![
+				Path::new("/run/systemd/generator.late").into(),
+				Path::new("/usr/lib/systemd/system").into(),
+				Path::new("/usr/local/lib/systemd/system").into(),
+				Path::new("/run/systemd/generator").into(),
+				Path::new("/run/systemd/system.attached").into(),
+				Path::new("/run/systemd/system").into(),
+				Path::new("/etc/systemd/system.attached").into(),
+				Path::new("/etc/systemd/system").into(),
+				Path::new("/run/systemd/generator.early").into(),
+				Path::new("/run/systemd/transient").into(),
+				Path::new("/run/systemd/system.control").into(),
+				Path::new("/etc/systemd/system.control").into(),
+			],
+			root: None,
+			finder_cache: None,
+			chroots: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
 		}
 	}
 
@@ -82,38 +307,169 @@ impl<'a> SearchDirectories<'a> {
 	///
 	/// If the `dirs` crate feature is enabled, then `dirs::config_dir()` is used for the implementation of `$XDG_CONFIG_HOME`,
 	/// else a custom implementation is used.
+	///
+	/// Nothing is appended if the directory cannot be determined or is not a valid search directory.
+	/// Use [`try_with_user_directory`](Self::try_with_user_directory) to find out whether it was appended.
 	#[must_use]
-	pub fn with_user_directory(mut self) -> Self {
-		let user_config_dir;
+	pub fn with_user_directory(self) -> Self {
+		self.try_with_user_directory().0
+	}
+
+	/// Same as [`with_user_directory`](Self::with_user_directory), but also returns whether the directory was appended,
+	/// such as to warn the user that their config overrides are ignored because `$HOME` is not set
+	/// or because `$XDG_CONFIG_HOME` is not an absolute path.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let (search_directories, user_directory) = uapi_config::SearchDirectories::modern_system().try_with_user_directory();
+	/// match user_directory {
+	///     uapi_config::UserDirectory::Added(_) => (),
+	///     uapi_config::UserDirectory::NotFound => eprintln!("warning: user config directory not found, neither $XDG_CONFIG_HOME nor $HOME is set"),
+	///     uapi_config::UserDirectory::Invalid(path) => eprintln!("warning: ignoring invalid user config directory {}", path.display()),
+	/// }
+	/// ```
+	pub fn try_with_user_directory(self) -> (Self, UserDirectory) {
 		#[cfg(feature = "dirs")]
-		{
-			user_config_dir = dirs::config_dir();
-		}
+		let user_config_dir = dirs::config_dir();
 		#[cfg(not(feature = "dirs"))]
-		match std::env::var_os("XDG_CONFIG_HOME") {
-			Some(value) if !value.is_empty() => user_config_dir = Some(value.into()),
-
-			_ => match std::env::var_os("HOME") {
-				Some(value) if !value.is_empty() => {
-					let mut value: PathBuf = value.into();
-					value.push(".config");
-					user_config_dir = Some(value);
-				},
+		let user_config_dir = user_config_dir(std::env::var_os("XDG_CONFIG_HOME"), std::env::var_os("HOME"));
+		self.try_with_user_config_dir(user_config_dir)
+	}
 
-				_ => {
-					user_config_dir = None;
-				},
+	/// [`try_with_user_directory`](Self::try_with_user_directory) with the directory that was determined for `$XDG_CONFIG_HOME`, if any.
+	fn try_with_user_config_dir(mut self, user_config_dir: Option<PathBuf>) -> (Self, UserDirectory) {
+		let Some(user_config_dir) = user_config_dir else {
+			return (self, UserDirectory::NotFound);
+		};
+
+		let user_directory = match self.validate_and_root(user_config_dir.clone().into()) {
+			Ok(path) => {
+				let result = UserDirectory::Added(path.to_path_buf());
+				self.inner.push(path);
+				result
 			},
-		}
+			Err(InvalidPathError) => UserDirectory::Invalid(user_config_dir),
+		};
+		(self, user_directory)
+	}
 
-		if let Some(user_config_dir) = user_config_dir {
+	/// Append the directories for config files of the XDG Base Directory Specification from `base_directories`,
+	/// `$XDG_CONFIG_DIRS` followed by `$XDG_CONFIG_HOME`.
+	///
+	/// `$XDG_CONFIG_DIRS` is listed in decreasing order of precedence, so it is appended in reverse. The prefix and profile
+	/// of `base_directories` are not applied, since the search already looks for the project or file name in each directory.
+	#[cfg(feature = "xdg")]
+	#[must_use]
+	pub fn with_xdg_directories(mut self, base_directories: &xdg::BaseDirectories) -> Self {
+		for path in base_directories.config_dirs.iter().rev().chain(&base_directories.config_home) {
 			// If the value fails validation, ignore it.
-			_ = self.push(user_config_dir.into());
+			_ = self.push(path.clone().into());
+		}
+
+		self
+	}
+
+	/// Append the config directory of `strategy` as the directory for local user config overrides,
+	/// like [`with_user_directory`](Self::with_user_directory) but following the platform conventions that `strategy` implements.
+	#[cfg(feature = "etcetera")]
+	#[must_use]
+	pub fn with_etcetera_directory<S>(mut self, strategy: &S) -> Self where S: etcetera::BaseStrategy + ?Sized {
+		// If the value fails validation, ignore it.
+		_ = self.push(strategy.config_dir().into());
+
+		self
+	}
+
+	/// Append the directory of the credentials that systemd passes to the service, `$CREDENTIALS_DIRECTORY`,
+	/// so that secrets delivered as credentials override the config files in all other search directories.
+	///
+	/// Since the credentials directory only contains files, a credential overrides the main file with the same name,
+	/// such as a credential named `foobar.conf` for [`with_file_name("foobar.conf")`](Self::with_file_name), and it has no dropins.
+	/// The names of the credentials are set by the `LoadCredential=` and `SetCredential=` settings of the service,
+	/// so only the names that the unit loads can override config files. To only let specific names be overridden,
+	/// use [`with_credentials_directory_for`](Self::with_credentials_directory_for).
+	///
+	/// The directory is a path in the namespace of the current process, so unlike [`push`](Self::push) it is not placed under the root
+	/// of [`chroot_contained`](Self::chroot_contained).
+	///
+	/// Call this after adding all other search directories, so that the credentials directory has the highest precedence.
+	/// Nothing is appended if the service was not started with credentials.
+	#[must_use]
+	pub fn with_credentials_directory(self) -> Self {
+		self.with_credentials_directory_at(std::env::var_os("CREDENTIALS_DIRECTORY"), None)
+	}
+
+	/// Same as [`with_credentials_directory`](Self::with_credentials_directory), but the credentials directory is only searched
+	/// for main files named one of `file_names`, so other credentials that the unit loads do not override config files.
+	///
+	/// The credentials directory is not searched by [`with_project`](Self::with_project) and [`with_file_name_glob`](Self::with_file_name_glob),
+	/// and the fallback file names of [`SearchDirectoriesForFileName`] are not matched against `file_names`.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_credentials_directory_for(["foobar.conf"])
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap();
+	/// ```
+	#[must_use]
+	pub fn with_credentials_directory_for<I>(self, file_names: I) -> Self where I: IntoIterator, I::Item: AsRef<OsStr> {
+		let file_names = file_names.into_iter().map(|file_name| file_name.as_ref().to_owned()).collect();
+		self.with_credentials_directory_at(std::env::var_os("CREDENTIALS_DIRECTORY"), Some(file_names))
+	}
+
+	/// Appends the credentials directory from the value of `$CREDENTIALS_DIRECTORY`, if it is set,
+	/// for [`with_credentials_directory`](Self::with_credentials_directory) and [`with_credentials_directory_for`](Self::with_credentials_directory_for).
+	fn with_credentials_directory_at(mut self, credentials_directory: Option<OsString>, file_names: Option<BTreeSet<OsString>>) -> Self {
+		let Some(credentials_directory) = credentials_directory else {
+			return self;
+		};
+
+		// If the value fails validation, ignore it.
+		let credentials_directory = PathBuf::from(credentials_directory);
+		if self.insert_unrooted(self.inner.len(), credentials_directory.clone().into()).is_ok() {
+			if let Some(file_names) = file_names {
+				self.file_name_filters.insert(credentials_directory, file_names);
+			}
 		}
 
 		self
 	}
 
+	/// Insert the directory that Docker and Docker Swarm mount secrets in, `/run/secrets`, at position `index` in the list,
+	/// or at the end of the list if `index > len`.
+	///
+	/// Use `0` for secrets that only provide defaults, or [`len`](Self::len) for secrets that override all other config files.
+	/// As with [`with_credentials_directory`](Self::with_credentials_directory), each secret is a file that overrides
+	/// the main file with the same name, and the directory is not placed under the root of [`chroot_contained`](Self::chroot_contained).
+	#[must_use]
+	pub fn with_run_secrets(mut self, index: usize) -> Self {
+		self.inner.insert(index.min(self.inner.len()), Path::new("/run/secrets").into());
+		self
+	}
+
+	/// Insert the directory that a Kubernetes `ConfigMap` or `Secret` is mounted at as a volume, at position `index` in the list.
+	///
+	/// Each key of the `ConfigMap` is a file in the directory that overrides the main file with the same name. Kubernetes implements
+	/// atomic updates of the volume by making each file a symlink into a hidden `..data` directory, so the files are not found
+	/// with [`SymlinkPolicy::Refuse`].
+	///
+	/// # Errors
+	///
+	/// Returns `Err(InvalidPathError)` if `path` does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
+	///
+	/// # Panics
+	///
+	/// Panics if `index > len`.
+	pub fn with_mounted_configmap(mut self, index: usize, path: Cow<'a, Path>) -> Result<Self, InvalidPathError> {
+		self.insert(index, path)?;
+		Ok(self)
+	}
+
 	/// Prepend the specified path to all search directories.
 	///
 	/// # Errors
@@ -122,7 +478,7 @@ impl<'a> SearchDirectories<'a> {
 	pub fn chroot(mut self, root: &Path) -> Result<Self, InvalidPathError> {
 		validate_path(root)?;
 
-		for dir in &mut self.inner {
+		let chroot = |dir: &Path| {
 			let mut new_dir = root.to_owned();
 			for component in dir.components() {
 				match component {
@@ -135,597 +491,6790 @@ impl<'a> SearchDirectories<'a> {
 					},
 				}
 			}
-			*dir = new_dir.into();
+			new_dir
+		};
+
+		for dir in &mut self.inner {
+			*dir = chroot(dir).into();
 		}
+		self.disabled = self.disabled.iter().map(|dir| chroot(dir)).collect();
+		self.labels = std::mem::take(&mut self.labels).into_iter().map(|(dir, label)| (chroot(&dir), label)).collect();
+		self.file_name_filters = std::mem::take(&mut self.file_name_filters).into_iter().map(|(dir, file_names)| (chroot(&dir), file_names)).collect();
+		self.chroots = std::iter::once(root.to_owned()).chain(self.chroots.iter().map(|previous_root| chroot(previous_root))).collect();
 
 		Ok(self)
 	}
 
-	/// Appends a search directory to the end of the list.
-	/// Files found in this directory will override files found in earlier directories.
+	/// Search the same search directories under each of the given roots in one search, with the search directories under later roots
+	/// overriding the ones under earlier roots, such as to layer a container's rootfs and then an overlay directory over the host.
+	///
+	/// The result contains all the search directories [`chroot`](Self::chroot)ed to the first root, followed by all of them chrooted to
+	/// the second root, and so on. So the main file is the one under the last root that has it, and a dropin overrides dropins with the same
+	/// file name under earlier roots, like for any other list of search directories. Disabled search directories and labels
+	/// are carried over to each root.
 	///
 	/// # Errors
 	///
-	/// Returns `Err(InvalidPathError)` if `path` does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
-	pub fn push(&mut self, path: Cow<'a, Path>) -> Result<(), InvalidPathError> {
-		validate_path(&path)?;
-
-		self.inner.push(path);
-
-		Ok(())
-	}
-
-	/// Search for configuration files for the given project name.
+	/// Returns `Err(InvalidPathError)` if any of the roots does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
 	///
-	/// The project name is usually the name of your application.
-	pub fn with_project<TProject>(
-		self,
-		project: TProject,
-	) -> SearchDirectoriesForProject<'a, TProject>
-	{
-		SearchDirectoriesForProject {
-			inner: self.inner,
-			project,
-		}
-	}
-
-	/// Search for configuration files with the given config file name.
-	pub fn with_file_name<TFileName>(
-		self,
-		file_name: TFileName,
-	) -> SearchDirectoriesForFileName<'a, TFileName>
-	{
-		SearchDirectoriesForFileName {
-			inner: self.inner,
-			file_name,
+	/// # Examples
+	///
+	/// ```rust
+	/// use std::path::Path;
+	///
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .chroot_all([Path::new("/"), Path::new("/var/lib/machines/foo"), Path::new("/run/foo/overlay")])
+	///     .unwrap()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap();
+	/// ```
+	pub fn chroot_all<I, P>(self, roots: I) -> Result<Self, InvalidPathError> where I: IntoIterator<Item = P>, P: AsRef<Path> {
+		let mut result = Self {
+			inner: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
+			chroots: vec![],
+			..self.clone()
+		};
+		for root in roots {
+			let chrooted = self.clone().chroot(root.as_ref())?;
+			result.inner.extend(chrooted.inner);
+			result.disabled.extend(chrooted.disabled);
+			result.labels.extend(chrooted.labels);
+			result.file_name_filters.extend(chrooted.file_name_filters);
+			result.chroots.extend(chrooted.chroots);
 		}
+		Ok(result)
 	}
-}
 
-impl Default for SearchDirectories<'_> {
-	fn default() -> Self {
-		Self::empty()
+	/// Prepend the path in the environment variable `name` to all search directories like [`chroot`](Self::chroot),
+	/// or the path in [`ROOT_ENV_VAR`] if `name` is not set. Nothing is prepended if neither variable is set or if its value is empty.
+	///
+	/// This lets test suites and tools that inspect OS images redirect the whole search to a different root without code changes.
+	/// Since whoever sets the environment of the process can redirect the search, do not use this in setuid programs or in other programs
+	/// that run with more privileges than whoever starts them.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(InvalidPathError)` if the value of the variable does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
+	pub fn chroot_from_env<K>(self, name: K) -> Result<Self, InvalidPathError> where K: AsRef<OsStr> {
+		self.chroot_from_env_values(std::env::var_os(name), std::env::var_os(ROOT_ENV_VAR))
 	}
-}
 
-impl<'a> FromIterator<Cow<'a, Path>> for SearchDirectories<'a> {
-	fn from_iter<T>(iter: T) -> Self where T: IntoIterator<Item = Cow<'a, Path>> {
-		Self {
-			inner: FromIterator::from_iter(iter),
+	/// [`chroot_from_env`](Self::chroot_from_env) with the values of its environment variable and of [`ROOT_ENV_VAR`].
+	fn chroot_from_env_values(self, value: Option<OsString>, root_env_var_value: Option<OsString>) -> Result<Self, InvalidPathError> {
+		match [value, root_env_var_value].into_iter().flatten().find(|value| !value.is_empty()) {
+			Some(root) => self.chroot(Path::new(&root)),
+			None => Ok(self),
 		}
 	}
-}
-
-/// Error returned when a path does not start with [`Component::RootDir`] or when it contains [`Component::ParentDir`].
-#[derive(Debug)]
-pub struct InvalidPathError;
 
-impl std::fmt::Display for InvalidPathError {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.write_str("path contains Component::ParentDir")
+	/// Prepend the specified path to all search directories like [`chroot`](Self::chroot), and also contain all path resolution
+	/// within `root`.
+	///
+	/// [`chroot`](Self::chroot) only rewrites the search directories, so an absolute symlink like `/etc/foo.conf -> /usr/lib/foo.conf`
+	/// inside the root would be resolved against the host filesystem. With this function, all directories and files are opened with
+	/// `openat2(RESOLVE_IN_ROOT | RESOLVE_NO_MAGICLINKS)` relative to `root`, so symlinks and `..` are resolved as if `root` was `/`.
+	/// This is useful for inspecting the configuration of a container or OS image.
+	///
+	/// Search directories that are [`push`](Self::push)ed afterwards are also interpreted relative to `root`.
+	/// Calling [`chroot`](Self::chroot) afterwards will cause the search to fail, since the search directories will no longer be under `root`.
+	///
+	/// Since files are opened with `openat2`, [`FindOptions::open_options`] is not used. Files are always opened read-only.
+	///
+	/// This requires Linux 5.6 or newer, and `/proc` to be mounted if [`SymlinkPolicy::WithinSearchDirectories`] or
+	/// [`FindOptions::dedupe_canonical_paths`] is used.
+	///
+	/// # Errors
+	///
+	/// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `root` does not start with a [`Component::RootDir`]
+	/// or if it contains [`Component::ParentDir`]. Returns any I/O error from opening `root`.
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	pub fn chroot_contained(self, root: &Path) -> io::Result<Self> {
+		let mut result = self.chroot(root).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+		result.root = Some(Arc::new(Root::open(root.to_owned())?));
+		Ok(result)
 	}
-}
 
-impl std::error::Error for InvalidPathError {}
+	/// Restricts the filesystem access of the current thread to reading the search directories, using a Landlock ruleset.
+	///
+	/// This is for hardened services that want to ensure that the search, and the parsing of the files that it finds,
+	/// cannot be used to read anything else, such as by crafting symlinks that point outside the search directories.
+	/// Looking up a file outside the search directories then fails with an error of kind [`io::ErrorKind::PermissionDenied`].
+	///
+	/// The restriction applies to the current thread and any threads and processes that it creates afterwards, and cannot be undone.
+	/// All other filesystem access is denied, including writing to the search directories and reading `/proc`,
+	/// so [`SymlinkPolicy::WithinSearchDirectories`] and [`FindOptions::dedupe_canonical_paths`] cannot be used without
+	/// [`chroot_contained`](Self::chroot_contained). Search directories that do not exist yet are not accessible even if they are
+	/// created later. If the search directories were created with [`chroot_contained`](Self::chroot_contained), reading the whole root is allowed,
+	/// since symlinks are resolved within it.
+	///
+	/// Landlock requires Linux 5.13 or newer. On older kernels, or if Landlock is disabled, this succeeds without restricting anything,
+	/// so check the returned status if the restriction must be enforced.
+	///
+	/// # Errors
+	///
+	/// Returns any I/O error from opening the search directories, and any error from creating or applying the ruleset.
+	#[cfg(all(feature = "landlock", target_os = "linux"))]
+	pub fn restrict_with_landlock(&self) -> io::Result<landlock::RestrictionStatus> {
+		use landlock::{Access as _, AccessFs, PathBeneath, Ruleset, RulesetAttr as _, RulesetCreatedAttr as _, ABI};
 
-/// A list of search directories that the config files will be searched under, scoped to a particular project.
-///
-/// Created using [`SearchDirectories::with_project`].
-#[derive(Clone, Debug)]
-pub struct SearchDirectoriesForProject<'a, TProject> {
-	inner: Vec<Cow<'a, Path>>,
-	project: TProject,
-}
+		const LANDLOCK_ABI: ABI = ABI::V5;
 
-impl<'a, TProject> SearchDirectoriesForProject<'a, TProject> {
-	/// Search for configuration files of this project with the given config file name.
-	pub fn with_file_name<TFileName>(
-		self,
-		file_name: TFileName,
-	) -> SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName>
-	{
-		SearchDirectoriesForProjectAndFileName {
-			inner: self.inner,
-			project: self.project,
-			file_name,
+		let paths: Vec<&Path> = match &self.root {
+			Some(root) => vec![root.path()],
+			None => self.inner.iter().map(AsRef::as_ref).filter(|path| self.is_enabled(path)).collect(),
+		};
+
+		let mut ruleset =
+			Ruleset::default()
+			.handle_access(AccessFs::from_all(LANDLOCK_ABI))
+			.and_then(Ruleset::create)
+			.map_err(io::Error::other)?;
+		for path in paths {
+			let dir = match File::open(path) {
+				Ok(dir) => dir,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err),
+			};
+			ruleset = ruleset.add_rule(PathBeneath::new(dir, AccessFs::from_read(LANDLOCK_ABI))).map_err(io::Error::other)?;
 		}
+		ruleset.restrict_self().map_err(io::Error::other)
 	}
 
-	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
-	/// The name `format!("{project}.d")` is appended to each search directory, then those directories are searched as if they are
-	/// dropin directories. Only dropin files whose name ends with `dropin_suffix` will be considered.
-	/// Note that if you intend to use a file extension as a suffix, then `dropin_suffix` must include the `.`, such as `".conf"`.
-	///
-	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
-	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
-	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
+	/// Creates search directories from the given paths, in lowest-precedence-first order, validating each of them like [`push`](Self::push).
 	///
 	/// # Errors
 	///
-	/// Any errors from reading non-existing directories and non-existing files are ignored.
-	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	/// Returns an [`InvalidSearchDirectoryError`] with the first path that does not start with a [`Component::RootDir`]
+	/// or that contains [`Component::ParentDir`].
 	///
 	/// # Examples
 	///
-	/// ## Get all config files for the system service `foobar`
+	/// ```rust
+	/// use std::path::Path;
 	///
-	/// ... taking into account OS vendor configs, ephemeral overrides and sysadmin overrides.
+	/// let err = uapi_config::SearchDirectories::try_from_iter([Path::new("/etc"), Path::new("/usr/../etc")]).unwrap_err();
+	/// assert_eq!(err.index(), 1);
+	/// assert_eq!(err.path(), Path::new("/usr/../etc"));
+	/// ```
+	pub fn try_from_iter<I, P>(paths: I) -> Result<Self, InvalidSearchDirectoryError>
+	where
+		I: IntoIterator<Item = P>,
+		P: Into<Cow<'a, Path>>,
+	{
+		let mut result = Self::empty();
+		for (index, path) in paths.into_iter().enumerate() {
+			let path = path.into();
+			if validate_path(&path).is_err() {
+				return Err(InvalidSearchDirectoryError { index, path: path.into_owned() });
+			}
+			result.inner.push(path);
+		}
+		Ok(result)
+	}
+
+	/// Appends a search directory to the end of the list.
+	/// Files found in this directory will override files found in earlier directories.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(InvalidPathError)` if `path` does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
+	pub fn push(&mut self, path: Cow<'a, Path>) -> Result<(), InvalidPathError> {
+		let path = self.validate_and_root(path)?;
+		self.inner.push(path);
+		Ok(())
+	}
+
+	/// Inserts a search directory at position `index` in the list, shifting all directories after it to the right.
+	/// Files found in this directory will override files found in earlier directories.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(InvalidPathError)` if `path` does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
+	///
+	/// # Panics
+	///
+	/// Panics if `index > len`.
+	pub fn insert(&mut self, index: usize, path: Cow<'a, Path>) -> Result<(), InvalidPathError> {
+		let path = self.validate_and_root(path)?;
+		self.inner.insert(index, path);
+		Ok(())
+	}
+
+	/// Removes and returns the search directory at position `index` in the list, shifting all directories after it to the left.
+	///
+	/// # Panics
+	///
+	/// Panics if `index` is out of bounds.
+	pub fn remove(&mut self, index: usize) -> Cow<'a, Path> {
+		let path = self.inner.remove(index);
+		self.forget_removed_directories();
+		path
+	}
+
+	/// Retains only the search directories for which `f` returns `true`.
+	pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&Path) -> bool {
+		self.inner.retain(|path| f(path));
+		self.forget_removed_directories();
+	}
+
+	/// Enables or disables the search directory at `path`. Disabled search directories stay in the list, but are not searched,
+	/// such as to implement a `--no-runtime-config` flag by disabling `/run` without rebuilding the list.
+	/// All search directories are enabled by default.
+	///
+	/// `path` is compared with the paths of the search directories as returned by [`iter`](Self::iter), so it includes the root
+	/// that the search directories were [`chroot`](Self::chroot)ed to, if any. If the list contains `path` more than once,
+	/// all of them are enabled or disabled. Returns `false` if the list does not contain `path`, in which case nothing is changed.
+	///
+	/// # Examples
 	///
 	/// ```rust
+	/// let mut search_directories = uapi_config::SearchDirectories::modern_system();
+	/// search_directories.set_enabled(std::path::Path::new("/run"), false);
 	/// let files =
-	///     uapi_config::SearchDirectories::modern_system()
+	///     search_directories
 	///     .with_project("foobar")
 	///     .find_files(".conf")
 	///     .unwrap();
 	/// ```
+	pub fn set_enabled(&mut self, path: &Path, enabled: bool) -> bool {
+		if !self.inner.iter().any(|search_directory| search_directory == path) {
+			return false;
+		}
+
+		if enabled {
+			self.disabled.remove(path);
+		}
+		else {
+			self.disabled.insert(path.to_owned());
+		}
+		true
+	}
+
+	/// Returns `false` if the search directory at `path` was disabled with [`set_enabled`](Self::set_enabled), `true` otherwise.
+	pub fn is_enabled(&self, path: &Path) -> bool {
+		!self.disabled.contains(path)
+	}
+
+	/// Attaches a caller-defined label to the search directory at `path`, such as the name of the tier of configuration that it contains,
+	/// replacing any previous label. The label of the search directory that a file was found under can then be looked up with
+	/// [`label_of`](Self::label_of) or [`Files::labeled`].
 	///
-	/// This will locate all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf` in lexicographical order.
-	///
-	/// ## Get all config files for the application `foobar`
+	/// `path` is compared with the paths of the search directories like in [`set_enabled`](Self::set_enabled).
+	/// Returns `false` if the list does not contain `path`, in which case nothing is changed.
 	///
-	/// ... taking into account OS vendor configs, ephemeral overrides, sysadmin overrides and local user overrides.
+	/// # Examples
 	///
 	/// ```rust
+	/// use std::path::Path;
+	///
+	/// let mut search_directories = uapi_config::SearchDirectories::empty();
+	/// for (path, label) in [("/usr/share/foobar/site", "site"), ("/etc/foobar/cluster", "cluster"), ("/etc/foobar/host", "host")] {
+	///     search_directories.push_with_label(Path::new(path).into(), label).unwrap();
+	/// }
+	///
 	/// let files =
-	///     uapi_config::SearchDirectories::modern_system()
-	///     .with_user_directory()
-	///     .with_project("foobar")
-	///     .find_files(".conf")
-	///     .unwrap();
+	///     search_directories.clone()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	///     .labeled(&search_directories);
+	/// for (label, path, file) in files {
+	///     // Parse `file` and record that its settings came from the `label` tier
+	/// }
 	/// ```
+	pub fn set_label<L>(&mut self, path: &Path, label: L) -> bool where L: Into<String> {
+		if !self.inner.iter().any(|search_directory| search_directory == path) {
+			return false;
+		}
+
+		self.labels.insert(path.to_owned(), label.into());
+		true
+	}
+
+	/// Appends a search directory to the end of the list like [`push`](Self::push), and attaches `label` to it like [`set_label`](Self::set_label).
 	///
-	/// This will locate all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf`, `$XDG_CONFIG_HOME/foobar.d/*.conf`
-	/// in lexicographical order.
+	/// # Errors
 	///
-	#[cfg_attr(feature = "dirs", doc = r#"## Get all config files for the application `foobar`"#)]
-	#[cfg_attr(feature = "dirs", doc = r#""#)]
-	#[cfg_attr(feature = "dirs", doc = r#"... with custom paths for the OS vendor configs, sysadmin overrides and local user overrides."#)]
-	#[cfg_attr(feature = "dirs", doc = r#""#)]
-	#[cfg_attr(feature = "dirs", doc = r#"```rust"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"// OS and sysadmin configs"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"let mut search_directories: uapi_config::SearchDirectories = ["#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    std::path::Path::new("/usr/share").into(),"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    std::path::Path::new("/etc").into(),"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"].into_iter().collect();"#)]
-	#[cfg_attr(feature = "dirs", doc = r#""#)]
-	#[cfg_attr(feature = "dirs", doc = r#"// Local user configs under `${XDG_CONFIG_HOME:-$HOME/.config}`"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"if let Some(user_config_dir) = dirs::config_dir() {"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    search_directories.push(user_config_dir.into());"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"}"#)]
-	#[cfg_attr(feature = "dirs", doc = r#""#)]
-	#[cfg_attr(feature = "dirs", doc = r#"let files ="#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    search_directories"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    .with_project("foobar")"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    .find_files(".conf")"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"    .unwrap();"#)]
-	#[cfg_attr(feature = "dirs", doc = r#"```"#)]
-	#[cfg_attr(feature = "dirs", doc = r#""#)]
-	#[cfg_attr(feature = "dirs", doc = r#"This will locate `/usr/share/foobar.d/*.conf`, `/etc/foobar.d/*.conf`, `$XDG_CONFIG_HOME/foobar.d/*.conf` in that order and return the last one."#)]
-	pub fn find_files<TDropinSuffix>(
-		self,
-		dropin_suffix: TDropinSuffix,
-	) -> io::Result<Files>
-	where
-		TProject: AsRef<OsStr>,
-		TDropinSuffix: AsRef<OsStr>,
-	{
-		let project = self.project.as_ref().as_bytes();
+	/// Returns `Err(InvalidPathError)` if `path` does not start with a [`Component::RootDir`] or if it contains [`Component::ParentDir`].
+	pub fn push_with_label<L>(&mut self, path: Cow<'a, Path>, label: L) -> Result<(), InvalidPathError> where L: Into<String> {
+		let path = self.validate_and_root(path)?;
+		self.labels.insert(path.to_path_buf(), label.into());
+		self.inner.push(path);
+		Ok(())
+	}
 
-		let dropins = find_dropins(dropin_suffix.as_ref(), self.inner.into_iter().map(|path| {
-			let mut path_bytes = path.into_owned().into_os_string().into_vec();
-			path_bytes.push(b'/');
-			path_bytes.extend_from_slice(project);
-			path_bytes.extend_from_slice(b".d");
-			PathBuf::from(OsString::from_vec(path_bytes))
-		}))?;
+	/// Returns the label of the search directory at `path`, if one was attached with [`set_label`](Self::set_label).
+	pub fn label(&self, path: &Path) -> Option<&str> {
+		self.labels.get(path).map(AsRef::as_ref)
+	}
 
-		Ok(Files {
-			inner: None.into_iter().chain(dropins),
-		})
+	/// Returns the label of the search directory that the file at `path` was found under, if it has one.
+	///
+	/// `path` is the path of a file that a search with these search directories found. Like [`Files::grouped_by_root`],
+	/// the search directory is the one with the most components that `path` starts with.
+	pub fn label_of(&self, path: &Path) -> Option<&str> {
+		let i = search_directory_index(path, self.iter())?;
+		self.label(&self.inner[i])
 	}
-}
 
-/// A list of search directories that the config files will be searched under, scoped to a particular config file name.
-///
-/// Created using [`SearchDirectories::with_file_name`].
-#[derive(Clone, Debug)]
-pub struct SearchDirectoriesForFileName<'a, TFileName> {
-	inner: Vec<Cow<'a, Path>>,
-	file_name: TFileName,
-}
+	/// Forgets that the search directories that are no longer in the list were disabled or labeled,
+	/// so that they are enabled and unlabeled if they are added again.
+	fn forget_removed_directories(&mut self) {
+		let inner = &self.inner;
+		self.disabled.retain(|path| inner.iter().any(|search_directory| search_directory == path));
+		self.labels.retain(|path, _| inner.iter().any(|search_directory| search_directory == path));
+		self.file_name_filters.retain(|path, _| inner.iter().any(|search_directory| search_directory == path));
+	}
 
-impl<'a, TFileName> SearchDirectoriesForFileName<'a, TFileName> {
-	/// Search for configuration files for the given project name and with this config file name.
+	/// Takes the search directories that are enabled, for a search for the main file `file_name`.
+	///
+	/// Search directories from [`with_credentials_directory_for`](Self::with_credentials_directory_for) are only kept
+	/// if `file_name` is one of the names that they are searched for.
+	fn enabled_directories(&mut self, file_name: Option<&OsStr>) -> Vec<Cow<'a, Path>> {
+		let mut result = std::mem::take(&mut self.inner);
+		result.retain(|path| {
+			self.is_enabled(path) &&
+			self.file_name_filters.get(&**path).is_none_or(|file_names| file_name.is_some_and(|file_name| file_names.contains(file_name)))
+		});
+		result
+	}
+
+	/// Returns an [`Iterator`] of the search directories, in order of increasing precedence.
+	///
+	/// The paths include the root that the search directories were [`chroot`](Self::chroot)ed to, if any.
+	/// Search directories that were disabled with [`set_enabled`](Self::set_enabled) are included.
+	pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Path> + ExactSizeIterator {
+		self.inner.iter().map(AsRef::as_ref)
+	}
+
+	/// Returns the search directories as a slice, in order of increasing precedence.
+	///
+	/// The paths include the root that the search directories were [`chroot`](Self::chroot)ed to, if any.
+	pub fn as_slice(&self) -> &[Cow<'a, Path>] {
+		&self.inner
+	}
+
+	/// Returns the number of search directories.
+	pub fn len(&self) -> usize {
+		self.inner.len()
+	}
+
+	/// Returns `true` if there are no search directories.
+	pub fn is_empty(&self) -> bool {
+		self.inner.is_empty()
+	}
+
+	/// Copies any borrowed search directories so that the result does not borrow anything.
+	///
+	/// `SearchDirectories` is [`Send`] and [`Sync`], so the result can be stored in a `static` [`OnceLock`](std::sync::OnceLock)
+	/// or sent to a worker thread.
+	#[must_use]
+	pub fn into_owned(self) -> SearchDirectories<'static> {
+		SearchDirectories {
+			inner: self.inner.into_iter().map(|path| Cow::Owned(path.into_owned())).collect(),
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			disabled: self.disabled,
+			labels: self.labels,
+			file_name_filters: self.file_name_filters,
+		}
+	}
+
+	/// Validates a search directory that is being added to the list, and inserts it at position `index` without prepending the root
+	/// of [`chroot_contained`](Self::chroot_contained) to it, for directories that are in the namespace of the current process.
+	fn insert_unrooted(&mut self, index: usize, path: Cow<'a, Path>) -> Result<(), InvalidPathError> {
+		validate_path(&path)?;
+		self.inner.insert(index, path);
+		Ok(())
+	}
+
+	/// Validates a search directory that is being added to the list, and prepends the root to it if the search directories
+	/// were created with [`chroot_contained`](Self::chroot_contained).
+	fn validate_and_root(&self, path: Cow<'a, Path>) -> Result<Cow<'a, Path>, InvalidPathError> {
+		validate_path(&path)?;
+
+		let path = match &self.root {
+			Some(root) => {
+				let mut new_path = root.path().to_owned();
+				new_path.extend(path.components().skip(1));
+				new_path.into()
+			},
+
+			None => path,
+		};
+
+		Ok(path)
+	}
+
+	/// Search for configuration files for the given project name.
 	///
 	/// The project name is usually the name of your application.
 	pub fn with_project<TProject>(
-		self,
+		mut self,
 		project: TProject,
-	) -> SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName>
+	) -> SearchDirectoriesForProject<'a, TProject>
 	{
-		SearchDirectoriesForProjectAndFileName {
-			inner: self.inner,
+		SearchDirectoriesForProject {
+			inner: self.enabled_directories(None),
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
 			project,
-			file_name: self.file_name,
+			legacy_projects: vec![],
+			main_file: false,
 		}
 	}
 
-	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
-	/// Only files named `file_name` under the search directories will be considered.
+	/// Search for configuration files with the given config file name.
+	pub fn with_file_name<TFileName>(
+		mut self,
+		file_name: TFileName,
+	) -> SearchDirectoriesForFileName<'a, TFileName>
+	where
+		TFileName: AsRef<OsStr>,
+	{
+		SearchDirectoriesForFileName {
+			inner: self.enabled_directories(Some(file_name.as_ref())),
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			file_name,
+			fallback_file_names: vec![],
+		}
+	}
+
+	/// Search for configuration files whose names match the given glob pattern, such as `"*.network"`.
 	///
-	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
-	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
-	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
+	/// The pattern supports `*`, `?` and bracket expressions like `[a-z]`, and is matched against file names only.
+	pub fn with_file_name_glob<TPattern>(
+		mut self,
+		pattern: TPattern,
+	) -> SearchDirectoriesForFileNameGlob<'a, TPattern>
+	{
+		SearchDirectoriesForFileNameGlob {
+			inner: self.enabled_directories(None),
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			pattern,
+		}
+	}
+
+	/// Search for configuration files whose names end with the given suffix, such as `".service"`.
 	///
-	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
-	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
-	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
+	/// The suffix is matched literally, so unlike [`with_file_name_glob`](Self::with_file_name_glob) it does not need to be escaped.
+	/// A file whose name is only the suffix, such as `.service`, does not match.
+	pub fn with_file_name_suffix<TSuffix>(
+		mut self,
+		suffix: TSuffix,
+	) -> SearchDirectoriesForFileNameSuffix<'a, TSuffix>
+	{
+		SearchDirectoriesForFileNameSuffix {
+			inner: self.enabled_directories(None),
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			suffix,
+		}
+	}
+
+	/// Returns the names of all the projects that have any configuration under the search directories, in lexicographic order.
+	///
+	/// A project named `foo` is considered to have configuration if any search directory contains a `foo.d` directory,
+	/// as searched by [`SearchDirectoriesForProject`], or a `foo` directory, as searched by [`SearchDirectoriesForProjectAndFileName`].
+	/// Note that this means the dropin directory `foo.conf.d` of a config file `foo.conf` is reported as a project named `foo.conf`.
+	/// Hidden directories whose names start with `.` are ignored.
 	///
 	/// # Errors
 	///
-	/// Any errors from reading non-existing directories and non-existing files are ignored.
-	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	/// Any errors from reading non-existing directories are ignored.
+	/// Apart from that, any I/O errors from walking the directories are propagated.
 	///
 	/// # Examples
 	///
-	/// ## Get all config files for the system service `foobar`
-	///
-	/// ... taking into account OS vendor configs, ephemeral overrides and sysadmin overrides.
-	///
-	/// ```rust
-	/// let files =
-	///     uapi_config::SearchDirectories::modern_system()
-	///     .with_file_name("foobar.conf")
-	///     .find_files(Some(".conf"))
-	///     .unwrap();
-	/// ```
-	///
-	/// This will locate `/usr/etc/foobar.conf` `/run/foobar.conf`, `/etc/foobar.conf` in that order and return the last one,
-	/// then all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf` in lexicographical order.
-	///
-	/// ## Get the config files for the "foo.service" systemd system unit like systemd would do
-	///
 	/// ```rust
-	/// let search_directories: uapi_config::SearchDirectories =
-	///     // From `man systemd.unit`
-	///     [
-	///         "/run/systemd/generator.late",
-	///         "/usr/lib/systemd/system",
-	///         "/usr/local/lib/systemd/system",
-	///         "/run/systemd/generator",
-	///         "/run/systemd/system",
-	///         "/etc/systemd/system",
-	///         "/run/systemd/generator.early",
-	///         "/run/systemd/transient",
-	///         "/run/systemd/system.control",
-	///         "/etc/systemd/system.control",
-	///     ].into_iter()
-	///     .map(|path| std::path::Path::new(path).into())
-	///     .collect();
-	/// let files =
-	///     search_directories
-	///     .with_file_name("foo.service")
-	///     .find_files(Some(".conf"))
-	///     .unwrap();
+	/// let projects = uapi_config::SearchDirectories::modern_system().discover_projects().unwrap();
+	/// for project in projects {
+	///     println!("{} has local configuration", project.display());
+	/// }
 	/// ```
-	///
-	/// This will locate `/run/systemd/generator.late/foobar.service` `/usr/lib/systemd/system/foo.service`, ... in that order and return the last one,
-	/// then all dropins `/run/systemd/generator.late/foo.service.d/*.conf`, `/usr/lib/systemd/system/foo.service.d/*.conf`, ... in lexicographical order.
-	pub fn find_files<TDropinSuffix>(
-		self,
-		dropin_suffix: Option<TDropinSuffix>,
-	) -> io::Result<Files>
-	where
-		TFileName: AsRef<OsStr>,
-		TDropinSuffix: AsRef<OsStr>,
-	{
-		let file_name = self.file_name.as_ref();
+	pub fn discover_projects(&self) -> io::Result<BTreeSet<OsString>> {
+		let options = FindOptions::default();
+		let context = SearchContext::new(&options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let search_directories = context.open_dirs(self.inner.iter().filter(|path| self.is_enabled(path)).map(|path| path.to_path_buf()))?;
+
+		let mut result = BTreeSet::new();
+		let mut scratch = PathBuf::new();
+
+		for search_directory in &search_directories {
+			let Some(entries) = context.file_names(search_directory)? else {
+				continue;
+			};
+			for entry in entries {
+				let (file_name, kind) = entry?;
+
+				// Skip entries that cannot be directories without needing to stat them.
+				if kind == EntryKind::File || file_name.as_bytes().starts_with(b".") {
+					continue;
+				}
+
+				let metadata = match search_directory.metadata(&file_name, true, &mut scratch) {
+					Ok(metadata) => metadata,
+					Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+					Err(err) => return Err(err),
+				};
+				if !metadata.is_dir() {
+					continue;
+				}
+
+				let project = match file_name.as_bytes().strip_suffix(b".d") {
+					Some(project) if !project.is_empty() => OsStr::from_bytes(project).to_owned(),
+					_ => file_name,
+				};
+				result.insert(project);
+			}
+		}
+
+		Ok(result)
+	}
+}
+
+impl Default for SearchDirectories<'_> {
+	fn default() -> Self {
+		Self::empty()
+	}
+}
+
+/// Returns the directory for `$XDG_CONFIG_HOME` from the values of the `XDG_CONFIG_HOME` and `HOME` environment variables,
+/// for [`SearchDirectories::try_with_user_directory`] when the `dirs` crate feature is not enabled.
+#[cfg(not(feature = "dirs"))]
+fn user_config_dir(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+	match xdg_config_home {
+		Some(value) if !value.is_empty() => Some(value.into()),
+
+		_ => match home {
+			Some(value) if !value.is_empty() => {
+				let mut value: PathBuf = value.into();
+				value.push(".config");
+				Some(value)
+			},
+
+			_ => None,
+		},
+	}
+}
+
+/// Creates search directories from the given paths as-is.
+///
+/// Unlike [`SearchDirectories::push`], the paths are not validated, so relative paths and paths that contain [`Component::ParentDir`]
+/// are accepted. Use [`SearchDirectories::try_from_iter`] or `TryFrom<Vec<PathBuf>>` to validate them.
+impl<'a> FromIterator<Cow<'a, Path>> for SearchDirectories<'a> {
+	fn from_iter<T>(iter: T) -> Self where T: IntoIterator<Item = Cow<'a, Path>> {
+		Self {
+			inner: FromIterator::from_iter(iter),
+			root: None,
+			finder_cache: None,
+			chroots: vec![],
+			disabled: BTreeSet::new(),
+			labels: BTreeMap::new(),
+			file_name_filters: BTreeMap::new(),
+		}
+	}
+}
+
+/// Equivalent to [`SearchDirectories::try_from_iter`].
+impl TryFrom<Vec<PathBuf>> for SearchDirectories<'static> {
+	type Error = InvalidSearchDirectoryError;
+
+	fn try_from(paths: Vec<PathBuf>) -> Result<Self, Self::Error> {
+		Self::try_from_iter(paths)
+	}
+}
+
+/// Equivalent to `SearchDirectories::empty().with_xdg_directories(base_directories)`.
+#[cfg(feature = "xdg")]
+impl From<&xdg::BaseDirectories> for SearchDirectories<'static> {
+	fn from(base_directories: &xdg::BaseDirectories) -> Self {
+		Self::empty().with_xdg_directories(base_directories)
+	}
+}
+
+/// Whether [`SearchDirectories::try_with_user_directory`] appended the directory for local user config overrides.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UserDirectory {
+	/// The directory was appended. This is its path, prefixed with the root if the search directories were created with
+	/// `SearchDirectories::chroot_contained`.
+	Added(PathBuf),
+
+	/// The directory could not be determined, such as because neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+	NotFound,
+
+	/// The directory was not appended because its path does not start with [`Component::RootDir`] or contains [`Component::ParentDir`],
+	/// such as because `$XDG_CONFIG_HOME` is a relative path.
+	Invalid(PathBuf),
+}
+
+/// Error returned when a path does not start with [`Component::RootDir`] or when it contains [`Component::ParentDir`].
+#[derive(Debug)]
+pub struct InvalidPathError;
+
+impl std::fmt::Display for InvalidPathError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("path contains Component::ParentDir")
+	}
+}
+
+impl std::error::Error for InvalidPathError {}
+
+/// Error returned by [`SearchDirectories::try_from_iter`] when one of the paths is not a valid search directory.
+#[derive(Debug)]
+pub struct InvalidSearchDirectoryError {
+	index: usize,
+	path: PathBuf,
+}
+
+impl InvalidSearchDirectoryError {
+	/// The position of the invalid path among the paths.
+	pub fn index(&self) -> usize {
+		self.index
+	}
+
+	/// The invalid path.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl std::fmt::Display for InvalidSearchDirectoryError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "search directory {} at index {} is not an absolute path without Component::ParentDir", self.path.display(), self.index)
+	}
+}
+
+impl std::error::Error for InvalidSearchDirectoryError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&InvalidPathError)
+	}
+}
+
+/// Error returned when the dropin directories of a config file contain more dropins than the [`FindOptions::max_dropins`] limit.
+///
+/// This is wrapped in an [`io::Error`] of kind [`io::ErrorKind::Other`], from which it can be retrieved with [`io::Error::get_ref`].
+#[derive(Debug)]
+pub struct TooManyDropinsError {
+	directory: PathBuf,
+	max_dropins: usize,
+}
+
+impl TooManyDropinsError {
+	/// The dropin directory in which the limit was exceeded.
+	pub fn directory(&self) -> &Path {
+		&self.directory
+	}
+
+	/// The limit that was exceeded.
+	pub fn max_dropins(&self) -> usize {
+		self.max_dropins
+	}
+}
+
+impl std::fmt::Display for TooManyDropinsError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "more than {} dropins found, the last in {}", self.max_dropins, self.directory.display())
+	}
+}
+
+impl std::error::Error for TooManyDropinsError {}
+
+/// Error returned when a file that was found is larger than the [`FindOptions::max_file_size`] limit.
+///
+/// This is wrapped in an [`io::Error`] of kind [`io::ErrorKind::Other`], from which it can be retrieved with [`io::Error::get_ref`].
+#[derive(Debug)]
+pub struct FileTooLargeError {
+	path: PathBuf,
+	size: u64,
+	max_file_size: u64,
+}
+
+impl FileTooLargeError {
+	/// The path of the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The size of the file in bytes.
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+
+	/// The limit that was exceeded.
+	pub fn max_file_size(&self) -> u64 {
+		self.max_file_size
+	}
+}
+
+impl std::fmt::Display for FileTooLargeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} is {} bytes, which is larger than the limit of {} bytes", self.path.display(), self.size, self.max_file_size)
+	}
+}
+
+impl std::error::Error for FileTooLargeError {}
+
+/// Error returned when a search did not find a main file and [`FindOptions::require_main_file`] is set,
+/// or did not find any files and [`FindOptions::require_files`] is set.
+///
+/// This is wrapped in an [`io::Error`] of kind [`io::ErrorKind::NotFound`], from which it can be retrieved with [`io::Error::get_ref`].
+#[derive(Debug)]
+pub struct ConfigNotFoundError {
+	name: OsString,
+	search_directories: Vec<PathBuf>,
+	main_file_required: bool,
+}
+
+impl ConfigNotFoundError {
+	/// The name that was searched for, ie the project name, the config file name, or both joined as a path.
+	pub fn name(&self) -> &OsStr {
+		&self.name
+	}
+
+	/// The search directories that were searched, in lowest-precedence-first order.
+	pub fn search_directories(&self) -> &[PathBuf] {
+		&self.search_directories
+	}
+
+	/// Returns `true` if the error is because no main file was found, or `false` if it is because no files were found at all.
+	pub fn main_file_required(&self) -> bool {
+		self.main_file_required
+	}
+}
+
+impl std::fmt::Display for ConfigNotFoundError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.main_file_required {
+			write!(f, "config file {} not found in any of ", self.name.display())?;
+		}
+		else {
+			write!(f, "no config files for {} found in any of ", self.name.display())?;
+		}
+		for (i, search_directory) in self.search_directories.iter().enumerate() {
+			if i > 0 {
+				f.write_str(", ")?;
+			}
+			write!(f, "{}", search_directory.display())?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ConfigNotFoundError {}
+
+/// Options that control which files are found, for use with the `find_files_with` methods.
+///
+/// The default options find files the same way as the `find_files` methods. Each option is set with a method that consumes
+/// and returns the options, so new options can be added without changing the signatures of the `_with` methods.
+/// The same options can be reused for multiple searches.
+///
+/// # Examples
+///
+/// ```rust
+/// let options =
+///     uapi_config::FindOptions::new()
+///     .skip_hidden_files(true)
+///     .skip_backup_files(true)
+///     .symlink_policy(uapi_config::SymlinkPolicy::WithinSearchDirectories)
+///     .ordering(uapi_config::Ordering::Strverscmp);
+///
+/// let search_directories = uapi_config::SearchDirectories::modern_system();
+///
+/// let files =
+///     search_directories.clone()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &options)
+///     .unwrap();
+///
+/// let files =
+///     search_directories
+///     .with_file_name("foobar.conf")
+///     .find_files_with(Some(".conf"), &options)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)] // Each bool is an independent option, not a state machine.
+pub struct FindOptions {
+	#[cfg(feature = "regex")]
+	dropin_regex: Option<regex::bytes::Regex>,
+	skip_backup_files: bool,
+	skip_hidden_files: bool,
+	dropin_suffix_match: SuffixMatch,
+	ordering: Ordering,
+	open_options: Option<fs::OpenOptions>,
+	symlink_policy: SymlinkPolicy,
+	dropin_directory_symlink_policy: SymlinkPolicy,
+	canonicalize_paths: bool,
+	dedupe_canonical_paths: bool,
+	dedupe_inodes: bool,
+	dedupe_search_directories: bool,
+	template_dropins: bool,
+	type_dropins: bool,
+	prefix_dropins: bool,
+	max_dropins: Option<usize>,
+	max_file_size: Option<u64>,
+	require_main_file: bool,
+	require_files: bool,
+	permission_policy: Option<PermissionPolicy>,
+	executables_only: bool,
+	conditional_dropin_subdirectories: Option<SubdirectoryCondition>,
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	filesystem_condition: Option<FilesystemCondition>,
+	search_directory_timeout: Option<std::time::Duration>,
+	skip_diagnostics: Option<SkipDiagnostics>,
+	observer: Option<Observer>,
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
+	io_uring: bool,
+}
+
+impl FindOptions {
+	/// Start with the default options.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Only consider dropin files whose name matches the given regular expression,
+	/// in addition to ending with the dropin suffix.
+	///
+	/// For example, `^[0-9]+-` only considers dropins whose name starts with a numeric prefix like `10-foo.conf`.
+	#[cfg(feature = "regex")]
+	#[must_use]
+	pub fn dropin_regex(mut self, dropin_regex: regex::bytes::Regex) -> Self {
+		self.dropin_regex = Some(dropin_regex);
+		self
+	}
+
+	/// If `true`, dropin files that look like editor or package manager backups are not considered,
+	/// even if they end with the dropin suffix. Defaults to `false`.
+	///
+	/// This matches names ending with `~`, and names ending with extensions like `.bak`, `.swp`, `.rpmnew`, `.rpmsave`,
+	/// `.dpkg-dist`, `.dpkg-old`, `.ucf-dist`, `.pacnew` and `.pacsave`. See [`BACKUP_FILE_SUFFIXES`] for the full list.
+	#[must_use]
+	pub fn skip_backup_files(mut self, skip_backup_files: bool) -> Self {
+		self.skip_backup_files = skip_backup_files;
+		self
+	}
+
+	/// If `true`, dropin files whose name starts with `.` are not considered, like systemd does. Defaults to `false`,
+	/// since the spec does not exclude such files.
+	#[must_use]
+	pub fn skip_hidden_files(mut self, skip_hidden_files: bool) -> Self {
+		self.skip_hidden_files = skip_hidden_files;
+		self
+	}
+
+	/// Set how the dropin suffix is matched against the names of dropin files. Defaults to [`SuffixMatch::EndsWith`].
+	#[must_use]
+	pub fn dropin_suffix_match(mut self, dropin_suffix_match: SuffixMatch) -> Self {
+		self.dropin_suffix_match = dropin_suffix_match;
+		self
+	}
+
+	/// Set the order in which dropin files are yielded, by their file names. Defaults to [`Ordering::Lexicographic`].
+	#[must_use]
+	pub fn ordering(mut self, ordering: Ordering) -> Self {
+		self.ordering = ordering;
+		self
+	}
+
+	/// Set the [`OpenOptions`](fs::OpenOptions) that are used to open the files that are found.
+	/// Defaults to options that only enable [`read`](fs::OpenOptions::read).
+	///
+	/// This can be used to set additional flags with [`std::os::unix::fs::OpenOptionsExt`], such as `O_NOATIME`.
+	/// Note that the standard library always opens files with `O_CLOEXEC`. The returned [`File`]s can be converted into
+	/// [`OwnedFd`](std::os::fd::OwnedFd)s with [`From`] if necessary.
+	///
+	/// The options should not enable writing, creating or truncating, since that could modify the config files.
+	///
+	/// With the `rustix` feature, files are normally opened relative to a file descriptor of the directory they were found in.
+	/// If this is set, they are opened by their full path instead, since [`OpenOptions`](fs::OpenOptions) can only open paths.
+	#[must_use]
+	pub fn open_options(mut self, open_options: fs::OpenOptions) -> Self {
+		self.open_options = Some(open_options);
+		self
+	}
+
+	/// Set how config files that are symlinks are handled. Defaults to [`SymlinkPolicy::Follow`].
+	///
+	/// This only applies to the config files themselves, not to the search directories or dropin directories they are in.
+	/// See [`FindOptions::dropin_directory_symlink_policy`] for the latter.
+	#[must_use]
+	pub fn symlink_policy(mut self, symlink_policy: SymlinkPolicy) -> Self {
+		self.symlink_policy = symlink_policy;
+		self
+	}
+
+	/// Set how dropin directories that are symlinks, like `/etc/foo.conf.d -> /usr/share/foo/conf.d`, are handled.
+	/// Defaults to [`SymlinkPolicy::Follow`].
+	///
+	/// With [`SymlinkPolicy::Refuse`], such dropin directories are ignored, as if they did not exist. With [`SymlinkPolicy::WithinSearchDirectories`],
+	/// they are ignored unless the directory they point to is inside one of the search directories. The search directories themselves
+	/// are always followed if they are symlinks.
+	///
+	/// The dropin directory is checked before it is opened, so this protects against misconfiguration rather than against
+	/// an attacker who can replace the dropin directory during the search.
+	#[must_use]
+	pub fn dropin_directory_symlink_policy(mut self, dropin_directory_symlink_policy: SymlinkPolicy) -> Self {
+		self.dropin_directory_symlink_policy = dropin_directory_symlink_policy;
+		self
+	}
+
+	/// If `true`, the paths of the files that are found are canonicalized, so that they are the real paths of the files with
+	/// all symlinks resolved, instead of the paths in the search directories that the files were found at. Defaults to `false`.
+	///
+	/// This is useful for logging the files, or for comparing them with the paths used by other programs. It requires one additional
+	/// syscall per file. The files are still opened through the paths that they were found at, so this does not affect which files are found.
+	///
+	/// If the search directories were created with [`SearchDirectories::chroot_contained`], symlinks are resolved within the root,
+	/// but the resulting paths still include the root.
+	#[must_use]
+	pub fn canonicalize_paths(mut self, canonicalize_paths: bool) -> Self {
+		self.canonicalize_paths = canonicalize_paths;
+		self
+	}
+
+	/// If `true`, files whose paths resolve to the same canonical path, such as because of symlinks, are only returned once.
+	/// Defaults to `false`.
+	///
+	/// When the same file is found more than once, only the occurrence that would have been yielded last is kept,
+	/// since that is the position in which its settings take effect.
+	#[must_use]
+	pub fn dedupe_canonical_paths(mut self, dedupe_canonical_paths: bool) -> Self {
+		self.dedupe_canonical_paths = dedupe_canonical_paths;
+		self
+	}
+
+	/// If `true`, files that are the same inode, such as hardlinks of each other, are only returned once. Defaults to `false`.
+	///
+	/// As with [`FindOptions::dedupe_canonical_paths`], only the occurrence that would have been yielded last is kept.
+	/// Unlike that option, this does not require any additional syscalls.
+	#[must_use]
+	pub fn dedupe_inodes(mut self, dedupe_inodes: bool) -> Self {
+		self.dedupe_inodes = dedupe_inodes;
+		self
+	}
+
+	/// If `true`, and the config file name looks like an instance of a template such as `foo@bar.service`,
+	/// then the dropin directories of the template such as `foo@.service.d` are also searched, like systemd does. Defaults to `false`.
+	///
+	/// Within each search directory, dropins in the dropin directory of the instance override dropins with the same name
+	/// in the dropin directory of the template. As usual, dropins in later search directories override dropins in earlier ones.
+	///
+	/// This only applies to searches with a config file name, not to searches with only a project name.
+	#[must_use]
+	pub fn template_dropins(mut self, template_dropins: bool) -> Self {
+		self.template_dropins = template_dropins;
+		self
+	}
+
+	/// If `true`, and the config file name has an extension such as `foo.service`, then the dropin directories
+	/// for all config files with that extension such as `service.d` are also searched, like systemd does. Defaults to `false`.
+	///
+	/// Within each search directory, these dropin directories are the least specific, so dropins in the other dropin directories
+	/// override dropins with the same name in them. As usual, dropins in later search directories override dropins in earlier ones.
+	///
+	/// This only applies to searches with a config file name, not to searches with only a project name.
+	#[must_use]
+	pub fn type_dropins(mut self, type_dropins: bool) -> Self {
+		self.type_dropins = type_dropins;
+		self
+	}
+
+	/// If `true`, and the config file name contains dashes such as `foo-bar-baz.service`, then the dropin directories for the name
+	/// truncated after each dash such as `foo-.service.d` and `foo-bar-.service.d` are also searched, like systemd does. Defaults to `false`.
+	///
+	/// Within each search directory, dropins in the dropin directories of longer prefixes override dropins with the same name
+	/// in the dropin directories of shorter prefixes, and dropins in the dropin directories of the config file itself (and its template,
+	/// if [`FindOptions::template_dropins`] is set) override both. As usual, dropins in later search directories override dropins in earlier ones.
+	///
+	/// This only applies to searches with a config file name, not to searches with only a project name.
+	#[must_use]
+	pub fn prefix_dropins(mut self, prefix_dropins: bool) -> Self {
+		self.prefix_dropins = prefix_dropins;
+		self
+	}
+
+	/// Set the maximum number of dropin files that are considered for each config file. Defaults to no limit.
+	///
+	/// If the dropin directories contain more candidate files than this, the search fails with an error that wraps a [`TooManyDropinsError`],
+	/// rather than using an unbounded amount of memory and time. Candidates are counted after filtering by their names,
+	/// and files with the same name in multiple dropin directories are only counted once.
+	#[must_use]
+	pub fn max_dropins(mut self, max_dropins: usize) -> Self {
+		self.max_dropins = Some(max_dropins);
+		self
+	}
+
+	/// Set the maximum size in bytes of the files that are found. Defaults to no limit.
+	///
+	/// If a main file or dropin file is larger than this, the search fails with an error that wraps a [`FileTooLargeError`],
+	/// so that a file that was misplaced into a config directory is not read into memory. The size is checked when the file is found,
+	/// so a file that grows after that is not caught. Use [`Read::take`](std::io::Read::take) when reading the files if that matters.
+	#[must_use]
+	pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+		self.max_file_size = Some(max_file_size);
+		self
+	}
+
+	/// If `true`, the search fails with an error that wraps a [`ConfigNotFoundError`] if no main file is found,
+	/// instead of only returning the dropins. Defaults to `false`.
+	///
+	/// This is for programs that cannot run without their config file. With [`SearchDirectoriesForProject`], the search always fails
+	/// unless [`SearchDirectoriesForProject::with_main_file`] is used, since no main file is searched for otherwise.
+	#[must_use]
+	pub fn require_main_file(mut self, require_main_file: bool) -> Self {
+		self.require_main_file = require_main_file;
+		self
+	}
+
+	/// If `true`, the search fails with an error that wraps a [`ConfigNotFoundError`] if no files are found at all,
+	/// instead of returning no files. Defaults to `false`.
+	#[must_use]
+	pub fn require_files(mut self, require_files: bool) -> Self {
+		self.require_files = require_files;
+		self
+	}
+
+	/// Set the owners and permissions that the files that are found are required to have. Defaults to allowing all files.
+	///
+	/// If a main file or dropin file violates the policy, the search fails with an error that wraps a [`PermissionError`].
+	/// See [`PermissionPolicy`] for how to only warn about such files instead.
+	///
+	/// On platforms where files do not have owners and modes, such as WASI, the search fails with an error of kind
+	/// [`io::ErrorKind::Unsupported`] when a file is found.
+	#[must_use]
+	pub fn permission_policy(mut self, permission_policy: PermissionPolicy) -> Self {
+		self.permission_policy = Some(permission_policy);
+		self
+	}
+
+	/// If `true`, only files that have at least one of the executable bits set are found. Defaults to `false`.
+	///
+	/// This is for hook directories that contain scripts to run, like `run-parts`. Files that are not executable are skipped
+	/// like files that are not regular files, so they do not override files with the same name in directories with lower precedence.
+	///
+	/// On platforms where files do not have modes, such as WASI, the search fails with an error of kind
+	/// [`io::ErrorKind::Unsupported`] when a file is found.
+	#[must_use]
+	pub fn executables_only(mut self, executables_only: bool) -> Self {
+		self.executables_only = executables_only;
+		self
+	}
+
+	/// Also treat the subdirectories of dropin directories that match `condition` as dropin directories, such as per-architecture
+	/// subdirectories like `foo.d/x86-64/` with [`SubdirectoryCondition::architecture`]. Defaults to not using any subdirectories.
+	///
+	/// The dropins in a matching subdirectory are treated as if they were in the dropin directory itself, except that they
+	/// override the dropins with the same name in the dropin directory. If more than one subdirectory matches, they are used
+	/// in lexicographic order, so dropins in later subdirectories override those in earlier ones. As usual, dropins in
+	/// the dropin directories of later search directories override all of them.
+	#[must_use]
+	pub fn conditional_dropin_subdirectories(mut self, condition: SubdirectoryCondition) -> Self {
+		self.conditional_dropin_subdirectories = Some(condition);
+		self
+	}
+
+	/// Only search the search directories that are on filesystems that match `condition`, such as to skip search directories
+	/// on network filesystems with [`FilesystemCondition::local_only`]. Defaults to searching all search directories.
+	///
+	/// The filesystem of each search directory is looked up with `statfs(2)` at the start of every search.
+	/// The skipped search directories are recorded with [`SkipReason::Filesystem`] in the [`FindOptions::skip_diagnostics`], if set.
+	/// Since `statfs(2)` itself can block if the filesystem does not respond, combine this with [`FindOptions::search_directory_timeout`]
+	/// to also skip such search directories.
+	///
+	/// Requires the `rustix` feature.
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[must_use]
+	pub fn filesystem_condition(mut self, condition: FilesystemCondition) -> Self {
+		self.filesystem_condition = Some(condition);
+		self
+	}
+
+	/// Skip the search directories that do not respond within `timeout`, such as because they are on a network filesystem whose server
+	/// is unreachable or an automount that hangs. Defaults to no timeout.
+	///
+	/// At the start of every search, a new thread is spawned for each search directory to look it up, and the search directory is skipped
+	/// if the lookup does not finish within `timeout`. This costs a thread spawn per search directory per search, so only set this
+	/// if the search directories can be on filesystems that hang. A thread whose lookup does not finish stays blocked until
+	/// the filesystem responds, and cannot be cleaned up. While it is blocked, later searches skip that search directory immediately
+	/// without spawning another thread, so at most one thread is left behind per hung search directory.
+	/// The skipped search directories are recorded with [`SkipReason::TimedOut`] in the [`FindOptions::skip_diagnostics`], if set.
+	///
+	/// This only applies to the search directories themselves. A filesystem that is mounted under a search directory and hangs
+	/// can still block the search.
+	#[must_use]
+	pub fn search_directory_timeout(mut self, timeout: std::time::Duration) -> Self {
+		self.search_directory_timeout = Some(timeout);
+		self
+	}
+
+	/// Record the files in dropin directories that the search looks at but does not return, and why, in `diagnostics`.
+	/// Defaults to not recording them.
+	///
+	/// This is for finding out why a dropin is not being used, such as because it has the wrong suffix or is overridden
+	/// by a dropin with the same name in a search directory with higher precedence.
+	#[must_use]
+	pub fn skip_diagnostics(mut self, diagnostics: SkipDiagnostics) -> Self {
+		self.skip_diagnostics = Some(diagnostics);
+		self
+	}
+
+	/// Report the timings and counters of searches with these options, and of the helpers that read the files that they find,
+	/// to `observer`. Defaults to not reporting them.
+	///
+	/// This is for exporting the latency of loading config as metrics, such as from a long-running daemon.
+	/// See [`SearchObserver`] for what is reported.
+	#[must_use]
+	pub fn observer(mut self, observer: Arc<dyn SearchObserver>) -> Self {
+		self.observer = Some(Observer(observer));
+		self
+	}
+
+	/// If `true`, search directories that refer to the same directory, such as `/var/run` and `/run` when `/var/run` is a symlink to `/run`,
+	/// are only searched once. Defaults to `false`.
+	///
+	/// As with [`FindOptions::dedupe_canonical_paths`], only the occurrence with the highest precedence is kept. This requires one `stat`
+	/// of each search directory per search.
+	#[must_use]
+	pub fn dedupe_search_directories(mut self, dedupe_search_directories: bool) -> Self {
+		self.dedupe_search_directories = dedupe_search_directories;
+		self
+	}
+
+	/// If `true`, the files that are found are opened in batches using `io_uring`, which submits the `openat` and `statx` calls
+	/// for all the files in a batch at once. Defaults to `false`.
+	///
+	/// This reduces the syscall overhead of opening a large number of files, such as when loading thousands of units at boot.
+	/// If `io_uring` is not available, such as because the kernel is too old or because it is disabled by a seccomp filter,
+	/// the files are opened normally.
+	///
+	/// This only applies to the `find_files` methods. Files that are opened with a custom [`FindOptions::open_options`]
+	/// or within a root with `SearchDirectories::chroot_contained` are always opened normally.
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
+	#[must_use]
+	pub fn io_uring(mut self, io_uring: bool) -> Self {
+		self.io_uring = io_uring;
+		self
+	}
+
+	/// Opens all the entries that were found with these options.
+	fn open_all(&self, entries: Entries) -> io::Result<Files> {
+		let started = self.observer.as_ref().map(|_| std::time::Instant::now());
+
+		#[cfg(all(feature = "io-uring", target_os = "linux"))]
+		let files = if self.io_uring { entries.open_all_io_uring() } else { entries.open_all() };
+		#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+		let files = entries.open_all();
+		let mut files = files?;
+
+		if let (Some(observer), Some(started)) = (&self.observer, started) {
+			observer.0.files_opened(files.len(), started.elapsed());
+			files.observer = Some(observer.clone());
+		}
+
+		Ok(files)
+	}
+
+	/// Returns why a file with the given name is not a dropin candidate, or `None` if it is one.
+	fn dropin_candidate_skip_reason(&self, file_name: &[u8], suffix: &[u8]) -> Option<SkipReason> {
+		if !self.dropin_suffix_match.matches(file_name, suffix) {
+			return Some(SkipReason::SuffixMismatch);
+		}
+
+		if self.skip_hidden_files && file_name.starts_with(b".") {
+			return Some(SkipReason::Hidden);
+		}
+
+		if self.skip_backup_files && BACKUP_FILE_SUFFIXES.iter().any(|backup_suffix| file_name.ends_with(backup_suffix.as_bytes())) {
+			return Some(SkipReason::Backup);
+		}
+
+		#[cfg(feature = "regex")]
+		if let Some(dropin_regex) = &self.dropin_regex {
+			if !dropin_regex.is_match(file_name) {
+				return Some(SkipReason::RegexMismatch);
+			}
+		}
+
+		None
+	}
+
+	/// Records that the file at `path` was skipped, if [`FindOptions::skip_diagnostics`] is set.
+	/// `path` is only called if the file needs to be recorded.
+	fn record_skipped<F>(&self, path: F, reason: SkipReason) where F: FnOnce() -> PathBuf {
+		if let Some(skip_diagnostics) = &self.skip_diagnostics {
+			skip_diagnostics.record(path(), reason);
+		}
+	}
+
+	/// Returns an error if `num_dropins` dropins exceed the [`FindOptions::max_dropins`] limit.
+	/// `directory` is the dropin directory that the last of those dropins was found in.
+	fn check_max_dropins(&self, num_dropins: usize, directory: &Path) -> io::Result<()> {
+		match self.max_dropins {
+			Some(max_dropins) if num_dropins > max_dropins => Err(io::Error::other(TooManyDropinsError {
+				directory: directory.to_owned(),
+				max_dropins,
+			})),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns an error if `entries` do not contain the files required by [`FindOptions::require_main_file`] and [`FindOptions::require_files`].
+	/// `name` is only called to get the name that was searched for for the error.
+	fn check_required_files<F>(&self, entries: &Entries, search_directories: &[&Path], name: F) -> io::Result<()> where F: FnOnce() -> OsString {
+		let main_file_required =
+			if self.require_main_file && entries.main_file.is_none() {
+				true
+			}
+			else if self.require_files && entries.is_empty() {
+				false
+			}
+			else {
+				return Ok(());
+			};
+
+		Err(io::Error::new(io::ErrorKind::NotFound, ConfigNotFoundError {
+			name: name(),
+			search_directories: search_directories.iter().map(|&path| path.to_owned()).collect(),
+			main_file_required,
+		}))
+	}
+
+	/// Returns an error if a file of the given size exceeds the [`FindOptions::max_file_size`] limit.
+	/// `path` is only called to get the path of the file for the error.
+	fn check_max_file_size<F>(&self, size: u64, path: F) -> io::Result<()> where F: FnOnce() -> PathBuf {
+		match self.max_file_size {
+			Some(max_file_size) if size > max_file_size => Err(io::Error::other(FileTooLargeError {
+				path: path(),
+				size,
+				max_file_size,
+			})),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns an error if a file with the given metadata violates the [`FindOptions::permission_policy`].
+	/// `path` is only called to get the path of the file for the error.
+	fn check_permission_policy<F>(&self, metadata: &EntryMetadata, path: F) -> io::Result<()> where F: FnOnce() -> PathBuf {
+		if let Some(permission_policy) = &self.permission_policy {
+			let (uid, mode) = metadata.owner_and_mode()?;
+			permission_policy.check_owner_and_mode(uid, mode, path)?;
+		}
+		Ok(())
+	}
+
+	/// Returns whether a file with the given metadata should be skipped because of the [`FindOptions::executables_only`] option.
+	fn skip_non_executable(&self, metadata: &EntryMetadata) -> io::Result<bool> {
+		if !self.executables_only {
+			return Ok(false);
+		}
+
+		let (_, mode) = metadata.owner_and_mode()?;
+		Ok(mode & 0o111 == 0)
+	}
+
+	/// Sorts dropins that have been deduplicated by file name according to the ordering option.
+	fn sort_dropins<T>(&self, dropins: BTreeMap<OsString, T>) -> Vec<(OsString, T)> {
+		let mut dropins: Vec<_> = dropins.into_iter().collect();
+		match &self.ordering {
+			// Already sorted by the BTreeMap, since `OsStr`s are ordered by their bytes.
+			Ordering::Lexicographic => (),
+
+			Ordering::Strverscmp => dropins.sort_by(|(file_name1, _), (file_name2, _)| strverscmp::strverscmp(file_name1.as_bytes(), file_name2.as_bytes())),
+
+			// The sort is stable, so file names that compare equal stay in lexicographic order.
+			Ordering::Custom(f) => dropins.sort_by(|(file_name1, _), (file_name2, _)| f(file_name1, file_name2)),
+		}
+		dropins
+	}
+
+}
+
+/// How the dropin suffix is matched against the names of dropin files.
+///
+/// Set with [`FindOptions::dropin_suffix_match`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SuffixMatch {
+	/// The file name must end with the suffix.
+	///
+	/// For example, the suffix `".conf"` matches `foo.conf` and also a file named just `.conf`,
+	/// and the suffix `"conf"` matches `foo.conf` and also `fooconf`.
+	#[default]
+	EndsWith,
+
+	/// The suffix must be the file extension of the file name, ie the file name must end with the suffix,
+	/// the suffix must be preceded by a `.` if it does not start with one itself, and the rest of the file name must not be empty.
+	///
+	/// For example, both the suffixes `".conf"` and `"conf"` match `foo.conf`, but neither of them match `.conf` or `fooconf`.
+	Extension,
+}
+
+impl SuffixMatch {
+	fn matches(self, file_name: &[u8], suffix: &[u8]) -> bool {
+		let Some(stem) = file_name.strip_suffix(suffix) else {
+			return false;
+		};
+
+		match self {
+			Self::EndsWith => true,
+
+			Self::Extension => {
+				let stem =
+					if suffix.starts_with(b".") {
+						stem
+					}
+					else if let Some(stem) = stem.strip_suffix(b".") {
+						stem
+					}
+					else {
+						return false;
+					};
+				!stem.is_empty()
+			},
+		}
+	}
+}
+
+/// The order in which dropin files are yielded, by their file names.
+///
+/// Set with [`FindOptions::ordering`].
+#[derive(Clone, Default)]
+pub enum Ordering {
+	/// Dropins are sorted by the bytes of their file names, so `10-foo.conf` sorts before `2-foo.conf`.
+	/// This is the order described by the spec.
+	#[default]
+	Lexicographic,
+
+	/// Dropins are sorted like glibc's `strverscmp` would sort their file names, so `2-foo.conf` sorts before `10-foo.conf`.
+	Strverscmp,
+
+	/// Dropins are sorted using the given function to compare their file names.
+	///
+	/// File names that the function considers equal are sorted lexicographically relative to each other.
+	Custom(Comparator),
+}
+
+/// A function that compares two dropin file names, for use with [`Ordering::Custom`].
+pub type Comparator = Arc<dyn Fn(&OsStr, &OsStr) -> std::cmp::Ordering + Send + Sync>;
+
+impl Ordering {
+	/// Convenience function to create an [`Ordering::Custom`] from the given function.
+	///
+	/// # Examples
+	///
+	/// ## Sort dropins by their length, then lexicographically
+	///
+	/// ```rust
+	/// let ordering = uapi_config::Ordering::custom(|file_name1, file_name2| file_name1.len().cmp(&file_name2.len()));
+	/// ```
+	pub fn custom<F>(f: F) -> Self where F: Fn(&OsStr, &OsStr) -> std::cmp::Ordering + Send + Sync + 'static {
+		Self::Custom(Arc::new(f))
+	}
+}
+
+impl std::fmt::Debug for Ordering {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Lexicographic => f.write_str("Lexicographic"),
+			Self::Strverscmp => f.write_str("Strverscmp"),
+			Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+		}
+	}
+}
+
+/// How config files or dropin directories that are symlinks are handled.
+///
+/// Set with [`FindOptions::symlink_policy`] and [`FindOptions::dropin_directory_symlink_policy`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+	/// Symlinks are followed, and the file or directory they point to is used.
+	#[default]
+	Follow,
+
+	/// Symlinks are ignored, as if they did not exist.
+	///
+	/// This protects against symlinks placed in world-writable directories that point to files the process should not read.
+	Refuse,
+
+	/// Symlinks are followed only if the file or directory they point to is inside one of the search directories.
+	/// Symlinks to anywhere else are ignored, as if they did not exist.
+	WithinSearchDirectories,
+}
+
+/// The environment variable that [`SearchDirectories::chroot_from_env`] uses if the application's own variable is not set.
+///
+/// Setting it redirects the searches of all programs that use [`SearchDirectories::chroot_from_env`], regardless of their own variable.
+pub const ROOT_ENV_VAR: &str = "UAPI_CONFIG_ROOT";
+
+/// The suffixes of file names that are considered to be editor or package manager backups by [`FindOptions::skip_backup_files`].
+pub const BACKUP_FILE_SUFFIXES: &[&str] = &[
+	"~",
+	".bak",
+	".new",
+	".old",
+	".orig",
+	".swp",
+	".rpmnew",
+	".rpmorig",
+	".rpmsave",
+	".dpkg-backup",
+	".dpkg-bak",
+	".dpkg-dist",
+	".dpkg-new",
+	".dpkg-old",
+	".dpkg-remove",
+	".dpkg-tmp",
+	".ucf-dist",
+	".ucf-new",
+	".ucf-old",
+	".pacnew",
+	".pacorig",
+	".pacsave",
+];
+
+/// A list of search directories that the config files will be searched under, scoped to a particular project.
+///
+/// Created using [`SearchDirectories::with_project`].
+#[derive(Clone, Debug)]
+pub struct SearchDirectoriesForProject<'a, TProject> {
+	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+	chroots: Vec<PathBuf>,
+	project: TProject,
+	legacy_projects: Vec<TProject>,
+	main_file: bool,
+}
+
+impl<'a, TProject> SearchDirectoriesForProject<'a, TProject> {
+	/// Search for configuration files of this project with the given config file name.
+	pub fn with_file_name<TFileName>(
+		self,
+		file_name: TFileName,
+	) -> SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName>
+	{
+		SearchDirectoriesForProjectAndFileName {
+			inner: self.inner,
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			project: self.project,
+			legacy_projects: self.legacy_projects,
+			file_name,
+			fallback_file_names: vec![],
+		}
+	}
+
+	/// Also search for configuration files under the given legacy project names, in decreasing order of preference,
+	/// such as when a project has been renamed but existing overrides under its old name must still be honored.
+	///
+	/// Within each search directory, dropins under the project name override dropins with the same name under the legacy project names,
+	/// and dropins under earlier legacy project names override those under later ones. As usual, dropins in later search directories
+	/// override dropins in earlier ones, so an override under a legacy project name in `/etc` still overrides a vendor config
+	/// under the project name in `/usr/etc`.
+	#[must_use]
+	pub fn with_legacy_projects<I>(mut self, legacy_projects: I) -> Self where I: IntoIterator<Item = TProject> {
+		self.legacy_projects.extend(legacy_projects);
+		self
+	}
+
+	/// Also search for a main file named `format!("{project}{dropin_suffix}")` directly under the search directories, like the spec suggests
+	/// for a project's main config file. For example, with a `dropin_suffix` of `".conf"`, this finds `/etc/foobar.conf`
+	/// in addition to the dropins `/etc/foobar.d/*.conf`.
+	///
+	/// As with [`SearchDirectoriesForFileName`], the main file is the one in the last search directory that contains it,
+	/// and it is yielded before the dropins. If [`with_legacy_projects`](Self::with_legacy_projects) is used, then within each
+	/// search directory the main file of the project name is looked for first, followed by those of the legacy project names in order.
+	#[must_use]
+	pub fn with_main_file(mut self) -> Self {
+		self.main_file = true;
+		self
+	}
+
+	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
+	/// The name `format!("{project}.d")` is appended to each search directory, then those directories are searched as if they are
+	/// dropin directories. Only dropin files whose name ends with `dropin_suffix` will be considered.
+	/// Note that if you intend to use a file extension as a suffix, then `dropin_suffix` must include the `.`, such as `".conf"`.
+	///
+	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
+	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
+	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	///
+	/// # Examples
+	///
+	/// ## Get all config files for the system service `foobar`
+	///
+	/// ... taking into account OS vendor configs, ephemeral overrides and sysadmin overrides.
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap();
+	/// ```
+	///
+	/// This will locate all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf` in lexicographical order.
+	///
+	/// ## Get all config files for the application `foobar`
+	///
+	/// ... taking into account OS vendor configs, ephemeral overrides, sysadmin overrides and local user overrides.
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_user_directory()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap();
+	/// ```
+	///
+	/// This will locate all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf`, `$XDG_CONFIG_HOME/foobar.d/*.conf`
+	/// in lexicographical order.
+	///
+	#[cfg_attr(feature = "dirs", doc = r"## Get all config files for the application `foobar`")]
+	#[cfg_attr(feature = "dirs", doc = r"")]
+	#[cfg_attr(feature = "dirs", doc = r"... with custom paths for the OS vendor configs, sysadmin overrides and local user overrides.")]
+	#[cfg_attr(feature = "dirs", doc = r"")]
+	#[cfg_attr(feature = "dirs", doc = r"```rust")]
+	#[cfg_attr(feature = "dirs", doc = r"// OS and sysadmin configs")]
+	#[cfg_attr(feature = "dirs", doc = r"let mut search_directories: uapi_config::SearchDirectories = [")]
+	#[cfg_attr(feature = "dirs", doc = r#"    std::path::Path::new("/usr/share").into(),"#)]
+	#[cfg_attr(feature = "dirs", doc = r#"    std::path::Path::new("/etc").into(),"#)]
+	#[cfg_attr(feature = "dirs", doc = r"].into_iter().collect();")]
+	#[cfg_attr(feature = "dirs", doc = r"")]
+	#[cfg_attr(feature = "dirs", doc = r"// Local user configs under `${XDG_CONFIG_HOME:-$HOME/.config}`")]
+	#[cfg_attr(feature = "dirs", doc = r"if let Some(user_config_dir) = dirs::config_dir() {")]
+	#[cfg_attr(feature = "dirs", doc = r"    search_directories.push(user_config_dir.into());")]
+	#[cfg_attr(feature = "dirs", doc = r"}")]
+	#[cfg_attr(feature = "dirs", doc = r"")]
+	#[cfg_attr(feature = "dirs", doc = r"let files =")]
+	#[cfg_attr(feature = "dirs", doc = r"    search_directories")]
+	#[cfg_attr(feature = "dirs", doc = r#"    .with_project("foobar")"#)]
+	#[cfg_attr(feature = "dirs", doc = r#"    .find_files(".conf")"#)]
+	#[cfg_attr(feature = "dirs", doc = r"    .unwrap();")]
+	#[cfg_attr(feature = "dirs", doc = r"```")]
+	#[cfg_attr(feature = "dirs", doc = r"")]
+	#[cfg_attr(feature = "dirs", doc = r"This will locate `/usr/share/foobar.d/*.conf`, `/etc/foobar.d/*.conf`, `$XDG_CONFIG_HOME/foobar.d/*.conf` in that order and return the last one.")]
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: TDropinSuffix,
+	) -> io::Result<Files>
+	where
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: TDropinSuffix,
+		options: &FindOptions,
+	) -> io::Result<Files>
+	where
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		options.open_all(self.find_entries_with(dropin_suffix, options)?)
+	}
+
+	/// Same as [`find_files`](Self::find_files), but the files are not opened. Instead, each [`Entry`] can be opened when it is needed.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries<TDropinSuffix>(
+		self,
+		dropin_suffix: TDropinSuffix,
+	) -> io::Result<Entries>
+	where
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_entries_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_entries`](Self::find_entries), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries_with<TDropinSuffix>(
+		self,
+		dropin_suffix: TDropinSuffix,
+		options: &FindOptions,
+	) -> io::Result<Entries>
+	where
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		let (main_file, dropins) = self.find_in(&context, &paths, dropin_suffix.as_ref())?;
+
+		let entries = context.entries(main_file, dropins)?;
+		options.check_required_files(&entries, &paths, || self.project.as_ref().to_owned())?;
+		Ok(entries)
+	}
+
+	/// Finds the main file and dropins in the search directories at `paths` on the filesystem of `context`.
+	fn find_in<F>(&self, context: &SearchContext<'_, F>, paths: &[&Path], dropin_suffix: &OsStr) -> io::Result<FoundFiles<F::Dir>>
+	where
+		F: OpenDir,
+		TProject: AsRef<OsStr>,
+	{
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let main_file =
+			if self.main_file {
+				let file_names: Vec<OsString> =
+					projects.iter().rev()
+					.map(|project| {
+						let mut file_name = project.to_os_string();
+						file_name.push(dropin_suffix);
+						file_name
+					})
+					.collect();
+				let file_names: Vec<&OsStr> = file_names.iter().map(AsRef::as_ref).collect();
+				let search_directories = context.open_dirs(paths.iter().map(|path| path.to_path_buf()))?;
+				find_main_file(&file_names, context, &search_directories)?
+			}
+			else {
+				None
+			};
+
+		let dropin_directories = context.open_dropin_dirs(paths.iter().flat_map(|path| projects.iter().map(|project| dropin_directory(path.to_path_buf(), project))))?;
+		let dropins = find_dropins(dropin_suffix, context, &dropin_directories)?;
+
+		Ok((main_file, dropins))
+	}
+
+	/// Returns the paths of the dropin directories that exist, in lowest-precedence-first order, without reading them.
+	///
+	/// This is for programs that load something other than config files from the layered directories, such as plugins or scripts,
+	/// and so want to read the directories themselves.
+	///
+	/// # Errors
+	///
+	/// Any errors from non-existing directories are ignored. Apart from that, any I/O errors from looking up the directories are propagated.
+	pub fn find_dropin_directories(self) -> io::Result<Vec<PathBuf>> where TProject: AsRef<OsStr> {
+		self.find_dropin_directories_with(&Default::default())
+	}
+
+	/// Same as [`find_dropin_directories`](Self::find_dropin_directories), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_dropin_directories`](Self::find_dropin_directories).
+	pub fn find_dropin_directories_with(self, options: &FindOptions) -> io::Result<Vec<PathBuf>> where TProject: AsRef<OsStr> {
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		context.existing_dropin_dirs(paths.iter().flat_map(|path| projects.iter().map(|project| dropin_directory(path.to_path_buf(), project))))
+	}
+
+	/// Returns the paths that [`find_files`](Self::find_files) would look at, without reading any directories.
+	///
+	/// See [`SearchPlan`] for details.
+	pub fn describe<TDropinSuffix>(&self, dropin_suffix: TDropinSuffix) -> SearchPlan
+	where
+		TProject: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let dropin_suffix = dropin_suffix.as_ref();
+
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let main_files =
+			if self.main_file {
+				self.inner.iter()
+				.flat_map(|path| projects.iter().map(move |project| {
+					let mut file_name = project.to_os_string();
+					file_name.push(dropin_suffix);
+					path.join(file_name)
+				}))
+				.collect()
+			}
+			else {
+				vec![]
+			};
+
+		let dropin_directories = self.inner.iter().flat_map(|path| projects.iter().map(|project| dropin_directory(path.to_path_buf(), project))).collect();
+
+		SearchPlan::new(main_files, dropin_directories, Some(dropin_suffix.to_owned()))
+	}
+}
+
+/// A list of search directories that the config files will be searched under, scoped to a particular config file name.
+///
+/// Created using [`SearchDirectories::with_file_name`].
+#[derive(Clone, Debug)]
+pub struct SearchDirectoriesForFileName<'a, TFileName> {
+	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+	chroots: Vec<PathBuf>,
+	file_name: TFileName,
+	fallback_file_names: Vec<TFileName>,
+}
+
+impl<'a, TFileName> SearchDirectoriesForFileName<'a, TFileName> {
+	/// Search for configuration files for the given project name and with this config file name.
+	///
+	/// The project name is usually the name of your application.
+	pub fn with_project<TProject>(
+		self,
+		project: TProject,
+	) -> SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName>
+	{
+		SearchDirectoriesForProjectAndFileName {
+			inner: self.inner,
+			root: self.root,
+			finder_cache: self.finder_cache,
+			chroots: self.chroots,
+			project,
+			legacy_projects: vec![],
+			file_name: self.file_name,
+			fallback_file_names: self.fallback_file_names,
+		}
+	}
+
+	/// Also accept the given config file names for the main file, in decreasing order of preference, such as when migrating
+	/// from `foobar.conf` to `foobar.toml`.
+	///
+	/// The search directories are searched in the usual order, and within each search directory the config file name is tried first,
+	/// followed by the fallback file names in order. The first file that is found is the main file, so a file with a fallback name
+	/// in a later search directory overrides a file with the config file name in an earlier search directory.
+	///
+	/// The dropins are those of the name that the main file was found with, or of the config file name if no main file was found.
+	#[must_use]
+	pub fn with_fallback_file_names<I>(mut self, fallback_file_names: I) -> Self where I: IntoIterator<Item = TFileName> {
+		self.fallback_file_names.extend(fallback_file_names);
+		self
+	}
+
+	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
+	/// Only files named `file_name` under the search directories will be considered.
+	///
+	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
+	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
+	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
+	///
+	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
+	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
+	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
+	///
+	/// The main file is the one in the last search directory that contained it when the directories were read. If that file is removed
+	/// before it is opened, the result has no main file rather than the one from an earlier search directory, unlike with
+	/// [`find_first`](Self::find_first), which goes on to the next search directory in that case.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	///
+	/// # Examples
+	///
+	/// ## Get all config files for the system service `foobar`
+	///
+	/// ... taking into account OS vendor configs, ephemeral overrides and sysadmin overrides.
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap();
+	/// ```
+	///
+	/// This will locate `/usr/etc/foobar.conf` `/run/foobar.conf`, `/etc/foobar.conf` in that order and return the last one,
+	/// then all dropins `/usr/etc/foobar.d/*.conf`, `/run/foobar.d/*.conf`, `/etc/foobar.d/*.conf` in lexicographical order.
+	///
+	/// ## Get the config files for the "foo.service" systemd system unit like systemd would do
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::systemd_system_units()
+	///     .with_file_name("foo.service")
+	///     .find_files(Some(".conf"))
+	///     .unwrap();
+	/// ```
+	///
+	/// This will locate `/run/systemd/generator.late/foobar.service` `/usr/lib/systemd/system/foo.service`, ... in that order and return the last one,
+	/// then all dropins `/run/systemd/generator.late/foo.service.d/*.conf`, `/usr/lib/systemd/system/foo.service.d/*.conf`, ... in lexicographical order.
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<Files>
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<Files>
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		options.open_all(self.find_entries_with(dropin_suffix, options)?)
+	}
+
+	/// Same as [`find_files`](Self::find_files), but the files are not opened. Instead, each [`Entry`] can be opened when it is needed.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<Entries>
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_entries_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_entries`](Self::find_entries), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<Entries>
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		let (main_file, dropins) = self.find_in(&context, &paths, dropin_suffix)?;
+
+		let entries = context.entries(main_file, dropins)?;
+		options.check_required_files(&entries, &paths, || self.file_name.as_ref().to_owned())?;
+		Ok(entries)
+	}
+
+	/// Finds the main file and dropins in the search directories at `paths` on the filesystem of `context`.
+	fn find_in<F, TDropinSuffix>(
+		&self,
+		context: &SearchContext<'_, F>,
+		paths: &[&Path],
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<FoundFiles<F::Dir>>
+	where
+		F: OpenDir,
+		TDropinSuffix: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+	{
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let search_directories = context.open_dirs(paths.iter().map(|path| path.to_path_buf()))?;
+		let main_file = find_main_file(&file_names, context, &search_directories)?;
+		let file_name = main_file.as_ref().map_or(file_names[0], |main_file| &main_file.file_name);
+
+		let dropins =
+			if let Some(dropin_suffix) = dropin_suffix {
+				let dropin_directories = context.open_dropin_dirs(dropin_directories(paths.iter().copied(), file_name, context.options))?;
+				find_dropins(dropin_suffix.as_ref(), context, &dropin_directories)?
+			}
+			else {
+				Default::default()
+			};
+
+		Ok((main_file, dropins))
+	}
+
+	/// Returns only the main file with the highest precedence, ignoring any dropins.
+	///
+	/// The search directories are looked up from the highest precedence downwards, and the search stops at the first one
+	/// that contains the file, so this is cheaper than [`find_files`](Self::find_files) for programs that only want "the" config file.
+	///
+	/// This is the same override resolution that [`find_files`](Self::find_files) uses for the main file, so it can also be used
+	/// by programs that handle dropins themselves. The only difference is that if the file is removed between being found and being opened,
+	/// this goes on to look for it in the search directories with lower precedence, whereas [`find_files`](Self::find_files) returns no main file.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let main_file =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_first()
+	///     .unwrap();
+	/// if let Some((path, file)) = main_file {
+	///     // Parse `file`, then find and parse the dropins separately
+	/// }
+	/// ```
+	pub fn find_first(self) -> io::Result<Option<(PathBuf, File)>> where TFileName: AsRef<OsStr> {
+		self.find_first_with(&Default::default())
+	}
+
+	/// Same as [`find_first`](Self::find_first), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_first_with(self, options: &FindOptions) -> io::Result<Option<(PathBuf, File)>> where TFileName: AsRef<OsStr> {
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		find_first_main_file(&file_names, &context, paths.iter().map(|path| path.to_path_buf()))
+	}
+
+	/// Returns the paths of the dropin directories that exist, in lowest-precedence-first order, without reading them.
+	///
+	/// This is for programs that load something other than config files from the layered directories, such as plugins or scripts,
+	/// and so want to read the directories themselves.
+	///
+	/// If [`with_fallback_file_names`](Self::with_fallback_file_names) is used, the main file is looked up first,
+	/// and the dropin directories are those of its file name, as with [`find_files`](Self::find_files).
+	///
+	/// # Errors
+	///
+	/// Any errors from non-existing directories are ignored. Apart from that, any I/O errors from looking up the directories are propagated.
+	pub fn find_dropin_directories(self) -> io::Result<Vec<PathBuf>> where TFileName: AsRef<OsStr> {
+		self.find_dropin_directories_with(&Default::default())
+	}
+
+	/// Same as [`find_dropin_directories`](Self::find_dropin_directories), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_dropin_directories`](Self::find_dropin_directories).
+	pub fn find_dropin_directories_with(self, options: &FindOptions) -> io::Result<Vec<PathBuf>> where TFileName: AsRef<OsStr> {
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		let main_file =
+			if file_names.len() > 1 {
+				let search_directories = context.open_dirs(paths.iter().map(|path| path.to_path_buf()))?;
+				find_main_file(&file_names, &context, &search_directories)?
+			}
+			else {
+				None
+			};
+		let file_name = main_file.as_ref().map_or(file_names[0], |main_file| &main_file.file_name);
+
+		context.existing_dropin_dirs(dropin_directories(paths.iter().copied(), file_name, options))
+	}
+
+	/// Returns the paths that [`find_files`](Self::find_files) would look at, without reading any directories.
+	///
+	/// See [`SearchPlan`] for details. If [`with_fallback_file_names`](Self::with_fallback_file_names) is used,
+	/// the dropin directories are those of the config file name.
+	pub fn describe<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>) -> SearchPlan
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.describe_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`describe`](Self::describe), but with the given [`FindOptions`].
+	pub fn describe_with<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>, options: &FindOptions) -> SearchPlan
+	where
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		// Within each search directory, the preferred file name is looked up first, so it has the highest precedence.
+		let main_files = self.inner.iter().flat_map(|path| file_names.iter().rev().map(|file_name| path.join(file_name))).collect();
+
+		let dropin_directories = dropin_directories(self.inner.iter().map(AsRef::as_ref), file_names[0], options).collect();
+
+		SearchPlan::new(main_files, dropin_directories, dropin_suffix.map(|dropin_suffix| dropin_suffix.as_ref().to_owned()))
+	}
+}
+
+/// A list of search directories that the config files will be searched under, scoped to a particular project and config file name.
+///
+/// Created using [`SearchDirectoriesForProject::with_file_name`] or [`SearchDirectoriesForFileName::with_project`].
+#[derive(Clone, Debug)]
+pub struct SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName> {
+	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+	chroots: Vec<PathBuf>,
+	project: TProject,
+	legacy_projects: Vec<TProject>,
+	file_name: TFileName,
+	fallback_file_names: Vec<TFileName>,
+}
+
+impl<TProject, TFileName> SearchDirectoriesForProjectAndFileName<'_, TProject, TFileName> {
+	/// Also search for configuration files under the given legacy project names, in decreasing order of preference.
+	///
+	/// Within each search directory, the main file is looked for under the project name first, followed by the legacy project names in order.
+	/// See [`SearchDirectoriesForProject::with_legacy_projects`] for details.
+	#[must_use]
+	pub fn with_legacy_projects<I>(mut self, legacy_projects: I) -> Self where I: IntoIterator<Item = TProject> {
+		self.legacy_projects.extend(legacy_projects);
+		self
+	}
+
+	/// Also accept the given config file names for the main file, in decreasing order of preference.
+	///
+	/// See [`SearchDirectoriesForFileName::with_fallback_file_names`] for details.
+	#[must_use]
+	pub fn with_fallback_file_names<I>(mut self, fallback_file_names: I) -> Self where I: IntoIterator<Item = TFileName> {
+		self.fallback_file_names.extend(fallback_file_names);
+		self
+	}
+
+	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
+	/// The project name is appended to each search directory, then those directories are searched for files named `file_name`.
+	///
+	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
+	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
+	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
+	///
+	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
+	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
+	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
+	///
+	/// The main file is the one in the last search directory that contained it when the directories were read. If that file is removed
+	/// before it is opened, the result has no main file rather than the one from an earlier search directory, unlike with
+	/// [`find_first`](Self::find_first), which goes on to the next search directory in that case.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<Files>
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<Files>
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		options.open_all(self.find_entries_with(dropin_suffix, options)?)
+	}
+
+	/// Same as [`find_files`](Self::find_files), but the files are not opened. Instead, each [`Entry`] can be opened when it is needed.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<Entries>
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_entries_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_entries`](Self::find_entries), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_entries_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<Entries>
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		let (main_file, dropins) = self.find_in(&context, &paths, dropin_suffix)?;
+
+		let entries = context.entries(main_file, dropins)?;
+		options.check_required_files(&entries, &paths, || Path::new(self.project.as_ref()).join(self.file_name.as_ref()).into_os_string())?;
+		Ok(entries)
+	}
+
+	/// Finds the main file and dropins in the search directories at `paths` on the filesystem of `context`.
+	fn find_in<F, TDropinSuffix>(
+		&self,
+		context: &SearchContext<'_, F>,
+		paths: &[&Path],
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<FoundFiles<F::Dir>>
+	where
+		F: OpenDir,
+		TDropinSuffix: AsRef<OsStr>,
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+	{
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let project_directories = context.open_dirs(paths.iter().flat_map(|path| projects.iter().map(|project| path.join(project))))?;
+		let main_file = find_main_file(&file_names, context, &project_directories)?;
+		let file_name = main_file.as_ref().map_or(file_names[0], |main_file| &main_file.file_name);
+
+		let dropins =
+			if let Some(dropin_suffix) = dropin_suffix {
+				let dropin_directories = context.open_dropin_dirs(dropin_directories(project_directories.iter().map(|dir| dir.path()), file_name, context.options))?;
+				find_dropins(dropin_suffix.as_ref(), context, &dropin_directories)?
+			}
+			else {
+				Default::default()
+			};
+
+		Ok((main_file, dropins))
+	}
+
+	/// Returns only the main file with the highest precedence, ignoring any dropins.
+	///
+	/// See [`SearchDirectoriesForFileName::find_first`] for details.
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_first(self) -> io::Result<Option<(PathBuf, File)>> where TProject: AsRef<OsStr>, TFileName: AsRef<OsStr> {
+		self.find_first_with(&Default::default())
+	}
+
+	/// Same as [`find_first`](Self::find_first), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_first_with(self, options: &FindOptions) -> io::Result<Option<(PathBuf, File)>> where TProject: AsRef<OsStr>, TFileName: AsRef<OsStr> {
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		find_first_main_file(&file_names, &context, paths.iter().flat_map(|path| projects.iter().map(|project| path.join(project))))
+	}
+
+	/// Returns the paths of the dropin directories that exist, in lowest-precedence-first order, without reading them.
+	///
+	/// See [`SearchDirectoriesForFileName::find_dropin_directories`] for details.
+	///
+	/// # Errors
+	///
+	/// Any errors from non-existing directories are ignored. Apart from that, any I/O errors from looking up the directories are propagated.
+	pub fn find_dropin_directories(self) -> io::Result<Vec<PathBuf>> where TProject: AsRef<OsStr>, TFileName: AsRef<OsStr> {
+		self.find_dropin_directories_with(&Default::default())
+	}
+
+	/// Same as [`find_dropin_directories`](Self::find_dropin_directories), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_dropin_directories`](Self::find_dropin_directories).
+	pub fn find_dropin_directories_with(self, options: &FindOptions) -> io::Result<Vec<PathBuf>> where TProject: AsRef<OsStr>, TFileName: AsRef<OsStr> {
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let context = SearchContext::new(options, &self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref())?;
+		let paths = context.search_directory_paths(&self.inner)?;
+
+		let project_directories: Vec<PathBuf> = paths.iter().flat_map(|path| projects.iter().map(|project| path.join(project))).collect();
+
+		let main_file =
+			if file_names.len() > 1 {
+				let project_directories = context.open_dirs(project_directories.iter().cloned())?;
+				find_main_file(&file_names, &context, &project_directories)?
+			}
+			else {
+				None
+			};
+		let file_name = main_file.as_ref().map_or(file_names[0], |main_file| &main_file.file_name);
+
+		context.existing_dropin_dirs(dropin_directories(project_directories.iter().map(AsRef::as_ref), file_name, options))
+	}
+
+	/// Returns the paths that [`find_files`](Self::find_files) would look at, without reading any directories.
+	///
+	/// See [`SearchPlan`] for details. If [`with_fallback_file_names`](Self::with_fallback_file_names) is used,
+	/// the dropin directories are those of the config file name.
+	pub fn describe<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>) -> SearchPlan
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.describe_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`describe`](Self::describe), but with the given [`FindOptions`].
+	pub fn describe_with<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>, options: &FindOptions) -> SearchPlan
+	where
+		TProject: AsRef<OsStr>,
+		TFileName: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		// Lowest-precedence-first, like the search directories.
+		let projects: Vec<&OsStr> = self.legacy_projects.iter().rev().chain(std::iter::once(&self.project)).map(AsRef::as_ref).collect();
+
+		let file_names: Vec<&OsStr> = std::iter::once(&self.file_name).chain(&self.fallback_file_names).map(AsRef::as_ref).collect();
+
+		let project_directories: Vec<PathBuf> = self.inner.iter().flat_map(|path| projects.iter().map(|project| path.join(project))).collect();
+
+		// Within each project directory, the preferred file name is looked up first, so it has the highest precedence.
+		let main_files = project_directories.iter().flat_map(|path| file_names.iter().rev().map(|file_name| path.join(file_name))).collect();
+
+		let dropin_directories = dropin_directories(project_directories.iter().map(AsRef::as_ref), file_names[0], options).collect();
+
+		SearchPlan::new(main_files, dropin_directories, dropin_suffix.map(|dropin_suffix| dropin_suffix.as_ref().to_owned()))
+	}
+}
+
+/// A list of search directories that the config files will be searched under, scoped to config file names matching a glob pattern.
+///
+/// Created using [`SearchDirectories::with_file_name_glob`].
+#[derive(Clone, Debug)]
+pub struct SearchDirectoriesForFileNameGlob<'a, TPattern> {
+	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+	chroots: Vec<PathBuf>,
+	pattern: TPattern,
+}
+
+impl<TPattern> SearchDirectoriesForFileNameGlob<'_, TPattern> {
+	/// Returns an [`Iterator`] of `(`[`OsString`]`, `[`Files`]`)`s, one for each distinct file name matching the glob pattern
+	/// that was found in any of the specified search directories, in lexicographic order of file name.
+	///
+	/// For each file name, the [`Files`] are the same as what [`SearchDirectoriesForFileName::find_files`] would return for that file name,
+	/// ie the main file is the one in the last search directory that contains it, followed by its dropins.
+	/// File names that only match directories or other non-regular files are not returned.
+	///
+	/// Each search directory is only read once, and only the files and dropin directories that it contains are looked up,
+	/// so this is much cheaper than searching for each file name individually when there are many of them, such as all the units of a service manager.
+	///
+	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
+	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
+	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	///
+	/// # Examples
+	///
+	/// ## Get the config files for all systemd-networkd `.network` units
+	///
+	/// ```rust
+	/// let search_directories: uapi_config::SearchDirectories =
+	///     [
+	///         "/usr/lib/systemd/network",
+	///         "/usr/local/lib/systemd/network",
+	///         "/run/systemd/network",
+	///         "/etc/systemd/network",
+	///     ].into_iter()
+	///     .map(|path| std::path::Path::new(path).into())
+	///     .collect();
+	/// for (file_name, files) in
+	///     search_directories
+	///     .with_file_name_glob("*.network")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	/// {
+	///     // Parse and merge `files` for the unit named `file_name`
+	/// }
+	/// ```
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<FilesByFileName>
+	where
+		TPattern: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<FilesByFileName>
+	where
+		TPattern: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let pattern = self.pattern.as_ref().as_bytes();
+		find_files_by_file_name(&self.inner, self.root.as_ref(), &self.chroots, self.finder_cache.as_ref(), dropin_suffix, options, |file_name| glob::matches(pattern, file_name))
+	}
+
+	/// Returns the paths that [`find_files`](Self::find_files) would look at, without reading any directories.
+	///
+	/// See [`SearchPlan`] for details. The paths contain the glob pattern in place of each file name.
+	pub fn describe<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>) -> SearchPlan
+	where
+		TPattern: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.describe_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`describe`](Self::describe), but with the given [`FindOptions`].
+	pub fn describe_with<TDropinSuffix>(&self, dropin_suffix: Option<TDropinSuffix>, options: &FindOptions) -> SearchPlan
+	where
+		TPattern: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let pattern = self.pattern.as_ref();
+
+		let main_files = self.inner.iter().map(|path| path.join(pattern)).collect();
+
+		let dropin_directories = dropin_directories(self.inner.iter().map(AsRef::as_ref), pattern, options).collect();
+
+		SearchPlan::new(main_files, dropin_directories, dropin_suffix.map(|dropin_suffix| dropin_suffix.as_ref().to_owned()))
+	}
+}
+
+/// A list of search directories that the config files will be searched under, scoped to config file names ending with a suffix.
+///
+/// Created using [`SearchDirectories::with_file_name_suffix`].
+#[derive(Clone, Debug)]
+pub struct SearchDirectoriesForFileNameSuffix<'a, TSuffix> {
+	inner: Vec<Cow<'a, Path>>,
+	root: Option<Arc<Root>>,
+	finder_cache: Option<Arc<FinderCache>>,
+	chroots: Vec<PathBuf>,
+	suffix: TSuffix,
+}
+
+impl<TSuffix> SearchDirectoriesForFileNameSuffix<'_, TSuffix> {
+	/// Returns an [`Iterator`] of `(`[`OsString`]`, `[`Files`]`)`s, one for each distinct file name ending with the suffix
+	/// that was found in any of the specified search directories, in lexicographic order of file name.
+	///
+	/// This is the same as [`SearchDirectoriesForFileNameGlob::find_files`] with a pattern of `*` followed by the suffix.
+	/// Each search directory is read once, and then only the dropin directories that it contains are read, so enumerating all the units
+	/// of a service manager takes a number of syscalls proportional to the number of files, rather than to the number of unit names
+	/// times the number of search directories like searching for each unit name individually would.
+	///
+	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
+	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
+	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
+	///
+	/// # Errors
+	///
+	/// Any errors from reading non-existing directories and non-existing files are ignored.
+	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
+	///
+	/// # Examples
+	///
+	/// ## Get the unit files and dropins of all systemd services
+	///
+	/// ```rust
+	/// for (unit_name, files) in
+	///     uapi_config::SearchDirectories::systemd_system_units()
+	///     .with_file_name_suffix(".service")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	/// {
+	///     // Parse and merge `files` for the unit named `unit_name`
+	/// }
+	/// ```
+	pub fn find_files<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+	) -> io::Result<FilesByFileName>
+	where
+		TSuffix: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		self.find_files_with(dropin_suffix, &Default::default())
+	}
+
+	/// Same as [`find_files`](Self::find_files), but with the given [`FindOptions`].
+	///
+	/// # Errors
+	///
+	/// See [`find_files`](Self::find_files).
+	pub fn find_files_with<TDropinSuffix>(
+		self,
+		dropin_suffix: Option<TDropinSuffix>,
+		options: &FindOptions,
+	) -> io::Result<FilesByFileName>
+	where
+		TSuffix: AsRef<OsStr>,
+		TDropinSuffix: AsRef<OsStr>,
+	{
+		let suffix = self.suffix.as_ref().as_bytes();
+		find_files_by_file_name(
+			&self.inner,
+			self.root.as_ref(),
+			&self.chroots,
+			self.finder_cache.as_ref(),
+			dropin_suffix,
+			options,
+			|file_name| file_name.len() > suffix.len() && file_name.ends_with(suffix),
+		)
+	}
+}
+
+/// Finds the main file and dropins of each distinct file name that `matches` in the search directories,
+/// for [`SearchDirectoriesForFileNameGlob`] and [`SearchDirectoriesForFileNameSuffix`].
+fn find_files_by_file_name<TDropinSuffix, F>(
+	search_directories: &[Cow<'_, Path>],
+	root: Option<&Arc<Root>>,
+	chroots: &[PathBuf],
+	finder_cache: Option<&Arc<FinderCache>>,
+	dropin_suffix: Option<TDropinSuffix>,
+	options: &FindOptions,
+	matches: F,
+) -> io::Result<FilesByFileName>
+where
+	TDropinSuffix: AsRef<OsStr>,
+	F: Fn(&[u8]) -> bool,
+{
+	let dropin_suffix: Option<OsString> = dropin_suffix.map(|dropin_suffix| dropin_suffix.as_ref().to_owned());
+
+	let context = SearchContext::new(options, search_directories, root, chroots, finder_cache)?;
+	let paths = context.search_directory_paths(search_directories)?;
+
+	let opened_search_directories = context.open_dirs(paths.iter().map(|path| path.to_path_buf()))?;
+
+	// Each search directory is only read once. The matching file names are recorded with the search directories that contain them,
+	// and the names that could be dropin directories are recorded too, so that only files and directories that exist are looked up
+	// for each file name.
+	let mut file_names: BTreeMap<OsString, Vec<Arc<Dir>>> = Default::default();
+	let mut dropin_directory_paths: std::collections::HashSet<PathBuf> = Default::default();
+
+	for search_directory in &opened_search_directories {
+		let Some(entries) = context.file_names(search_directory)? else {
+			continue;
+		};
+		for file_name in entries {
+			let (file_name, _) = file_name?;
+			if dropin_suffix.is_some() && file_name.as_bytes().ends_with(b".d") {
+				dropin_directory_paths.insert(search_directory.path().join(&file_name));
+			}
+			if matches(file_name.as_bytes()) {
+				file_names.entry(file_name).or_default().push(search_directory.clone());
+			}
+		}
+	}
+
+	let mut result = Vec::with_capacity(file_names.len());
+
+	for (file_name, search_directories) in file_names {
+		let Some(main_file) = find_main_file(&[&file_name], &context, &search_directories)? else {
+			continue;
+		};
+
+		let dropins =
+			if let Some(dropin_suffix) = &dropin_suffix {
+				let dropin_directories =
+					dropin_directories(paths.iter().copied(), &file_name, options)
+					.filter(|path| dropin_directory_paths.contains(path));
+				let dropin_directories = context.open_dropin_dirs(dropin_directories)?;
+				find_dropins(dropin_suffix, &context, &dropin_directories)?
+			}
+			else {
+				Default::default()
+			};
+
+		let files = options.open_all(context.entries(Some(main_file), dropins)?)?;
+		result.push((file_name, files));
+	}
+
+	Ok(FilesByFileName {
+		inner: result.into_iter(),
+	})
+}
+
+fn validate_path(path: &Path) -> Result<(), InvalidPathError> {
+	let mut components = path.components();
+
+	if components.next() != Some(Component::RootDir) {
+		return Err(InvalidPathError);
+	}
+
+	if components.any(|component| matches!(component, Component::ParentDir)) {
+		return Err(InvalidPathError);
+	}
+
+	Ok(())
+}
+
+/// Returns the path of the dropin directory for `name` under `path`, ie `format!("{path}/{name}.d")`.
+fn dropin_directory(path: PathBuf, name: &OsStr) -> PathBuf {
+	let mut path = path.into_os_string();
+	path.reserve(name.len() + "/.d".len());
+	path.push("/");
+	path.push(name);
+	path.push(".d");
+	path.into()
+}
+
+/// Returns the paths of the dropin directories of the config file named `file_name` under each of `paths`,
+/// in lowest-precedence-first order.
+///
+/// Under each path, the dropin directories are ordered from least specific to most specific,
+/// so that a dropin in a more specific directory overrides a dropin with the same name in a less specific one.
+fn dropin_directories<'a, I>(paths: I, file_name: &'a OsStr, options: &FindOptions) -> impl Iterator<Item = PathBuf> + 'a
+where
+	I: IntoIterator<Item = &'a Path>,
+	I::IntoIter: 'a,
+{
+	let mut names = vec![];
+
+	if options.type_dropins {
+		if let Some(type_name) = type_name(file_name.as_bytes()) {
+			names.push(Cow::Borrowed(OsStr::from_bytes(type_name)));
+		}
+	}
+
+	if options.prefix_dropins {
+		names.extend(prefix_names(file_name.as_bytes()).map(|name| Cow::Owned(OsString::from_vec(name))));
+	}
+
+	if options.template_dropins {
+		if let Some(template) = template_name(file_name.as_bytes()) {
+			names.push(Cow::Owned(OsString::from_vec(template)));
+		}
+	}
+
+	names.push(Cow::Borrowed(file_name));
+
+	paths.into_iter().flat_map(move |path| names.clone().into_iter().map(move |name| dropin_directory(path.to_owned(), &name)))
+}
+
+/// If `file_name` has an extension like `foo.service`, returns the extension without the `.` like `service`.
+fn type_name(file_name: &[u8]) -> Option<&[u8]> {
+	let dot = file_name.iter().rposition(|&b| b == b'.')?;
+	let type_name = &file_name[(dot + 1)..];
+	(dot > 0 && !type_name.is_empty()).then_some(type_name)
+}
+
+/// If `file_name` contains dashes like `foo-bar-baz.service`, returns the names truncated after each dash
+/// like `foo-.service` and `foo-bar-.service`, in that order.
+///
+/// For an instance of a template like `foo-bar@baz.service`, only the part before the `@` is truncated.
+fn prefix_names(file_name: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+	let dot = file_name.iter().rposition(|&b| b == b'.').unwrap_or(file_name.len());
+	let (stem, suffix) = file_name.split_at(dot);
+	let stem = stem.iter().position(|&b| b == b'@').map_or(stem, |at| &stem[..at]);
+
+	stem.iter().enumerate()
+		// A dash at the end of the stem would produce the name itself.
+		.filter(move |&(i, &b)| b == b'-' && i + 1 < stem.len())
+		.map(move |(i, _)| [&stem[..=i], suffix].concat())
+}
+
+/// If `file_name` is the name of an instance of a template like `foo@bar.service`, returns the name of the template like `foo@.service`.
+fn template_name(file_name: &[u8]) -> Option<Vec<u8>> {
+	let at = file_name.iter().position(|&b| b == b'@')?;
+	let dot = file_name.iter().rposition(|&b| b == b'.')?;
+	if dot <= at + 1 {
+		return None;
+	}
+
+	let mut result = Vec::with_capacity(file_name.len() - (dot - at - 1));
+	result.extend_from_slice(&file_name[..=at]);
+	result.extend_from_slice(&file_name[dot..]);
+	Some(result)
+}
+
+/// The state shared by all the lookups of a single search.
+///
+/// The search is performed on [`StdFileSystem`] by default, and through the `find_files_in` methods and `CapSearchDirectories`
+/// on other filesystems. The options that resolve paths and symlinks always go through [`std::fs`], so they are only supported
+/// with [`StdFileSystem`].
+struct SearchContext<'a, F = StdFileSystem> {
+	options: &'a FindOptions,
+
+	file_system: &'a F,
+
+	/// The root that path resolution is contained in, if the search directories were created with [`SearchDirectories::chroot_contained`].
+	root: Option<Arc<Root>>,
+
+	/// The canonicalized search directories, if they are needed for [`SymlinkPolicy::WithinSearchDirectories`]
+	/// as the [`symlink_policy`](FindOptions::symlink_policy) or [`dropin_directory_symlink_policy`](FindOptions::dropin_directory_symlink_policy).
+	canonical_search_directories: Vec<PathBuf>,
+
+	/// The directories that are known to not exist and the search directories that were kept open, if the search was made through a [`Finder`].
+	finder_cache: Option<Arc<FinderCache>>,
+
+	/// The parts of the search that also apply to the files that the files it finds include, for [`Files::read_all_with_includes`].
+	includes: Arc<IncludeContext>,
+
+	/// Counts the work of the search for the [`FindOptions::observer`], if one is set.
+	metrics: Option<MetricsRecorder>,
+}
+
+impl<'a> SearchContext<'a> {
+	fn new(
+		options: &'a FindOptions,
+		search_directories: &[Cow<'_, Path>],
+		root: Option<&Arc<Root>>,
+		chroots: &[PathBuf],
+		finder_cache: Option<&Arc<FinderCache>>,
+	) -> io::Result<Self> {
+		let mut result = Self {
+			root: root.cloned(),
+			finder_cache: finder_cache.cloned(),
+			metrics: options.observer.as_ref().map(MetricsRecorder::new),
+			..SearchContext::with_file_system(options, &StdFileSystem)
+		};
+
+		if options.symlink_policy == SymlinkPolicy::WithinSearchDirectories || options.dropin_directory_symlink_policy == SymlinkPolicy::WithinSearchDirectories {
+			for search_directory in search_directories {
+				match result.canonicalize(search_directory) {
+					Ok(search_directory) => result.canonical_search_directories.push(search_directory),
+					Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+					Err(err) => return Err(err),
+				}
+			}
+		}
+
+		result.includes = Arc::new(IncludeContext {
+			chroots: chroots.to_owned(),
+			root: result.root.clone(),
+			symlink_policy: options.symlink_policy,
+			canonical_search_directories: result.canonical_search_directories.clone(),
+			max_file_size: options.max_file_size,
+		});
+
+		Ok(result)
+	}
+
+	/// Canonicalizes the path of a file that was found, or returns it unchanged if the file has since been removed.
+	/// Such a file is skipped when it is opened, and there is nothing it could be a duplicate of.
+	fn canonicalize_if_exists(&self, path: &Path) -> io::Result<PathBuf> {
+		match self.canonicalize(path) {
+			Ok(path) => Ok(path),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(path.to_owned()),
+			Err(err) => Err(err),
+		}
+	}
+
+	/// Returns the paths of the search directories, without the ones that refer to the same directory as a later one
+	/// if [`FindOptions::dedupe_search_directories`] is set, and without the ones that are skipped because of
+	/// [`FindOptions::filesystem_condition`] or [`FindOptions::search_directory_timeout`].
+	fn search_directory_paths<'b>(&self, search_directories: &'b [Cow<'_, Path>]) -> io::Result<Vec<&'b Path>> {
+		let mut result: Vec<&Path> = search_directories.iter().map(AsRef::as_ref).collect();
+
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		let check_search_directories = self.options.filesystem_condition.is_some() || self.options.search_directory_timeout.is_some();
+		#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+		let check_search_directories = self.options.search_directory_timeout.is_some();
+		if check_search_directories {
+			let mut allowed = Vec::with_capacity(result.len());
+			for path in result {
+				match fs_type::search_directory_skip_reason(path, self.options)? {
+					None => allowed.push(path),
+					Some(reason) => self.options.record_skipped(|| path.to_owned(), reason),
+				}
+			}
+			result = allowed;
+		}
+
+		if self.options.dedupe_search_directories {
+			let mut seen = std::collections::HashSet::new();
+			let mut keep = vec![true; result.len()];
+			for (i, path) in result.iter().enumerate().rev() {
+				let metadata = match self.metadata(path) {
+					Ok(metadata) => metadata,
+					Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+					Err(err) => return Err(err),
+				};
+				// Directories cannot be deduplicated if the platform cannot identify them.
+				if let Some(file_id) = platform::file_id(&metadata) {
+					keep[i] = seen.insert(file_id);
+				}
+			}
+			let mut keep = keep.into_iter();
+			result.retain(|_| keep.next().unwrap_or_default());
+		}
+
+		Ok(result)
+	}
+
+	/// Returns the paths of the dropin directories that are directories, following symlinks,
+	/// skipping any that do not exist or that are not allowed by [`FindOptions::dropin_directory_symlink_policy`].
+	///
+	/// Each dropin directory is followed by its subdirectories that match [`FindOptions::conditional_dropin_subdirectories`], if any.
+	fn existing_dropin_dirs<I>(&self, paths: I) -> io::Result<Vec<PathBuf>> where I: IntoIterator<Item = PathBuf> {
+		let mut result = vec![];
+		for path in paths {
+			if self.finder_cache.as_ref().is_some_and(|finder_cache| finder_cache.contains(&path)) {
+				continue;
+			}
+
+			if !self.is_dropin_directory_allowed(&path)? {
+				continue;
+			}
+
+			match self.metadata(&path) {
+				Ok(metadata) if metadata.is_dir() => {
+					let subdirectories =
+						if self.options.conditional_dropin_subdirectories.is_some() {
+							self.conditional_dropin_subdirectories(&Dir::open(path.clone(), self.root.as_ref())?)?
+						}
+						else {
+							vec![]
+						};
+					result.push(path);
+					result.extend(subdirectories);
+				},
+				Ok(_) => (),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => self.directory_not_found(path),
+				Err(err) => return Err(err),
+			}
+		}
+		Ok(result)
+	}
+
+	/// Applies the options that act on the whole result of the search and returns it as [`Entries`].
+	fn entries(&self, main_file: Option<FoundFile<Dir>>, dropins: Vec<FoundFile<Dir>>) -> io::Result<Entries> {
+		let mut main_file = main_file.map(|main_file| self.entry(main_file));
+		let mut dropins: Vec<_> = dropins.into_iter().map(|dropin| self.entry(dropin)).collect();
+
+		if self.options.canonicalize_paths {
+			for entry in main_file.iter_mut().chain(&mut dropins) {
+				entry.path = self.canonicalize_if_exists(&entry.path)?;
+			}
+		}
+
+		if self.options.dedupe_canonical_paths {
+			let keys: Vec<_> =
+				main_file.iter().chain(&dropins)
+				// The paths have already been canonicalized if `canonicalize_paths` is set.
+				.map(|entry| if self.options.canonicalize_paths { Ok(entry.path.clone()) } else { self.canonicalize_if_exists(&entry.path) })
+				.collect::<io::Result<_>>()?;
+			dedupe(&mut main_file, &mut dropins, &keys, self.options);
+		}
+
+		if self.options.dedupe_inodes {
+			// Files cannot be deduplicated if the platform cannot identify them.
+			let keys: Option<Vec<_>> = main_file.iter().chain(&dropins).map(|entry| entry.metadata.file_id()).collect();
+			if let Some(keys) = keys {
+				dedupe(&mut main_file, &mut dropins, &keys, self.options);
+			}
+		}
+
+		if let Some(metrics) = &self.metrics {
+			metrics.files_found(usize::from(main_file.is_some()) + dropins.len());
+		}
+
+		Ok(Entries {
+			main_file,
+			dropins: dropins.into_iter(),
+			includes: self.includes.clone(),
+		})
+	}
+
+	fn entry(&self, FoundFile { dir, file_name, metadata }: FoundFile<Dir>) -> Entry {
+		Entry {
+			path: dir.path().join(&file_name),
+			metadata,
+			dir,
+			file_name,
+			open_options: self.options.open_options.clone(),
+			verify_identity: self.options.symlink_policy != SymlinkPolicy::Follow,
+		}
+	}
+}
+
+impl<'a, F> SearchContext<'a, F> where F: SearchFileSystem {
+	/// The context of a search on `file_system` that does not use the [`Root`] or [`Finder`] of any [`SearchDirectories`].
+	fn with_file_system(options: &'a FindOptions, file_system: &'a F) -> Self {
+		Self {
+			options,
+			file_system,
+			root: None,
+			canonical_search_directories: vec![],
+			finder_cache: None,
+			includes: Default::default(),
+			metrics: None,
+		}
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+		match &self.root {
+			Some(root) => root.metadata(path, true),
+			None => fs::metadata(path),
+		}
+	}
+
+	/// Returns whether the dropin directory at `path` may be used according to [`FindOptions::dropin_directory_symlink_policy`].
+	/// A path that does not exist is allowed, since it is skipped when it is opened.
+	fn is_dropin_directory_allowed(&self, path: &Path) -> io::Result<bool> {
+		if self.options.dropin_directory_symlink_policy == SymlinkPolicy::Follow {
+			return Ok(true);
+		}
+
+		let metadata = match &self.root {
+			Some(root) => root.metadata(path, false),
+			None => fs::symlink_metadata(path),
+		};
+		match metadata {
+			Ok(metadata) if metadata.file_type().is_symlink() => (),
+			Ok(_) => return Ok(true),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+			Err(err) => return Err(err),
+		}
+
+		if self.options.dropin_directory_symlink_policy == SymlinkPolicy::Refuse {
+			return Ok(false);
+		}
+
+		match self.canonicalize(path) {
+			Ok(target) => Ok(self.canonical_search_directories.iter().any(|search_directory| target.starts_with(search_directory))),
+			// A dangling symlink.
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+			Err(err) => Err(err),
+		}
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		match &self.root {
+			Some(root) => root.canonicalize(path),
+			None => fs::canonicalize(path),
+		}
+	}
+
+	/// Returns the names of the entries of `dir`, or `None` if it does not exist.
+	fn file_names(&self, dir: &F::Dir) -> io::Result<Option<F::FileNames>> {
+		match self.file_system.file_names(dir) {
+			Ok(file_names) => {
+				if let Some(metrics) = &self.metrics {
+					metrics.directory_scanned();
+				}
+				Ok(Some(file_names))
+			},
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				self.directory_not_found(dir.path().to_owned());
+				Ok(None)
+			},
+			Err(err) => Err(err),
+		}
+	}
+
+	fn directory_not_found(&self, path: PathBuf) {
+		if let Some(finder_cache) = &self.finder_cache {
+			finder_cache.insert(path);
+		}
+	}
+
+	/// Returns the metadata of the file named `file_name` in `dir`, or the reason to skip it if the file does not exist
+	/// or must be ignored because of the symlink policy.
+	///
+	/// `scratch` is used as a buffer for building the path of the file, if necessary.
+	fn stat(&self, dir: &F::Dir, file_name: &OsStr, scratch: &mut PathBuf) -> io::Result<Result<EntryMetadata, SkipReason>> {
+		let metadata = self.file_system.metadata(dir, file_name, self.options.symlink_policy == SymlinkPolicy::Follow, scratch);
+		let metadata = match metadata {
+			Ok(metadata) => metadata,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Err(SkipReason::NotFound)),
+			Err(err) => return Err(err),
+		};
+
+		if !metadata.is_symlink() {
+			return Ok(Ok(metadata));
+		}
+
+		// SymlinkPolicy::Follow followed symlinks, so it would not have returned the metadata of a symlink.
+		if self.options.symlink_policy != SymlinkPolicy::WithinSearchDirectories {
+			return Ok(Err(SkipReason::Symlink));
+		}
+
+		let target = match self.canonicalize(dir.path_of(file_name, scratch)) {
+			Ok(target) => target,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Err(SkipReason::NotFound)),
+			Err(err) => return Err(err),
+		};
+		if !self.canonical_search_directories.iter().any(|search_directory| target.starts_with(search_directory)) {
+			return Ok(Err(SkipReason::Symlink));
+		}
+
+		match self.metadata(&target) {
+			Ok(metadata) => Ok(Ok((&metadata).into())),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Err(SkipReason::NotFound)),
+			Err(err) => Err(err),
+		}
+	}
+}
+
+impl<F> SearchContext<'_, F> where F: OpenDir {
+	/// Opens the directories at the given paths, skipping any that do not exist.
+	fn open_dirs<I>(&self, paths: I) -> io::Result<Vec<Arc<F::Dir>>> where I: IntoIterator<Item = PathBuf> {
+		let mut result = vec![];
+		for path in paths {
+			let missing_path = match &self.finder_cache {
+				Some(finder_cache) if finder_cache.contains(&path) => continue,
+				Some(_) => Some(path.clone()),
+				None => None,
+			};
+
+			match F::open_dir(self, path) {
+				Ok(dir) => result.push(dir),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => {
+					if let Some(missing_path) = missing_path {
+						self.directory_not_found(missing_path);
+					}
+				},
+				Err(err) => return Err(err),
+			}
+		}
+		Ok(result)
+	}
+
+	/// Opens the dropin directories at `paths`, skipping any that do not exist or that are not allowed by
+	/// [`FindOptions::dropin_directory_symlink_policy`].
+	///
+	/// Each dropin directory is followed by its subdirectories that match [`FindOptions::conditional_dropin_subdirectories`], if any.
+	fn open_dropin_dirs<I>(&self, paths: I) -> io::Result<Vec<Arc<F::Dir>>> where I: IntoIterator<Item = PathBuf> {
+		let mut allowed_paths = vec![];
+		for path in paths {
+			if self.is_dropin_directory_allowed(&path)? {
+				allowed_paths.push(path);
+			}
+		}
+		let dirs = self.open_dirs(allowed_paths)?;
+
+		if self.options.conditional_dropin_subdirectories.is_none() {
+			return Ok(dirs);
+		}
+
+		let mut result = vec![];
+		for dir in dirs {
+			let subdirectories = self.conditional_dropin_subdirectories(&dir)?;
+			result.push(dir);
+			result.extend(self.open_dirs(subdirectories)?);
+		}
+		Ok(result)
+	}
+
+	/// Returns the paths of the subdirectories of the dropin directory `dir` that match
+	/// [`FindOptions::conditional_dropin_subdirectories`], in lexicographic order.
+	fn conditional_dropin_subdirectories(&self, dir: &F::Dir) -> io::Result<Vec<PathBuf>> {
+		let Some(condition) = &self.options.conditional_dropin_subdirectories else {
+			return Ok(vec![]);
+		};
+
+		let Some(file_names) = self.file_names(dir)? else {
+			return Ok(vec![]);
+		};
+
+		let mut result = vec![];
+		for file_name in file_names {
+			let (file_name, kind) = file_name?;
+			if !matches!(kind, EntryKind::Symlink | EntryKind::Other | EntryKind::Unknown) || !condition.matches(&file_name) {
+				continue;
+			}
+
+			let path = dir.path().join(file_name);
+			if !self.is_dropin_directory_allowed(&path)? {
+				continue;
+			}
+			match self.metadata(&path) {
+				Ok(metadata) if metadata.is_dir() => result.push(path),
+				Ok(_) => (),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+				Err(err) => return Err(err),
+			}
+		}
+		result.sort();
+		Ok(result)
+	}
+}
+
+/// Removes all but the last of the entries that have the same key.
+///
+/// `keys` contains the key of each entry of `main_file` and `dropins`, in order.
+fn dedupe<K>(main_file: &mut Option<Entry>, dropins: &mut Vec<Entry>, keys: &[K], options: &FindOptions) where K: Eq + std::hash::Hash {
+	let last_indices: std::collections::HashMap<_, _> = keys.iter().enumerate().map(|(i, key)| (key, i)).collect();
+	let mut keep = keys.iter().enumerate().map(|(i, key)| last_indices[key] == i);
+	let mut keep = |entry: &Entry| {
+		let keep = keep.next().unwrap_or_default();
+		if !keep {
+			options.record_skipped(|| entry.path.clone(), SkipReason::Duplicate);
+		}
+		keep
+	};
+
+	if main_file.as_ref().is_some_and(|main_file| !keep(main_file)) {
+		*main_file = None;
+	}
+	dropins.retain(|dropin| keep(dropin));
+}
+
+/// A file that the search found in `dir`, before it is turned into an [`Entry`] or opened.
+struct FoundFile<D> {
+	dir: Arc<D>,
+	file_name: OsString,
+	metadata: EntryMetadata,
+}
+
+/// The main file (if any) and the dropins that a search found, in lowest-precedence-first order.
+type FoundFiles<D> = (Option<FoundFile<D>>, Vec<FoundFile<D>>);
+
+/// Finds the main file with the highest precedence like [`find_main_file`], but opens the search directories one at a time
+/// from the highest precedence downwards, and stops at the first one that contains the file.
+///
+/// `paths` are the search directories in lowest-precedence-first order.
+fn find_first_main_file<I>(file_names: &[&OsStr], context: &SearchContext<'_>, paths: I) -> io::Result<Option<(PathBuf, File)>>
+where
+	I: IntoIterator<Item = PathBuf>,
+	I::IntoIter: DoubleEndedIterator,
+{
+	for path in paths.into_iter().rev() {
+		let search_directories = context.open_dirs(std::iter::once(path))?;
+		let Some(main_file) = find_main_file(file_names, context, &search_directories)? else {
+			continue;
+		};
+
+		// The file may have been removed since it was found, in which case the search continues with the next directory.
+		if let Some(file) = context.options.open_all(context.entries(Some(main_file), vec![])?)?.next() {
+			return Ok(Some(file));
+		}
+	}
+
+	Ok(None)
+}
+
+/// Returns the main file, which is the first of `file_names` that is found in the last of the search directories that contains any of them.
+fn find_main_file<F>(
+	file_names: &[&OsStr],
+	context: &SearchContext<'_, F>,
+	search_directories: &[Arc<F::Dir>],
+) -> io::Result<Option<FoundFile<F::Dir>>>
+where
+	F: SearchFileSystem,
+{
+	let mut scratch = PathBuf::new();
+
+	for search_directory in search_directories.iter().rev() {
+		for &file_name in file_names {
+			let Ok(metadata) = context.stat(search_directory, file_name, &mut scratch)? else {
+				continue;
+			};
+
+			if !metadata.is_file() || context.options.skip_non_executable(&metadata)? {
+				continue;
+			}
+
+			context.options.check_max_file_size(metadata.size(), || search_directory.path().join(file_name))?;
+			context.options.check_permission_policy(&metadata, || search_directory.path().join(file_name))?;
+
+			return Ok(Some(FoundFile { dir: search_directory.clone(), file_name: file_name.to_owned(), metadata }));
+		}
+	}
+
+	Ok(None)
+}
+
+fn find_dropins<F>(
+	suffix: &OsStr,
+	context: &SearchContext<'_, F>,
+	search_directories: &[Arc<F::Dir>],
+) -> io::Result<Vec<FoundFile<F::Dir>>>
+where
+	F: SearchFileSystem,
+{
+	let mut result: BTreeMap<_, _> = Default::default();
+
+	// Reused to build the paths of files for the lookups that need a full path.
+	let mut scratch = PathBuf::new();
+
+	// Candidates that turn out to not be regular files are not in `result`, but still count towards the limit.
+	let mut num_candidates = 0;
+
+	for search_directory in search_directories.iter().rev() {
+		let Some(file_names) = context.file_names(search_directory)? else {
+			continue;
+		};
+		for file_name in file_names {
+			let (file_name, kind) = file_name?;
+			let skipped = |reason| context.options.record_skipped(|| search_directory.path().join(&file_name), reason);
+
+			if let Some(reason) = context.options.dropin_candidate_skip_reason(file_name.as_bytes(), suffix.as_bytes()) {
+				skipped(reason);
+				continue;
+			}
+
+			if result.contains_key(&file_name) {
+				skipped(SkipReason::Shadowed);
+				continue;
+			}
+
+			num_candidates += 1;
+			context.options.check_max_dropins(num_candidates, search_directory.path())?;
+
+			// Skip entries that cannot be regular files without needing to stat them.
+			match kind {
+				EntryKind::File |
+				EntryKind::Unknown => (),
+				EntryKind::Symlink if context.options.symlink_policy != SymlinkPolicy::Refuse => (),
+				EntryKind::Symlink => {
+					skipped(SkipReason::Symlink);
+					continue;
+				},
+				EntryKind::Other => {
+					skipped(SkipReason::NotRegularFile);
+					continue;
+				},
+			}
+
+			let metadata = match context.stat(search_directory, &file_name, &mut scratch)? {
+				Ok(metadata) => metadata,
+				Err(reason) => {
+					skipped(reason);
+					continue;
+				},
+			};
+
+			if !metadata.is_file() {
+				skipped(SkipReason::NotRegularFile);
+				continue;
+			}
+
+			if context.options.skip_non_executable(&metadata)? {
+				skipped(SkipReason::NotExecutable);
+				continue;
+			}
+
+			context.options.check_max_file_size(metadata.size(), || search_directory.path().join(&file_name))?;
+			context.options.check_permission_policy(&metadata, || search_directory.path().join(&file_name))?;
+
+			// The found file is only created once the dropins have been sorted, so that the file name does not need to be cloned for the key.
+			result.insert(file_name, (search_directory, metadata));
+		}
+	}
+
+	let result =
+		context.options.sort_dropins(result).into_iter()
+		.map(|(file_name, (search_directory, metadata))| FoundFile { dir: search_directory.clone(), file_name, metadata })
+		.collect();
+	Ok(result)
+}
+
+/// The iterator of files returned by [`SearchDirectoriesForProject::find_files`],
+/// [`SearchDirectoriesForFileName::find_files`] and [`SearchDirectoriesForProjectAndFileName::find_files`].
+///
+/// Files are yielded in lowest-precedence-first order, ie the main file (if any) followed by the dropins,
+/// so that settings in files yielded later override settings in files yielded earlier.
+/// Use [`Files::highest_first`] to iterate in the opposite order.
+#[derive(Debug)]
+pub struct Files {
+	main_file: Option<(PathBuf, File, fs::Metadata)>,
+	dropins: std::vec::IntoIter<(PathBuf, File, fs::Metadata)>,
+
+	/// The [`FindOptions::observer`] of the search, which the helpers that read the files report to.
+	observer: Option<Observer>,
+
+	/// The parts of the search that also apply to the files that these files include.
+	includes: Arc<IncludeContext>,
+}
+
+impl Files {
+	/// Returns an [`Iterator`] over the files in highest-precedence-first order, ie the last dropin first and the main file (if any) last.
+	///
+	/// This is useful for callers that implement "first definition wins" lookup of individual settings instead of merging all the files.
+	/// It is equivalent to [`Iterator::rev`].
+	pub fn highest_first(self) -> std::iter::Rev<Self> {
+		self.rev()
+	}
+
+	/// Returns the number of files remaining in this iterator.
+	///
+	/// All files are found when the search is performed, so this is always exact.
+	pub fn len(&self) -> usize {
+		usize::from(self.main_file.is_some()) + self.dropins.len()
+	}
+
+	/// Returns `true` if there are no files remaining in this iterator, such as when no config files were found at all.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns an [`Iterator`] over just the paths of the files, in the same order.
+	///
+	/// All the [`File`]s remaining in this iterator are closed before this function returns.
+	/// This is useful when the files are going to be opened by something else, such as when passing their paths to another process.
+	pub fn into_paths(self) -> Paths {
+		Paths {
+			inner: self.map(|(path, _)| path).collect::<Vec<_>>().into_iter(),
+		}
+	}
+
+	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`, `[`fs::Metadata`]`)`s over the files, in the same order.
+	///
+	/// The metadata was already looked up when the files were opened, so this avoids looking it up again,
+	/// such as to display the sizes of the files or to compare their modification times when reloading the config.
+	/// It is the metadata of the opened file, except with `FindOptions::io_uring` where it is the metadata that was found by the search.
+	pub fn with_metadata(self) -> FilesWithMetadata {
+		FilesWithMetadata {
+			inner: self,
+		}
+	}
+
+	/// Splits the main file, if one was found, from the dropins. The returned [`Files`] only contains the dropins.
+	///
+	/// This is for callers that treat the main file differently from the dropins, such as when the main file defines
+	/// the version of the config format, since the first file yielded by this iterator may or may not be the main file.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let (main_file, dropins) =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	///     .split_main();
+	/// if let Some((path, file)) = main_file {
+	///     // Parse `file` with the full schema
+	/// }
+	/// for (path, file) in dropins {
+	///     // Parse `file` as a partial override
+	/// }
+	/// ```
+	pub fn split_main(mut self) -> (Option<(PathBuf, File)>, Self) {
+		let main_file = self.main_file.take().map(|(path, file, _)| (path, file));
+		(main_file, self)
+	}
+
+	/// Groups the files by the search directory that each one was found under, such as to check that all the files
+	/// come from the OS vendor's directory.
+	///
+	/// `search_directories` must be the search directories that the search was performed with. The groups are in the order
+	/// of the search directories, ie lowest-precedence-first, and only search directories that any files were found under have a group.
+	/// Each group is a [`Files`] with the files in the same order as in this iterator.
+	///
+	/// A file is grouped under the search directory with the most components that its path starts with, so that it is grouped correctly
+	/// even if one search directory is under another. Files that are not under any of the search directories, such as because
+	/// [`FindOptions::canonicalize_paths`] resolved them to somewhere else, are grouped under `None` as the first group.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let search_directories = uapi_config::SearchDirectories::modern_system();
+	/// let groups =
+	///     search_directories.clone()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap()
+	///     .grouped_by_root(&search_directories);
+	/// for (search_directory, files) in groups {
+	///     if search_directory.as_deref() != Some(std::path::Path::new("/usr/etc")) {
+	///         // Report that `files` are not shipped by the OS vendor
+	///     }
+	/// }
+	/// ```
+	pub fn grouped_by_root(self, search_directories: &SearchDirectories<'_>) -> Vec<(Option<PathBuf>, Self)> {
+		let mut groups: Vec<_> = search_directories.iter().map(|search_directory| (search_directory, vec![])).collect();
+		let mut outside = vec![];
+
+		for (path, file, metadata) in self.main_file.into_iter().chain(self.dropins) {
+			match search_directory_index(&path, groups.iter().map(|(search_directory, _)| *search_directory)) {
+				Some(i) => groups[i].1.push((path, file, metadata)),
+				None => outside.push((path, file, metadata)),
+			}
+		}
+
+		let files = |files: Vec<_>| Self { main_file: None, dropins: files.into_iter(), observer: self.observer.clone(), includes: self.includes.clone() };
+		let outside = (!outside.is_empty()).then(|| (None, files(outside)));
+		outside.into_iter()
+		.chain(
+			groups.into_iter()
+			.filter(|(_, files)| !files.is_empty())
+			.map(|(search_directory, group)| (Some(search_directory.to_owned()), files(group)))
+		)
+		.collect()
+	}
+
+	/// Returns the files together with the label of the search directory that each one was found under,
+	/// as attached with [`SearchDirectories::set_label`], in the same order as this iterator.
+	///
+	/// `search_directories` must be the search directories that the search was performed with. Files are matched to search directories
+	/// like in [`grouped_by_root`](Self::grouped_by_root). The label is `None` for files under search directories without labels,
+	/// and for files that are not under any of the search directories.
+	///
+	/// See [`SearchDirectories::set_label`] for an example.
+	pub fn labeled(self, search_directories: &SearchDirectories<'_>) -> Vec<(Option<String>, PathBuf, File)> {
+		self.main_file.into_iter().chain(self.dropins)
+		.map(|(path, file, _)| (search_directories.label_of(&path).map(ToOwned::to_owned), path, file))
+		.collect()
+	}
+}
+
+/// Returns the index of the search directory that `path` is under, using the one with the most components if there are more than one.
+///
+/// If the same search directory is listed more than once, the index of the last one is returned, since it has the highest precedence.
+fn search_directory_index<'a, I>(path: &Path, search_directories: I) -> Option<usize> where I: IntoIterator<Item = &'a Path> {
+	search_directories.into_iter()
+	.enumerate()
+	.filter(|(_, search_directory)| path.starts_with(search_directory))
+	// `max_by_key` returns the last of equal elements.
+	.max_by_key(|(_, search_directory)| search_directory.components().count())
+	.map(|(i, _)| i)
+}
+
+impl Iterator for Files {
+	type Item = (PathBuf, File);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (path, file, _) = self.main_file.take().or_else(|| self.dropins.next())?;
+		Some((path, file))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.len();
+		(len, Some(len))
+	}
+}
+
+impl DoubleEndedIterator for Files {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		let (path, file, _) = self.dropins.next_back().or_else(|| self.main_file.take())?;
+		Some((path, file))
+	}
+}
+
+impl ExactSizeIterator for Files {}
+
+impl std::iter::FusedIterator for Files {}
+
+/// The iterator of files and their metadata returned by [`Files::with_metadata`].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct FilesWithMetadata {
+	inner: Files,
+}
+
+impl Iterator for FilesWithMetadata {
+	type Item = (PathBuf, File, fs::Metadata);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.main_file.take().or_else(|| self.inner.dropins.next())
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for FilesWithMetadata {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.inner.dropins.next_back().or_else(|| self.inner.main_file.take())
+	}
+}
+
+impl ExactSizeIterator for FilesWithMetadata {}
+
+impl std::iter::FusedIterator for FilesWithMetadata {}
+
+/// The iterator of paths returned by [`Files::into_paths`].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Paths {
+	inner: std::vec::IntoIter<PathBuf>,
+}
+
+impl Iterator for Paths {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for Paths {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.inner.next_back()
+	}
+}
+
+impl ExactSizeIterator for Paths {}
+
+impl std::iter::FusedIterator for Paths {}
+
+/// The iterator of file names and their files returned by [`SearchDirectoriesForFileNameGlob::find_files`].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct FilesByFileName {
+	inner: std::vec::IntoIter<(OsString, Files)>,
+}
+
+impl Iterator for FilesByFileName {
+	type Item = (OsString, Files);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.inner.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for FilesByFileName {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		self.inner.next_back()
+	}
+}
+
+impl ExactSizeIterator for FilesByFileName {}
+
+impl std::iter::FusedIterator for FilesByFileName {}
+
+#[cfg(test)]
+mod tests {
+	use std::path::{Path, PathBuf};
+
+	use crate::SearchDirectories;
+
+	/// Creates a new empty directory for a test that needs to create files that cannot be checked in, like hardlinks,
+	/// or that changes the files during the test. The directory is removed when the returned guard is dropped, even if the test fails.
+	fn temp_dir(name: &str) -> TempDir {
+		let path = std::env::temp_dir().join(format!("uapi-config-test-{}-{name}", std::process::id()));
+		match std::fs::remove_dir_all(&path) {
+			Ok(()) => (),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+			Err(err) => panic!("{err}"),
+		}
+		std::fs::create_dir_all(&path).unwrap();
+		TempDir(path)
+	}
+
+	/// A directory created by [`temp_dir`], which is removed when this is dropped.
+	struct TempDir(PathBuf);
+
+	impl std::ops::Deref for TempDir {
+		type Target = Path;
+
+		fn deref(&self) -> &Self::Target {
+			&self.0
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			_ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+
+	#[test]
+	fn search_directory_precedence() {
+		for include_usr_etc in [false, true] {
+			for include_run in [false, true] {
+				for include_etc in [false, true] {
+					let mut search_directories = vec![];
+					if include_usr_etc {
+						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc"));
+					}
+					if include_run {
+						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run"));
+					}
+					if include_etc {
+						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc"));
+					}
+					let search_directories: SearchDirectories<'_> =
+						search_directories
+						.into_iter()
+						.map(|path| Path::new(path).into())
+						.collect();
+					let files: Vec<_> =
+						search_directories
+						.with_project("foo")
+						.with_file_name("a.conf")
+						.find_files(Some(".conf"))
+						.unwrap()
+						.map(|(path, _)| path)
+						.collect();
+					if include_etc {
+						assert_eq!(files, [
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc/foo/a.conf"),
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc/foo/a.conf.d/b.conf"),
+						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+					}
+					else if include_run {
+						assert_eq!(files, [
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run/foo/a.conf"),
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run/foo/a.conf.d/b.conf"),
+						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+					}
+					else if include_usr_etc {
+						assert_eq!(files, [
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc/foo/a.conf"),
+							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc/foo/a.conf.d/b.conf"),
+						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+					}
+					else {
+						assert_eq!(files, Vec::<PathBuf>::new());
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn only_project() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/a.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/b.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/c.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/d.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/e.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/f.conf"),
+		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+	}
+
+	#[cfg(feature = "capi")]
+	#[test]
+	fn capi() {
+		use std::ffi::{CStr, CString};
+
+		use crate::capi::*;
+
+		unsafe {
+			let search_directories = uapi_config_search_directories_new();
+			assert_eq!(uapi_config_search_directories_push(search_directories, c"usr/etc".as_ptr()), -libc::EINVAL);
+			for search_directory in ["/usr/etc", "/run", "/etc"] {
+				let search_directory = CString::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project").to_owned() + search_directory).unwrap();
+				assert_eq!(uapi_config_search_directories_push(search_directories, search_directory.as_ptr()), 0);
+			}
+
+			let mut paths = std::ptr::null_mut();
+			assert_eq!(uapi_config_find(search_directories, c"foo".as_ptr(), std::ptr::null(), std::ptr::null(), &raw mut paths), -libc::EINVAL);
+			assert_eq!(uapi_config_find(search_directories, c"foo".as_ptr(), std::ptr::null(), c".conf".as_ptr(), &raw mut paths), 0);
+
+			let mut files = vec![];
+			loop {
+				let path = uapi_config_paths_next(paths);
+				if path.is_null() {
+					break;
+				}
+				files.push(CStr::from_ptr(path).to_str().unwrap().to_owned());
+			}
+			assert!(uapi_config_paths_next(paths).is_null());
+			assert_eq!(files, [
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/a.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/b.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/c.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/d.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/e.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/f.conf"),
+			]);
+
+			uapi_config_paths_free(paths);
+			uapi_config_search_directories_free(search_directories);
+		}
+	}
+
+	#[test]
+	fn only_file_name() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name")))
+			.unwrap()
+			.with_file_name("foo.service")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/a.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/b.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/c.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/d.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/e.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/f.conf"),
+		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+	}
+
+	#[test]
+	fn into_paths() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name"));
+		let find = || SearchDirectories::modern_system().chroot(root).unwrap().with_file_name("foo.service").find_files(Some(".conf")).unwrap();
+
+		let expected: Vec<_> = find().map(|(path, _)| path).collect();
+		let mut paths = find().into_paths();
+		assert_eq!(paths.len(), expected.len());
+		assert_eq!(paths.next_back().as_ref(), expected.last());
+		assert_eq!(paths.collect::<Vec<_>>(), expected[..expected.len() - 1]);
+
+		// Only the files remaining in the iterator are included.
+		let mut files = find();
+		files.next();
+		assert_eq!(files.into_paths().collect::<Vec<_>>(), expected[1..]);
+	}
+
+	#[test]
+	fn std_file_system() {
+		let search_directories = |name: &str| SearchDirectories::modern_system().chroot(&Path::new(env!("CARGO_MANIFEST_DIR")).join("test-files").join(name)).unwrap();
+		let options = crate::FindOptions::new();
+
+		let expected: Vec<_> = search_directories("only_project").with_project("foo").find_files(".conf").unwrap().into_paths().collect();
+		let files: Vec<_> = search_directories("only_project").with_project("foo").find_files_in(&crate::StdFileSystem, ".conf", &options).unwrap().map(|(path, _)| path).collect();
+		assert_eq!(files, expected);
+
+		let expected: Vec<_> = search_directories("only_file_name").with_file_name("foo.service").find_files(Some(".conf")).unwrap().into_paths().collect();
+		let files: Vec<_> = search_directories("only_file_name").with_file_name("foo.service").find_files_in(&crate::StdFileSystem, Some(".conf"), &options).unwrap().map(|(path, _)| path).collect();
+		assert_eq!(files, expected);
+
+		let expected: Vec<_> = search_directories("search_directory_precedence").with_project("foo").with_file_name("a.conf").find_files(Some(".conf")).unwrap().into_paths().collect();
+		let files: Vec<_> =
+			search_directories("search_directory_precedence").with_project("foo").with_file_name("a.conf")
+			.find_files_in(&crate::StdFileSystem, Some(".conf"), &options)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files.len(), 2);
+		assert_eq!(files, expected);
+
+		// Options that the search cannot apply to an arbitrary filesystem are rejected rather than ignored.
+		let err =
+			search_directories("only_project").with_project("foo")
+			.find_files_in(&crate::StdFileSystem, ".conf", &crate::FindOptions::new().dedupe_inodes(true))
+			.unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+	}
+
+	#[test]
+	fn in_memory_fs() {
+		use std::io::Read as _;
+
+		let file_system =
+			crate::InMemoryFs::new()
+			.file("/usr/etc/foo/a.conf", "usr")
+			.file("/usr/etc/foo/a.conf.d/b.conf", "usr b")
+			.file("/usr/etc/foo/a.conf.d/c.conf", "usr c")
+			.file("/run/foo/a.conf.d/c.conf", "run c")
+			.file("/run/foo/a.conf.d/d.txt", "run d")
+			.dir("/etc/foo/a.conf")
+			.dir("/etc/foo/a.conf.d/b.conf");
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.with_project("foo")
+			.with_file_name("a.conf")
+			.find_files_in(&file_system, Some(".conf"), &crate::FindOptions::new())
+			.unwrap()
+			.map(|(path, mut file)| {
+				let mut contents = String::new();
+				file.read_to_string(&mut contents).unwrap();
+				(path, contents)
+			})
+			.collect();
+		assert_eq!(files, [
+			(PathBuf::from("/usr/etc/foo/a.conf"), "usr".to_owned()),
+			(PathBuf::from("/usr/etc/foo/a.conf.d/b.conf"), "usr b".to_owned()),
+			(PathBuf::from("/run/foo/a.conf.d/c.conf"), "run c".to_owned()),
+		]);
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.with_project("bar")
+			.find_files_in(&file_system, ".conf", &crate::FindOptions::new())
+			.unwrap()
+			.collect();
+		assert!(files.is_empty());
+	}
+
+	#[test]
+	fn discover_projects() {
+		let root = temp_dir("discover_projects");
+
+		for path in [
+			"usr/etc/foo.d/a.conf",
+			"usr/etc/bar/a.conf",
+			"run/baz.d/",
+			"etc/foo/a.conf",
+			"etc/.hidden.d/",
+			"etc/qux.d",
+			"etc/.d/",
+		] {
+			let path = root.join(path);
+			if path.as_os_str().as_encoded_bytes().ends_with(b"/") {
+				std::fs::create_dir_all(path).unwrap();
+			}
+			else {
+				std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+				std::fs::write(path, "").unwrap();
+			}
+		}
+
+		let projects = SearchDirectories::modern_system().chroot(&root).unwrap().discover_projects().unwrap();
+		assert_eq!(projects.into_iter().collect::<Vec<_>>(), ["bar", "baz", "foo"]);
+	}
+
+	#[test]
+	fn file_name_glob() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_glob")))
+			.unwrap()
+			.with_file_name_glob("*.network")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(file_name, files)| (file_name, files.map(|(path, _)| path).collect::<Vec<_>>()))
+			.collect();
+		assert_eq!(files, [
+			("a.network".into(), [
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_glob/usr/etc/a.network"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_glob/run/a.network.d/x.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_glob/etc/a.network.d/y.conf"),
+			].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+			("b.network".into(), [
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_glob/run/b.network"),
+			].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+		].into_iter().collect::<Vec<(std::ffi::OsString, _)>>());
+	}
+
+	#[test]
+	fn file_name_suffix() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_suffix")))
+			.unwrap()
+			.with_file_name_suffix(".service")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(file_name, files)| (file_name, files.map(|(path, _)| path).collect::<Vec<_>>()))
+			.collect();
+		assert_eq!(files, [
+			("a.service".into(), [
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_suffix/usr/etc/a.service"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_suffix/etc/a.service.d/override.conf"),
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_suffix/run/a.service.d/x.conf"),
+			].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+			("b.service".into(), [
+				concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/file_name_suffix/etc/b.service"),
+			].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+		].into_iter().collect::<Vec<(std::ffi::OsString, _)>>());
+	}
+
+	#[cfg(feature = "regex")]
+	#[test]
+	fn dropin_regex() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_regex")))
+			.unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().dropin_regex(regex::bytes::Regex::new("^[0-9]+-").unwrap()))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_regex/etc/foo.d/10-a.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_regex/etc/foo.d/2-c.conf"),
+		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+	}
+
+	#[test]
+	fn skip_backup_files() {
+		for skip_backup_files in [false, true] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with("", &crate::FindOptions::new().skip_backup_files(skip_backup_files))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			if skip_backup_files {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/run/foo.d/c.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+			else {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/etc/foo.d/a.conf~"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/etc/foo.d/b.conf.rpmnew"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/run/foo.d/c.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/run/foo.d/c.conf.pacnew"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_backup_files/run/foo.d/d.conf.bak"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+		}
+	}
+
+	#[test]
+	fn skip_hidden_files() {
+		for skip_hidden_files in [false, true] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_hidden_files")))
+				.unwrap()
+				.with_file_name("foo.service")
+				.find_files_with(Some(".conf"), &crate::FindOptions::new().skip_hidden_files(skip_hidden_files))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			if skip_hidden_files {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_hidden_files/etc/foo.service.d/a.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+			else {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_hidden_files/usr/etc/foo.service.d/.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_hidden_files/etc/foo.service.d/.hidden.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_hidden_files/etc/foo.service.d/a.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+		}
+	}
+
+	#[test]
+	fn dropin_suffix_match() {
+		for dropin_suffix_match in [crate::SuffixMatch::EndsWith, crate::SuffixMatch::Extension] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with("conf", &crate::FindOptions::new().dropin_suffix_match(dropin_suffix_match))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			match dropin_suffix_match {
+				crate::SuffixMatch::EndsWith => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match/etc/foo.d/.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match/etc/foo.d/bconf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match/etc/foo.d/c.notconf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+
+				crate::SuffixMatch::Extension => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dropin_suffix_match/etc/foo.d/a.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+			}
+		}
+	}
+
+	#[test]
+	fn ordering() {
+		let reverse = crate::Ordering::custom(|file_name1, file_name2| file_name2.cmp(file_name1));
+
+		for ordering in [crate::Ordering::Lexicographic, crate::Ordering::Strverscmp, reverse] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().ordering(ordering.clone()))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			match ordering {
+				crate::Ordering::Lexicographic => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/run/foo.d/1-a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/etc/foo.d/10-c.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/usr/etc/foo.d/2-b.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+
+				crate::Ordering::Strverscmp => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/run/foo.d/1-a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/usr/etc/foo.d/2-b.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/etc/foo.d/10-c.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+
+				crate::Ordering::Custom(_) => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/usr/etc/foo.d/2-b.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/etc/foo.d/10-c.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/ordering/run/foo.d/1-a.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+			}
+		}
+	}
+
+	#[test]
+	fn highest_first() {
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name")))
+			.unwrap()
+			.with_file_name("foo.service")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.highest_first()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/f.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/e.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/d.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/c.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/b.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/a.conf"),
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service"),
+		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+	}
+
+	#[test]
+	fn files_len() {
+		let mut files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name")))
+			.unwrap()
+			.with_file_name("foo.service")
+			.find_files(Some(".conf"))
+			.unwrap();
+		assert_eq!(files.len(), 7);
+		assert_eq!(files.size_hint(), (7, Some(7)));
+		assert!(!files.is_empty());
+
+		_ = files.next();
+		_ = files.next_back();
+		assert_eq!(files.len(), 5);
+		assert_eq!(files.by_ref().count(), 5);
+		assert!(files.is_empty());
+
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name")))
+			.unwrap()
+			.with_file_name("bar.service")
+			.find_files(Some(".conf"))
+			.unwrap();
+		assert!(files.is_empty());
+	}
+
+	#[test]
+	fn read_all() {
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap()
+			.read_all_to_string()
+			.unwrap();
+		assert_eq!(files, [
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/foo.d/a.conf").into(), "a = 1\n".to_owned()),
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/run/foo.d/b.conf").into(), "b = 2\n".to_owned()),
+		].into_iter().collect::<Vec<(PathBuf, _)>>());
+
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("bar")
+			.find_files(".conf")
+			.unwrap()
+			.read_all()
+			.unwrap();
+		assert_eq!(files, [
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/bar.d/a.conf").into(), b"\xff\xfe\n".to_vec()),
+		].into_iter().collect::<Vec<(PathBuf, _)>>());
+
+		let err =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("bar")
+			.find_files(".conf")
+			.unwrap()
+			.read_all_to_string()
+			.unwrap_err();
+		assert_eq!(err.path(), Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/bar.d/a.conf")));
+		assert_eq!(err.io_error().kind(), std::io::ErrorKind::InvalidData);
+	}
+
+	#[cfg(feature = "memmap2")]
+	#[test]
+	fn mmap_all() {
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap();
+		// SAFETY: The test files are not modified.
+		let files = unsafe { files.mmap_all() }.unwrap();
+		let files: Vec<_> = files.iter().map(|(path, mmap)| (&**path, &**mmap)).collect();
+		assert_eq!(files, [
+			(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/foo.d/a.conf")), &b"a = 1\n"[..]),
+			(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/run/foo.d/b.conf")), &b"b = 2\n"[..]),
+		]);
+
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap();
+		// SAFETY: The test files are not modified.
+		let files = unsafe { files.mmap_all() }.unwrap();
+		assert_eq!(files.len(), 6);
+		assert!(files.iter().all(|(_, mmap)| mmap.is_empty()));
+	}
+
+	#[cfg(feature = "cap-std")]
+	#[test]
+	fn cap_search_directories() {
+		use cap_std::{ambient_authority, fs::Dir};
+
+		let open = |path| Dir::open_ambient_dir(path, ambient_authority()).unwrap();
+		let usr_etc = open(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc"));
+		let run = open(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run"));
+		let etc = open(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc"));
+
+		let files: Vec<_> =
+			super::CapSearchDirectories::new([&usr_etc, &run, &etc])
+			.with_file_name("foo.service")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(i, path, _)| (i, path))
+			.collect();
+		assert_eq!(files, [
+			(2, "foo.service"),
+			(2, "foo.service.d/a.conf"),
+			(0, "foo.service.d/b.conf"),
+			(1, "foo.service.d/c.conf"),
+			(2, "foo.service.d/d.conf"),
+			(1, "foo.service.d/e.conf"),
+			(0, "foo.service.d/f.conf"),
+		].into_iter().map(|(i, path)| (i, path.into())).collect::<Vec<(usize, PathBuf)>>());
+
+		let files =
+			super::CapSearchDirectories::new([&usr_etc, &run, &etc])
+			.find_files(Some(".conf"))
+			.unwrap();
+		assert_eq!(files.len(), 0);
+	}
+
+	#[test]
+	fn into_readers() {
+		use std::io::BufRead as _;
+
+		let lines: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap()
+			.into_readers()
+			.flat_map(|(_, reader)| reader.lines())
+			.collect::<Result<_, _>>()
+			.unwrap();
+		assert_eq!(lines, ["a = 1", "b = 2"]);
+	}
+
+	#[test]
+	fn find_entries() {
+		let entries =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_entries(".conf")
+			.unwrap();
+		assert_eq!(entries.len(), 2);
+		let contents: Vec<_> =
+			entries
+			.map(|entry| {
+				assert!(entry.metadata().is_file());
+				let contents = std::io::read_to_string(entry.open().unwrap()).unwrap();
+				(entry.into_path(), contents)
+			})
+			.collect();
+		assert_eq!(contents, [
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/foo.d/a.conf").into(), "a = 1\n".to_owned()),
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/run/foo.d/b.conf").into(), "b = 2\n".to_owned()),
+		].into_iter().collect::<Vec<(PathBuf, _)>>());
+	}
+
+	#[test]
+	fn open_options() {
+		let err =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().open_options(std::fs::OpenOptions::new()))
+			.unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+		let entries =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo")
+			.find_entries_with(".conf", &crate::FindOptions::new().open_options(std::fs::OpenOptions::new()))
+			.unwrap();
+		for entry in entries {
+			assert_eq!(entry.open().unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		}
+	}
+
+	#[test]
+	fn symlink_policy() {
+		for symlink_policy in [crate::SymlinkPolicy::Follow, crate::SymlinkPolicy::Refuse, crate::SymlinkPolicy::WithinSearchDirectories] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().symlink_policy(symlink_policy))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			match symlink_policy {
+				crate::SymlinkPolicy::Follow => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/b.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/c.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+
+				crate::SymlinkPolicy::Refuse => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/run/foo.d/c.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+
+				crate::SymlinkPolicy::WithinSearchDirectories => assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/a.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/etc/foo.d/b.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/symlink_policy/run/foo.d/c.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>()),
+			}
+		}
+	}
+
+	#[test]
+	fn dropin_suffix() {
+		for suffix in ["", ".", "conf"] {
+			assert!(crate::DropinSuffix::new(suffix).is_err());
+		}
+		assert_eq!(crate::DropinSuffix::new(".conf").unwrap().as_os_str(), ".conf");
+		assert_eq!(crate::DropinSuffix::raw("conf").as_os_str(), "conf");
+
+		let root = temp_dir("dropin_suffix");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "").unwrap();
+		std::fs::write(root.join("etc/foo.d/bconf"), "").unwrap();
+
+		let find = |dropin_suffix: &crate::DropinSuffix| -> Vec<_> {
+			SearchDirectories::modern_system()
+				.chroot(&root).unwrap()
+				.with_project("foo")
+				.find_files(dropin_suffix)
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect()
+		};
+		assert_eq!(find(&crate::DropinSuffix::new(".conf").unwrap()), [root.join("etc/foo.d/a.conf")]);
+		assert_eq!(find(&crate::DropinSuffix::raw("conf")), [root.join("etc/foo.d/a.conf"), root.join("etc/foo.d/bconf")]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn dropin_directory_symlink_policy() {
+		let root = temp_dir("dropin_directory_symlink_policy");
+		for path in ["usr/etc/foo.d/a.conf", "usr/share/foo/b.conf", "usr/etc/bar.d/c.conf"] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+		std::fs::create_dir_all(root.join("run")).unwrap();
+		std::fs::create_dir_all(root.join("etc")).unwrap();
+		// Outside the search directories
+		std::os::unix::fs::symlink("../usr/share/foo", root.join("run/foo.d")).unwrap();
+		// Within the search directories
+		std::os::unix::fs::symlink("../usr/etc/bar.d", root.join("etc/foo.d")).unwrap();
+
+		for dropin_directory_symlink_policy in [crate::SymlinkPolicy::Follow, crate::SymlinkPolicy::Refuse, crate::SymlinkPolicy::WithinSearchDirectories] {
+			let options = crate::FindOptions::new().dropin_directory_symlink_policy(dropin_directory_symlink_policy);
+			let search_directories =
+				SearchDirectories::modern_system()
+				.chroot(&root)
+				.unwrap()
+				.with_project("foo");
+			let files: Vec<_> =
+				search_directories.clone()
+				.find_files_with(".conf", &options)
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			let dropin_directories = search_directories.find_dropin_directories_with(&options).unwrap();
+			match dropin_directory_symlink_policy {
+				crate::SymlinkPolicy::Follow => {
+					assert_eq!(files, [root.join("usr/etc/foo.d/a.conf"), root.join("run/foo.d/b.conf"), root.join("etc/foo.d/c.conf")]);
+					assert_eq!(dropin_directories, [root.join("usr/etc/foo.d"), root.join("run/foo.d"), root.join("etc/foo.d")]);
+				},
+
+				crate::SymlinkPolicy::Refuse => {
+					assert_eq!(files, [root.join("usr/etc/foo.d/a.conf")]);
+					assert_eq!(dropin_directories, [root.join("usr/etc/foo.d")]);
+				},
+
+				crate::SymlinkPolicy::WithinSearchDirectories => {
+					assert_eq!(files, [root.join("usr/etc/foo.d/a.conf"), root.join("etc/foo.d/c.conf")]);
+					assert_eq!(dropin_directories, [root.join("usr/etc/foo.d"), root.join("etc/foo.d")]);
+				},
+			}
+		}
+	}
+
+	#[test]
+	fn audit() {
+		use std::os::unix::fs::{MetadataExt as _, PermissionsExt as _};
+
+		let root = temp_dir("audit");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "").unwrap();
+		std::os::unix::fs::symlink("a.conf", root.join("etc/foo.d/b.conf")).unwrap();
+		std::fs::set_permissions(root.join("etc/foo.d"), std::fs::Permissions::from_mode(0o757)).unwrap();
+		let metadata = std::fs::metadata(root.join("etc/foo.d/a.conf")).unwrap();
+
+		let audits =
+			SearchDirectories::modern_system()
+			.chroot(&root)
+			.unwrap()
+			.with_project("foo")
+			.find_entries(".conf")
+			.unwrap()
+			.audit()
+			.unwrap();
+		assert_eq!(audits.len(), 2);
+		for (audit, (path, via_symlink)) in audits.iter().zip([("etc/foo.d/a.conf", false), ("etc/foo.d/b.conf", true)]) {
+			assert_eq!(audit.path(), root.join(path));
+			assert_eq!(audit.uid(), metadata.uid());
+			assert_eq!(audit.gid(), metadata.gid());
+			assert_eq!(audit.mode(), metadata.mode());
+			assert_eq!(audit.writable_ancestor(), Some(&*root.join("etc/foo.d")));
+			assert_eq!(audit.via_symlink(), via_symlink);
+		}
+	}
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[test]
+	fn chroot_contained() {
+		// `a.conf` is an absolute symlink to `/usr/etc/a.target`, which only exists inside the root.
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained")))
+			.unwrap()
+			.with_project("foo")
+			.find_files(".conf")
+			.unwrap()
+			.read_all_to_string()
+			.unwrap();
+		assert_eq!(files, [
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained/etc/foo.d/b.conf").into(), "b = 2\n".to_owned()),
+		]);
+
+		for symlink_policy in [crate::SymlinkPolicy::Follow, crate::SymlinkPolicy::WithinSearchDirectories] {
+			let files =
+				SearchDirectories::modern_system()
+				.chroot_contained(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().symlink_policy(symlink_policy))
+				.unwrap()
+				.read_all_to_string()
+				.unwrap();
+			assert_eq!(files, [
+				(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained/etc/foo.d/a.conf").into(), "a = 1\n".to_owned()),
+				(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained/etc/foo.d/b.conf").into(), "b = 2\n".to_owned()),
+			]);
+		}
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot_contained(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained")))
+			.unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().symlink_policy(crate::SymlinkPolicy::Refuse))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_contained/etc/foo.d/b.conf"),
+		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn search_only_directory() {
+		use std::os::unix::fs::PermissionsExt as _;
+
+		let root = temp_dir("search_only_directory");
+		let path = root.join("etc/foo/foo.conf");
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(&path, "").unwrap();
+
+		// The directory can only be searched, not listed, which is enough to find the main file.
+		std::fs::set_permissions(root.join("etc/foo"), std::fs::Permissions::from_mode(0o311)).unwrap();
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(&root)
+			.unwrap()
+			.with_project("foo")
+			.with_file_name("foo.conf")
+			.find_files(None::<&str>)
+			.unwrap()
+			.into_paths()
+			.collect();
+		assert_eq!(files, [path]);
+
+		std::fs::set_permissions(root.join("etc/foo"), std::fs::Permissions::from_mode(0o755)).unwrap();
+	}
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[test]
+	fn dir_replaced_after_search() {
+		let root = temp_dir("dir_replaced_after_search");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "a = 1\n").unwrap();
+
+		let mut entries =
+			SearchDirectories::modern_system()
+			.chroot(&root)
+			.unwrap()
+			.with_project("foo")
+			.find_entries(".conf")
+			.unwrap();
+		let entry = entries.next().unwrap();
+		assert!(entries.next().is_none());
+
+		std::fs::rename(root.join("etc/foo.d"), root.join("etc/foo.d.old")).unwrap();
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "a = 2\n").unwrap();
+
+		// The entry is opened relative to the directory it was found in, even though that directory has been renamed.
+		let contents = std::io::read_to_string(entry.open().unwrap()).unwrap();
+		assert_eq!(contents, "a = 1\n");
+		assert_eq!(entry.path(), root.join("etc/foo.d/a.conf"));
+	}
+
+	#[test]
+	fn finder() {
+		let root = temp_dir("finder");
+		std::fs::create_dir_all(root.join("etc")).unwrap();
+
+		let finder = crate::Finder::new(SearchDirectories::modern_system().chroot(&root).unwrap());
+		let find = || -> Vec<_> { finder.with_project("foo").find_files(".conf").unwrap().map(|(path, _)| path).collect() };
+		assert_eq!(find(), Vec::<PathBuf>::new());
+
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "").unwrap();
+
+		// The directory is still remembered as missing until the finder is invalidated.
+		assert_eq!(find(), Vec::<PathBuf>::new());
+		finder.invalidate_directory(&root.join("etc"));
+		assert_eq!(find(), [root.join("etc/foo.d/a.conf")]);
+
+		std::fs::create_dir_all(root.join("run/foo.d")).unwrap();
+		std::fs::write(root.join("run/foo.d/b.conf"), "").unwrap();
+		assert_eq!(find(), [root.join("etc/foo.d/a.conf")]);
+		finder.invalidate();
+		assert_eq!(find(), [root.join("etc/foo.d/a.conf"), root.join("run/foo.d/b.conf")]);
+	}
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[test]
+	fn finder_keeps_search_directories_open() {
+		let root = temp_dir("finder_keeps_search_directories_open");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "a").unwrap();
+
+		let finder = crate::Finder::new(SearchDirectories::modern_system().chroot(&root).unwrap());
+		let find = || -> Vec<_> {
+			finder.with_project("foo").find_files(".conf").unwrap()
+			.map(|(path, mut file)| {
+				let mut contents = String::new();
+				std::io::Read::read_to_string(&mut file, &mut contents).unwrap();
+				(path, contents)
+			})
+			.collect()
+		};
+		assert_eq!(find(), [(root.join("etc/foo.d/a.conf"), "a".to_owned())]);
+
+		// The search directory is replaced, but the finder keeps using the one that it opened until it is invalidated.
+		std::fs::rename(root.join("etc"), root.join("etc.old")).unwrap();
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/b.conf"), "b").unwrap();
+		assert_eq!(find(), [(root.join("etc/foo.d/a.conf"), "a".to_owned())]);
+		finder.invalidate();
+		assert_eq!(find(), [(root.join("etc/foo.d/b.conf"), "b".to_owned())]);
+	}
+
+	#[cfg(all(feature = "landlock", target_os = "linux"))]
+	#[test]
+	fn restrict_with_landlock() {
+		let root = temp_dir("restrict_with_landlock");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::create_dir_all(root.join("outside")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "").unwrap();
+		std::fs::write(root.join("outside/b.conf"), "").unwrap();
+		std::os::unix::fs::symlink(root.join("outside/b.conf"), root.join("etc/foo.d/b.conf")).unwrap();
+
+		// The restriction only applies to the thread that makes it, so make it in a new thread to not affect the other tests.
+		std::thread::scope(|s| s.spawn(|| {
+			let search_directories = SearchDirectories::modern_system().chroot(&root).unwrap();
+			let status = search_directories.restrict_with_landlock().unwrap();
+			// There is nothing to test if the kernel does not support Landlock.
+			if status.ruleset == landlock::RulesetStatus::NotEnforced {
+				return;
+			}
+
+			let err = search_directories.clone().with_project("foo").find_files(".conf").unwrap_err();
+			assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+			let files: Vec<_> =
+				search_directories
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().symlink_policy(crate::SymlinkPolicy::Refuse))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			assert_eq!(files, [root.join("etc/foo.d/a.conf")]);
+		}).join().unwrap());
+	}
+
+	#[test]
+	fn max_dropins() {
+		let root = temp_dir("max_dropins");
+		std::fs::create_dir_all(root.join("usr/etc/foo.d")).unwrap();
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("usr/etc/foo.d/0.conf"), "").unwrap();
+		for i in 0..5 {
+			std::fs::write(root.join(format!("etc/foo.d/{i}.conf")), "").unwrap();
+		}
+		std::fs::write(root.join("etc/foo.d/5.txt"), "").unwrap();
+
+		let find = |max_dropins| SearchDirectories::modern_system().chroot(&root).unwrap().with_project("foo").find_files_with(".conf", &crate::FindOptions::new().max_dropins(max_dropins));
+
+		// `0.conf` is only counted once, and `5.txt` is not a candidate.
+		assert_eq!(find(5).unwrap().len(), 5);
+
+		let err = find(4).unwrap_err();
+		let err = err.get_ref().unwrap().downcast_ref::<crate::TooManyDropinsError>().unwrap();
+		assert_eq!(err.max_dropins(), 4);
+		assert_eq!(err.directory(), root.join("etc/foo.d"));
+	}
+
+	#[test]
+	fn max_file_size() {
+		let root = temp_dir("max_file_size");
+		std::fs::create_dir_all(root.join("etc/foo.conf.d")).unwrap();
+		std::fs::write(root.join("etc/foo.conf"), "a = 1\n").unwrap();
+		std::fs::write(root.join("etc/foo.conf.d/a.conf"), "b = 22\n").unwrap();
+
+		let find = |max_file_size| SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf").find_files_with(Some(".conf"), &crate::FindOptions::new().max_file_size(max_file_size));
+
+		assert_eq!(find(7).unwrap().len(), 2);
+
+		let err = find(6).unwrap_err();
+		let err = err.get_ref().unwrap().downcast_ref::<crate::FileTooLargeError>().unwrap();
+		assert_eq!(err.path(), root.join("etc/foo.conf.d/a.conf"));
+		assert_eq!(err.size(), 7);
+		assert_eq!(err.max_file_size(), 6);
+	}
+
+	#[test]
+	fn require_files() {
+		let root = temp_dir("require_files");
+		std::fs::create_dir_all(root.join("etc/foo.conf.d")).unwrap();
+		std::fs::write(root.join("etc/foo.conf.d/a.conf"), "").unwrap();
+
+		let find = |file_name, options: &crate::FindOptions| SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name(file_name).find_files_with(Some(".conf"), options);
+
+		assert_eq!(find("foo.conf", &crate::FindOptions::new().require_files(true)).unwrap().len(), 1);
+
+		let err = find("foo.conf", &crate::FindOptions::new().require_main_file(true)).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+		let err = err.get_ref().unwrap().downcast_ref::<crate::ConfigNotFoundError>().unwrap();
+		assert_eq!(err.name(), "foo.conf");
+		assert_eq!(err.search_directories(), [root.join("usr/etc"), root.join("run"), root.join("etc")]);
+		assert!(err.main_file_required());
+		assert_eq!(
+			err.to_string(),
+			format!("config file foo.conf not found in any of {}, {}, {}", root.join("usr/etc").display(), root.join("run").display(), root.join("etc").display()),
+		);
+
+		assert_eq!(find("bar.conf", &crate::FindOptions::new()).unwrap().len(), 0);
+		let err = find("bar.conf", &crate::FindOptions::new().require_files(true)).unwrap_err();
+		let err = err.get_ref().unwrap().downcast_ref::<crate::ConfigNotFoundError>().unwrap();
+		assert!(!err.main_file_required());
+
+		let err = SearchDirectories::modern_system().chroot(&root).unwrap().with_project("foo").with_file_name("foo.conf").find_files_with(None::<&str>, &crate::FindOptions::new().require_files(true)).unwrap_err();
+		let err = err.get_ref().unwrap().downcast_ref::<crate::ConfigNotFoundError>().unwrap();
+		assert_eq!(err.name(), "foo/foo.conf");
+	}
+
+	#[test]
+	fn permission_policy() {
+		use std::os::unix::fs::{MetadataExt as _, PermissionsExt as _};
+
+		let root = temp_dir("permission_policy");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "").unwrap();
+		let uid = std::fs::metadata(root.join("etc/foo.d/a.conf")).unwrap().uid();
+
+		let find = |permission_policy| SearchDirectories::modern_system().chroot(&root).unwrap().with_project("foo").find_files_with(".conf", &crate::FindOptions::new().permission_policy(permission_policy));
+
+		for (mode, violation) in [
+			(0o644, None),
+			(0o664, Some(crate::PermissionViolation::GroupWritable)),
+			(0o646, Some(crate::PermissionViolation::WorldWritable)),
+		] {
+			std::fs::set_permissions(root.join("etc/foo.d/a.conf"), std::fs::Permissions::from_mode(mode)).unwrap();
+
+			let policy = crate::PermissionPolicy::new().owners([uid]).reject_group_writable(true).reject_world_writable(true);
+			match violation {
+				None => assert_eq!(find(policy).unwrap().len(), 1),
+				Some(violation) => {
+					let err = find(policy).unwrap_err();
+					assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+					let err = err.get_ref().unwrap().downcast_ref::<crate::PermissionError>().unwrap();
+					assert_eq!(err.path(), root.join("etc/foo.d/a.conf"));
+					assert_eq!(err.mode() & 0o777, mode);
+					assert_eq!(err.violation(), violation);
+				},
+			}
+		}
+
+		let err = find(crate::PermissionPolicy::new().owners([uid.wrapping_add(1)])).unwrap_err();
+		let err = err.get_ref().unwrap().downcast_ref::<crate::PermissionError>().unwrap();
+		assert_eq!(err.uid(), uid);
+		assert_eq!(err.violation(), crate::PermissionViolation::Owner);
+
+		// Checking entries only reports the violation without failing the search.
+		let entries = SearchDirectories::modern_system().chroot(&root).unwrap().with_project("foo").find_entries(".conf").unwrap();
+		let violations: Vec<_> =
+			entries
+			.filter_map(|entry| crate::PermissionPolicy::strict().reject_group_writable(false).owners([uid]).check(entry.path(), entry.metadata()).err())
+			.map(|err| err.violation())
+			.collect();
+		assert_eq!(violations, [crate::PermissionViolation::WorldWritable]);
+	}
+
+	#[test]
+	fn template_dropins() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/template_dropins"));
+
+		for (file_name, template_dropins, expected) in [
+			("foo@bar.service", true, &[
+				"usr/etc/foo@.service.d/10.conf",
+				"etc/foo@bar.service.d/20.conf",
+				"etc/foo@.service.d/30.conf",
+				"etc/foo@bar.service.d/40.conf",
+			][..]),
+			("foo@bar.service", false, &[
+				"etc/foo@bar.service.d/20.conf",
+				"run/foo@bar.service.d/30.conf",
+				"etc/foo@bar.service.d/40.conf",
+			][..]),
+			("foo@.service", true, &[
+				"usr/etc/foo@.service.d/10.conf",
+				"usr/etc/foo@.service.d/20.conf",
+				"etc/foo@.service.d/30.conf",
+				"etc/foo@.service.d/40.conf",
+			][..]),
+		] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(root)
+				.unwrap()
+				.with_file_name(file_name)
+				.find_files_with(Some(".conf"), &crate::FindOptions::new().template_dropins(template_dropins))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			assert_eq!(files, expected.iter().map(|path| root.join(path)).collect::<Vec<_>>());
+		}
+	}
+
+	#[test]
+	fn type_dropins() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/type_dropins"));
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root)
+			.unwrap()
+			.with_file_name("foo@bar.service")
+			.find_files_with(Some(".conf"), &crate::FindOptions::new().type_dropins(true).template_dropins(true))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			"usr/etc/service.d/10.conf",
+			"usr/etc/foo@.service.d/20.conf",
+			"etc/foo@bar.service.d/30.conf",
+		].into_iter().map(|path| root.join(path)).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn prefix_dropins() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/prefix_dropins"));
+
+		for (file_name, expected) in [
+			("foo-bar-baz.service", &[
+				"usr/etc/foo-.service.d/10.conf",
+				"usr/etc/foo-bar-.service.d/20.conf",
+				"etc/foo-.service.d/30.conf",
+				"etc/foo-.service.d/40.conf",
+			][..]),
+			("foo-bar-baz@qux-quux.service", &[
+				"usr/etc/foo-.service.d/10.conf",
+				"usr/etc/foo-bar-.service.d/20.conf",
+				"etc/foo-.service.d/30.conf",
+				"etc/foo-.service.d/40.conf",
+			][..]),
+			("foo-bar-baz-.service", &[
+				"usr/etc/foo-.service.d/10.conf",
+				"usr/etc/foo-bar-.service.d/20.conf",
+				"etc/foo-.service.d/30.conf",
+				"etc/foo-bar-baz-.service.d/40.conf",
+			][..]),
+		] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(root)
+				.unwrap()
+				.with_file_name(file_name)
+				.find_files_with(Some(".conf"), &crate::FindOptions::new().prefix_dropins(true))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			assert_eq!(files, expected.iter().map(|path| root.join(path)).collect::<Vec<_>>(), "{file_name}");
+		}
+	}
+
+	#[test]
+	fn fallback_file_names() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/fallback_file_names"));
+
+		let find = |file_name, fallback_file_names: &[&'static str]| -> Vec<_> {
+			SearchDirectories::modern_system()
+			.chroot(root)
+			.unwrap()
+			.with_file_name(file_name)
+			.with_fallback_file_names(fallback_file_names.iter().copied())
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect()
+		};
+
+		// The fallback name in a later search directory overrides the preferred name in an earlier one.
+		assert_eq!(find("foobar.toml", &["foobar.conf"]), [
+			root.join("etc/foobar.conf"),
+			root.join("usr/etc/foobar.conf.d/b.conf"),
+			root.join("etc/foobar.conf.d/c.conf"),
+		]);
+
+		// No main file was found, so the dropins are those of the preferred name.
+		assert_eq!(find("foobar.yaml", &["foobar.json"]), [root.join("usr/etc/foobar.yaml.d/d.conf")]);
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root)
+			.unwrap()
+			.with_project("project")
+			.with_file_name("foobar.toml")
+			.with_fallback_file_names(["foobar.conf"])
+			.find_files(None::<&str>)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("usr/etc/project/foobar.toml")]);
+	}
+
+	#[test]
+	fn legacy_projects() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/legacy_projects"));
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root)
+			.unwrap()
+			.with_project("foobar")
+			.with_legacy_projects(["foo-bar", "foo_bar"])
+			.find_files(".conf")
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			root.join("etc/foo_bar.d/10.conf"),
+			root.join("usr/etc/foobar.d/20.conf"),
+			root.join("usr/etc/foo-bar.d/30.conf"),
+		]);
+
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root)
+			.unwrap()
+			.with_project("foobar")
+			.with_legacy_projects(["foo-bar", "foo_bar"])
+			.with_file_name("main.conf")
+			.find_files(None::<&str>)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("etc/foo-bar/main.conf")]);
+	}
+
+	#[test]
+	fn project_main_file() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/project_main_file"));
+
+		let find = |main_file, legacy_projects: &[&'static str]| -> Vec<_> {
+			let search_directories =
+				SearchDirectories::modern_system()
+				.chroot(root)
+				.unwrap()
+				.with_project("foobar")
+				.with_legacy_projects(legacy_projects.iter().copied());
+			let search_directories = if main_file { search_directories.with_main_file() } else { search_directories };
+			search_directories.find_files(".conf").unwrap().map(|(path, _)| path).collect()
+		};
+
+		assert_eq!(find(false, &[]), [root.join("usr/etc/foobar.d/a.conf")]);
+		assert_eq!(find(true, &[]), [root.join("usr/etc/foobar.conf"), root.join("usr/etc/foobar.d/a.conf")]);
+		assert_eq!(find(true, &["foo-bar"]), [root.join("run/foo-bar.conf"), root.join("usr/etc/foobar.d/a.conf")]);
+	}
+
+	#[test]
+	fn dedupe_search_directories() {
+		let root = temp_dir("dedupe_search_directories");
+		std::fs::create_dir_all(root.join("usr/etc")).unwrap();
+		std::fs::create_dir_all(root.join("run")).unwrap();
+		std::os::unix::fs::symlink("run", root.join("etc")).unwrap();
+
+		let search_directories: Vec<std::borrow::Cow<'_, Path>> =
+			["usr/etc", "run", "missing", "etc", "missing"].into_iter().map(|path| root.join(path).into()).collect();
+
+		for (dedupe_search_directories, expected) in [
+			(false, &["usr/etc", "run", "missing", "etc", "missing"][..]),
+			(true, &["usr/etc", "missing", "etc", "missing"][..]),
+		] {
+			let options = crate::FindOptions::new().dedupe_search_directories(dedupe_search_directories);
+			let context = super::SearchContext::new(&options, &search_directories, None, &[], None).unwrap();
+			let paths = context.search_directory_paths(&search_directories).unwrap();
+			assert_eq!(paths, expected.iter().map(|path| root.join(path)).collect::<Vec<_>>());
+		}
+	}
+
+	#[test]
+	fn edit_search_directories() {
+		let mut search_directories = SearchDirectories::modern_system();
+		assert_eq!(search_directories.len(), 3);
+		assert!(!search_directories.is_empty());
+
+		search_directories.insert(1, Path::new("/usr/local/etc").into()).unwrap();
+		assert!(search_directories.insert(0, Path::new("usr/etc").into()).is_err());
+		assert_eq!(search_directories.remove(2), Path::new("/run"));
+		search_directories.retain(|path| path != Path::new("/usr/etc"));
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [Path::new("/usr/local/etc"), Path::new("/etc")]);
+		assert_eq!(search_directories.as_slice(), [Path::new("/usr/local/etc"), Path::new("/etc")]);
+
+		let search_directories = SearchDirectories::empty().chroot(Path::new("/tmp")).unwrap();
+		assert!(search_directories.is_empty());
+	}
+
+	#[test]
+	fn set_enabled() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/set_enabled"));
+
+		let find = |search_directories: &SearchDirectories<'_>| -> Vec<_> {
+			search_directories.clone().with_project("foo").find_files(".conf").unwrap().map(|(path, _)| path).collect()
+		};
+
+		let mut search_directories = SearchDirectories::modern_system();
+		assert!(search_directories.set_enabled(Path::new("/run"), false));
+		assert!(!search_directories.set_enabled(Path::new("/var/run"), false));
+		let mut search_directories = search_directories.chroot(root).unwrap();
+		assert!(!search_directories.is_enabled(&root.join("run")));
+		assert_eq!(search_directories.len(), 3);
+		assert_eq!(find(&search_directories), [root.join("usr/etc/foo.d/a.conf"), root.join("etc/foo.d/c.conf")]);
+		assert_eq!(search_directories.clone().with_project("foo").describe(".conf").dropin_directories().collect::<Vec<_>>(), [
+			root.join("usr/etc/foo.d"),
+			root.join("etc/foo.d"),
+		]);
+
+		assert!(search_directories.set_enabled(&root.join("run"), true));
+		assert_eq!(find(&search_directories), [root.join("usr/etc/foo.d/a.conf"), root.join("run/foo.d/b.conf"), root.join("etc/foo.d/c.conf")]);
+
+		// Removing a disabled search directory forgets that it was disabled.
+		assert!(search_directories.set_enabled(&root.join("etc"), false));
+		search_directories.retain(|path| path != root.join("etc"));
+		search_directories.push(root.join("etc").into()).unwrap();
+		assert!(search_directories.is_enabled(&root.join("etc")));
+	}
+
+	#[test]
+	fn labels() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/labels"));
+
+		let mut search_directories = SearchDirectories::empty();
+		search_directories.push_with_label(Path::new("/site").into(), "site").unwrap();
+		search_directories.push_with_label(Path::new("/cluster").into(), "cluster").unwrap();
+		search_directories.push(Path::new("/host").into()).unwrap();
+		let mut search_directories = search_directories.chroot(root).unwrap();
+		assert!(!search_directories.set_label(Path::new("/host"), "host"));
+		assert_eq!(search_directories.label(&root.join("host")), None);
+		assert!(search_directories.set_label(&root.join("host"), "host"));
+		assert_eq!(search_directories.label(&root.join("site")), Some("site"));
+
+		let files: Vec<_> =
+			search_directories.clone()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.labeled(&search_directories)
+			.into_iter()
+			.map(|(label, path, _)| (label, path))
+			.collect();
+		assert_eq!(files, [
+			(Some("site".to_owned()), root.join("site/foo.conf")),
+			(Some("cluster".to_owned()), root.join("cluster/foo.conf.d/a.conf")),
+			(Some("host".to_owned()), root.join("host/foo.conf.d/b.conf")),
+		]);
+		assert_eq!(search_directories.label_of(Path::new("/elsewhere/foo.conf")), None);
+
+		search_directories.retain(|path| path != root.join("site"));
+		assert_eq!(search_directories.label(&root.join("site")), None);
+	}
+
+	#[test]
+	fn chroot_all() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/chroot_all"));
+
+		let mut search_directories = SearchDirectories::modern_system();
+		search_directories.set_label(Path::new("/etc"), "admin");
+		let search_directories = search_directories.chroot_all(["host", "container", "overlay"].map(|name| root.join(name))).unwrap();
+		assert_eq!(search_directories.len(), 9);
+		assert_eq!(search_directories.label(&root.join("container/etc")), Some("admin"));
+
+		let files: Vec<_> =
+			search_directories
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			root.join("container/usr/etc/foo.conf"),
+			root.join("container/usr/etc/foo.conf.d/a.conf"),
+			root.join("host/etc/foo.conf.d/b.conf"),
+			root.join("overlay/run/foo.conf.d/c.conf"),
+		]);
+
+		assert!(SearchDirectories::modern_system().chroot_all([Path::new("/"), Path::new("relative")]).is_err());
+	}
+
+	#[test]
+	fn layers() {
+		let root = temp_dir("layers");
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a = 1\n"),
+			("etc/foo.conf.d/a.conf", "a = 2\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let search = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf");
+		let mut layers =
+			crate::Layers::new()
+			.layer(crate::SearchLayer::new("files", move || search.clone().find_files(Some(".conf"))))
+			.layer(crate::StaticLayer::new("command line").entry("--a", b"a = 4\n".as_slice()));
+		layers.insert(1, crate::StaticLayer::new("environment").entry("$A", b"a = 3\n".as_slice()));
+		assert_eq!(layers.names().collect::<Vec<_>>(), ["files", "environment", "command line"]);
+
+		let entries: Vec<_> = layers.entries().unwrap().map(|(path, entry)| (path, entry.layer().to_owned())).collect();
+		assert_eq!(entries, [
+			(root.join("usr/etc/foo.conf"), "files".to_owned()),
+			(root.join("etc/foo.conf.d/a.conf"), "files".to_owned()),
+			(PathBuf::from("$A"), "environment".to_owned()),
+			(PathBuf::from("--a"), "command line".to_owned()),
+		]);
+
+		let mut output = vec![];
+		layers.entries().unwrap().write_cat_config(&mut output).unwrap();
+		assert_eq!(String::from_utf8(output).unwrap(), format!("\
+# {root}/usr/etc/foo.conf (files)
+a = 1
+
+# {root}/etc/foo.conf.d/a.conf (files)
+a = 2
+
+# $A (environment)
+a = 3
+
+# --a (command line)
+a = 4
+", root = root.display()));
+	}
+
+	#[cfg(feature = "http")]
+	#[test]
+	fn http_layer() {
+		use std::io::{BufRead as _, Write as _};
+
+		let root = temp_dir("http_layer");
+
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/foo.conf", listener.local_addr().unwrap());
+		let server = std::thread::spawn(move || {
+			let mut if_none_match = vec![];
+			for response in [
+				"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 6\r\n\r\na = 1\n",
+				"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nContent-Length: 0\r\n\r\n",
+				"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n",
+				"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+			] {
+				let (stream, _) = listener.accept().unwrap();
+				let mut reader = std::io::BufReader::new(&stream);
+				let mut header = None;
+				loop {
+					let mut line = String::new();
+					reader.read_line(&mut line).unwrap();
+					let line = line.trim_end();
+					if line.is_empty() {
+						break;
+					}
+					if let Some((name, value)) = line.split_once(':') {
+						if name.eq_ignore_ascii_case("if-none-match") {
+							header = Some(value.trim().to_owned());
+						}
+					}
+				}
+				if_none_match.push(header);
+				(&stream).write_all(response.as_bytes()).unwrap();
+			}
+			if_none_match
+		});
+
+		let layers = crate::Layers::new().layer(crate::HttpLayer::new("fleet", root.join("cache")).url(&url));
+		let read = || -> Vec<_> {
+			layers.entries().unwrap().map(|(path, mut entry)| {
+				let mut contents = String::new();
+				std::io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+				(path, entry.layer().to_owned(), contents)
+			}).collect()
+		};
+		let expected = [(PathBuf::from(&url), "fleet".to_owned(), "a = 1\n".to_owned())];
+
+		// 200 saves the response, 304 and 500 reuse it, 404 deletes it.
+		assert_eq!(read(), expected);
+		assert_eq!(std::fs::read_dir(root.join("cache")).unwrap().count(), 2);
+		assert_eq!(read(), expected);
+		assert_eq!(read(), expected);
+		assert_eq!(read(), []);
+		assert_eq!(std::fs::read_dir(root.join("cache")).unwrap().count(), 0);
+
+		assert_eq!(server.join().unwrap(), [None, Some("\"v1\"".to_owned()), Some("\"v1\"".to_owned()), Some("\"v1\"".to_owned())]);
+
+		// Unreachable, and nothing saved.
+		assert!(layers.entries().is_err());
+	}
+
+	#[cfg(feature = "http")]
+	#[test]
+	fn http_cache_file_name() {
+		use crate::http::cache_file_name;
+
+		assert_eq!(cache_file_name("https://h/a.conf"), "https%3A%2F%2Fh%2Fa%2Econf");
+
+		// The ETag file of one URL is not the cache file of another.
+		let mut etag_file_name = cache_file_name("https://h/a");
+		etag_file_name.push_str(".etag");
+		assert_ne!(cache_file_name("https://h/a.etag"), etag_file_name);
+
+		let long_url = format!("https://h/{}", "a".repeat(300));
+		let file_name = cache_file_name(&long_url);
+		assert_eq!(file_name.len(), 200);
+		assert!(file_name.starts_with("https%3A%2F%2Fh%2Faaa"));
+		assert_ne!(file_name, cache_file_name(&format!("{long_url}b")));
+	}
+
+	#[cfg(all(feature = "derive", feature = "toml"))]
+	#[test]
+	fn derive_uapi_config() {
+		#[derive(Debug, Default, PartialEq, crate::UapiConfig)]
+		#[uapi_config(project = "foo", format = "toml")]
+		struct Config {
+			a: u32,
+			b: Option<String>,
+			#[uapi_config(rename = "c-list")]
+			c: Vec<u32>,
+		}
+
+		let root = temp_dir("derive_uapi_config");
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a = 1\nb = \"x\"\nc-list = [1]\n"),
+			("etc/foo.d/a.conf", "a = 2\n"),
+			("run/foo.d/b.conf", "c-list = [2, 3]\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let search_directories = || SearchDirectories::modern_system().chroot(&root).unwrap();
+
+		let config = Config::load_from(search_directories()).unwrap();
+		assert_eq!(config, Config { a: 2, b: Some("x".to_owned()), c: vec![2, 3] });
+
+		for (contents, key) in [
+			("a = \"x\"\n", Some("a")),
+			("d = 1\n", Some("d")),
+			("a =\n", None),
+		] {
+			std::fs::write(root.join("run/foo.d/c.conf"), contents).unwrap();
+			let err = Config::load_from(search_directories()).unwrap_err();
+			assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+			let err: &crate::MergeError<crate::FormatError> = err.get_ref().unwrap().downcast_ref().unwrap();
+			assert_eq!(err.path(), root.join("run/foo.d/c.conf"));
+			assert_eq!(err.inner().key(), key);
+		}
+	}
+
+	#[test]
+	fn reloader() {
+		let root = temp_dir("reloader");
+		std::fs::create_dir_all(root.join("etc/foo.conf.d")).unwrap();
+		std::fs::write(root.join("etc/foo.conf"), "").unwrap();
+
+		let search = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf");
+		let mut reloader = crate::Reloader::new(move || search.clone().find_entries(Some(".conf")));
+
+		// The second trigger finds the dropin that the first callback creates, and the third finds nothing new.
+		let (trigger_send, trigger_recv) = std::sync::mpsc::channel();
+		trigger_send.send(()).unwrap();
+		trigger_send.send(()).unwrap();
+		drop(trigger_send);
+
+		let mut changes = vec![];
+		reloader.run(trigger_recv, |result| {
+			let (entries, diff) = result.unwrap();
+			if changes.is_empty() {
+				std::fs::write(root.join("etc/foo.conf.d/a.conf"), "").unwrap();
+			}
+			changes.push((entries.map(|entry| entry.path().to_owned()).collect::<Vec<_>>(), diff.to_string()));
+		});
+		assert_eq!(changes, [
+			(vec![root.join("etc/foo.conf")], format!("+ {}/etc/foo.conf\n", root.display())),
+			(vec![root.join("etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")], format!("+ {}/etc/foo.conf.d/a.conf\n", root.display())),
+		]);
+		assert_eq!(reloader.snapshot().unwrap().paths().count(), 2);
+	}
+
+	#[test]
+	fn reloading_config() {
+		#[derive(Debug, Default)]
+		struct Config(Vec<String>);
+
+		impl crate::MergeFrom for Config {
+			type Error = std::io::Error;
+
+			fn merge_from<R>(&mut self, _path: &Path, mut reader: R) -> Result<(), Self::Error> where R: std::io::Read {
+				let mut contents = String::new();
+				reader.read_to_string(&mut contents)?;
+				if contents == "invalid" {
+					return Err(std::io::ErrorKind::InvalidData.into());
+				}
+				self.0.push(contents);
+				Ok(())
+			}
+		}
+
+		let root = temp_dir("reloading_config");
+		std::fs::create_dir_all(root.join("etc/foo.conf.d")).unwrap();
+		std::fs::write(root.join("etc/foo.conf"), "a").unwrap();
+
+		let spawn = |file_name| {
+			let search = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name(file_name);
+			let (err_send, err_recv) = std::sync::mpsc::channel();
+			let config = crate::ReloadingConfig::<Config>::spawn(
+				move || search.clone().find_entries(Some(".conf")),
+				(),
+				std::time::Duration::from_millis(10),
+				move |err| err_send.send(err).unwrap(),
+			);
+			(config, err_recv)
+		};
+
+		let (config, err_recv) = spawn("foo.conf");
+		let config = config.unwrap();
+		assert_eq!(config.load().0, ["a"]);
+
+		std::fs::write(root.join("etc/foo.conf.d/b.conf"), "b").unwrap();
+		config.reload();
+		let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+		while config.load().0.len() < 2 {
+			assert!(std::time::Instant::now() < deadline, "config was not reloaded");
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		assert_eq!(config.load().0, ["a", "b"]);
+
+		// A broken dropin keeps the previous value.
+		std::fs::write(root.join("etc/foo.conf.d/c.conf"), "invalid").unwrap();
+		config.reload();
+		let err = err_recv.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+		assert!(matches!(err, crate::ReloadError::Merge(err) if err.path() == root.join("etc/foo.conf.d/c.conf")));
+		assert_eq!(config.load().0, ["a", "b"]);
+
+		drop(config);
+
+		assert!(matches!(spawn("foo.conf").0, Err(crate::ReloadError::Merge(_))));
+	}
+
+	#[cfg(all(feature = "signal", target_os = "linux"))]
+	#[test]
+	fn reload_signal() {
+		#[derive(Default)]
+		struct Config;
+
+		impl crate::MergeFrom for Config {
+			type Error = std::io::Error;
+
+			fn merge_from<R>(&mut self, _path: &Path, _reader: R) -> Result<(), Self::Error> {
+				Ok(())
+			}
+		}
+
+		let mut signal = crate::ReloadSignal::install().unwrap();
+		assert_eq!(crate::ReloadSignal::install().unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+
+		// SAFETY: The handler is installed, so the signal does not terminate the process.
+		assert_eq!(unsafe { libc::raise(libc::SIGHUP) }, 0);
+		assert_eq!(signal.next(), Some(()));
+
+		// The pipe is reused by the next handler, after discarding the signals that were not read from it.
+		// SAFETY: As above.
+		assert_eq!(unsafe { libc::raise(libc::SIGHUP) }, 0);
+		drop(signal);
+		let mut signal = crate::ReloadSignal::install().unwrap();
+		// SAFETY: As above.
+		assert_eq!(unsafe { libc::raise(libc::SIGHUP) }, 0);
+		assert_eq!(signal.next(), Some(()));
+
+		// Dropping a `ReloadingConfig` drops its trigger.
+		let search =
+			SearchDirectories::modern_system()
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
+			.unwrap()
+			.with_project("foo");
+		let config = crate::ReloadingConfig::<Config>::spawn(
+			move || search.clone().find_entries(".conf"),
+			signal,
+			std::time::Duration::from_millis(10),
+			|err| panic!("{err}"),
+		).unwrap();
+		drop(config);
+		drop(crate::ReloadSignal::install().unwrap());
+	}
+
+	#[test]
+	fn try_from_paths() {
+		let search_directories = SearchDirectories::try_from(vec![PathBuf::from("/usr/etc"), PathBuf::from("/etc")]).unwrap();
+		assert_eq!(search_directories.as_slice(), [Path::new("/usr/etc"), Path::new("/etc")]);
+
+		for (paths, index) in [
+			(&["/usr/etc", "etc"][..], 1),
+			(&["/etc/../usr/etc", "/etc"][..], 0),
+			(&["/usr/etc", "/run", "/etc/.."][..], 2),
+		] {
+			let err = SearchDirectories::try_from(paths.iter().map(PathBuf::from).collect::<Vec<_>>()).unwrap_err();
+			assert_eq!((err.index(), err.path()), (index, Path::new(paths[index])));
+		}
+
+		// `FromIterator` does not validate the paths.
+		let search_directories: SearchDirectories<'_> = [Path::new("etc").into()].into_iter().collect();
+		assert_eq!(search_directories.as_slice(), [Path::new("etc")]);
+	}
+
+	#[test]
+	fn describe() {
+		let search_directories = SearchDirectories::modern_system();
+
+		let plan = search_directories.clone().with_file_name("foo.service").with_fallback_file_names(["foo.unit"]).describe_with(Some(".conf"), &crate::FindOptions::new().type_dropins(true));
+		assert_eq!(plan.to_string(), "\
+Main file looked up in, later entries override earlier ones:
+  /usr/etc/foo.unit
+  /usr/etc/foo.service
+  /run/foo.unit
+  /run/foo.service
+  /etc/foo.unit
+  /etc/foo.service
+Dropins *.conf read from, later entries override earlier ones with the same file name:
+  /usr/etc/service.d
+  /usr/etc/foo.service.d
+  /run/service.d
+  /run/foo.service.d
+  /etc/service.d
+  /etc/foo.service.d
+");
+
+		let plan = search_directories.clone().with_project("foo").with_legacy_projects(["bar"]).describe(".conf");
+		assert_eq!(plan.main_files().count(), 0);
+		assert_eq!(plan.dropin_directories().collect::<Vec<_>>(), [
+			Path::new("/usr/etc/bar.d"),
+			Path::new("/usr/etc/foo.d"),
+			Path::new("/run/bar.d"),
+			Path::new("/run/foo.d"),
+			Path::new("/etc/bar.d"),
+			Path::new("/etc/foo.d"),
+		]);
+
+		let plan = search_directories.clone().with_file_name("foo.conf").describe(Some(".conf"));
+		assert_eq!(plan.candidate_paths().collect::<Vec<_>>(), [
+			Path::new("/usr/etc/foo.conf"),
+			Path::new("/run/foo.conf"),
+			Path::new("/etc/foo.conf"),
+			Path::new("/usr/etc/foo.conf.d"),
+			Path::new("/run/foo.conf.d"),
+			Path::new("/etc/foo.conf.d"),
+		]);
+
+		let plan = search_directories.with_project("foo").with_file_name("foo.conf").describe(None::<&str>);
+		assert_eq!(plan.to_string(), "\
+Main file looked up in, later entries override earlier ones:
+  /usr/etc/foo/foo.conf
+  /run/foo/foo.conf
+  /etc/foo/foo.conf
+");
+		assert_eq!(plan.candidate_paths().count(), 3);
+	}
+
+	#[cfg(feature = "serde_json")]
+	#[test]
+	fn to_json() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/to_json"));
+
+		let search_directories: SearchDirectories<'_> = [root.join("run"), root.join("etc")].into_iter().map(Into::into).collect();
+
+		let plan = search_directories.clone().with_file_name("foo.conf").describe(Some(".conf"));
+		assert_eq!(plan.to_json().unwrap(), format!(
+			r#"{{"main_files":["{root}/run/foo.conf","{root}/etc/foo.conf"],"dropin_directories":["{root}/run/foo.conf.d","{root}/etc/foo.conf.d"],"dropin_suffix":".conf"}}"#,
+			root = root.display(),
+		));
+
+		let skip_diagnostics = crate::SkipDiagnostics::new();
+		let files =
+			search_directories.clone()
+			.with_file_name("foo.conf")
+			.find_files_with(Some(".conf"), &crate::FindOptions::new().skip_diagnostics(skip_diagnostics.clone()))
+			.unwrap();
+		let outcome = files.outcome(&search_directories, Some(&skip_diagnostics));
+		assert_eq!(outcome.to_json().unwrap(), format!(
+			concat!(
+				r#"{{"main_file":null,"main_file_search_directory":null,"#,
+				r#""dropins":[["{root}/etc/foo.conf.d/a.conf","{root}/etc"]],"#,
+				r#""dropins_per_search_directory":[["{root}/run",0],["{root}/etc",1]],"#,
+				r#""skipped":[["{root}/run/foo.conf.d/a.conf","Shadowed"]]}}"#,
+			),
+			root = root.display(),
+		));
+	}
+
+	#[test]
+	fn to_dot() {
+		let root = temp_dir("to_dot");
+
+		for path in [
+			"usr/etc/foo.conf",
+			"etc/foo.conf.d/a.conf",
+			"etc/foo.conf.d/b\"c.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let entries =
+			SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_entries(Some(".conf"))
+			.unwrap();
+		assert_eq!(entries.to_dot(), format!("\
+digraph config {{
+	rankdir=LR;
+	node [shape=box];
+	subgraph cluster_0 {{
+		label=\"{root}/usr/etc\";
+		file_0 [label=\"foo.conf\"];
+	}}
+	subgraph cluster_1 {{
+		label=\"{root}/etc/foo.conf.d\";
+		file_1 [label=\"a.conf\"];
+		file_2 [label=\"b\\\"c.conf\"];
+	}}
+	file_0 -> file_1 [label=\"overridden by\"];
+	file_1 -> file_2 [label=\"overridden by\"];
+}}
+", root = root.display()));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn executables_only() {
+		use std::os::unix::fs::PermissionsExt as _;
+
+		let root = temp_dir("executables_only");
+
+		for (path, mode) in [
+			("usr/etc/foo.d/10-a", 0o755),
+			("usr/etc/foo.d/20-b", 0o755),
+			("usr/etc/foo.d/30-c", 0o644),
+			("etc/foo.d/20-b", 0o644),
+			("etc/foo.d/40-d", 0o700),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(&path, "").unwrap();
+			std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+		}
+		std::fs::create_dir_all(root.join("etc/foo.d/50-e")).unwrap();
+
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_project("foo")
+			.find_files_with("", &crate::FindOptions::new().executables_only(true))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect::<Vec<_>>();
+		assert_eq!(files, [
+			root.join("usr/etc/foo.d/10-a"),
+			root.join("usr/etc/foo.d/20-b"),
+			root.join("etc/foo.d/40-d"),
+		]);
+	}
+
+	#[test]
+	fn extensions() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/extensions"));
+
+		// ccc is not merged, and ddd.raw cannot be introspected.
+		let extensions = crate::Extensions::confext_in(root).unwrap();
+		assert_eq!(extensions.images().collect::<Vec<_>>(), ["aaa", "bbb"]);
+
+		let origins: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root).unwrap()
+			.with_file_name("foo.conf")
+			.find_entries(Some(".conf"))
+			.unwrap()
+			.map(|entry| (entry.path().file_name().unwrap().to_owned(), extensions.origin(entry.path()).unwrap().map(ToOwned::to_owned)))
+			.collect();
+		assert_eq!(origins, [
+			("foo.conf".into(), None),
+			("a.conf".into(), Some("aaa".into())),
+			("b.conf".into(), Some("bbb".into())),
+			("c.conf".into(), None),
+		]);
+
+		assert_eq!(extensions.origin(Path::new("/usr/etc/foo.conf")).unwrap(), None);
+	}
+
+	#[cfg(feature = "xdg")]
+	#[test]
+	fn xdg_directories() {
+		let mut base_directories = xdg::BaseDirectories::with_prefix("foo");
+		base_directories.config_home = Some("/home/user/.config".into());
+		base_directories.config_dirs = vec!["/etc/xdg/a".into(), "/etc/xdg/b".into()];
+
+		let search_directories = SearchDirectories::modern_system().with_xdg_directories(&base_directories);
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [
+			Path::new("/usr/etc"),
+			Path::new("/run"),
+			Path::new("/etc"),
+			Path::new("/etc/xdg/b"),
+			Path::new("/etc/xdg/a"),
+			Path::new("/home/user/.config"),
+		]);
+
+		let search_directories: SearchDirectories<'static> = (&base_directories).into();
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [
+			Path::new("/etc/xdg/b"),
+			Path::new("/etc/xdg/a"),
+			Path::new("/home/user/.config"),
+		]);
+	}
+
+	#[cfg(feature = "etcetera")]
+	#[test]
+	fn etcetera_directory() {
+		struct Strategy;
+
+		impl etcetera::BaseStrategy for Strategy {
+			fn home_dir(&self) -> &Path { Path::new("/home/user") }
+			fn config_dir(&self) -> PathBuf { "/home/user/Library/Preferences".into() }
+			fn data_dir(&self) -> PathBuf { "/home/user/Library/Application Support".into() }
+			fn cache_dir(&self) -> PathBuf { "/home/user/Library/Caches".into() }
+			fn state_dir(&self) -> Option<PathBuf> { None }
+			fn runtime_dir(&self) -> Option<PathBuf> { None }
+		}
+
+		let search_directories = SearchDirectories::modern_system().with_etcetera_directory(&Strategy);
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [
+			Path::new("/usr/etc"),
+			Path::new("/run"),
+			Path::new("/etc"),
+			Path::new("/home/user/Library/Preferences"),
+		]);
+	}
+
+	#[test]
+	fn chroot_from_env() {
+		fn paths(value: Option<&str>, root_env_var_value: Option<&str>) -> Vec<PathBuf> {
+			let search_directories = SearchDirectories::modern_system().chroot_from_env_values(value.map(Into::into), root_env_var_value.map(Into::into)).unwrap();
+			search_directories.iter().map(ToOwned::to_owned).collect()
+		}
+
+		assert_eq!(paths(None, None), [Path::new("/usr/etc"), Path::new("/run"), Path::new("/etc")]);
+		assert_eq!(paths(Some(""), Some("")), [Path::new("/usr/etc"), Path::new("/run"), Path::new("/etc")]);
+		assert_eq!(paths(None, Some("/generic")), [Path::new("/generic/usr/etc"), Path::new("/generic/run"), Path::new("/generic/etc")]);
+		assert_eq!(paths(Some(""), Some("/generic")), [Path::new("/generic/usr/etc"), Path::new("/generic/run"), Path::new("/generic/etc")]);
+		assert_eq!(paths(Some("/specific"), Some("/generic")), [Path::new("/specific/usr/etc"), Path::new("/specific/run"), Path::new("/specific/etc")]);
+
+		assert!(SearchDirectories::modern_system().chroot_from_env_values(Some("relative".into()), Some("/generic".into())).is_err());
+	}
+
+	#[cfg(not(feature = "dirs"))]
+	#[test]
+	fn try_with_user_directory() {
+		fn try_with_user_directory(xdg_config_home: Option<&str>, home: Option<&str>) -> (SearchDirectories<'static>, crate::UserDirectory) {
+			SearchDirectories::empty().try_with_user_config_dir(super::user_config_dir(xdg_config_home.map(Into::into), home.map(Into::into)))
+		}
+
+		let (search_directories, user_directory) = try_with_user_directory(None, Some("/home/user"));
+		assert_eq!(user_directory, crate::UserDirectory::Added("/home/user/.config".into()));
+		assert_eq!(search_directories.as_slice(), [Path::new("/home/user/.config")]);
+
+		let (search_directories, user_directory) = try_with_user_directory(Some(""), Some("/home/user"));
+		assert_eq!(user_directory, crate::UserDirectory::Added("/home/user/.config".into()));
+		assert_eq!(search_directories.as_slice(), [Path::new("/home/user/.config")]);
+
+		let (search_directories, user_directory) = try_with_user_directory(Some("/xdg/.config"), Some("/home/user"));
+		assert_eq!(user_directory, crate::UserDirectory::Added("/xdg/.config".into()));
+		assert_eq!(search_directories.as_slice(), [Path::new("/xdg/.config")]);
+
+		let (search_directories, user_directory) = try_with_user_directory(Some("relative/.config"), Some("/home/user"));
+		assert_eq!(user_directory, crate::UserDirectory::Invalid("relative/.config".into()));
+		assert!(search_directories.is_empty());
+
+		let (search_directories, user_directory) = try_with_user_directory(None, None);
+		assert_eq!(user_directory, crate::UserDirectory::NotFound);
+		assert!(search_directories.is_empty());
+	}
+
+	#[test]
+	fn into_owned() {
+		fn assert_send_sync<T>(_: &T) where T: Send + Sync + 'static {}
+
+		let search_directories = {
+			let path = PathBuf::from("/opt/foo/etc");
+			let mut search_directories = SearchDirectories::modern_system();
+			search_directories.push(Path::new(&path).into()).unwrap();
+			search_directories.into_owned()
+		};
+		assert_send_sync(&search_directories);
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [
+			Path::new("/usr/etc"),
+			Path::new("/run"),
+			Path::new("/etc"),
+			Path::new("/opt/foo/etc"),
+		]);
+
+		let len = std::thread::spawn(move || search_directories.len()).join().unwrap();
+		assert_eq!(len, 4);
+	}
+
+	#[test]
+	fn credentials_directory() {
+		let root = temp_dir("credentials_directory");
+
+		for path in [
+			"etc/foo.conf",
+			"etc/foo.conf.d/a.conf",
+			"credentials/foo.conf",
+			"image/etc/foo.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let search_directories = SearchDirectories::modern_system().with_credentials_directory_at(Some("/credentials".into()), None);
+		let filtered_search_directories = SearchDirectories::modern_system().with_credentials_directory_at(Some("/credentials".into()), Some([std::ffi::OsString::from("bar.conf")].into()));
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		let contained_search_directories = SearchDirectories::modern_system().chroot_contained(&root.join("image")).unwrap().with_credentials_directory_at(Some(root.join("credentials").into()), None);
+
+		let files: Vec<_> =
+			search_directories
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("credentials/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+
+		// The credentials directory is only searched for the file names that it is filtered to.
+		let files: Vec<_> =
+			filtered_search_directories
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+
+		// The credentials directory is not placed under a contained root.
+		#[cfg(all(feature = "rustix", target_os = "linux"))]
+		{
+			assert_eq!(contained_search_directories.iter().last(), Some(&*root.join("credentials")));
+			let files: Vec<_> =
+				contained_search_directories
+				.with_file_name("foo.conf")
+				.find_files(Some(".conf"))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			assert_eq!(files, [root.join("credentials/foo.conf")]);
+		}
+
+		assert_eq!(SearchDirectories::modern_system().with_credentials_directory_at(None, None).len(), 3);
+	}
+
+	#[test]
+	fn systemd_system_units() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/systemd_system_units"));
+
+		let files: Vec<_> =
+			SearchDirectories::systemd_system_units()
+			.chroot(root).unwrap()
+			.with_file_name("foo.service")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [
+			root.join("etc/systemd/system/foo.service"),
+			root.join("usr/lib/systemd/system/foo.service.d/a.conf"),
+			root.join("run/systemd/generator.late/foo.service.d/b.conf"),
+			root.join("run/systemd/generator.early/foo.service.d/c.conf"),
+			root.join("run/systemd/transient/foo.service.d/d.conf"),
+			root.join("etc/systemd/system.control/foo.service.d/e.conf"),
+		]);
+
+		// The order documented in `man systemd.unit`, highest precedence first.
+		let search_directories = SearchDirectories::systemd_system_units();
+		let mut paths: Vec<_> = search_directories.iter().collect();
+		paths.reverse();
+		assert_eq!(paths, [
+			Path::new("/etc/systemd/system.control"),
+			Path::new("/run/systemd/system.control"),
+			Path::new("/run/systemd/transient"),
+			Path::new("/run/systemd/generator.early"),
+			Path::new("/etc/systemd/system"),
+			Path::new("/etc/systemd/system.attached"),
+			Path::new("/run/systemd/system"),
+			Path::new("/run/systemd/system.attached"),
+			Path::new("/run/systemd/generator"),
+			Path::new("/usr/local/lib/systemd/system"),
+			Path::new("/usr/lib/systemd/system"),
+			Path::new("/run/systemd/generator.late"),
+		]);
+	}
+
+	#[test]
+	fn skip_diagnostics() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/skip_diagnostics"));
+
+		let diagnostics = crate::SkipDiagnostics::new();
+		let files: Vec<_> =
+			SearchDirectories::modern_system()
+			.chroot(root).unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().skip_hidden_files(true).skip_diagnostics(diagnostics.clone()))
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("etc/foo.d/a.conf")]);
+
+		let mut skipped = diagnostics.take();
+		skipped.sort_by(|(a, _), (b, _)| a.cmp(b));
+		assert_eq!(skipped, [
+			(root.join("etc/foo.d/.c.conf"), crate::SkipReason::Hidden),
+			(root.join("etc/foo.d/b.txt"), crate::SkipReason::SuffixMismatch),
+			(root.join("etc/foo.d/e.conf"), crate::SkipReason::NotRegularFile),
+			(root.join("usr/etc/foo.d/a.conf"), crate::SkipReason::Shadowed),
+		]);
+		assert!(diagnostics.take().is_empty());
+	}
+
+	#[cfg(feature = "miette")]
+	#[test]
+	fn miette_diagnostics() {
+		use miette::Diagnostic as _;
+
+		let root = temp_dir("miette_diagnostics");
+		for path in ["etc/foo.d/a.conf", "etc/foo.d/b.txt", "etc/foo.d/.c.conf"] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let diagnostics = crate::SkipDiagnostics::new();
+		_ =
+			SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().skip_hidden_files(true).skip_diagnostics(diagnostics.clone()))
+			.unwrap();
+		let mut warnings = diagnostics.take_warnings();
+		warnings.sort_by(|a, b| a.path().cmp(b.path()));
+		let warnings: Vec<_> =
+			warnings.iter()
+			.map(|warning| (warning.to_string(), warning.code().unwrap().to_string(), warning.severity(), warning.help().is_some()))
+			.collect();
+		assert_eq!(warnings, [
+			(
+				format!("{}/etc/foo.d/.c.conf was skipped because it is hidden", root.display()),
+				"uapi_config::skipped::hidden".to_owned(),
+				Some(miette::Severity::Advice),
+				false,
+			),
+			(
+				format!("{}/etc/foo.d/b.txt was skipped because its name does not end with the dropin suffix", root.display()),
+				"uapi_config::skipped::suffix_mismatch".to_owned(),
+				Some(miette::Severity::Warning),
+				true,
+			),
+		]);
+
+		#[cfg(all(feature = "derive", feature = "toml"))]
+		{
+			#[derive(Debug, Default, crate::UapiConfig)]
+			#[uapi_config(project = "foo", format = "toml")]
+			struct Config {
+				#[allow(dead_code)]
+				a: u32,
+			}
+
+			std::fs::write(root.join("etc/foo.d/a.conf"), "a = 1\nb = = 2\n").unwrap();
+			let err = Config::load_from(SearchDirectories::modern_system().chroot(&root).unwrap()).unwrap_err();
+			let err: &crate::MergeError<crate::FormatError> = err.get_ref().unwrap().downcast_ref().unwrap();
+			assert_eq!(err.code().unwrap().to_string(), "uapi_config::format::parse");
+			let labels: Vec<_> = err.labels().unwrap().collect();
+			assert_eq!(labels.len(), 1);
+			assert_eq!(labels[0].offset(), err.inner().span().unwrap().start);
+			assert_eq!(&err.inner().contents().unwrap()[labels[0].offset()..], "= 2\n");
+			assert!(err.source_code().is_some());
+		}
+	}
+
+	#[cfg(feature = "cache")]
+	#[test]
+	fn search_cache() {
+		let root = temp_dir("search_cache");
+		for path in [
+			"usr/etc/foo.conf",
+			"etc/foo.conf.d/a.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let cache_path = root.join("cache/search-cache.json");
+		let search = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf");
+		let plan = search.clone().describe(Some(".conf"));
+		let num_searches = std::cell::Cell::new(0);
+		let resolve = |cache: &mut crate::SearchCache| {
+			let snapshot = cache.resolve("foo", &plan, || {
+				num_searches.set(num_searches.get() + 1);
+				search.clone().find_entries(Some(".conf"))
+			}).unwrap();
+			snapshot.paths().map(ToOwned::to_owned).collect::<Vec<_>>()
+		};
+
+		let mut cache = crate::SearchCache::load(&cache_path).unwrap();
+		assert_eq!(resolve(&mut cache), [root.join("usr/etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+		assert_eq!(num_searches.get(), 1);
+		cache.save().unwrap();
+
+		// A new process reuses the result without searching.
+		let mut cache = crate::SearchCache::load(&cache_path).unwrap();
+		assert_eq!(resolve(&mut cache), [root.join("usr/etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+		assert_eq!(num_searches.get(), 1);
+
+		// New dropins and new main files invalidate the result.
+		std::fs::write(root.join("etc/foo.conf.d/b.conf"), "").unwrap();
+		assert_eq!(resolve(&mut cache), [root.join("usr/etc/foo.conf"), root.join("etc/foo.conf.d/a.conf"), root.join("etc/foo.conf.d/b.conf")]);
+		assert_eq!(num_searches.get(), 2);
+		std::fs::write(root.join("etc/foo.conf"), "").unwrap();
+		assert_eq!(resolve(&mut cache), [root.join("etc/foo.conf"), root.join("etc/foo.conf.d/a.conf"), root.join("etc/foo.conf.d/b.conf")]);
+		assert_eq!(num_searches.get(), 3);
+		assert_eq!(resolve(&mut cache), [root.join("etc/foo.conf"), root.join("etc/foo.conf.d/a.conf"), root.join("etc/foo.conf.d/b.conf")]);
+		assert_eq!(num_searches.get(), 3);
+
+		// So does a corrupt cache file.
+		cache.save().unwrap();
+		std::fs::write(&cache_path, "{").unwrap();
+		let mut cache = crate::SearchCache::load(&cache_path).unwrap();
+		_ = resolve(&mut cache);
+		assert_eq!(num_searches.get(), 4);
+	}
+
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[test]
+	fn filesystem_condition() {
+		let root = temp_dir("filesystem_condition");
+		for path in [
+			"usr/etc/foo.d/a.conf",
+			"etc/foo.d/b.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let search_directories = SearchDirectories::modern_system().chroot(&root).unwrap();
+		let etc = root.join("etc");
+
+		let diagnostics = crate::SkipDiagnostics::new();
+		let condition = {
+			let etc = etc.clone();
+			crate::FilesystemCondition::new(move |path, filesystem_type| {
+				assert!(!filesystem_type.is_network());
+				path != etc
+			})
+		};
+		let options =
+			crate::FindOptions::new()
+			.filesystem_condition(condition)
+			.search_directory_timeout(std::time::Duration::from_secs(10))
+			.skip_diagnostics(diagnostics.clone());
+		let files: Vec<_> =
+			search_directories.clone()
+			.with_project("foo")
+			.find_files_with(".conf", &options)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("usr/etc/foo.d/a.conf")]);
+		assert_eq!(diagnostics.take(), [(etc, crate::SkipReason::Filesystem)]);
+
+		let options = crate::FindOptions::new().filesystem_condition(crate::FilesystemCondition::local_only());
+		let files: Vec<_> =
+			search_directories
+			.with_project("foo")
+			.find_files_with(".conf", &options)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("usr/etc/foo.d/a.conf"), root.join("etc/foo.d/b.conf")]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn observer() {
+		#[derive(Default)]
+		struct Observer(std::sync::Mutex<Vec<String>>);
+
+		impl crate::SearchObserver for Observer {
+			fn search_finished(&self, metrics: &crate::SearchMetrics) {
+				self.0.lock().unwrap().push(format!("search_finished {} {}", metrics.directories_scanned(), metrics.files_found()));
+			}
+
+			fn files_opened(&self, num_files: usize, _duration: std::time::Duration) {
+				self.0.lock().unwrap().push(format!("files_opened {num_files}"));
+			}
+
+			fn file_read(&self, path: &Path, num_bytes: u64) {
+				self.0.lock().unwrap().push(format!("file_read {} {num_bytes}", path.file_name().unwrap().display()));
+			}
+		}
+
+		let root = temp_dir("observer");
+
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a"),
+			("usr/etc/foo.conf.d/a.conf", "bc"),
+			("etc/foo.conf.d/b.conf", "def"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let observer = std::sync::Arc::new(Observer::default());
+		let options = crate::FindOptions::new().observer(observer.clone());
+
+		let search_directories = SearchDirectories::modern_system().chroot(&root).unwrap();
+		_ = search_directories.clone().with_file_name("foo.conf").find_files_with(Some(".conf"), &options).unwrap().read_all().unwrap();
+		assert_eq!(*observer.0.lock().unwrap(), [
+			"search_finished 2 3",
+			"files_opened 3",
+			"file_read foo.conf 1",
+			"file_read a.conf 2",
+			"file_read b.conf 3",
+		]);
+
+		observer.0.lock().unwrap().clear();
+		_ = search_directories.with_file_name("foo.conf").find_entries_with(Some(".conf"), &options).unwrap();
+		assert_eq!(*observer.0.lock().unwrap(), ["search_finished 2 3"]);
+	}
+
+	#[test]
+	fn load_merged() {
+		#[derive(Debug, Default, PartialEq)]
+		struct Config(Vec<(PathBuf, String)>);
+
+		impl crate::MergeFrom for Config {
+			type Error = std::io::Error;
+
+			fn merge_from<R>(&mut self, path: &Path, mut reader: R) -> Result<(), Self::Error> where R: std::io::Read {
+				let mut contents = String::new();
+				reader.read_to_string(&mut contents)?;
+				if contents == "invalid" {
+					return Err(std::io::ErrorKind::InvalidData.into());
+				}
+				self.0.push((path.to_owned(), contents));
+				Ok(())
+			}
+		}
+
+		let root = temp_dir("load_merged");
+
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a"),
+			("etc/foo.conf.d/a.conf", "b"),
+			("etc/bar.conf.d/a.conf", "c"),
+			("etc/bar.conf.d/b.conf", "invalid"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let find = |file_name| SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name(file_name).find_files(Some(".conf")).unwrap();
+
+		let config: Config = find("foo.conf").load_merged().unwrap();
+		assert_eq!(config, Config(vec![
+			(root.join("usr/etc/foo.conf"), "a".to_owned()),
+			(root.join("etc/foo.conf.d/a.conf"), "b".to_owned()),
+		]));
+
+		let mut config = Config::default();
+		let err = find("bar.conf").load_merged_into(&mut config).unwrap_err();
+		assert_eq!(err.path(), root.join("etc/bar.conf.d/b.conf"));
+		assert_eq!(err.inner().kind(), std::io::ErrorKind::InvalidData);
+		assert_eq!(config, Config(vec![(root.join("etc/bar.conf.d/a.conf"), "c".to_owned())]));
+
+		let mut config = Config::default();
+		let errors = find("bar.conf").for_each_file(|path, file| crate::MergeFrom::merge_from(&mut config, path, file)).unwrap_err();
+		assert_eq!(errors.iter().map(crate::MergeError::path).collect::<Vec<_>>(), [root.join("etc/bar.conf.d/b.conf")]);
+		find("foo.conf").for_each_file(|path, file| crate::MergeFrom::merge_from(&mut config, path, file)).unwrap();
+		assert_eq!(config.0.len(), 3);
+
+		// Later files are still processed after a file fails.
+		let mut paths = vec![];
+		let errors = find("foo.conf").for_each_file(|path, _| { paths.push(path.to_owned()); Err(()) }).unwrap_err();
+		assert_eq!(errors.len(), 2);
+		assert_eq!(paths, [root.join("usr/etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+
+		// Built-in defaults are merged before the files.
+		let files = find("foo.conf").with_defaults("<defaults>", b"z".as_slice());
+		assert_eq!(files.len(), 3);
+		let config: Config = files.load_merged().unwrap();
+		assert_eq!(config, Config(vec![
+			(PathBuf::from("<defaults>"), "z".to_owned()),
+			(root.join("usr/etc/foo.conf"), "a".to_owned()),
+			(root.join("etc/foo.conf.d/a.conf"), "b".to_owned()),
+		]));
+		let paths: Vec<_> = find("foo.conf").with_defaults("<defaults>", b"z".to_vec()).rev().map(|(path, _)| path).collect();
+		assert_eq!(paths, [root.join("etc/foo.conf.d/a.conf"), root.join("usr/etc/foo.conf"), PathBuf::from("<defaults>")]);
+		let err = find("foo.conf").with_defaults("<defaults>", b"invalid".as_slice()).load_merged::<Config>().unwrap_err();
+		assert_eq!(err.path(), Path::new("<defaults>"));
+	}
+
+	#[test]
+	fn snapshot() {
+		let root = temp_dir("snapshot");
+
+		for path in [
+			"usr/etc/foo.conf",
+			"etc/foo.conf.d/a.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "a").unwrap();
+		}
+
+		let snapshot = || SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf").find_entries(Some(".conf")).unwrap().snapshot();
+
+		let original = snapshot();
+		assert_eq!(original.paths().collect::<Vec<_>>(), [root.join("usr/etc/foo.conf"), root.join("etc/foo.conf.d/a.conf")]);
+		assert!(!original.is_stale().unwrap());
+		assert_eq!(original, snapshot());
+
+		// A new dropin is only detected by a new search.
+		std::fs::write(root.join("etc/foo.conf.d/b.conf"), "b").unwrap();
+		assert!(!original.is_stale().unwrap());
+		assert_ne!(original, snapshot());
+
+		std::fs::write(root.join("etc/foo.conf.d/a.conf"), "aa").unwrap();
+		assert!(original.is_stale().unwrap());
+
+		std::fs::remove_file(root.join("usr/etc/foo.conf")).unwrap();
+		assert!(snapshot().is_stale().is_ok_and(|is_stale| !is_stale));
+		assert!(original.is_stale().unwrap());
+
+		#[cfg(feature = "sha2")]
+		{
+			let entries = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf").find_entries(Some(".conf")).unwrap();
+			let snapshot = entries.snapshot_with_digests().unwrap();
+			assert!(snapshot.files().all(|file| file.digest().is_some()));
+			assert!(!snapshot.is_stale().unwrap());
+
+			// Same size, and restore the modification time, so that only the digest changes.
+			let modified = std::fs::metadata(root.join("etc/foo.conf.d/b.conf")).unwrap().modified().unwrap();
+			let file = std::fs::OpenOptions::new().write(true).open(root.join("etc/foo.conf.d/b.conf")).unwrap();
+			std::io::Write::write_all(&mut &file, b"c").unwrap();
+			file.set_modified(modified).unwrap();
+			drop(file);
+			assert!(snapshot.is_stale().unwrap());
+		}
+	}
+
+	#[test]
+	fn snapshot_diff() {
+		let root = temp_dir("snapshot_diff");
+
+		for (path, contents) in [
+			("old/usr/etc/foo.conf", "a"),
+			("old/etc/foo.conf.d/10-a.conf", "a"),
+			("old/etc/foo.conf.d/20-b.conf", "b"),
+			("old/etc/foo.conf.d/30-c.conf", "c"),
+			("new/etc/foo.conf", "a"),
+			("new/etc/foo.conf.d/10-a.conf", "aa"),
+			("new/etc/foo.conf.d/20-b.conf", "b"),
+			("new/etc/foo.conf.d/30-c.conf", "c"),
+			("new/run/foo.conf.d/30-c.conf", "c"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+		let modified = std::fs::metadata(root.join("old/etc/foo.conf.d/20-b.conf")).unwrap().modified().unwrap();
+		std::fs::File::options().write(true).open(root.join("new/etc/foo.conf.d/20-b.conf")).unwrap().set_modified(modified).unwrap();
+
+		let snapshot = |root: &Path| {
+			let search_directories: SearchDirectories<'_> = ["/usr/etc", "/etc", "/run"].into_iter().map(|path| Path::new(path).into()).collect();
+			search_directories.chroot(root).unwrap().with_file_name("foo.conf").find_entries(Some(".conf")).unwrap().snapshot().relative_to(root)
+		};
+		let old = snapshot(&root.join("old"));
+		let new = snapshot(&root.join("new"));
+
+		let diff = old.diff(&new);
+		assert_eq!(diff.added(), [Path::new("/etc/foo.conf"), Path::new("/run/foo.conf.d/30-c.conf")]);
+		assert_eq!(diff.removed(), [Path::new("/usr/etc/foo.conf"), Path::new("/etc/foo.conf.d/30-c.conf")]);
+		assert_eq!(diff.changed(), [Path::new("/etc/foo.conf.d/10-a.conf")]);
+		assert!(diff.reordered().is_empty());
+		assert_eq!(diff.to_string(), "\
++ /etc/foo.conf
++ /run/foo.conf.d/30-c.conf
+- /usr/etc/foo.conf
+- /etc/foo.conf.d/30-c.conf
+~ /etc/foo.conf.d/10-a.conf
+");
+
+		assert!(new.diff(&new).is_empty());
+	}
+
+	#[test]
+	fn with_metadata() {
+		let root = temp_dir("with_metadata");
+
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a = 1\n"),
+			("etc/foo.conf.d/a.conf", "a = 22\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let search_directories =
+			SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf");
+
+		let files: Vec<_> =
+			search_directories.clone()
+			.find_files(Some(".conf"))
+			.unwrap()
+			.with_metadata()
+			.map(|(path, _, metadata)| (path, metadata.len()))
+			.collect();
+		assert_eq!(files, [(root.join("usr/etc/foo.conf"), 6), (root.join("etc/foo.conf.d/a.conf"), 7)]);
+
+		let mut entries = search_directories.find_entries(Some(".conf")).unwrap();
+		let entry = entries.next_back().unwrap();
+		std::fs::write(root.join("etc/foo.conf.d/a.conf"), "a = 333\n").unwrap();
+		assert_eq!(entry.metadata().size(), 7);
+		let (_, metadata) = entry.open_with_metadata().unwrap();
+		assert_eq!(metadata.len(), 8);
+	}
+
+	#[test]
+	fn split_main() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/split_main"));
+
+		let find = |file_name| SearchDirectories::modern_system().chroot(root).unwrap().with_file_name(file_name).find_files(Some(".conf")).unwrap();
+
+		let (main_file, dropins) = find("foo.conf").split_main();
+		assert_eq!(main_file.map(|(path, _)| path), Some(root.join("usr/etc/foo.conf")));
+		assert_eq!(dropins.into_paths().collect::<Vec<_>>(), [root.join("etc/foo.conf.d/a.conf")]);
+
+		let (main_file, dropins) = find("bar.conf").split_main();
+		assert!(main_file.is_none());
+		assert_eq!(dropins.into_paths().collect::<Vec<_>>(), [root.join("etc/bar.conf.d/b.conf")]);
+	}
+
+	#[test]
+	fn outcome() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/outcome"));
+
+		let search_directories = SearchDirectories::modern_system().chroot(root).unwrap();
+		let skip_diagnostics = crate::SkipDiagnostics::new();
+		let files =
+			search_directories.clone()
+			.with_file_name("foo.conf")
+			.find_files_with(Some(".conf"), &crate::FindOptions::new().skip_diagnostics(skip_diagnostics.clone()))
+			.unwrap();
+
+		let outcome = files.outcome(&search_directories, Some(&skip_diagnostics));
+		assert_eq!(outcome.main_file(), Some(&*root.join("usr/etc/foo.conf")));
+		assert_eq!(outcome.dropins_per_search_directory().collect::<Vec<_>>(), [
+			(&*root.join("usr/etc"), 1),
+			(&*root.join("run"), 0),
+			(&*root.join("etc"), 2),
+		]);
+		assert_eq!(outcome.num_dropins(), 3);
+		assert_eq!(outcome.skipped(), Some(1));
+		assert_eq!(outcome.main_file_search_directory(), Some(&*root.join("usr/etc")));
+		assert_eq!(outcome.dropins().collect::<Vec<_>>(), [
+			(&*root.join("usr/etc/foo.conf.d/a.conf"), Some(&*root.join("usr/etc"))),
+			(&*root.join("etc/foo.conf.d/b.conf"), Some(&*root.join("etc"))),
+			(&*root.join("etc/foo.conf.d/c.conf"), Some(&*root.join("etc"))),
+		]);
+		assert_eq!(outcome.skipped_files(), Some(&[(root.join("etc/foo.conf.d/d.txt"), crate::SkipReason::SuffixMismatch)][..]));
+
+		assert_eq!(files.outcome(&search_directories, None).skipped(), None);
+	}
+
+	#[test]
+	fn grouped_by_root() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/grouped_by_root"));
+
+		let mut search_directories = SearchDirectories::modern_system();
+		search_directories.push(Path::new("/etc/foo.conf.d/nested").into()).unwrap();
+		let search_directories = search_directories.chroot(root).unwrap();
+
+		let groups: Vec<_> =
+			search_directories.clone()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.grouped_by_root(&search_directories)
+			.into_iter()
+			.map(|(search_directory, files)| (search_directory, files.into_paths().collect::<Vec<_>>()))
+			.collect();
+		assert_eq!(groups, [
+			(Some(root.join("usr/etc")), vec![root.join("usr/etc/foo.conf.d/a.conf")]),
+			(Some(root.join("etc")), vec![root.join("etc/foo.conf.d/b.conf"), root.join("etc/foo.conf.d/c.conf")]),
+			(Some(root.join("etc/foo.conf.d/nested")), vec![root.join("etc/foo.conf.d/nested/foo.conf")]),
+		]);
+
+		let groups = SearchDirectories::modern_system().chroot(root).unwrap().with_file_name("foo.conf").find_files(Some(".conf")).unwrap().grouped_by_root(&SearchDirectories::classic_system());
+		assert_eq!(groups.len(), 1);
+		assert_eq!(groups[0].0, None);
+		assert_eq!(groups[0].1.len(), 4);
+	}
+
+	#[test]
+	fn write_cat_config() {
+		let root = temp_dir("write_cat_config");
+
+		for (path, contents) in [
+			("usr/etc/foo.conf", "a = 1\n"),
+			("etc/foo.conf.d/a.conf", "a = 2"),
+			("etc/foo.conf.d/b.conf", ""),
+			("run/foo.conf.d/c.conf", "a = 3\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let mut output = vec![];
+		SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.write_cat_config(&mut output)
+			.unwrap();
+		assert_eq!(String::from_utf8(output).unwrap(), format!("\
+# {root}/usr/etc/foo.conf
+a = 1
+
+# {root}/etc/foo.conf.d/a.conf
+a = 2
+
+# {root}/etc/foo.conf.d/b.conf
+
+# {root}/run/foo.conf.d/c.conf
+a = 3
+", root = root.display()));
+	}
+
+	#[test]
+	fn embedded_default() {
+		const DEFAULTS: crate::EmbeddedDefault = crate::embedded_default!("/usr/share/foo/foo.conf", "../test-files/embedded_default/foo.conf");
+		assert_eq!(DEFAULTS.path(), "/usr/share/foo/foo.conf");
+		assert_eq!(DEFAULTS.contents(), b"a = 0\n");
+		assert_eq!(crate::embedded_default!("../test-files/embedded_default/foo.conf").path(), "../test-files/embedded_default/foo.conf");
+
+		let root = temp_dir("embedded_default");
+		std::fs::create_dir_all(root.join("etc")).unwrap();
+		std::fs::write(root.join("etc/foo.conf"), "a = 1\n").unwrap();
+
+		let mut output = vec![];
+		SearchDirectories::modern_system()
+			.chroot(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf"))
+			.unwrap()
+			.with_embedded_default(DEFAULTS)
+			.write_cat_config(&mut output)
+			.unwrap();
+		assert_eq!(String::from_utf8(output).unwrap(), format!("\
+# /usr/share/foo/foo.conf (built-in default)
+a = 0
+
+# {root}/etc/foo.conf
+a = 1
+", root = root.display()));
+	}
+
+	#[cfg(feature = "zbus")]
+	#[test]
+	fn config_service() {
+		let root = temp_dir("config_service");
+
+		for (path, contents) in [
+			("usr/etc/foo.d/a.conf", "a = 1\n"),
+			("etc/foo.d/b.conf", "a = 2\n"),
+			("etc/foo.d/c.txt", "a = 3\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+
+		let service = crate::ConfigService::new(SearchDirectories::modern_system().chroot(&root).unwrap());
+
+		assert_eq!(service.list_files("foo", ".conf").unwrap(), [
+			format!("{}/usr/etc/foo.d/a.conf", root.display()),
+			format!("{}/etc/foo.d/b.conf", root.display()),
+		]);
+		assert_eq!(service.cat_config("foo", ".conf").unwrap(), format!("\
+# {root}/usr/etc/foo.d/a.conf
+a = 1
+
+# {root}/etc/foo.d/b.conf
+a = 2
+", root = root.display()));
+		assert!(service.list_files("bar", ".conf").unwrap().is_empty());
+		assert!(matches!(service.list_files("", ".conf"), Err(zbus::fdo::Error::InvalidArgs(_))));
+	}
+
+	#[test]
+	fn read_all_with_includes() {
+		let root = temp_dir("read_all_with_includes");
+
+		for (path, contents) in [
+			("etc/foo.conf", "a\n.include common.conf\nb\n.include-dir  foo.include.d \n"),
+			("etc/common.conf", "c\n.include nested/d.conf\n"),
+			("etc/nested/d.conf", "d\n"),
+			("etc/foo.include.d/2.conf", "f\n"),
+			("etc/foo.include.d/1.conf", ".include ../nested/d.conf\n"),
+			("etc/foo.include.d/.hidden", "g\n"),
+			("etc/foo.conf.d/a.conf", "e\n.included\n"),
+			("etc/cycle.conf", "x\n.include cycle2.conf\n"),
+			("etc/cycle2.conf", ".include /etc/cycle.conf\n"),
+			("etc/absolute.conf", ".include /../usr/lib/common.conf\n.include-dir /etc/foo.include.d\n"),
+			("usr/lib/common.conf", "h\n"),
+			("etc/symlink.conf", ".include common.conf.link\n"),
+			("etc/large.conf", ".include foo.conf\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+		#[cfg(unix)]
+		std::os::unix::fs::symlink("common.conf", root.join("etc/common.conf.link")).unwrap();
 
-		let main_file = find_main_file(file_name, self.inner.iter().map(Deref::deref))?;
+		let find = |file_name| SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name(file_name).find_files(Some(".conf")).unwrap();
 
-		let dropins =
-			if let Some(dropin_suffix) = dropin_suffix {
-				find_dropins(dropin_suffix.as_ref(), self.inner.into_iter().map(|path| {
-					let mut path_bytes = path.into_owned().into_os_string().into_vec();
-					path_bytes.push(b'/');
-					path_bytes.extend_from_slice(file_name.as_bytes());
-					path_bytes.extend_from_slice(b".d");
-					PathBuf::from(OsString::from_vec(path_bytes))
-				}))?
-			}
-			else {
-				Default::default()
-			};
+		let files = find("foo.conf").read_all_with_includes(&Default::default()).unwrap();
+		assert_eq!(files, [
+			(root.join("etc/foo.conf"), b"a\n".to_vec()),
+			(root.join("etc/common.conf"), b"c\n".to_vec()),
+			(root.join("etc/nested/d.conf"), b"d\n".to_vec()),
+			(root.join("etc/foo.conf"), b"b\n".to_vec()),
+			(root.join("etc/foo.include.d/../nested/d.conf"), b"d\n".to_vec()),
+			(root.join("etc/foo.include.d/2.conf"), b"f\n".to_vec()),
+			(root.join("etc/foo.conf.d/a.conf"), b"e\n.included\n".to_vec()),
+		]);
 
-		Ok(Files {
-			inner: main_file.into_iter().chain(dropins),
-		})
-	}
-}
+		let err = find("foo.conf").read_all_with_includes(&crate::IncludeOptions::new().max_depth(2)).unwrap_err();
+		assert_eq!(err.path(), root.join("etc/common.conf"));
+		assert!(matches!(err.io_error().get_ref().unwrap().downcast_ref(), Some(crate::IncludeError::TooDeep { max_depth: 2, .. })));
 
-/// A list of search directories that the config files will be searched under, scoped to a particular project and config file name.
-///
-/// Created using [`SearchDirectoriesForProject::with_file_name`] or [`SearchDirectoriesForFileName::with_project`].
-#[derive(Clone, Debug)]
-pub struct SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName> {
-	inner: Vec<Cow<'a, Path>>,
-	project: TProject,
-	file_name: TFileName,
-}
+		let err = find("cycle.conf").read_all_with_includes(&Default::default()).unwrap_err();
+		assert_eq!(err.path(), root.join("etc/cycle2.conf"));
+		assert!(matches!(err.io_error().get_ref().unwrap().downcast_ref(), Some(crate::IncludeError::Cycle { .. })));
 
-impl<'a, TProject, TFileName> SearchDirectoriesForProjectAndFileName<'a, TProject, TFileName> {
-	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`File`]`)`s for all the files found in the specified search directories.
-	/// The project name is appended to each search directory, then those directories are searched for files named `file_name`.
-	///
-	/// If `dropin_suffix` is provided, then directories named `format!("{file_name}.d")` under the search directories are treated as dropin directories.
-	/// Only dropin files whose name ends with `dropin_suffix` will be considered. Note that if you intend to use a file extension as a suffix,
-	/// then `dropin_suffix` must include the `.`, such as `".conf"`.
-	///
-	/// You will likely want to parse each file returned by this function according to whatever format they're supposed to contain
-	/// and merge them into a unified config object, with settings from later files overriding settings from earlier files.
-	/// This function does not guarantee that the files are well-formed, only that they exist and could be opened for reading.
-	///
-	/// # Errors
-	///
-	/// Any errors from reading non-existing directories and non-existing files are ignored.
-	/// Apart from that, any I/O errors from walking the directories and from opening the files found within are propagated.
-	pub fn find_files<TDropinSuffix>(
-		self,
-		dropin_suffix: Option<TDropinSuffix>,
-	) -> io::Result<Files>
-	where
-		TProject: AsRef<OsStr>,
-		TFileName: AsRef<OsStr>,
-		TDropinSuffix: AsRef<OsStr>,
-	{
-		let project = self.project.as_ref();
+		// Absolute paths are resolved under the root that the search directories were chrooted to.
+		let files = find("absolute.conf").read_all_with_includes(&Default::default()).unwrap();
+		assert_eq!(files, [
+			(root.join("usr/lib/common.conf"), b"h\n".to_vec()),
+			(root.join("etc/foo.include.d/../nested/d.conf"), b"d\n".to_vec()),
+			(root.join("etc/foo.include.d/2.conf"), b"f\n".to_vec()),
+		]);
 
-		let file_name = self.file_name.as_ref();
+		let find_with = |file_name, options: &crate::FindOptions| SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name(file_name).find_files_with(Some(".conf"), options).unwrap();
 
-		let main_file = find_main_file(file_name, self.inner.iter().map(|path| path.join(project)))?;
+		#[cfg(unix)]
+		{
+			let files = find_with("symlink.conf", &crate::FindOptions::new()).read_all_with_includes(&Default::default()).unwrap();
+			assert_eq!(files[0].0, root.join("etc/common.conf.link"));
 
-		let dropins =
-			if let Some(dropin_suffix) = dropin_suffix {
-				find_dropins(dropin_suffix.as_ref(), self.inner.into_iter().map(|path| {
-					let mut path_bytes = path.into_owned().into_os_string().into_vec();
-					path_bytes.push(b'/');
-					path_bytes.extend_from_slice(project.as_bytes());
-					path_bytes.push(b'/');
-					path_bytes.extend_from_slice(file_name.as_bytes());
-					path_bytes.extend_from_slice(b".d");
-					PathBuf::from(OsString::from_vec(path_bytes))
-				}))?
-			}
-			else {
-				Default::default()
-			};
+			let err = find_with("symlink.conf", &crate::FindOptions::new().symlink_policy(crate::SymlinkPolicy::Refuse)).read_all_with_includes(&Default::default()).unwrap_err();
+			assert_eq!(err.path(), root.join("etc/symlink.conf"));
+			assert!(matches!(err.io_error().get_ref().unwrap().downcast_ref(), Some(crate::IncludeError::Symlink { .. })));
 
-		Ok(Files {
-			inner: main_file.into_iter().chain(dropins),
-		})
+			let files = find_with("symlink.conf", &crate::FindOptions::new().symlink_policy(crate::SymlinkPolicy::WithinSearchDirectories)).read_all_with_includes(&Default::default()).unwrap();
+			assert_eq!(files[0].0, root.join("etc/common.conf.link"));
+		}
+
+		let err = find_with("large.conf", &crate::FindOptions::new().max_file_size(20)).read_all_with_includes(&Default::default()).unwrap_err();
+		assert_eq!(err.path(), root.join("etc/foo.conf"));
+		assert!(err.io_error().get_ref().unwrap().downcast_ref::<crate::FileTooLargeError>().is_some());
 	}
-}
 
-fn validate_path(path: &Path) -> Result<(), InvalidPathError> {
-	let mut components = path.components();
+	#[cfg(all(feature = "rustix", target_os = "linux"))]
+	#[test]
+	fn read_all_with_includes_contained() {
+		let root = temp_dir("read_all_with_includes_contained");
 
-	if components.next() != Some(Component::RootDir) {
-		return Err(InvalidPathError);
-	}
+		for (path, contents) in [
+			("etc/foo.conf", ".include bar.conf\n.include ../../../usr/lib/bar.conf\n"),
+			("usr/lib/bar.conf", "b\n"),
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+		// An absolute symlink is resolved within the root.
+		std::os::unix::fs::symlink("/usr/lib/bar.conf", root.join("etc/bar.conf")).unwrap();
 
-	if components.any(|component| matches!(component, Component::ParentDir)) {
-		return Err(InvalidPathError);
+		let files =
+			SearchDirectories::modern_system()
+			.chroot_contained(&root).unwrap()
+			.with_file_name("foo.conf")
+			.find_files(Some(".conf")).unwrap()
+			.read_all_with_includes(&Default::default()).unwrap();
+		assert_eq!(files, [
+			(root.join("etc/bar.conf"), b"b\n".to_vec()),
+			(root.join("etc/../../../usr/lib/bar.conf"), b"b\n".to_vec()),
+		]);
 	}
 
-	Ok(())
-}
-
-fn find_main_file<I>(
-	file_name: &OsStr,
-	search_directories: I,
-) -> io::Result<Option<(PathBuf, File)>>
-where
-	I: DoubleEndedIterator,
-	I::Item: Deref<Target = Path>,
-{
-	for search_directory in search_directories.rev() {
-		let path = search_directory.join(file_name);
-		let file = match File::open(&path) {
-			Ok(file) => file,
-			Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
-			Err(err) => return Err(err),
-		};
+	#[cfg(unix)]
+	#[test]
+	fn container_mounts() {
+		let root = temp_dir("container_mounts");
 
-		if !file.metadata()?.file_type().is_file() {
-			continue;
+		for path in [
+			"usr/etc/foo.conf",
+			"etc/foo.conf",
+			"etc/bar.conf",
+			"run/secrets/bar.conf",
+			"config/..2024_01_01/foo.conf",
+			"config/..2024_01_01/bar.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
 		}
+		std::os::unix::fs::symlink("..2024_01_01", root.join("config/..data")).unwrap();
+		std::os::unix::fs::symlink("..data/foo.conf", root.join("config/foo.conf")).unwrap();
+		std::os::unix::fs::symlink("..data/bar.conf", root.join("config/bar.conf")).unwrap();
 
-		return Ok(Some((path, file)));
+		let search_directories =
+			SearchDirectories::modern_system()
+			.with_mounted_configmap(1, Path::new("/config").into()).unwrap()
+			.with_run_secrets(usize::MAX)
+			.chroot(&root).unwrap();
+		assert_eq!(search_directories.iter().collect::<Vec<_>>(), [
+			root.join("usr/etc"),
+			root.join("config"),
+			root.join("run"),
+			root.join("etc"),
+			root.join("run/secrets"),
+		]);
+
+		let find = |file_name| search_directories.clone().with_file_name(file_name).find_files(None::<&str>).unwrap().map(|(path, _)| path).collect::<Vec<_>>();
+		assert_eq!(find("foo.conf"), [root.join("etc/foo.conf")]);
+		assert_eq!(find("bar.conf"), [root.join("run/secrets/bar.conf")]);
 	}
 
-	Ok(None)
-}
+	#[test]
+	fn system_for_sandbox() {
+		fn paths(sandbox: Option<&crate::Sandbox>) -> Vec<PathBuf> {
+			SearchDirectories::system_for_sandbox(sandbox).iter().map(ToOwned::to_owned).collect()
+		}
 
-fn find_dropins<I>(
-	suffix: &OsStr,
-	search_directories: I,
-) -> io::Result<std::collections::btree_map::IntoValues<Vec<u8>, (PathBuf, File)>>
-where
-	I: DoubleEndedIterator,
-	I::Item: Deref<Target = Path>,
-{
-	let mut result: BTreeMap<_, _> = Default::default();
+		assert_eq!(paths(None), SearchDirectories::modern_system().iter().collect::<Vec<_>>());
+		assert_eq!(paths(Some(&crate::Sandbox::Container)), SearchDirectories::modern_system().iter().collect::<Vec<_>>());
 
-	for search_directory in search_directories.rev() {
-		let entries = match fs::read_dir(&*search_directory) {
-			Ok(entries) => entries,
-			Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
-			Err(err) => return Err(err),
-		};
-		for entry in entries {
-			let entry = entry?;
+		assert_eq!(paths(Some(&crate::Sandbox::Flatpak)), [
+			Path::new("/usr/etc"),
+			Path::new("/etc"),
+			Path::new("/app/etc"),
+			Path::new("/run/host/usr/etc"),
+			Path::new("/run"),
+			Path::new("/run/host/etc"),
+		]);
 
-			let file_name = entry.file_name();
-			if !file_name.as_bytes().ends_with(suffix.as_bytes()) {
-				continue;
-			}
+		assert_eq!(paths(Some(&crate::Sandbox::Snap { snap: "/snap/foo/1".into(), snap_data: Some("/var/snap/foo/1".into()) })), [
+			Path::new("/snap/foo/1/etc"),
+			Path::new("/usr/etc"),
+			Path::new("/run"),
+			Path::new("/etc"),
+			Path::new("/var/snap/foo/1"),
+		]);
 
-			if result.contains_key(file_name.as_bytes()) {
-				continue;
-			}
+		assert_eq!(paths(Some(&crate::Sandbox::Snap { snap: "snap".into(), snap_data: None })), [
+			Path::new("/usr/etc"),
+			Path::new("/run"),
+			Path::new("/etc"),
+		]);
+	}
 
-			let path = search_directory.join(&file_name);
-			let file = match File::open(&path) {
-				Ok(file) => file,
-				Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
-				Err(err) => return Err(err),
-			};
+	#[test]
+	fn conditional_dropin_subdirectories() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/conditional_dropin_subdirectories"));
 
-			if !file.metadata()?.file_type().is_file() {
-				continue;
-			}
+		let options = crate::FindOptions::new().conditional_dropin_subdirectories(crate::SubdirectoryCondition::new(|name| name == "x86-64"));
+		let search_directories = SearchDirectories::modern_system().chroot(root).unwrap().with_project("foo");
+
+		let files: Vec<_> =
+			search_directories.clone()
+			.find_files_with(".conf", &options)
+			.unwrap()
+			.map(|(path, _)| path)
+			.collect();
+		assert_eq!(files, [root.join("etc/foo.d/x86-64/a.conf"), root.join("usr/etc/foo.d/x86-64/b.conf")]);
+
+		let dropin_directories = search_directories.find_dropin_directories_with(&options).unwrap();
+		assert_eq!(dropin_directories, [
+			root.join("usr/etc/foo.d"),
+			root.join("usr/etc/foo.d/x86-64"),
+			root.join("etc/foo.d"),
+			root.join("etc/foo.d/x86-64"),
+		]);
 
-			result.insert(file_name.into_vec(), (path, file));
+		#[cfg(target_arch = "x86_64")]
+		{
+			let condition = crate::SubdirectoryCondition::architecture();
+			assert!(condition.matches("x86-64".as_ref()));
+			assert!(!condition.matches("arm64".as_ref()));
 		}
 	}
 
-	Ok(result.into_values())
-}
+	#[test]
+	fn find_first() {
+		let root = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/find_first"));
 
-/// The iterator of files returned by [`SearchDirectoriesForProject::find_files`],
-/// [`SearchDirectoriesForFileName::find_files`] and [`SearchDirectoriesForProjectAndFileName::find_files`].
-#[derive(Debug)]
-#[repr(transparent)]
-pub struct Files {
-	inner: FilesInner,
-}
+		let search_directories = SearchDirectories::modern_system().chroot(root).unwrap();
 
-type FilesInner =
-	std::iter::Chain<
-		std::option::IntoIter<(PathBuf, File)>,
-		std::collections::btree_map::IntoValues<Vec<u8>, (PathBuf, File)>,
-	>;
+		let (path, _) = search_directories.clone().with_file_name("foo.conf").find_first().unwrap().unwrap();
+		assert_eq!(path, root.join("run/foo.conf"));
 
-impl Iterator for Files {
-	type Item = (PathBuf, File);
+		let (path, _) = search_directories.clone().with_project("bar").with_file_name("foo.conf").find_first().unwrap().unwrap();
+		assert_eq!(path, root.join("usr/etc/bar/foo.conf"));
 
-	fn next(&mut self) -> Option<Self::Item> {
-		self.inner.next()
+		assert!(search_directories.with_file_name("baz.conf").find_first().unwrap().is_none());
 	}
-}
 
-impl DoubleEndedIterator for Files {
-	fn next_back(&mut self) -> Option<Self::Item> {
-		self.inner.next_back()
-	}
-}
+	#[test]
+	fn find_dropin_directories() {
+		let root = temp_dir("find_dropin_directories");
 
-const _STATIC_ASSERT_FILES_INNER_IS_FUSED_ITERATOR: () = {
-	const fn is_fused_iterator<T>() where T: std::iter::FusedIterator {}
-	is_fused_iterator::<FilesInner>();
-};
-impl std::iter::FusedIterator for Files {}
+		for path in [
+			"usr/etc/foo.d/a.so",
+			"usr/etc/foo.service.d/a.conf",
+			"usr/etc/service.d/a.conf",
+			"etc/bar.d/a.so",
+			"etc/foo.d/b.so",
+			"etc/foo.unit",
+			"etc/foo.unit.d/a.conf",
+			"etc/foo/foo.conf.d/a.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+		std::fs::create_dir_all(root.join("run")).unwrap();
+		std::fs::write(root.join("run/foo.d"), "").unwrap();
 
-#[cfg(test)]
-mod tests {
-	use std::path::{Path, PathBuf};
+		let search_directories = SearchDirectories::modern_system().chroot(&root).unwrap();
 
-	use crate::SearchDirectories;
+		let dropin_directories = search_directories.clone().with_project("foo").with_legacy_projects(["bar"]).find_dropin_directories().unwrap();
+		assert_eq!(dropin_directories, [
+			root.join("usr/etc/foo.d"),
+			root.join("etc/bar.d"),
+			root.join("etc/foo.d"),
+		]);
+
+		let dropin_directories =
+			search_directories.clone()
+			.with_file_name("foo.service")
+			.find_dropin_directories_with(&crate::FindOptions::new().type_dropins(true))
+			.unwrap();
+		assert_eq!(dropin_directories, [
+			root.join("usr/etc/service.d"),
+			root.join("usr/etc/foo.service.d"),
+		]);
+
+		let dropin_directories = search_directories.clone().with_file_name("foo.service").with_fallback_file_names(["foo.unit"]).find_dropin_directories().unwrap();
+		assert_eq!(dropin_directories, [root.join("etc/foo.unit.d")]);
+
+		let dropin_directories = search_directories.with_project("foo").with_file_name("foo.conf").find_dropin_directories().unwrap();
+		assert_eq!(dropin_directories, [root.join("etc/foo/foo.conf.d")]);
+	}
 
 	#[test]
-	fn search_directory_precedence() {
-		for include_usr_etc in [false, true] {
-			for include_run in [false, true] {
-				for include_etc in [false, true] {
-					let mut search_directories = vec![];
-					if include_usr_etc {
-						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc"));
-					}
-					if include_run {
-						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run"));
-					}
-					if include_etc {
-						search_directories.push(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc"));
-					}
-					let search_directories: SearchDirectories<'_> =
-						search_directories
-						.into_iter()
-						.map(|path| Path::new(path).into())
-						.collect();
-					let files: Vec<_> =
-						search_directories
-						.with_project("foo")
-						.with_file_name("a.conf")
-						.find_files(Some(".conf"))
-						.unwrap()
-						.map(|(path, _)| path)
-						.collect();
-					if include_etc {
-						assert_eq!(files, [
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc/foo/a.conf"),
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/etc/foo/a.conf.d/b.conf"),
-						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
-					}
-					else if include_run {
-						assert_eq!(files, [
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run/foo/a.conf"),
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/run/foo/a.conf.d/b.conf"),
-						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
-					}
-					else if include_usr_etc {
-						assert_eq!(files, [
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc/foo/a.conf"),
-							concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/search_directory_precedence/usr/etc/foo/a.conf.d/b.conf"),
-						].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
-					}
-					else {
-						assert_eq!(files, Vec::<PathBuf>::new());
-					}
-				}
+	fn non_regular_dropins() {
+		let root = temp_dir("non_regular_dropins");
+		std::fs::create_dir_all(root.join("etc/foo.d/b.conf")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "a = 1\n").unwrap();
+		let _socket = std::os::unix::net::UnixListener::bind(root.join("etc/foo.d/c.conf")).unwrap();
+		std::os::unix::fs::symlink("a.conf", root.join("etc/foo.d/d.conf")).unwrap();
+
+		for symlink_policy in [crate::SymlinkPolicy::Follow, crate::SymlinkPolicy::Refuse] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(&root)
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().symlink_policy(symlink_policy))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			match symlink_policy {
+				crate::SymlinkPolicy::Refuse => assert_eq!(files, [root.join("etc/foo.d/a.conf")]),
+				_ => assert_eq!(files, [root.join("etc/foo.d/a.conf"), root.join("etc/foo.d/d.conf")]),
 			}
 		}
 	}
 
+	#[cfg(unix)]
 	#[test]
-	fn only_project() {
-		let files: Vec<_> =
+	fn fifo_replaced_after_search() {
+		let root = temp_dir("fifo_replaced_after_search");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("etc/foo.d/a.conf"), "a = 1\n").unwrap();
+
+		let entries: Vec<_> =
 			SearchDirectories::modern_system()
-			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project")))
+			.chroot(&root)
 			.unwrap()
 			.with_project("foo")
-			.find_files(".conf")
+			.find_entries(".conf")
 			.unwrap()
-			.map(|(path, _)| path)
 			.collect();
-		assert_eq!(files, [
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/a.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/b.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/c.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/etc/foo.d/d.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/run/foo.d/e.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_project/usr/etc/foo.d/f.conf"),
-		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+		assert_eq!(entries.len(), 1);
+
+		// Opening the FIFO for reading would block until something opens it for writing, which nothing does.
+		std::fs::remove_file(root.join("etc/foo.d/a.conf")).unwrap();
+		assert!(std::process::Command::new("mkfifo").arg(root.join("etc/foo.d/a.conf")).status().unwrap().success());
+
+		let err = entries[0].open().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
 	}
 
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
 	#[test]
-	fn only_file_name() {
-		let files: Vec<_> =
+	fn io_uring() {
+		let files =
 			SearchDirectories::modern_system()
-			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name")))
+			.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all")))
 			.unwrap()
-			.with_file_name("foo.service")
-			.find_files(Some(".conf"))
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().io_uring(true))
 			.unwrap()
-			.map(|(path, _)| path)
-			.collect();
+			.read_all_to_string()
+			.unwrap();
 		assert_eq!(files, [
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/a.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/b.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/c.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/etc/foo.service.d/d.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/run/foo.service.d/e.conf"),
-			concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/only_file_name/usr/etc/foo.service.d/f.conf"),
-		].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/etc/foo.d/a.conf").into(), "a = 1\n".to_owned()),
+			(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/read_all/run/foo.d/b.conf").into(), "b = 2\n".to_owned()),
+		]);
+
+		// More files than fit in one batch
+		let root = temp_dir("io_uring");
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		for i in 0..1000 {
+			std::fs::write(root.join(format!("etc/foo.d/{i:04}.conf")), i.to_string()).unwrap();
+		}
+		std::fs::create_dir_all(root.join("etc/foo.d/0500-dir.conf")).unwrap();
+
+		let files =
+			SearchDirectories::modern_system()
+			.chroot(&root)
+			.unwrap()
+			.with_project("foo")
+			.find_files_with(".conf", &crate::FindOptions::new().io_uring(true))
+			.unwrap()
+			.read_all_to_string()
+			.unwrap();
+		assert_eq!(files, (0..1000).map(|i| (root.join(format!("etc/foo.d/{i:04}.conf")), i.to_string())).collect::<Vec<_>>());
+	}
+
+	#[cfg(all(feature = "io-uring", target_os = "linux"))]
+	#[test]
+	fn io_uring_main_file_removed() {
+		let root = temp_dir("io_uring_main_file_removed");
+		for path in [
+			"etc/foo.conf",
+			"etc/foo.conf.d/a.conf",
+		] {
+			let path = root.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, "").unwrap();
+		}
+
+		let entries = SearchDirectories::modern_system().chroot(&root).unwrap().with_file_name("foo.conf").find_entries(Some(".conf")).unwrap();
+		std::fs::remove_file(root.join("etc/foo.conf")).unwrap();
+
+		// The dropin must not take the place of the main file that was removed.
+		let (main_file, dropins) = entries.open_all_io_uring().unwrap().split_main();
+		assert!(main_file.is_none());
+		assert_eq!(dropins.into_paths().collect::<Vec<_>>(), [root.join("etc/foo.conf.d/a.conf")]);
+	}
+
+	#[test]
+	fn dedupe_canonical_paths() {
+		for dedupe_canonical_paths in [false, true] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths")))
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().dedupe_canonical_paths(dedupe_canonical_paths))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			if dedupe_canonical_paths {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths/etc/foo.d/20-y.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths/etc/foo.d/30-z.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+			else {
+				assert_eq!(files, [
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths/usr/etc/foo.d/10-x.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths/etc/foo.d/20-y.conf"),
+					concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths/etc/foo.d/30-z.conf"),
+				].into_iter().map(Into::into).collect::<Vec<PathBuf>>());
+			}
+		}
+	}
+
+	#[test]
+	fn canonicalize_paths() {
+		let root = std::fs::canonicalize(concat!(env!("CARGO_MANIFEST_DIR"), "/test-files/dedupe_canonical_paths")).unwrap();
+
+		for dedupe_canonical_paths in [false, true] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(&root)
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().canonicalize_paths(true).dedupe_canonical_paths(dedupe_canonical_paths))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			if dedupe_canonical_paths {
+				assert_eq!(files, [
+					root.join("usr/etc/foo.d/10-x.conf"),
+					root.join("etc/foo.d/30-z.conf"),
+				]);
+			}
+			else {
+				assert_eq!(files, [
+					root.join("usr/etc/foo.d/10-x.conf"),
+					root.join("usr/etc/foo.d/10-x.conf"),
+					root.join("etc/foo.d/30-z.conf"),
+				]);
+			}
+		}
+	}
+
+	#[test]
+	fn dedupe_inodes() {
+		let root = temp_dir("dedupe_inodes");
+		std::fs::create_dir_all(root.join("usr/etc/foo.d")).unwrap();
+		std::fs::create_dir_all(root.join("etc/foo.d")).unwrap();
+		std::fs::write(root.join("usr/etc/foo.d/10-x.conf"), "").unwrap();
+		std::fs::hard_link(root.join("usr/etc/foo.d/10-x.conf"), root.join("etc/foo.d/20-y.conf")).unwrap();
+		std::fs::write(root.join("etc/foo.d/30-z.conf"), "").unwrap();
+
+		for dedupe_inodes in [false, true] {
+			let files: Vec<_> =
+				SearchDirectories::modern_system()
+				.chroot(&root)
+				.unwrap()
+				.with_project("foo")
+				.find_files_with(".conf", &crate::FindOptions::new().dedupe_inodes(dedupe_inodes))
+				.unwrap()
+				.map(|(path, _)| path)
+				.collect();
+			if dedupe_inodes {
+				assert_eq!(files, [
+					root.join("etc/foo.d/20-y.conf"),
+					root.join("etc/foo.d/30-z.conf"),
+				]);
+			}
+			else {
+				assert_eq!(files, [
+					root.join("usr/etc/foo.d/10-x.conf"),
+					root.join("etc/foo.d/20-y.conf"),
+					root.join("etc/foo.d/30-z.conf"),
+				]);
+			}
+		}
 	}
 }
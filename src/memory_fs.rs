@@ -0,0 +1,161 @@
+use std::{
+	collections::BTreeMap,
+	ffi::OsString,
+	io,
+	path::{Component, Path, PathBuf},
+	sync::Arc,
+};
+
+use crate::{FileSystem, FileSystemMetadata};
+
+/// A [`FileSystem`] that only exists in memory, for unit-testing how config files are loaded without creating files on disk.
+///
+/// Directories are created implicitly for the parents of every file.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Read as _;
+///
+/// let file_system =
+///     uapi_config::InMemoryFs::new()
+///     .file("/usr/etc/foobar.conf", "a = 1\n")
+///     .file("/etc/foobar.conf.d/override.conf", "a = 2\n");
+///
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .find_files_in(&file_system, Some(".conf"), &Default::default())
+///     .unwrap();
+/// for (path, mut file) in files {
+///     let mut contents = String::new();
+///     file.read_to_string(&mut contents).unwrap();
+///     // Parse and merge `contents`
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct InMemoryFs {
+	nodes: BTreeMap<PathBuf, Node>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+	File(Arc<[u8]>),
+	Dir,
+}
+
+impl InMemoryFs {
+	/// Start with an empty filesystem that only contains the root directory.
+	pub fn new() -> Self {
+		let mut nodes = BTreeMap::new();
+		nodes.insert(PathBuf::from("/"), Node::Dir);
+		Self { nodes }
+	}
+
+	/// Adds a file at `path` with the given contents, replacing any existing file, and creating its parent directories if necessary.
+	///
+	/// # Panics
+	///
+	/// Panics if `path` does not start with a [`Component::RootDir`], if it contains [`Component::ParentDir`],
+	/// if it is `/`, or if it or any of its parents is an existing directory or file respectively.
+	#[must_use]
+	pub fn file<P, C>(mut self, path: P, contents: C) -> Self where P: AsRef<Path>, C: AsRef<[u8]> {
+		let path = normalize(path.as_ref());
+		self.create_dir_all(path.parent().expect("cannot create a file at /"));
+		if let Some(Node::Dir) = self.nodes.get(&path) {
+			panic!("{} is a directory", path.display());
+		}
+		self.nodes.insert(path, Node::File(contents.as_ref().into()));
+		self
+	}
+
+	/// Adds a directory at `path`, creating its parent directories if necessary.
+	///
+	/// # Panics
+	///
+	/// Panics if `path` does not start with a [`Component::RootDir`], if it contains [`Component::ParentDir`],
+	/// or if it or any of its parents is an existing file.
+	#[must_use]
+	pub fn dir<P>(mut self, path: P) -> Self where P: AsRef<Path> {
+		self.create_dir_all(&normalize(path.as_ref()));
+		self
+	}
+
+	fn create_dir_all(&mut self, path: &Path) {
+		for ancestor in path.ancestors() {
+			match self.nodes.get(ancestor) {
+				Some(Node::Dir) => break,
+				Some(Node::File(_)) => panic!("{} is a file", ancestor.display()),
+				None => (),
+			}
+		}
+
+		let mut ancestors: Vec<_> = path.ancestors().map(ToOwned::to_owned).collect();
+		ancestors.reverse();
+		for ancestor in ancestors {
+			self.nodes.entry(ancestor).or_insert(Node::Dir);
+		}
+	}
+}
+
+impl Default for InMemoryFs {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl FileSystem for InMemoryFs {
+	type File = io::Cursor<Arc<[u8]>>;
+
+	type ReadDir = std::vec::IntoIter<io::Result<OsString>>;
+
+	fn read_dir(&self, path: &Path) -> io::Result<Self::ReadDir> {
+		match self.nodes.get(path) {
+			Some(Node::Dir) => (),
+			Some(Node::File(_)) => return Err(io::ErrorKind::NotADirectory.into()),
+			None => return Err(io::ErrorKind::NotFound.into()),
+		}
+
+		let file_names: Vec<_> =
+			self.nodes.range::<Path, _>((std::ops::Bound::Excluded(path), std::ops::Bound::Unbounded))
+			.map(|(child, _)| child)
+			.take_while(|child| child.starts_with(path))
+			.filter(|child| child.parent() == Some(path))
+			.filter_map(|child| child.file_name())
+			.map(|file_name| Ok(file_name.to_owned()))
+			.collect();
+		Ok(file_names.into_iter())
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<FileSystemMetadata> {
+		match self.nodes.get(path) {
+			Some(Node::File(contents)) => Ok(FileSystemMetadata::file(contents.len() as u64)),
+			Some(Node::Dir) => Ok(FileSystemMetadata::other()),
+			None => Err(io::ErrorKind::NotFound.into()),
+		}
+	}
+
+	fn open(&self, path: &Path) -> io::Result<Self::File> {
+		match self.nodes.get(path) {
+			Some(Node::File(contents)) => Ok(io::Cursor::new(contents.clone())),
+			Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+			None => Err(io::ErrorKind::NotFound.into()),
+		}
+	}
+}
+
+/// Returns `path` without any [`Component::CurDir`] and trailing `/`, so that it can be used as a key.
+fn normalize(path: &Path) -> PathBuf {
+	let mut components = path.components();
+	assert_eq!(components.next(), Some(Component::RootDir), "{} is not an absolute path", path.display());
+
+	let mut result = PathBuf::from("/");
+	for component in components {
+		match component {
+			Component::Normal(name) => result.push(name),
+			Component::CurDir => (),
+			Component::RootDir | Component::Prefix(_) | Component::ParentDir => panic!("{} must not contain `..`", path.display()),
+		}
+	}
+	result
+}
@@ -0,0 +1,162 @@
+//! Parsing the files that were found and merging them into a single config object.
+
+use std::{
+	fs::File,
+	io::Read,
+	path::{Path, PathBuf},
+};
+
+use crate::Files;
+
+/// A config object that the files found by a search can be parsed and merged into, with [`Files::load_merged`].
+///
+/// # Examples
+///
+/// ```rust
+/// #[derive(Default)]
+/// struct Config {
+///     log_level: Option<String>,
+/// }
+///
+/// impl uapi_config::MergeFrom for Config {
+///     type Error = std::io::Error;
+///
+///     fn merge_from<R>(&mut self, _path: &std::path::Path, mut reader: R) -> Result<(), Self::Error> where R: std::io::Read {
+///         let mut contents = String::new();
+///         reader.read_to_string(&mut contents)?;
+///         for line in contents.lines() {
+///             if let Some(log_level) = line.strip_prefix("log_level=") {
+///                 self.log_level = Some(log_level.to_owned());
+///             }
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let config: Config =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files(".conf")
+///     .unwrap()
+///     .load_merged()
+///     .unwrap();
+/// ```
+pub trait MergeFrom {
+	/// The error returned when a file cannot be parsed.
+	type Error;
+
+	/// Parses the file at `path` from `reader` and merges it into `self`. Settings in the file override the settings
+	/// from the files that were merged before it.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the file cannot be read or parsed.
+	fn merge_from<R>(&mut self, path: &Path, reader: R) -> Result<(), Self::Error> where R: Read;
+}
+
+/// Error returned by [`Files::load_merged`] and [`Files::for_each_file`] when a file could not be merged.
+#[derive(Debug)]
+pub struct MergeError<E> {
+	path: PathBuf,
+	inner: E,
+}
+
+impl<E> MergeError<E> {
+	/// The path of the file that could not be merged.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The error returned by [`MergeFrom::merge_from`] or the callback of [`Files::for_each_file`].
+	pub fn inner(&self) -> &E {
+		&self.inner
+	}
+
+	/// Consumes this error and returns the error returned by [`MergeFrom::merge_from`] or the callback of [`Files::for_each_file`].
+	pub fn into_inner(self) -> E {
+		self.inner
+	}
+}
+
+impl<E> std::fmt::Display for MergeError<E> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "could not merge {}", self.path.display())
+	}
+}
+
+impl<E> std::error::Error for MergeError<E> where E: std::error::Error + 'static {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.inner)
+	}
+}
+
+/// Merges all the files in order into `value`, stopping at the first file that could not be merged.
+pub(crate) fn merge_into<I, R, T>(files: I, value: &mut T) -> Result<(), MergeError<T::Error>>
+where
+	I: IntoIterator<Item = (PathBuf, R)>,
+	R: Read,
+	T: MergeFrom,
+{
+	for (path, file) in files {
+		if let Err(inner) = value.merge_from(&path, file) {
+			return Err(MergeError { path, inner });
+		}
+	}
+
+	Ok(())
+}
+
+impl Files {
+	/// Merges all the files in order into a default-constructed `T`, so that settings in later files override settings in earlier files.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(MergeError)` for the first file that could not be merged. The error contains the path of that file.
+	pub fn load_merged<T>(self) -> Result<T, MergeError<T::Error>> where T: MergeFrom + Default {
+		let mut result = T::default();
+		self.load_merged_into(&mut result)?;
+		Ok(result)
+	}
+
+	/// Same as [`load_merged`](Self::load_merged), but merges the files into an existing value, such as one with built-in defaults.
+	///
+	/// # Errors
+	///
+	/// See [`load_merged`](Self::load_merged). The files before the one that could not be merged have already been merged into `value`.
+	pub fn load_merged_into<T>(self, value: &mut T) -> Result<(), MergeError<T::Error>> where T: MergeFrom {
+		merge_into(self, value)
+	}
+
+	/// Calls `f` with each file in order. Unlike [`load_merged`](Self::load_merged), a file that fails does not stop the remaining files
+	/// from being processed, so that all the files that fail can be reported together.
+	///
+	/// # Errors
+	///
+	/// Returns the errors of all the files that failed, in order. Each error contains the path of its file.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let result =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap()
+	///     .for_each_file(|path, file| -> std::io::Result<()> {
+	///         // Parse `file` and merge into the config
+	///         Ok(())
+	///     });
+	/// if let Err(errors) = result {
+	///     for err in errors {
+	///         eprintln!("{}: {:?}", err.path().display(), err.inner());
+	///     }
+	/// }
+	/// ```
+	pub fn for_each_file<F, E>(self, mut f: F) -> Result<(), Vec<MergeError<E>>> where F: FnMut(&Path, File) -> Result<(), E> {
+		let errors: Vec<_> =
+			self
+			.filter_map(|(path, file)| f(&path, file).err().map(|inner| MergeError { path, inner }))
+			.collect();
+		if errors.is_empty() { Ok(()) } else { Err(errors) }
+	}
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use crate::{Files, ReadError};
+
+impl Files {
+	/// Memory-maps all the files, in the same order that they would be yielded by this iterator.
+	///
+	/// This allows parsers that work on byte slices to borrow the contents of the files without copying them.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(ReadError)` for the first file that could not be mapped. The error contains the path of that file.
+	///
+	/// # Safety
+	///
+	/// See [`memmap2::Mmap::map`]. The files must not be modified, including being truncated, while the maps are alive,
+	/// otherwise the contents of the maps can change or accessing them can cause the process to crash.
+	/// This is generally only guaranteed if the files are only writable by a trusted user.
+	pub unsafe fn mmap_all(self) -> Result<Vec<(PathBuf, memmap2::Mmap)>, ReadError> {
+		self.map(|(path, file)| {
+			// SAFETY: Upheld by the caller.
+			match unsafe { memmap2::Mmap::map(&file) } {
+				Ok(mmap) => Ok((path, mmap)),
+				Err(inner) => Err(ReadError { path, inner }),
+			}
+		}).collect()
+	}
+}
@@ -0,0 +1,145 @@
+//! Reporting the timings and counters of searches, such as to export them as metrics.
+
+use std::{
+	cell::Cell,
+	path::Path,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Receives the timings and counters of searches, and of the helpers that read the files that were found,
+/// such as to export the latency of loading config from a long-running daemon to a metrics system.
+///
+/// Set with [`FindOptions::observer`](crate::FindOptions::observer). All methods do nothing by default, so only the ones
+/// that are needed have to be implemented. They are called on the thread that performs the search, so they should be cheap,
+/// like incrementing atomic counters.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+///
+/// #[derive(Default)]
+/// struct Metrics {
+///     searches: AtomicU64,
+///     search_micros: AtomicU64,
+///     bytes_read: AtomicU64,
+/// }
+///
+/// impl uapi_config::SearchObserver for Metrics {
+///     fn search_finished(&self, metrics: &uapi_config::SearchMetrics) {
+///         self.searches.fetch_add(1, Ordering::Relaxed);
+///         self.search_micros.fetch_add(metrics.duration().as_micros().try_into().unwrap_or(u64::MAX), Ordering::Relaxed);
+///     }
+///
+///     fn file_read(&self, _path: &std::path::Path, num_bytes: u64) {
+///         self.bytes_read.fetch_add(num_bytes, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let metrics = Arc::new(Metrics::default());
+/// let options = uapi_config::FindOptions::new().observer(metrics.clone());
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &options)
+///     .unwrap()
+///     .read_all()
+///     .unwrap();
+/// assert_eq!(metrics.searches.load(Ordering::Relaxed), 1);
+/// ```
+pub trait SearchObserver: Send + Sync {
+	/// Called when a search has finished looking up the files, whether it succeeded or failed.
+	///
+	/// For the `find_files` methods, the files are opened afterwards and reported to [`files_opened`](Self::files_opened).
+	fn search_finished(&self, metrics: &SearchMetrics) {
+		_ = metrics;
+	}
+
+	/// Called when the files that a `find_files` method found have been opened, with the number of files that were opened
+	/// and the time it took to open them.
+	///
+	/// This is called once for each set of files that is opened, so [`SearchDirectoriesForFileNameGlob::find_files`](crate::SearchDirectoriesForFileNameGlob::find_files)
+	/// calls it once for every file name that it finds.
+	fn files_opened(&self, num_files: usize, duration: Duration) {
+		_ = (num_files, duration);
+	}
+
+	/// Called when a helper of [`Files`](crate::Files) like [`read_all`](crate::Files::read_all) or
+	/// [`write_cat_config`](crate::Files::write_cat_config) has read the file at `path`, with the number of bytes that were read.
+	fn file_read(&self, path: &Path, num_bytes: u64) {
+		_ = (path, num_bytes);
+	}
+}
+
+/// The timing and counters of a search, passed to [`SearchObserver::search_finished`].
+#[derive(Clone, Debug)]
+pub struct SearchMetrics {
+	duration: Duration,
+	directories_scanned: usize,
+	files_found: usize,
+}
+
+impl SearchMetrics {
+	/// The time it took to look up the files.
+	pub fn duration(&self) -> Duration {
+		self.duration
+	}
+
+	/// The number of directories whose entries were read, ie search directories and dropin directories that exist.
+	pub fn directories_scanned(&self) -> usize {
+		self.directories_scanned
+	}
+
+	/// The number of files that the search found. If the search failed, this only counts the files that were found before it failed.
+	pub fn files_found(&self) -> usize {
+		self.files_found
+	}
+}
+
+/// The [`SearchObserver`] set with [`FindOptions::observer`](crate::FindOptions::observer).
+#[derive(Clone)]
+pub(crate) struct Observer(pub(crate) Arc<dyn SearchObserver>);
+
+impl std::fmt::Debug for Observer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("Observer").finish_non_exhaustive()
+	}
+}
+
+/// Counts the work of a single search, and reports it to the [`SearchObserver`] when it is dropped at the end of the search.
+pub(crate) struct MetricsRecorder {
+	observer: Arc<dyn SearchObserver>,
+	started: Instant,
+	directories_scanned: Cell<usize>,
+	files_found: Cell<usize>,
+}
+
+impl MetricsRecorder {
+	pub(crate) fn new(observer: &Observer) -> Self {
+		Self {
+			observer: observer.0.clone(),
+			started: Instant::now(),
+			directories_scanned: Cell::new(0),
+			files_found: Cell::new(0),
+		}
+	}
+
+	pub(crate) fn directory_scanned(&self) {
+		self.directories_scanned.set(self.directories_scanned.get() + 1);
+	}
+
+	pub(crate) fn files_found(&self, num_files: usize) {
+		self.files_found.set(self.files_found.get() + num_files);
+	}
+}
+
+impl Drop for MetricsRecorder {
+	fn drop(&mut self) {
+		self.observer.search_finished(&SearchMetrics {
+			duration: self.started.elapsed(),
+			directories_scanned: self.directories_scanned.get(),
+			files_found: self.files_found.get(),
+		});
+	}
+}
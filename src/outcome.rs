@@ -0,0 +1,132 @@
+//! Summarizing what a search found, such as to export it as metrics.
+
+use std::path::{Path, PathBuf};
+
+use crate::{search_directory_index, Files, SearchDirectories, SkipDiagnostics, SkipReason};
+
+/// A summary of the files that a search found, created with [`Files::outcome`].
+///
+/// If the `serde` feature is enabled, this implements `serde::Serialize` and `serde::Deserialize`.
+/// If the `serde_json` feature is enabled, [`to_json`](Self::to_json) renders the outcome as JSON for external tooling.
+///
+/// # Examples
+///
+/// ```rust
+/// let search_directories = uapi_config::SearchDirectories::modern_system();
+/// let skip_diagnostics = uapi_config::SkipDiagnostics::new();
+/// let files =
+///     search_directories.clone()
+///     .with_file_name("foobar.conf")
+///     .find_files_with(Some(".conf"), &uapi_config::FindOptions::new().skip_diagnostics(skip_diagnostics.clone()))
+///     .unwrap();
+/// let outcome = files.outcome(&search_directories, Some(&skip_diagnostics));
+/// println!("main file: {:?}", outcome.main_file());
+/// for (search_directory, num_dropins) in outcome.dropins_per_search_directory() {
+///     println!("{} dropins from {}", num_dropins, search_directory.display());
+/// }
+/// println!("{:?} files skipped", outcome.skipped());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SearchOutcome {
+	main_file: Option<PathBuf>,
+	main_file_search_directory: Option<PathBuf>,
+	/// The dropins in lowest-precedence-first order, with the search directory that each one was found under.
+	dropins: Vec<(PathBuf, Option<PathBuf>)>,
+	/// Lowest-precedence-first, like the search directories.
+	dropins_per_search_directory: Vec<(PathBuf, usize)>,
+	skipped: Option<Vec<(PathBuf, SkipReason)>>,
+}
+
+impl SearchOutcome {
+	/// The path of the main file, if one was found.
+	pub fn main_file(&self) -> Option<&Path> {
+		self.main_file.as_deref()
+	}
+
+	/// The search directory that the main file was found under, if a main file was found and it is under one of the search directories.
+	pub fn main_file_search_directory(&self) -> Option<&Path> {
+		self.main_file_search_directory.as_deref()
+	}
+
+	/// The paths of the dropins in lowest-precedence-first order, each with the search directory that it was found under,
+	/// or `None` if it is not under any of the search directories.
+	pub fn dropins(&self) -> impl DoubleEndedIterator<Item = (&Path, Option<&Path>)> + ExactSizeIterator {
+		self.dropins.iter().map(|(path, search_directory)| (&**path, search_directory.as_deref()))
+	}
+
+	/// The number of dropins that were found under each search directory, in the order of the search directories.
+	/// Every search directory is included, even if no dropins were found under it.
+	pub fn dropins_per_search_directory(&self) -> impl DoubleEndedIterator<Item = (&Path, usize)> + ExactSizeIterator {
+		self.dropins_per_search_directory.iter().map(|(search_directory, num_dropins)| (&**search_directory, *num_dropins))
+	}
+
+	/// The total number of dropins that were found.
+	///
+	/// This can be more than the sum of [`dropins_per_search_directory`](Self::dropins_per_search_directory) if some dropins
+	/// are not under any of the search directories, such as because [`FindOptions::canonicalize_paths`](crate::FindOptions::canonicalize_paths)
+	/// resolved them to somewhere else.
+	pub fn num_dropins(&self) -> usize {
+		self.dropins.len()
+	}
+
+	/// The number of files that the search looked at but did not return, if the [`SkipDiagnostics`] of the search
+	/// were given to [`Files::outcome`].
+	pub fn skipped(&self) -> Option<usize> {
+		self.skipped.as_ref().map(Vec::len)
+	}
+
+	/// The files that the search looked at but did not return, and why, in the order that they were looked at,
+	/// if the [`SkipDiagnostics`] of the search were given to [`Files::outcome`].
+	///
+	/// Files that were overridden by a dropin with the same name in a search directory with higher precedence
+	/// are included with [`SkipReason::Shadowed`].
+	pub fn skipped_files(&self) -> Option<&[(PathBuf, SkipReason)]> {
+		self.skipped.as_deref()
+	}
+
+	/// Renders the outcome as a JSON object with the fields `main_file`, `main_file_search_directory`, `dropins`,
+	/// `dropins_per_search_directory` and `skipped`, so that scripts and tests do not need to parse log output.
+	///
+	/// # Errors
+	///
+	/// Returns an error if any of the paths is not valid UTF-8.
+	#[cfg(feature = "serde_json")]
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		serde_json::to_string(self)
+	}
+}
+
+impl Files {
+	/// Returns a [`SearchOutcome`] that summarizes the files remaining in this iterator.
+	///
+	/// `search_directories` must be the search directories that the search was performed with. If the search was performed
+	/// with [`FindOptions::skip_diagnostics`](crate::FindOptions::skip_diagnostics), pass the same [`SkipDiagnostics`] as `skip_diagnostics`
+	/// to also record the skipped files. The skipped files are not removed from `skip_diagnostics`.
+	/// Files are attributed to search directories like [`Files::grouped_by_root`] does.
+	pub fn outcome(&self, search_directories: &SearchDirectories<'_>, skip_diagnostics: Option<&SkipDiagnostics>) -> SearchOutcome {
+		let mut dropins_per_search_directory: Vec<_> = search_directories.iter().map(|search_directory| (search_directory.to_owned(), 0)).collect();
+		let mut dropins = Vec::with_capacity(self.dropins.len());
+		for (path, _, _) in self.dropins.as_slice() {
+			let i = search_directory_index(path, search_directories.iter());
+			if let Some(i) = i {
+				dropins_per_search_directory[i].1 += 1;
+			}
+			dropins.push((path.clone(), i.map(|i| dropins_per_search_directory[i].0.clone())));
+		}
+
+		let main_file = self.main_file.as_ref().map(|(path, _, _)| path.clone());
+		let main_file_search_directory =
+			main_file.as_deref()
+			.and_then(|path| search_directory_index(path, search_directories.iter()))
+			.map(|i| dropins_per_search_directory[i].0.clone());
+
+		SearchOutcome {
+			main_file,
+			main_file_search_directory,
+			dropins,
+			dropins_per_search_directory,
+			skipped: skip_diagnostics.map(SkipDiagnostics::to_vec),
+		}
+	}
+}
@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+/// The owners and permissions that config files are required to have, like the checks that sshd and sudo make of their config files.
+///
+/// Set with [`FindOptions::permission_policy`](crate::FindOptions::permission_policy) to fail the search when a file violates the policy.
+/// To only warn about such files instead, use the `find_entries` methods and call [`PermissionPolicy::check`]
+/// with the [`Entry::metadata`](crate::Entry::metadata) of each entry.
+///
+/// The policy applies to the files themselves, not to the directories they are in. If a file is a symlink that is followed,
+/// the policy applies to the target of the symlink.
+///
+/// # Examples
+///
+/// ```rust
+/// let files =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_project("foobar")
+///     .find_files_with(".conf", &uapi_config::FindOptions::new().permission_policy(uapi_config::PermissionPolicy::strict()))
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PermissionPolicy {
+	owners: Option<Vec<u32>>,
+	reject_group_writable: bool,
+	reject_world_writable: bool,
+}
+
+impl PermissionPolicy {
+	/// Start with a policy that allows all files.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A policy that requires files to be owned by root and to not be group-writable or world-writable.
+	pub fn strict() -> Self {
+		Self::new().owners([0]).reject_group_writable(true).reject_world_writable(true)
+	}
+
+	/// Only allow files that are owned by one of the given uids. Defaults to allowing any owner.
+	#[must_use]
+	pub fn owners<I>(mut self, owners: I) -> Self where I: IntoIterator<Item = u32> {
+		self.owners = Some(owners.into_iter().collect());
+		self
+	}
+
+	/// If `true`, files that are writable by their group are rejected. Defaults to `false`.
+	#[must_use]
+	pub fn reject_group_writable(mut self, reject_group_writable: bool) -> Self {
+		self.reject_group_writable = reject_group_writable;
+		self
+	}
+
+	/// If `true`, files that are writable by all users are rejected. Defaults to `false`.
+	#[must_use]
+	pub fn reject_world_writable(mut self, reject_world_writable: bool) -> Self {
+		self.reject_world_writable = reject_world_writable;
+		self
+	}
+
+	/// Checks the file at `path` with the given metadata against this policy.
+	///
+	/// An [`fs::Metadata`](std::fs::Metadata) can be converted with [`EntryMetadata::from`](crate::EntryMetadata::from).
+	///
+	/// # Errors
+	///
+	/// Returns an error describing the first way in which the file violates the policy, if any.
+	#[cfg(unix)]
+	pub fn check(&self, path: &Path, metadata: &crate::EntryMetadata) -> Result<(), PermissionError> {
+		self.check_owner_and_mode(metadata.uid(), metadata.mode(), || path.to_owned())
+	}
+
+	/// Checks a file with the given owner and mode against this policy. `path` is only called to get the path of the file for the error.
+	pub(crate) fn check_owner_and_mode<F>(&self, uid: u32, mode: u32, path: F) -> Result<(), PermissionError> where F: FnOnce() -> PathBuf {
+		let violation =
+			if self.owners.as_ref().is_some_and(|owners| !owners.contains(&uid)) {
+				PermissionViolation::Owner
+			}
+			else if self.reject_group_writable && mode & 0o020 != 0 {
+				PermissionViolation::GroupWritable
+			}
+			else if self.reject_world_writable && mode & 0o002 != 0 {
+				PermissionViolation::WorldWritable
+			}
+			else {
+				return Ok(());
+			};
+
+		Err(PermissionError {
+			path: path(),
+			uid,
+			mode,
+			violation,
+		})
+	}
+}
+
+/// The way in which a file violates a [`PermissionPolicy`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PermissionViolation {
+	/// The file is not owned by one of the allowed owners.
+	Owner,
+
+	/// The file is writable by its group.
+	GroupWritable,
+
+	/// The file is writable by all users.
+	WorldWritable,
+}
+
+/// Error returned when a file violates a [`PermissionPolicy`].
+///
+/// When returned from a search, this is wrapped in an [`io::Error`](std::io::Error) of kind [`io::ErrorKind::PermissionDenied`](std::io::ErrorKind::PermissionDenied),
+/// from which it can be retrieved with [`io::Error::get_ref`](std::io::Error::get_ref).
+#[derive(Debug)]
+pub struct PermissionError {
+	path: PathBuf,
+	uid: u32,
+	mode: u32,
+	violation: PermissionViolation,
+}
+
+impl PermissionError {
+	/// The path of the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The uid of the owner of the file.
+	pub fn uid(&self) -> u32 {
+		self.uid
+	}
+
+	/// The mode of the file, including the file type bits, like [`MetadataExt::mode`](std::os::unix::fs::MetadataExt::mode).
+	pub fn mode(&self) -> u32 {
+		self.mode
+	}
+
+	/// The way in which the file violates the policy.
+	pub fn violation(&self) -> PermissionViolation {
+		self.violation
+	}
+}
+
+impl std::fmt::Display for PermissionError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.violation {
+			PermissionViolation::Owner => write!(f, "{} is owned by uid {}, which is not an allowed owner", self.path.display(), self.uid),
+			PermissionViolation::GroupWritable => write!(f, "{} is group-writable (mode {:o})", self.path.display(), self.mode & 0o7777),
+			PermissionViolation::WorldWritable => write!(f, "{} is world-writable (mode {:o})", self.path.display(), self.mode & 0o7777),
+		}
+	}
+}
+
+impl std::error::Error for PermissionError {}
+
+impl From<PermissionError> for std::io::Error {
+	fn from(err: PermissionError) -> Self {
+		Self::new(std::io::ErrorKind::PermissionDenied, err)
+	}
+}
@@ -0,0 +1,121 @@
+use std::{
+	ffi::{OsStr, OsString},
+	path::{Path, PathBuf},
+};
+
+/// The paths that a search would look at, returned by the `describe` methods of the search directories, such as
+/// [`SearchDirectoriesForFileName::describe`](crate::SearchDirectoriesForFileName::describe).
+///
+/// The [`Display`](std::fmt::Display) impl renders the plan in a form that is suitable for `--help` output and man pages,
+/// like how `man systemd.unit` documents the unit load paths. No directories are read to create the plan,
+/// so it lists every path that would be considered regardless of whether it exists.
+///
+/// If the `serde_json` feature is enabled, [`to_json`](Self::to_json) renders the plan as JSON for external tooling.
+///
+/// # Examples
+///
+/// ```rust
+/// let plan =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .describe(Some(".conf"));
+/// println!("{plan}");
+/// ```
+///
+/// prints:
+///
+/// ```text
+/// Main file looked up in, later entries override earlier ones:
+///   /usr/etc/foobar.conf
+///   /run/foobar.conf
+///   /etc/foobar.conf
+/// Dropins *.conf read from, later entries override earlier ones with the same file name:
+///   /usr/etc/foobar.conf.d
+///   /run/foobar.conf.d
+///   /etc/foobar.conf.d
+/// ```
+#[derive(Clone, Debug)]
+pub struct SearchPlan {
+	main_files: Vec<PathBuf>,
+	dropin_directories: Vec<PathBuf>,
+	dropin_suffix: Option<OsString>,
+}
+
+impl SearchPlan {
+	pub(crate) fn new(main_files: Vec<PathBuf>, dropin_directories: Vec<PathBuf>, dropin_suffix: Option<OsString>) -> Self {
+		Self {
+			main_files,
+			dropin_directories: if dropin_suffix.is_some() { dropin_directories } else { vec![] },
+			dropin_suffix,
+		}
+	}
+
+	/// The paths that the main file is looked up at, in lowest-precedence-first order.
+	///
+	/// The main file is the one at the last of these paths that exists.
+	pub fn main_files(&self) -> impl DoubleEndedIterator<Item = &Path> + ExactSizeIterator {
+		self.main_files.iter().map(AsRef::as_ref)
+	}
+
+	/// The dropin directories that dropins are read from, in lowest-precedence-first order.
+	///
+	/// A dropin overrides any dropins with the same file name in earlier directories.
+	pub fn dropin_directories(&self) -> impl DoubleEndedIterator<Item = &Path> + ExactSizeIterator {
+		self.dropin_directories.iter().map(AsRef::as_ref)
+	}
+
+	/// All the paths that the search would look at, ie the main file paths followed by the dropin directories,
+	/// each in lowest-precedence-first order. The paths are included whether they exist or not,
+	/// such as to set up watches on them before they are created.
+	pub fn candidate_paths(&self) -> impl DoubleEndedIterator<Item = &Path> {
+		self.main_files().chain(self.dropin_directories())
+	}
+
+	/// The suffix of the dropins, if dropins are searched for.
+	pub fn dropin_suffix(&self) -> Option<&OsStr> {
+		self.dropin_suffix.as_deref()
+	}
+
+	/// Renders the plan as a JSON object with the fields `main_files`, `dropin_directories` and `dropin_suffix`,
+	/// so that scripts and tests do not need to parse the [`Display`](std::fmt::Display) output.
+	///
+	/// # Errors
+	///
+	/// Returns an error if any of the paths or the dropin suffix is not valid UTF-8.
+	#[cfg(feature = "serde_json")]
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		#[derive(serde::Serialize)]
+		struct Json<'a> {
+			main_files: &'a [PathBuf],
+			dropin_directories: &'a [PathBuf],
+			// `OsStr` is serialized as its platform-specific representation, so go through `Path` to serialize it as a string.
+			dropin_suffix: Option<&'a Path>,
+		}
+
+		serde_json::to_string(&Json {
+			main_files: &self.main_files,
+			dropin_directories: &self.dropin_directories,
+			dropin_suffix: self.dropin_suffix.as_deref().map(Path::new),
+		})
+	}
+}
+
+impl std::fmt::Display for SearchPlan {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if !self.main_files.is_empty() {
+			writeln!(f, "Main file looked up in, later entries override earlier ones:")?;
+			for path in &self.main_files {
+				writeln!(f, "  {}", path.display())?;
+			}
+		}
+
+		if let Some(dropin_suffix) = &self.dropin_suffix {
+			writeln!(f, "Dropins *{} read from, later entries override earlier ones with the same file name:", dropin_suffix.display())?;
+			for path in &self.dropin_directories {
+				writeln!(f, "  {}", path.display())?;
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,108 @@
+//! Platform-specific APIs, so that the rest of the crate can be built for both Unix and WASI.
+//!
+//! On WASI, files do not have owners or modes, and the device and inode numbers of files are only available through unstable APIs.
+
+use std::{
+	fs::{self, File},
+	io,
+	path::Path,
+};
+
+#[cfg(unix)]
+pub(crate) use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+#[cfg(target_os = "wasi")]
+pub(crate) use std::os::wasi::ffi::{OsStrExt, OsStringExt};
+
+/// Returns the device and inode numbers of a file, which together identify it, or `None` if the platform does not provide them.
+#[cfg_attr(unix, allow(clippy::unnecessary_wraps))] // Only `None` on platforms without inode numbers.
+pub(crate) fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt as _;
+
+		Some((metadata.dev(), metadata.ino()))
+	}
+
+	#[cfg(not(unix))]
+	{
+		_ = metadata;
+		None
+	}
+}
+
+/// The flags that files are opened with in addition to `O_RDONLY`, so that opening a file that turns out to be a FIFO
+/// does not block until a writer opens it.
+///
+/// Opening a regular file with `O_NONBLOCK` has no effect, but the flag is still cleared with [`set_blocking`] once the file
+/// is known to be a regular file, so that the returned files are the same as files that were opened normally.
+///
+/// Clearing the flag requires `fcntl`, which is only available without `unsafe` code through the `rustix` feature.
+/// Without that feature, files are not opened with `O_NONBLOCK`. Instead [`open_nonblocking`] checks that the path refers to
+/// a regular file before opening it, which prevents opening FIFOs except ones that replace the file between the check and the open.
+#[cfg(all(unix, feature = "rustix"))]
+#[allow(clippy::cast_possible_wrap)] // The flag is a small positive number.
+pub(crate) const NONBLOCKING_OPEN_FLAGS: i32 = rustix::fs::OFlags::NONBLOCK.bits() as i32;
+
+/// The flags that files are opened with in addition to `O_RDONLY`. See the `rustix` version of this constant.
+#[cfg(all(unix, not(feature = "rustix")))]
+pub(crate) const NONBLOCKING_OPEN_FLAGS: i32 = 0;
+
+/// Opens the file at `path` for reading with [`NONBLOCKING_OPEN_FLAGS`], so that opening a FIFO does not block.
+/// The caller must verify that the file is a regular file and then clear the flag with [`set_blocking`].
+///
+/// Without the `rustix` feature, the path is checked to be a regular file before it is opened, and an error of kind
+/// [`io::ErrorKind::InvalidData`] is returned if it is not.
+pub(crate) fn open_nonblocking(path: &Path) -> io::Result<File> {
+	#[cfg(not(all(unix, feature = "rustix")))]
+	if !fs::metadata(path)?.is_file() {
+		return Err(not_a_regular_file(path));
+	}
+
+	let mut open_options = fs::OpenOptions::new();
+	open_options.read(true);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::OpenOptionsExt as _;
+
+		open_options.custom_flags(NONBLOCKING_OPEN_FLAGS);
+	}
+	open_options.open(path)
+}
+
+/// Clears the `O_NONBLOCK` flag of a file that was opened with [`NONBLOCKING_OPEN_FLAGS`] and is known to be a regular file.
+#[cfg_attr(not(all(unix, feature = "rustix")), allow(clippy::unnecessary_wraps))] // Files are only opened with `O_NONBLOCK` with `rustix`.
+pub(crate) fn set_blocking<F>(file: &F) -> io::Result<()> where F: std::os::fd::AsFd {
+	#[cfg(all(unix, feature = "rustix"))]
+	{
+		use rustix::fs::OFlags;
+
+		let flags = rustix::fs::fcntl_getfl(file)?;
+		if flags.contains(OFlags::NONBLOCK) {
+			rustix::fs::fcntl_setfl(file, flags - OFlags::NONBLOCK)?;
+		}
+	}
+
+	#[cfg(not(all(unix, feature = "rustix")))]
+	{
+		_ = file;
+	}
+
+	Ok(())
+}
+
+pub(crate) fn not_a_regular_file(path: &Path) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a regular file", path.display()))
+}
+
+/// Opens the file at `path` for reading without blocking if it is a FIFO, and verifies that it is a regular file.
+///
+/// Returns an error of kind [`io::ErrorKind::InvalidData`] if the file is not a regular file.
+pub(crate) fn open_regular_file(path: &Path) -> io::Result<File> {
+	let file = open_nonblocking(path)?;
+	if !file.metadata()?.is_file() {
+		return Err(not_a_regular_file(path));
+	}
+	set_blocking(&file)?;
+	Ok(file)
+}
@@ -0,0 +1,225 @@
+use std::{
+	fs::File,
+	io::{self, BufReader, Read as _, Write},
+	path::{Path, PathBuf},
+};
+
+use crate::{Files, Observer};
+
+impl Files {
+	/// Returns an [`Iterator`] of `(`[`PathBuf`]`, `[`BufReader`]`<`[`File`]`>)`s over the files, in the same order.
+	///
+	/// This is useful for parsers that read the files line by line.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// use std::io::BufRead as _;
+	///
+	/// let readers =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap()
+	///     .into_readers();
+	/// for (path, reader) in readers {
+	///     for line in reader.lines() {
+	///         let line = line.unwrap();
+	///         // Parse `line` and merge into the config
+	///     }
+	/// }
+	/// ```
+	pub fn into_readers(self) -> Readers {
+		Readers {
+			inner: self,
+		}
+	}
+
+	/// Reads the contents of all the files, in the same order that they would be yielded by this iterator.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(ReadError)` for the first file that could not be read. The error contains the path of that file.
+	pub fn read_all(mut self) -> Result<Vec<(PathBuf, Vec<u8>)>, ReadError> {
+		let observer = self.observer.take();
+		self.map(|(path, mut file)| {
+			let mut contents = vec![];
+			match file.read_to_end(&mut contents) {
+				Ok(_) => {
+					file_read(observer.as_ref(), &path, contents.len());
+					Ok((path, contents))
+				},
+				Err(inner) => Err(ReadError { path, inner }),
+			}
+		}).collect()
+	}
+
+	/// Reads the contents of all the files as UTF-8 strings, in the same order that they would be yielded by this iterator.
+	///
+	/// # Errors
+	///
+	/// Returns `Err(ReadError)` for the first file that could not be read or did not contain valid UTF-8.
+	/// The error contains the path of that file.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// let files =
+	///     uapi_config::SearchDirectories::modern_system()
+	///     .with_project("foobar")
+	///     .find_files(".conf")
+	///     .unwrap()
+	///     .read_all_to_string()
+	///     .unwrap();
+	/// for (path, contents) in files {
+	///     // Parse `contents` and merge into the config
+	/// }
+	/// ```
+	pub fn read_all_to_string(mut self) -> Result<Vec<(PathBuf, String)>, ReadError> {
+		let observer = self.observer.take();
+		self.map(|(path, mut file)| {
+			let mut contents = String::new();
+			match file.read_to_string(&mut contents) {
+				Ok(_) => {
+					file_read(observer.as_ref(), &path, contents.len());
+					Ok((path, contents))
+				},
+				Err(inner) => Err(ReadError { path, inner }),
+			}
+		}).collect()
+	}
+
+	/// Writes the contents of all the files to `writer`, each preceded by a `# /path/to/file` header, like `systemd-analyze cat-config`.
+	///
+	/// The files are separated by an empty line, and a newline is added to the end of a file that does not end with one.
+	///
+	/// # Errors
+	///
+	/// Returns an error if a file could not be read, in which case the error wraps a [`ReadError`] that contains the path of that file,
+	/// or if `writer` fails.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// uapi_config::SearchDirectories::modern_system()
+	///     .with_file_name("foobar.conf")
+	///     .find_files(Some(".conf"))
+	///     .unwrap()
+	///     .write_cat_config(&mut std::io::stdout().lock())
+	///     .unwrap();
+	/// ```
+	pub fn write_cat_config<W>(mut self, writer: &mut W) -> io::Result<()> where W: Write + ?Sized {
+		let observer = self.observer.take();
+		write_cat_config(self.map(|(path, file)| (path, file, None::<&str>)), observer.as_ref(), writer)
+	}
+}
+
+/// Reports that `num_bytes` bytes were read from the file at `path` to the [`FindOptions::observer`](crate::FindOptions::observer), if any.
+pub(crate) fn file_read(observer: Option<&Observer>, path: &Path, num_bytes: usize) {
+	if let Some(observer) = observer {
+		observer.0.file_read(path, num_bytes.try_into().unwrap_or(u64::MAX));
+	}
+}
+
+/// Writes the contents of `files` to `writer` like [`Files::write_cat_config`].
+/// Files with an annotation, such as built-in defaults, have it appended to the path in their header in parentheses.
+/// The files without an annotation are reported to `observer`, if any.
+pub(crate) fn write_cat_config<I, R, A, W>(files: I, observer: Option<&Observer>, writer: &mut W) -> io::Result<()>
+where
+	I: IntoIterator<Item = (PathBuf, R, Option<A>)>,
+	R: io::Read,
+	A: AsRef<str>,
+	W: Write + ?Sized,
+{
+	for (i, (path, mut file, annotation)) in files.into_iter().enumerate() {
+		if i > 0 {
+			writer.write_all(b"\n")?;
+		}
+		match &annotation {
+			Some(annotation) => writeln!(writer, "# {} ({})", path.display(), annotation.as_ref())?,
+			None => writeln!(writer, "# {}", path.display())?,
+		}
+
+		let mut contents = vec![];
+		if let Err(inner) = file.read_to_end(&mut contents) {
+			return Err(ReadError { path, inner }.into());
+		}
+		if annotation.is_none() {
+			file_read(observer, &path, contents.len());
+		}
+		writer.write_all(&contents)?;
+		if !contents.is_empty() && !contents.ends_with(b"\n") {
+			writer.write_all(b"\n")?;
+		}
+	}
+
+	Ok(())
+}
+
+/// The iterator of readers returned by [`Files::into_readers`].
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Readers {
+	inner: Files,
+}
+
+impl Iterator for Readers {
+	type Item = (PathBuf, BufReader<File>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (path, file) = self.inner.next()?;
+		Some((path, BufReader::new(file)))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl DoubleEndedIterator for Readers {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		let (path, file) = self.inner.next_back()?;
+		Some((path, BufReader::new(file)))
+	}
+}
+
+impl ExactSizeIterator for Readers {}
+
+impl std::iter::FusedIterator for Readers {}
+
+/// Error returned when a file that was found by the search could not be read.
+#[derive(Debug)]
+pub struct ReadError {
+	pub(crate) path: PathBuf,
+	pub(crate) inner: io::Error,
+}
+
+impl ReadError {
+	/// The path of the file that could not be read.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The underlying I/O error.
+	pub fn io_error(&self) -> &io::Error {
+		&self.inner
+	}
+}
+
+impl std::fmt::Display for ReadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "could not read {}", self.path.display())
+	}
+}
+
+impl std::error::Error for ReadError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.inner)
+	}
+}
+
+impl From<ReadError> for io::Error {
+	fn from(err: ReadError) -> Self {
+		Self::new(err.inner.kind(), err)
+	}
+}
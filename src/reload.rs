@@ -0,0 +1,495 @@
+//! Repeating a search when the application is asked to reload its config, and reporting whether the files have changed.
+
+use std::{
+	io,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc,
+		Arc,
+		PoisonError,
+		RwLock,
+	},
+	thread::JoinHandle,
+	time::Duration,
+};
+
+use crate::{Entries, MergeError, MergeFrom, Snapshot, SnapshotDiff};
+
+/// Repeats a search whenever it is triggered, such as by `SIGHUP`, and reports the new files only if they differ from the files
+/// that the previous search found.
+///
+/// Files are compared with [`Snapshot::diff`], so a file counts as changed if its size or modification time changed.
+///
+/// The first search always counts as a change, with every file that it found being added, so that the application can load
+/// its initial config in the same callback as later reloads.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let search = uapi_config::SearchDirectories::modern_system().with_file_name("foobar.conf");
+/// let mut reloader = uapi_config::Reloader::new(move || search.clone().find_entries(Some(".conf")));
+///
+/// # #[cfg(all(feature = "signal", target_os = "linux"))]
+/// reloader.run(uapi_config::ReloadSignal::install().unwrap(), |result| match result {
+///     Ok((entries, diff)) => {
+///         eprintln!("config changed:\n{diff}");
+///         // Load the new config from `entries`
+///     },
+///     Err(err) => eprintln!("could not search for config files: {err}"),
+/// });
+/// ```
+pub struct Reloader<S> {
+	search: S,
+	snapshot: Option<Snapshot>,
+}
+
+impl<S> Reloader<S> where S: FnMut() -> io::Result<Entries> {
+	/// Creates a reloader for the given search. The search is not performed until the first call to [`reload`](Self::reload)
+	/// or [`run`](Self::run).
+	pub fn new(search: S) -> Self {
+		Self {
+			search,
+			snapshot: None,
+		}
+	}
+
+	/// Performs the search, and returns the found files and their differences from the files that the previous search found,
+	/// or `None` if there are no differences.
+	///
+	/// # Errors
+	///
+	/// Any errors from the search are propagated. The result of the previous search is kept, so the next call compares against it.
+	pub fn reload(&mut self) -> io::Result<Option<(Entries, SnapshotDiff)>> {
+		let entries = (self.search)()?;
+		let snapshot = entries.snapshot();
+
+		let diff = self.snapshot.as_ref().unwrap_or(&Snapshot::empty()).diff(&snapshot);
+		let is_first = self.snapshot.is_none();
+		self.snapshot = Some(snapshot);
+
+		Ok((is_first || !diff.is_empty()).then_some((entries, diff)))
+	}
+
+	/// Performs the search, then again every time `trigger` yields, until it ends.
+	/// `on_change` is called with the result of every search that found different files than the previous one, and with every error.
+	///
+	/// `trigger` can be a [`ReloadSignal`](crate::ReloadSignal) to reload on `SIGHUP`, or any other iterator such as
+	/// the receiver of a [`std::sync::mpsc::channel`] to reload when the application sends to it.
+	pub fn run<T, F>(&mut self, trigger: T, mut on_change: F)
+	where
+		T: IntoIterator<Item = ()>,
+		F: FnMut(io::Result<(Entries, SnapshotDiff)>),
+	{
+		let mut trigger = trigger.into_iter();
+		loop {
+			match self.reload() {
+				Ok(Some(result)) => on_change(Ok(result)),
+				Ok(None) => (),
+				Err(err) => on_change(Err(err)),
+			}
+
+			if trigger.next().is_none() {
+				break;
+			}
+		}
+	}
+
+	/// The snapshot of the files that the last successful search found, if any.
+	pub fn snapshot(&self) -> Option<&Snapshot> {
+		self.snapshot.as_ref()
+	}
+}
+
+impl<S> std::fmt::Debug for Reloader<S> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Reloader").field("snapshot", &self.snapshot).finish_non_exhaustive()
+	}
+}
+
+/// A source of requests to reload the config, which [`ReloadingConfig`] waits on in a background thread.
+///
+/// Implemented for [`ReloadSignal`](crate::ReloadSignal) to reload on `SIGHUP`, for the receiver of a [`std::sync::mpsc::channel`]
+/// to reload when the application sends to it, and for `()` to only reload when [`ReloadingConfig::reload`] is called.
+pub trait ReloadTrigger: Send + 'static {
+	/// Blocks until a reload is requested or `timeout` elapses. `timeout` is never zero.
+	///
+	/// # Errors
+	///
+	/// Returns [`mpsc::RecvTimeoutError::Timeout`] if no reload was requested within `timeout`,
+	/// and [`mpsc::RecvTimeoutError::Disconnected`] if no more reloads will be requested.
+	fn wait_timeout(&mut self, timeout: Duration) -> Result<(), mpsc::RecvTimeoutError>;
+}
+
+impl ReloadTrigger for () {
+	fn wait_timeout(&mut self, _: Duration) -> Result<(), mpsc::RecvTimeoutError> {
+		Err(mpsc::RecvTimeoutError::Disconnected)
+	}
+}
+
+impl ReloadTrigger for mpsc::Receiver<()> {
+	fn wait_timeout(&mut self, timeout: Duration) -> Result<(), mpsc::RecvTimeoutError> {
+		self.recv_timeout(timeout)
+	}
+}
+
+/// A config that is loaded from the files found by a search, and loaded again in the background whenever the files change.
+///
+/// A background thread repeats the search with a [`Reloader`] every time `trigger` requests a reload or [`reload`](Self::reload) is called.
+/// Requests are debounced, so a burst of them, such as from a package manager that installs several dropins, causes a single reload
+/// once no request has arrived for the debounce duration. If the files have changed, they are merged into a new `T` with
+/// [`Files::load_merged`](crate::Files::load_merged), which replaces the current value. If the search or the merge fails,
+/// the current value is kept and the error is passed to `on_error`.
+///
+/// [`load`](Self::load) returns the current value. It only clones an [`Arc`] under an uncontended lock, so it is cheap enough to call
+/// on every request rather than holding on to the value. The value is kept in a [`RwLock`] rather than with a crate like `arc-swap`
+/// so that this crate keeps having no mandatory dependencies. The lock is only held to clone the `Arc` and to replace it after a reload,
+/// never while the files are searched for or merged, so `load` does not wait for reloads.
+///
+/// Dropping the `ReloadingConfig` stops the background threads, waits for them to exit and drops `trigger`, such as to restore
+/// the previous handler of `SIGHUP` for a [`ReloadSignal`](crate::ReloadSignal). Since `trigger` is checked for whether to stop
+/// every 100ms, this can take that long, or longer if a reload is in progress.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[derive(Default)]
+/// # struct Config;
+/// # impl uapi_config::MergeFrom for Config {
+/// #     type Error = std::io::Error;
+/// #     fn merge_from<R>(&mut self, _path: &std::path::Path, _reader: R) -> Result<(), Self::Error> { Ok(()) }
+/// # }
+/// let search = uapi_config::SearchDirectories::modern_system().with_file_name("foobar.conf");
+/// # #[cfg(all(feature = "signal", target_os = "linux"))]
+/// let config = uapi_config::ReloadingConfig::<Config>::spawn(
+///     move || search.clone().find_entries(Some(".conf")),
+///     uapi_config::ReloadSignal::install().unwrap(),
+///     std::time::Duration::from_millis(500),
+///     |err| eprintln!("could not reload config: {err}"),
+/// ).unwrap();
+///
+/// # #[cfg(all(feature = "signal", target_os = "linux"))]
+/// loop {
+///     let config = config.load();
+///     // Handle a request with `config`
+/// #   break;
+/// }
+/// ```
+pub struct ReloadingConfig<T> {
+	current: Arc<RwLock<Arc<T>>>,
+	sender: mpsc::Sender<Message>,
+	stop: Arc<AtomicBool>,
+	threads: Vec<JoinHandle<()>>,
+}
+
+/// A message to the background thread of [`ReloadingConfig`] that reloads the config.
+enum Message {
+	Reload,
+	Stop,
+}
+
+/// How often the background thread of [`ReloadingConfig`] that waits on the trigger checks whether it must stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl<T> ReloadingConfig<T> where T: MergeFrom + Default + Send + Sync + 'static {
+	/// Loads the config from the files that `search` finds, then starts the background threads that load it again when triggered.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the initial search or merge fails. The background threads are not started in that case.
+	pub fn spawn<S, R, F>(search: S, mut trigger: R, debounce: Duration, mut on_error: F) -> Result<Self, ReloadError<T::Error>>
+	where
+		S: FnMut() -> io::Result<Entries> + Send + 'static,
+		R: ReloadTrigger,
+		F: FnMut(ReloadError<T::Error>) + Send + 'static,
+	{
+		let mut reloader = Reloader::new(search);
+		let Some(value) = load(&mut reloader)? else {
+			unreachable!("the first search always counts as a change");
+		};
+		let current = Arc::new(RwLock::new(Arc::new(value)));
+
+		let (sender, receiver) = mpsc::channel();
+		let stop = Arc::new(AtomicBool::new(false));
+
+		let trigger_thread = {
+			let sender = sender.clone();
+			let stop = stop.clone();
+			std::thread::spawn(move || {
+				while !stop.load(Ordering::Acquire) {
+					match trigger.wait_timeout(STOP_POLL_INTERVAL) {
+						Ok(()) => if sender.send(Message::Reload).is_err() {
+							break;
+						},
+						Err(mpsc::RecvTimeoutError::Timeout) => (),
+						Err(mpsc::RecvTimeoutError::Disconnected) => break,
+					}
+				}
+			})
+		};
+
+		let reload_thread = {
+			let current = current.clone();
+			std::thread::spawn(move || {
+				while let Ok(Message::Reload) = receiver.recv() {
+					// Wait until the requests stop arriving.
+					loop {
+						match receiver.recv_timeout(debounce) {
+							Ok(Message::Reload) => (),
+							Ok(Message::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => return,
+							Err(mpsc::RecvTimeoutError::Timeout) => break,
+						}
+					}
+
+					match load(&mut reloader) {
+						Ok(Some(value)) => *current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(value),
+						Ok(None) => (),
+						Err(err) => on_error(err),
+					}
+				}
+			})
+		};
+
+		Ok(Self {
+			current,
+			sender,
+			stop,
+			threads: vec![trigger_thread, reload_thread],
+		})
+	}
+
+	/// Returns the current value of the config.
+	pub fn load(&self) -> Arc<T> {
+		self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+	}
+
+	/// Triggers a reload, like the `trigger` passed to [`spawn`](Self::spawn). The reload happens in the background after the debounce duration.
+	pub fn reload(&self) {
+		// The background thread only exits after `self` is dropped, so this cannot fail.
+		_ = self.sender.send(Message::Reload);
+	}
+}
+
+impl<T> Drop for ReloadingConfig<T> {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Release);
+		_ = self.sender.send(Message::Stop);
+		for thread in self.threads.drain(..) {
+			// A panic in `search` or `on_error` has already been reported by the panic hook.
+			_ = thread.join();
+		}
+	}
+}
+
+impl<T> std::fmt::Debug for ReloadingConfig<T> where T: std::fmt::Debug {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ReloadingConfig").field("current", &self.current).finish_non_exhaustive()
+	}
+}
+
+/// Repeats the search, and merges the files into a new `T` if they have changed.
+fn load<S, T>(reloader: &mut Reloader<S>) -> Result<Option<T>, ReloadError<T::Error>>
+where
+	S: FnMut() -> io::Result<Entries>,
+	T: MergeFrom + Default,
+{
+	let Some((entries, _)) = reloader.reload().map_err(ReloadError::Search)? else {
+		return Ok(None);
+	};
+	let files = entries.open_all().map_err(ReloadError::Search)?;
+	let value = files.load_merged().map_err(ReloadError::Merge)?;
+	Ok(Some(value))
+}
+
+/// Error returned by [`ReloadingConfig::spawn`] and passed to its `on_error` callback when the config could not be loaded.
+#[derive(Debug)]
+pub enum ReloadError<E> {
+	/// The search failed, or a file that it found could not be opened.
+	Search(io::Error),
+
+	/// A file could not be merged.
+	Merge(MergeError<E>),
+}
+
+impl<E> std::fmt::Display for ReloadError<E> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Search(_) => f.write_str("could not search for config files"),
+			Self::Merge(err) => err.fmt(f),
+		}
+	}
+}
+
+impl<E> std::error::Error for ReloadError<E> where E: std::error::Error + 'static {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Search(err) => Some(err),
+			Self::Merge(err) => err.source(),
+		}
+	}
+}
+
+#[cfg(all(feature = "signal", target_os = "linux"))]
+pub use signal::ReloadSignal;
+
+#[cfg(all(feature = "signal", target_os = "linux"))]
+mod signal {
+	use std::{
+		io::{self, Read as _},
+		os::{fd::AsRawFd as _, unix::net::UnixStream},
+		sync::{
+			atomic::{AtomicBool, AtomicI32, Ordering},
+			mpsc,
+			OnceLock,
+		},
+		time::Duration,
+	};
+
+	/// The pipe that the handler writes to. It is created by the first [`ReloadSignal`] and never closed,
+	/// so that a handler that is still running on another thread while a `ReloadSignal` is dropped cannot write to a closed fd,
+	/// or to an unrelated file that reused its number.
+	static PIPE: OnceLock<(UnixStream, UnixStream)> = OnceLock::new();
+
+	/// The write end of [`PIPE`], or -1 if it has not been created yet.
+	static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+	/// Whether a [`ReloadSignal`] is installed.
+	static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+	/// A handler for `SIGHUP`, the conventional signal that asks a daemon to reload its config.
+	///
+	/// This is an iterator that blocks until the process receives `SIGHUP`, for use as the trigger of [`Reloader::run`](crate::Reloader::run).
+	/// Signals that arrive while the previous one is being handled are coalesced into a single reload.
+	///
+	/// Only one handler can be installed at a time. Dropping it restores the previous disposition of `SIGHUP`.
+	/// The pipe that the handler uses to wake up the iterator is kept open for the rest of the process, to be reused by
+	/// the next `ReloadSignal`.
+	///
+	/// Requires the `signal` feature. Linux only.
+	pub struct ReloadSignal {
+		read: &'static UnixStream,
+		previous: libc::sigaction,
+	}
+
+	impl ReloadSignal {
+		/// Installs the handler for `SIGHUP`.
+		///
+		/// # Errors
+		///
+		/// Returns an error of kind [`io::ErrorKind::AlreadyExists`] if another `ReloadSignal` is installed.
+		/// Any other errors from installing the handler are propagated.
+		pub fn install() -> io::Result<Self> {
+			if INSTALLED.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+				return Err(io::Error::new(io::ErrorKind::AlreadyExists, "a ReloadSignal is already installed"));
+			}
+
+			match Self::install_inner() {
+				Ok(signal) => Ok(signal),
+				Err(err) => {
+					INSTALLED.store(false, Ordering::Release);
+					Err(err)
+				},
+			}
+		}
+
+		fn install_inner() -> io::Result<Self> {
+			let (read, write) = if let Some(pipe) = PIPE.get() { pipe } else {
+				let (read, write) = UnixStream::pair()?;
+				// The handler must not block if the pipe is full, since the pending byte already ensures a reload.
+				write.set_nonblocking(true)?;
+				// Only the installed `ReloadSignal` creates the pipe, so it cannot have been set concurrently.
+				PIPE.get_or_init(|| (read, write))
+			};
+			WRITE_FD.store(write.as_raw_fd(), Ordering::Release);
+
+			// Discard the signals that arrived after the previous `ReloadSignal` stopped reading.
+			read.set_nonblocking(true)?;
+			let mut buf = [0_u8; 64];
+			loop {
+				match (&*read).read(&mut buf) {
+					Ok(0) => break,
+					Ok(_) => (),
+					Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+					Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+					Err(err) => return Err(err),
+				}
+			}
+			read.set_nonblocking(false)?;
+
+			// SAFETY: `libc::sigaction` is a plain C struct, for which all zeroes is a valid value.
+			let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+			action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+			action.sa_flags = libc::SA_RESTART;
+			// SAFETY: `libc::sigaction` is a plain C struct, for which all zeroes is a valid value.
+			let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+			// SAFETY: `action` and `previous` are valid for reads and writes respectively.
+			if unsafe { libc::sigaction(libc::SIGHUP, &raw const action, &raw mut previous) } != 0 {
+				return Err(io::Error::last_os_error());
+			}
+
+			Ok(Self { read, previous })
+		}
+	}
+
+	impl ReloadSignal {
+		/// Blocks until the process receives `SIGHUP` or `timeout` elapses, and returns whether it received `SIGHUP`.
+		fn wait(&mut self, timeout: Option<Duration>) -> bool {
+			// The write end is never closed, so the read end cannot fail otherwise.
+			self.read.set_read_timeout(timeout).expect("could not set the timeout to wait for SIGHUP");
+
+			let mut buf = [0_u8; 64];
+			loop {
+				match self.read.read(&mut buf) {
+					Ok(_) => return true,
+					Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+					Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return false,
+					Err(err) => panic!("could not wait for SIGHUP: {err}"),
+				}
+			}
+		}
+	}
+
+	impl Iterator for ReloadSignal {
+		type Item = ();
+
+		/// Blocks until the process receives `SIGHUP`. Never returns `None`.
+		fn next(&mut self) -> Option<Self::Item> {
+			while !self.wait(None) {}
+			Some(())
+		}
+	}
+
+	impl crate::ReloadTrigger for ReloadSignal {
+		fn wait_timeout(&mut self, timeout: Duration) -> Result<(), mpsc::RecvTimeoutError> {
+			if self.wait(Some(timeout)) { Ok(()) } else { Err(mpsc::RecvTimeoutError::Timeout) }
+		}
+	}
+
+	impl Drop for ReloadSignal {
+		fn drop(&mut self) {
+			// SAFETY: `self.previous` was filled in by `libc::sigaction`.
+			unsafe { libc::sigaction(libc::SIGHUP, &raw const self.previous, std::ptr::null_mut()); }
+			// A handler that is still running on another thread writes to the pipe, which stays open.
+			// The next `ReloadSignal` discards what it wrote.
+			INSTALLED.store(false, Ordering::Release);
+		}
+	}
+
+	impl std::fmt::Debug for ReloadSignal {
+		fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+			f.debug_struct("ReloadSignal").finish_non_exhaustive()
+		}
+	}
+
+	extern "C" fn handler(_: libc::c_int) {
+		let fd = WRITE_FD.load(Ordering::Acquire);
+		if fd >= 0 {
+			// SAFETY: `fd` is the write end of `PIPE`, which is never closed. `write(2)` and `__errno_location` are async-signal-safe,
+			// and `errno` is restored so that the interrupted code does not observe the result of the write.
+			unsafe {
+				let errno = *libc::__errno_location();
+				libc::write(fd, b"\x01".as_ptr().cast(), 1);
+				*libc::__errno_location() = errno;
+			}
+		}
+	}
+}
@@ -0,0 +1,222 @@
+//! Rendering the errors of this crate and the files skipped by searches as `miette` diagnostics.
+
+use std::fmt::Display;
+
+use miette::{Diagnostic, LabeledSpan, Severity, SourceCode};
+
+use crate::{
+	ConfigNotFoundError,
+	FileTooLargeError,
+	IncludeError,
+	InvalidDropinSuffixError,
+	InvalidPathError,
+	InvalidSearchDirectoryError,
+	MergeError,
+	PermissionError,
+	PermissionViolation,
+	ReadError,
+	ReloadError,
+	SkipReason,
+	SkipWarning,
+	TooManyDropinsError,
+};
+
+impl Diagnostic for InvalidPathError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::invalid_path"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("use an absolute path without `..` components"))
+	}
+}
+
+impl Diagnostic for InvalidSearchDirectoryError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::invalid_search_directory"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("use an absolute path without `..` components"))
+	}
+}
+
+impl Diagnostic for InvalidDropinSuffixError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::invalid_dropin_suffix"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("use a file extension like `.conf`"))
+	}
+}
+
+impl Diagnostic for TooManyDropinsError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::too_many_dropins"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new(format!("remove the dropins that are no longer needed from {}", self.directory().display())))
+	}
+}
+
+impl Diagnostic for FileTooLargeError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::file_too_large"))
+	}
+}
+
+impl Diagnostic for ConfigNotFoundError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::config_not_found"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		let search_directory = self.search_directories().last()?;
+		Some(Box::new(format!("create {}", search_directory.join(self.name()).display())))
+	}
+}
+
+impl Diagnostic for PermissionError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::permission"))
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		let path = self.path().display();
+		match self.violation() {
+			PermissionViolation::Owner => Some(Box::new(format!("change the owner of {path} with `chown`"))),
+			PermissionViolation::GroupWritable => Some(Box::new(format!("remove the write permission of the group with `chmod g-w {path}`"))),
+			PermissionViolation::WorldWritable => Some(Box::new(format!("remove the write permission of other users with `chmod o-w {path}`"))),
+		}
+	}
+}
+
+impl Diagnostic for ReadError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new("uapi_config::read"))
+	}
+}
+
+impl Diagnostic for IncludeError {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		match self {
+			Self::Cycle { .. } => Some(Box::new("uapi_config::include_cycle")),
+			Self::Symlink { .. } => Some(Box::new("uapi_config::include_symlink")),
+			Self::TooDeep { .. } => Some(Box::new("uapi_config::include_too_deep")),
+		}
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		match self {
+			Self::Cycle { .. } => Some(Box::new("remove the include directive that closes the cycle")),
+			Self::Symlink { .. } => Some(Box::new("include the target of the symlink instead, or relax the symlink policy of the search")),
+			Self::TooDeep { .. } => None,
+		}
+	}
+}
+
+/// Forwards to the error of the file, so that its source code and labels are rendered under the path of the file.
+impl<E> Diagnostic for MergeError<E> where E: Diagnostic + 'static {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		self.inner().code()
+	}
+
+	fn severity(&self) -> Option<Severity> {
+		self.inner().severity()
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		self.inner().help()
+	}
+
+	fn url<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		self.inner().url()
+	}
+
+	fn source_code(&self) -> Option<&dyn SourceCode> {
+		self.inner().source_code()
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		self.inner().labels()
+	}
+}
+
+impl<E> Diagnostic for ReloadError<E> where E: Diagnostic + 'static {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		match self {
+			Self::Search(_) => Some(Box::new("uapi_config::search")),
+			Self::Merge(err) => err.code(),
+		}
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		match self {
+			Self::Search(_) => None,
+			Self::Merge(err) => err.help(),
+		}
+	}
+
+	fn source_code(&self) -> Option<&dyn SourceCode> {
+		match self {
+			Self::Search(_) => None,
+			Self::Merge(err) => err.source_code(),
+		}
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+		match self {
+			Self::Search(_) => None,
+			Self::Merge(err) => err.labels(),
+		}
+	}
+}
+
+/// Files that were skipped because of their names or because they are overridden are advice, since that is usually intended.
+/// The other reasons are warnings, since they usually mean that the file was meant to be used but is broken.
+impl Diagnostic for SkipWarning {
+	fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		Some(Box::new(match self.reason() {
+			SkipReason::SuffixMismatch => "uapi_config::skipped::suffix_mismatch",
+			SkipReason::Hidden => "uapi_config::skipped::hidden",
+			SkipReason::Backup => "uapi_config::skipped::backup",
+			SkipReason::RegexMismatch => "uapi_config::skipped::regex_mismatch",
+			SkipReason::NotRegularFile => "uapi_config::skipped::not_regular_file",
+			SkipReason::Symlink => "uapi_config::skipped::symlink",
+			SkipReason::NotFound => "uapi_config::skipped::not_found",
+			SkipReason::NotExecutable => "uapi_config::skipped::not_executable",
+			SkipReason::Shadowed => "uapi_config::skipped::shadowed",
+			SkipReason::Duplicate => "uapi_config::skipped::duplicate",
+			SkipReason::Filesystem => "uapi_config::skipped::filesystem",
+			SkipReason::TimedOut => "uapi_config::skipped::timed_out",
+		}))
+	}
+
+	fn severity(&self) -> Option<Severity> {
+		Some(match self.reason() {
+			SkipReason::Hidden |
+			SkipReason::Backup |
+			SkipReason::RegexMismatch |
+			SkipReason::NotFound |
+			SkipReason::Shadowed |
+			SkipReason::Duplicate => Severity::Advice,
+
+			SkipReason::SuffixMismatch |
+			SkipReason::NotRegularFile |
+			SkipReason::Symlink |
+			SkipReason::NotExecutable |
+			SkipReason::Filesystem |
+			SkipReason::TimedOut => Severity::Warning,
+		})
+	}
+
+	fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+		match self.reason() {
+			SkipReason::SuffixMismatch => Some(Box::new("rename the file so that its name ends with the dropin suffix, such as `.conf`")),
+			SkipReason::NotExecutable => Some(Box::new(format!("make the file executable with `chmod +x {}`", self.path().display()))),
+			_ => None,
+		}
+	}
+}
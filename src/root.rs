@@ -0,0 +1,104 @@
+//! Path resolution that is contained within a root directory, for [`SearchDirectories::chroot_contained`](crate::SearchDirectories::chroot_contained).
+//!
+//! Without the `rustix` feature or on other platforms, [`Root`] is uninhabited, so the search never has one
+//! and always resolves paths with the regular filesystem APIs.
+
+use std::{
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+};
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+pub(crate) struct Root {
+	path: PathBuf,
+	fd: std::os::fd::OwnedFd,
+}
+
+#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+pub(crate) enum Root {}
+
+#[cfg(all(feature = "rustix", target_os = "linux"))]
+impl Root {
+	pub(crate) fn open(path: PathBuf) -> io::Result<Self> {
+		use rustix::fs::{Mode, OFlags};
+
+		let fd = rustix::fs::open(&path, OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC, Mode::empty())?;
+		Ok(Self { path, fd })
+	}
+
+	pub(crate) fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Opens `path`, which must be under the root, with `openat2(RESOLVE_IN_ROOT | RESOLVE_NO_MAGICLINKS)`
+	/// so that `..` components and absolute symlinks cannot escape the root.
+	pub(crate) fn resolve(&self, path: &Path, oflags: rustix::fs::OFlags) -> io::Result<std::os::fd::OwnedFd> {
+		use rustix::fs::{Mode, OFlags, ResolveFlags};
+
+		let relative = path.strip_prefix(&self.path).map_err(|_| io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("{} is not under the contained root {}", path.display(), self.path.display()),
+		))?;
+		let relative = if relative.as_os_str().is_empty() { Path::new(".") } else { relative };
+
+		let fd = rustix::fs::openat2(
+			&self.fd,
+			relative,
+			oflags | OFlags::CLOEXEC,
+			Mode::empty(),
+			ResolveFlags::IN_ROOT | ResolveFlags::NO_MAGICLINKS,
+		)?;
+		Ok(fd)
+	}
+
+	/// Like [`fs::metadata`] if `follow_symlinks` is `true`, else like [`fs::symlink_metadata`].
+	pub(crate) fn metadata(&self, path: &Path, follow_symlinks: bool) -> io::Result<fs::Metadata> {
+		use rustix::fs::OFlags;
+
+		let oflags = if follow_symlinks { OFlags::PATH } else { OFlags::PATH | OFlags::NOFOLLOW };
+		File::from(self.resolve(path, oflags)?).metadata()
+	}
+
+	/// Opens the file at `path` for reading, without blocking if it is a FIFO like [`Dir::open_file`](crate::Dir::open_file).
+	pub(crate) fn open_file(&self, path: &Path) -> io::Result<File> {
+		use rustix::fs::OFlags;
+
+		Ok(self.resolve(path, OFlags::RDONLY | OFlags::NONBLOCK)?.into())
+	}
+
+	/// Like [`fs::canonicalize`], except that symlinks are resolved within the root.
+	///
+	/// The returned path is the path on the host, ie it starts with the path of the root.
+	pub(crate) fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		use std::os::fd::AsRawFd as _;
+
+		let fd = self.resolve(path, rustix::fs::OFlags::PATH)?;
+		fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+	}
+}
+
+#[cfg(not(all(feature = "rustix", target_os = "linux")))]
+impl Root {
+	pub(crate) fn path(&self) -> &Path {
+		match *self {}
+	}
+
+	pub(crate) fn metadata(&self, _: &Path, _: bool) -> io::Result<fs::Metadata> {
+		match *self {}
+	}
+
+	pub(crate) fn open_file(&self, _: &Path) -> io::Result<File> {
+		match *self {}
+	}
+
+	pub(crate) fn canonicalize(&self, _: &Path) -> io::Result<PathBuf> {
+		match *self {}
+	}
+}
+
+impl std::fmt::Debug for Root {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("Root").field(&self.path()).finish()
+	}
+}
@@ -0,0 +1,107 @@
+//! Detecting whether the process is confined in a Flatpak, Snap or container, and the search directories that are visible from within it.
+
+use std::{
+	ffi::OsString,
+	path::{Path, PathBuf},
+};
+
+use crate::SearchDirectories;
+
+/// The kind of sandbox that the process is confined in, which determines where the host's configuration is visible.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Sandbox {
+	/// A Flatpak app, detected by the existence of `/.flatpak-info`.
+	///
+	/// The app's `/usr` and `/etc` are those of its runtime. The host's `/usr` and `/etc` are visible under `/run/host`
+	/// if the app has the `host-os` and `host-etc` filesystem permissions respectively.
+	Flatpak,
+
+	/// A Snap, detected by `$SNAP` being set.
+	Snap {
+		/// The directory that the snap is mounted at, `$SNAP`.
+		snap: PathBuf,
+
+		/// The directory for the snap's system-wide writable data, `$SNAP_DATA`, if set.
+		snap_data: Option<PathBuf>,
+	},
+
+	/// Any other container, such as one run by Docker, Podman or `systemd-nspawn`, detected by `$container` being set
+	/// or by the existence of `/run/.containerenv` or `/.dockerenv`.
+	///
+	/// The container's root is the container image, so the regular search directories apply.
+	Container,
+}
+
+impl Sandbox {
+	/// Detects the sandbox that the process is confined in, or returns `None` if it is not confined.
+	pub fn detect() -> Option<Self> {
+		if Path::new("/.flatpak-info").exists() {
+			return Some(Self::Flatpak);
+		}
+
+		if let Some(snap) = non_empty_var("SNAP") {
+			return Some(Self::Snap {
+				snap: snap.into(),
+				snap_data: non_empty_var("SNAP_DATA").map(Into::into),
+			});
+		}
+
+		if non_empty_var("container").is_some() || Path::new("/run/.containerenv").exists() || Path::new("/.dockerenv").exists() {
+			return Some(Self::Container);
+		}
+
+		None
+	}
+}
+
+fn non_empty_var(name: &str) -> Option<OsString> {
+	std::env::var_os(name).filter(|value| !value.is_empty())
+}
+
+impl SearchDirectories<'_> {
+	/// Start with the default search directory roots for a system application on a modern Linux distribution,
+	/// as visible from within the sandbox that the process is confined in, if any. See [`Sandbox::detect`].
+	pub fn sandboxed_system() -> Self {
+		Self::system_for_sandbox(Sandbox::detect().as_ref())
+	}
+
+	/// Start with the default search directory roots for a system application on a modern Linux distribution,
+	/// as visible from within the given sandbox. If `sandbox` is `None`, this is the same as [`SearchDirectories::modern_system`].
+	///
+	/// - For [`Sandbox::Flatpak`], the search directories are `/usr/etc`, `/etc` and `/app/etc` for the runtime and the app,
+	///   then `/run/host/usr/etc`, `/run`, `/run/host/etc` for the host.
+	///
+	/// - For [`Sandbox::Snap`], the search directories are `$SNAP/etc` for the snap, then `/usr/etc`, `/run`, `/etc` for the host,
+	///   then `$SNAP_DATA` for the overrides that are specific to the snap.
+	///
+	/// - For [`Sandbox::Container`], the search directories are the same as [`SearchDirectories::modern_system`].
+	///
+	/// Paths from the environment that are not absolute are ignored.
+	pub fn system_for_sandbox(sandbox: Option<&Sandbox>) -> Self {
+		match sandbox {
+			Some(Sandbox::Flatpak) => [
+				"/usr/etc",
+				"/etc",
+				"/app/etc",
+				"/run/host/usr/etc",
+				"/run",
+				"/run/host/etc",
+			].into_iter().map(|path| Path::new(path).into()).collect(),
+
+			Some(Sandbox::Snap { snap, snap_data }) => {
+				let mut result = Self::empty();
+				// If the value fails validation, ignore it.
+				_ = result.push(snap.join("etc").into());
+				for path in ["/usr/etc", "/run", "/etc"] {
+					_ = result.push(Path::new(path).into());
+				}
+				if let Some(snap_data) = snap_data {
+					_ = result.push(snap_data.clone().into());
+				}
+				result
+			},
+
+			Some(Sandbox::Container) | None => Self::modern_system(),
+		}
+	}
+}
@@ -0,0 +1,299 @@
+//! Recording the files that a search found, to detect later whether they have changed.
+
+use std::{
+	fs,
+	io,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+#[cfg(feature = "sha2")]
+use crate::platform;
+use crate::{Entries, EntryMetadata};
+
+/// The paths and metadata of the files that a search found, for storing in a state file and checking later
+/// whether the files have changed, such as to skip re-validating the config on restart if they have not.
+///
+/// Created with [`Entries::snapshot`], or with `Entries::snapshot_with_digests` if the `sha2` feature is enabled.
+/// If the `serde` feature is enabled, this implements `serde::Serialize` and `serde::Deserialize`.
+///
+/// [`is_stale`](Self::is_stale) only checks the files that were found. Files that would now be found by the search
+/// but were not found before, such as a new dropin, are detected by comparing the snapshot with a snapshot of a new search.
+///
+/// # Examples
+///
+/// ```rust
+/// let snapshot =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .find_entries(Some(".conf"))
+///     .unwrap()
+///     .snapshot();
+///
+/// // Later...
+///
+/// let new_snapshot =
+///     uapi_config::SearchDirectories::modern_system()
+///     .with_file_name("foobar.conf")
+///     .find_entries(Some(".conf"))
+///     .unwrap()
+///     .snapshot();
+/// if snapshot.is_stale().unwrap() || snapshot != new_snapshot {
+///     // Re-validate the config
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Snapshot {
+	files: Vec<SnapshotFile>,
+}
+
+/// A file in a [`Snapshot`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SnapshotFile {
+	path: PathBuf,
+	size: u64,
+	modified: Option<SystemTime>,
+	/// The device and inode numbers of the file, if the platform provides them.
+	file_id: Option<(u64, u64)>,
+	#[cfg(feature = "sha2")]
+	digest: Option<[u8; 32]>,
+}
+
+impl Snapshot {
+	/// A snapshot of a search that found no files.
+	pub(crate) fn empty() -> Self {
+		Self { files: vec![] }
+	}
+
+	/// The paths of the files, in the order that they were found.
+	pub fn paths(&self) -> impl DoubleEndedIterator<Item = &Path> + ExactSizeIterator {
+		self.files.iter().map(|file| &*file.path)
+	}
+
+	/// The files, in the order that they were found.
+	pub fn files(&self) -> impl DoubleEndedIterator<Item = &SnapshotFile> + ExactSizeIterator {
+		self.files.iter()
+	}
+
+	/// Returns a copy of this snapshot with `root` removed from the start of the paths of the files, such as to compare
+	/// the snapshot of a search within an OS image with the snapshot of the same search on the host.
+	/// The resulting paths are absolute. Paths that do not start with `root` are unchanged.
+	#[must_use]
+	pub fn relative_to(&self, root: &Path) -> Self {
+		let files =
+			self.files.iter()
+			.map(|file| {
+				let path = match file.path.strip_prefix(root) {
+					Ok(path) => Path::new("/").join(path),
+					Err(_) => file.path.clone(),
+				};
+				SnapshotFile { path, ..file.clone() }
+			})
+			.collect();
+		Self { files }
+	}
+
+	/// Returns the differences between this snapshot and a newer snapshot `new`, matching files by their paths.
+	///
+	/// A file that is in both snapshots has changed if both snapshots have its digest and the digests differ,
+	/// or otherwise if its size or modification time differ. Its device and inode numbers are not compared,
+	/// since they differ between snapshots of different roots.
+	pub fn diff(&self, new: &Self) -> SnapshotDiff {
+		let old_files: std::collections::HashMap<_, _> = self.files.iter().map(|file| (&*file.path, file)).collect();
+		let new_files: std::collections::HashMap<_, _> = new.files.iter().map(|file| (&*file.path, file)).collect();
+
+		let added = new.paths().filter(|path| !old_files.contains_key(path)).map(ToOwned::to_owned).collect();
+		let removed = self.paths().filter(|path| !new_files.contains_key(path)).map(ToOwned::to_owned).collect();
+
+		let changed =
+			new.files.iter()
+			.filter(|new_file| old_files.get(&*new_file.path).is_some_and(|old_file| old_file.is_changed(new_file)))
+			.map(|file| file.path.clone())
+			.collect();
+
+		// The files that are in both snapshots, in the order of each snapshot. A file has moved if its position differs.
+		let old_common: Vec<&Path> = self.paths().filter(|path| new_files.contains_key(path)).collect();
+		let new_common: Vec<&Path> = new.paths().filter(|path| old_files.contains_key(path)).collect();
+		let reordered =
+			new_common.iter().zip(&old_common)
+			.filter(|(new_path, old_path)| new_path != old_path)
+			.map(|(new_path, _)| (*new_path).to_owned())
+			.collect();
+
+		SnapshotDiff { added, removed, changed, reordered }
+	}
+
+	/// Returns `true` if any of the files no longer exists or is no longer a regular file, or if its size, modification time
+	/// or device and inode numbers have changed. If the snapshot has digests, the contents of the files are also compared.
+	///
+	/// # Errors
+	///
+	/// Any I/O errors from looking up and reading the files, other than the files not existing, are propagated.
+	pub fn is_stale(&self) -> io::Result<bool> {
+		for file in &self.files {
+			let metadata = match fs::metadata(&file.path) {
+				Ok(metadata) => metadata,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+				Err(err) => return Err(err),
+			};
+			if !metadata.is_file() || SnapshotFile::new(file.path.clone(), &(&metadata).into()) != file.without_digest() {
+				return Ok(true);
+			}
+
+			#[cfg(feature = "sha2")]
+			if let Some(digest) = file.digest {
+				let mut contents = match platform::open_regular_file(&file.path) {
+					Ok(contents) => contents,
+					Err(err) if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::InvalidData) => return Ok(true),
+					Err(err) => return Err(err),
+				};
+				if sha256(&mut contents)? != digest {
+					return Ok(true);
+				}
+			}
+		}
+
+		Ok(false)
+	}
+}
+
+impl SnapshotFile {
+	fn new(path: PathBuf, metadata: &EntryMetadata) -> Self {
+		Self {
+			path,
+			size: metadata.size(),
+			modified: metadata.modified(),
+			file_id: metadata.file_id(),
+			#[cfg(feature = "sha2")]
+			digest: None,
+		}
+	}
+
+	fn is_changed(&self, new: &Self) -> bool {
+		#[cfg(feature = "sha2")]
+		if let (Some(old_digest), Some(new_digest)) = (&self.digest, &new.digest) {
+			return old_digest != new_digest;
+		}
+
+		self.size != new.size || self.modified != new.modified
+	}
+
+	fn without_digest(&self) -> Self {
+		Self {
+			#[cfg(feature = "sha2")]
+			digest: None,
+			..self.clone()
+		}
+	}
+
+	/// The path of the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The size of the file in bytes.
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+
+	/// The modification time of the file, if the platform provides it.
+	pub fn modified(&self) -> Option<SystemTime> {
+		self.modified
+	}
+
+	/// The SHA-256 digest of the contents of the file, if the snapshot was created with [`Entries::snapshot_with_digests`].
+	#[cfg(feature = "sha2")]
+	pub fn digest(&self) -> Option<&[u8; 32]> {
+		self.digest.as_ref()
+	}
+}
+
+/// The differences between two [`Snapshot`]s, returned by [`Snapshot::diff`].
+///
+/// The [`Display`](std::fmt::Display) impl renders one line per difference, prefixed with `+` for added files, `-` for removed files,
+/// `~` for changed files and `>` for reordered files.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SnapshotDiff {
+	added: Vec<PathBuf>,
+	removed: Vec<PathBuf>,
+	changed: Vec<PathBuf>,
+	reordered: Vec<PathBuf>,
+}
+
+impl SnapshotDiff {
+	/// The files that are only in the new snapshot, in the order of the new snapshot.
+	pub fn added(&self) -> &[PathBuf] {
+		&self.added
+	}
+
+	/// The files that are only in the old snapshot, in the order of the old snapshot.
+	pub fn removed(&self) -> &[PathBuf] {
+		&self.removed
+	}
+
+	/// The files that are in both snapshots and have changed, in the order of the new snapshot.
+	pub fn changed(&self) -> &[PathBuf] {
+		&self.changed
+	}
+
+	/// The files that are in both snapshots but have a different precedence relative to the other files in both snapshots,
+	/// such as because dropins were renamed, in the order of the new snapshot.
+	pub fn reordered(&self) -> &[PathBuf] {
+		&self.reordered
+	}
+
+	/// Returns `true` if the snapshots have no differences.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.reordered.is_empty()
+	}
+}
+
+impl std::fmt::Display for SnapshotDiff {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (prefix, paths) in [('+', &self.added), ('-', &self.removed), ('~', &self.changed), ('>', &self.reordered)] {
+			for path in paths {
+				writeln!(f, "{prefix} {}", path.display())?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Entries {
+	/// Returns a [`Snapshot`] of the paths and metadata of the remaining entries. The files are not opened.
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			files: self.main_file.iter().chain(self.dropins.as_slice()).map(|entry| SnapshotFile::new(entry.path.clone(), &entry.metadata)).collect(),
+		}
+	}
+
+	/// Same as [`snapshot`](Self::snapshot), but also records the SHA-256 digests of the contents of the files,
+	/// so that [`Snapshot::is_stale`] detects changes that do not change the metadata of the files.
+	///
+	/// # Errors
+	///
+	/// Any I/O errors from opening and reading the files are propagated. See [`Entry::open`](crate::Entry::open).
+	#[cfg(feature = "sha2")]
+	pub fn snapshot_with_digests(&self) -> io::Result<Snapshot> {
+		let files =
+			self.main_file.iter().chain(self.dropins.as_slice())
+			.map(|entry| {
+				let mut file = SnapshotFile::new(entry.path.clone(), &entry.metadata);
+				file.digest = Some(sha256(&mut entry.open()?)?);
+				Ok(file)
+			})
+			.collect::<io::Result<_>>()?;
+		Ok(Snapshot { files })
+	}
+}
+
+#[cfg(feature = "sha2")]
+fn sha256<R>(reader: &mut R) -> io::Result<[u8; 32]> where R: io::Read {
+	use sha2::Digest as _;
+
+	let mut hasher = sha2::Sha256::new();
+	io::copy(reader, &mut hasher)?;
+	Ok(hasher.finalize().into())
+}
@@ -0,0 +1,117 @@
+use std::cmp::Ordering;
+
+/// Compares two byte strings like glibc's `strverscmp`.
+///
+/// Runs of digits are compared as numbers, except that runs with leading zeros are treated as fractional parts,
+/// so for example `"9" < "10"` and `"000" < "00" < "01" < "010" < "09" < "0" < "1"`.
+pub(crate) fn strverscmp(s1: &[u8], s2: &[u8]) -> Ordering {
+	// This is a port of the glibc implementation, including its state machine.
+
+	const S_N: usize = 0x0;
+	const S_I: usize = 0x3;
+	const S_F: usize = 0x6;
+	const S_Z: usize = 0x9;
+
+	const CMP: i8 = 2;
+	const LEN: i8 = 3;
+
+	const NEXT_STATE: [usize; 12] = [
+		/* state    x    d    0  */
+		/* S_N */  S_N, S_I, S_Z,
+		/* S_I */  S_N, S_I, S_I,
+		/* S_F */  S_N, S_F, S_F,
+		/* S_Z */  S_N, S_F, S_Z,
+	];
+
+	const RESULT_TYPE: [i8; 36] = [
+		/* state   x/x  x/d  x/0  d/x  d/d  d/0  0/x  0/d  0/0  */
+		/* S_N */  CMP, CMP, CMP, CMP, LEN, CMP, CMP, CMP, CMP,
+		/* S_I */  CMP, -1,  -1,  1,   LEN, LEN, 1,   LEN, LEN,
+		/* S_F */  CMP, CMP, CMP, CMP, CMP, CMP, CMP, CMP, CMP,
+		/* S_Z */  CMP, 1,   1,   -1,  CMP, CMP, -1,  CMP, CMP,
+	];
+
+	// The C implementation relies on the strings being NUL-terminated, so treat the end of the slice as a NUL.
+	let at = |s: &[u8], i: usize| s.get(i).copied().unwrap_or(b'\0');
+	let class = |c: u8| usize::from(c == b'0') + usize::from(c.is_ascii_digit());
+
+	let mut p1 = 0;
+	let mut p2 = 0;
+
+	let mut c1 = at(s1, p1);
+	let mut c2 = at(s2, p2);
+	p1 += 1;
+	p2 += 1;
+
+	let mut state = S_N + class(c1);
+
+	while c1 == c2 {
+		if c1 == b'\0' {
+			return Ordering::Equal;
+		}
+
+		state = NEXT_STATE[state];
+		c1 = at(s1, p1);
+		c2 = at(s2, p2);
+		p1 += 1;
+		p2 += 1;
+		state += class(c1);
+	}
+
+	match RESULT_TYPE[state * 3 + class(c2)] {
+		CMP => c1.cmp(&c2),
+
+		LEN => {
+			loop {
+				let d1 = at(s1, p1);
+				p1 += 1;
+				if !d1.is_ascii_digit() {
+					break;
+				}
+
+				let d2 = at(s2, p2);
+				p2 += 1;
+				if !d2.is_ascii_digit() {
+					return Ordering::Greater;
+				}
+			}
+
+			if at(s2, p2).is_ascii_digit() {
+				Ordering::Less
+			}
+			else {
+				c1.cmp(&c2)
+			}
+		},
+
+		result => result.cmp(&0),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cmp::Ordering;
+
+	#[test]
+	fn strverscmp() {
+		// From the glibc manual.
+		let sorted = ["000", "00", "01", "010", "09", "0", "1", "9", "10"];
+		for (i, a) in sorted.iter().enumerate() {
+			for (j, b) in sorted.iter().enumerate() {
+				assert_eq!(super::strverscmp(a.as_bytes(), b.as_bytes()), i.cmp(&j), "{a:?} {b:?}");
+			}
+		}
+
+		for (a, b, expected) in [
+			("1-a.conf", "10-c.conf", Ordering::Less),
+			("2-b.conf", "10-c.conf", Ordering::Less),
+			("foo-1.2.conf", "foo-1.10.conf", Ordering::Less),
+			("a.conf", "b.conf", Ordering::Less),
+			("a", "a.conf", Ordering::Less),
+			("a.conf", "a.conf", Ordering::Equal),
+		] {
+			assert_eq!(super::strverscmp(a.as_bytes(), b.as_bytes()), expected, "{a:?} {b:?}");
+			assert_eq!(super::strverscmp(b.as_bytes(), a.as_bytes()), expected.reverse(), "{b:?} {a:?}");
+		}
+	}
+}
@@ -0,0 +1,210 @@
+//! Helpers for testing how config files are found, such as in the tests of applications that use this crate.
+
+use std::{
+	io,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::SearchDirectories;
+
+/// A directory tree of config files under a temporary root directory, for testing how config files are found
+/// and comparing the results with other implementations of the spec.
+///
+/// All paths given to a `Fixture` are relative to its root, and may optionally start with `/`.
+/// The root directory is removed when the `Fixture` is dropped.
+///
+/// Since this is meant to be used in tests, the methods that create files panic if they fail, instead of returning errors.
+///
+/// # Examples
+///
+/// ```rust
+/// let fixture =
+///     uapi_config::test_support::Fixture::new()
+///     .file("/usr/etc/foobar.conf", "")
+///     .file("/etc/foobar.conf.d/override.conf", "")
+///     .mask("/etc/foobar.conf.d/vendor.conf");
+///
+/// let files: Vec<_> =
+///     fixture.search_directories(["/usr/etc", "/run", "/etc"])
+///     .with_file_name("foobar.conf")
+///     .find_files(Some(".conf"))
+///     .unwrap()
+///     .map(|(path, _)| fixture.relative_path(&path).into_owned())
+///     .collect();
+/// assert_eq!(files, [
+///     std::path::Path::new("/usr/etc/foobar.conf"),
+///     std::path::Path::new("/etc/foobar.conf.d/override.conf"),
+/// ]);
+/// ```
+#[derive(Debug)]
+pub struct Fixture {
+	root: PathBuf,
+}
+
+impl Fixture {
+	/// Creates a new empty root directory under [`std::env::temp_dir`].
+	///
+	/// # Panics
+	///
+	/// Panics if the directory could not be created.
+	#[allow(clippy::new_without_default)] // Creating a directory is not a sensible default.
+	pub fn new() -> Self {
+		static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+		let root = std::env::temp_dir().join(format!("uapi-config-fixture-{}-{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+		match std::fs::remove_dir_all(&root) {
+			Ok(()) => (),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => panic!("could not remove {}: {err}", root.display()),
+		}
+		create_dir_all(&root);
+		Self { root }
+	}
+
+	/// The root directory.
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	/// Returns the full path of `path` under the root directory.
+	pub fn path<P>(&self, path: P) -> PathBuf where P: AsRef<Path> {
+		let path = path.as_ref();
+		self.root.join(path.strip_prefix("/").unwrap_or(path))
+	}
+
+	/// Returns `path` relative to the root directory as an absolute path, ie the path that it would have if the root directory were `/`.
+	/// If `path` is not under the root directory, it is returned unchanged.
+	pub fn relative_path<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, Path> {
+		match path.strip_prefix(&self.root) {
+			Ok(path) => Path::new("/").join(path).into(),
+			Err(_) => path.into(),
+		}
+	}
+
+	/// Creates a file at `path` with the given contents, creating its parent directories if necessary.
+	///
+	/// # Panics
+	///
+	/// Panics if the file or its parent directories could not be created.
+	#[must_use]
+	pub fn file<P, C>(self, path: P, contents: C) -> Self where P: AsRef<Path>, C: AsRef<[u8]> {
+		let path = self.path(path);
+		create_parent(&path);
+		if let Err(err) = std::fs::write(&path, contents) {
+			panic!("could not create {}: {err}", path.display());
+		}
+		self
+	}
+
+	/// Creates a directory at `path`, creating its parent directories if necessary.
+	///
+	/// # Panics
+	///
+	/// Panics if the directories could not be created.
+	#[must_use]
+	pub fn dir<P>(self, path: P) -> Self where P: AsRef<Path> {
+		create_dir_all(&self.path(path));
+		self
+	}
+
+	/// Creates a symlink at `path` that points to `target`, creating its parent directories if necessary.
+	///
+	/// `target` is used as-is, so an absolute target is not relative to the root directory.
+	///
+	/// # Panics
+	///
+	/// Panics if the symlink or its parent directories could not be created.
+	#[cfg(unix)]
+	#[must_use]
+	pub fn symlink<P, T>(self, path: P, target: T) -> Self where P: AsRef<Path>, T: AsRef<Path> {
+		let path = self.path(path);
+		create_parent(&path);
+		if let Err(err) = std::os::unix::fs::symlink(target, &path) {
+			panic!("could not create {}: {err}", path.display());
+		}
+		self
+	}
+
+	/// Masks the file at `path` by creating a symlink to `/dev/null`, the way that `systemctl mask` does,
+	/// creating its parent directories if necessary.
+	///
+	/// Such a symlink is not a regular file, so the search ignores it. Note that this means it does not hide a file
+	/// with the same name in a search directory or dropin directory of lower precedence.
+	///
+	/// # Panics
+	///
+	/// Panics if the symlink or its parent directories could not be created.
+	#[cfg(unix)]
+	#[must_use]
+	pub fn mask<P>(self, path: P) -> Self where P: AsRef<Path> {
+		self.symlink(path, "/dev/null")
+	}
+
+	/// Returns the given search directories, [`chroot`](SearchDirectories::chroot)ed to the root directory.
+	///
+	/// # Panics
+	///
+	/// Panics if any of the paths do not start with `/` or contain `..`.
+	pub fn search_directories<I, P>(&self, search_directories: I) -> SearchDirectories<'static> where I: IntoIterator<Item = P>, P: AsRef<Path> {
+		search_directories.into_iter()
+		.map(|path| path.as_ref().to_owned().into())
+		.collect::<SearchDirectories<'_>>()
+		.chroot(&self.root)
+		.expect("search directory must be an absolute path without `..`")
+	}
+
+	/// Returns the files that `systemd-analyze cat-config` finds for `name` under the root directory, in the order that it prints them,
+	/// as paths relative to the root directory.
+	///
+	/// `name` is given to `systemd-analyze cat-config` as-is, so it is usually a path relative to `/etc` like `systemd/journald.conf`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `systemd-analyze` could not be run or exited unsuccessfully.
+	pub fn systemd_cat_config(&self, name: &str) -> io::Result<Vec<PathBuf>> {
+		let output =
+			std::process::Command::new("systemd-analyze")
+			.arg("--no-pager")
+			.arg("cat-config")
+			.arg(format!("--root={}", self.root.display()))
+			.arg(name)
+			.stdin(std::process::Stdio::null())
+			.output()?;
+		if !output.status.success() {
+			return Err(io::Error::other(format!(
+				"systemd-analyze cat-config exited with {}: {}",
+				output.status,
+				String::from_utf8_lossy(&output.stderr).trim(),
+			)));
+		}
+
+		// Each file is preceded by a `# /path/of/file` header line.
+		let header_prefix = format!("# {}/", self.root.display());
+		let stdout = String::from_utf8_lossy(&output.stdout);
+		let result =
+			stdout.lines()
+			.filter_map(|line| line.strip_prefix(&header_prefix))
+			.map(|path| Path::new("/").join(path))
+			.collect();
+		Ok(result)
+	}
+}
+
+fn create_parent(path: &Path) {
+	if let Some(parent) = path.parent() {
+		create_dir_all(parent);
+	}
+}
+
+fn create_dir_all(path: &Path) {
+	if let Err(err) = std::fs::create_dir_all(path) {
+		panic!("could not create {}: {err}", path.display());
+	}
+}
+
+impl Drop for Fixture {
+	fn drop(&mut self) {
+		_ = std::fs::remove_dir_all(&self.root);
+	}
+}
@@ -0,0 +1,196 @@
+use std::{
+	ffi::CString,
+	fs::{self, File},
+	io,
+	os::{
+		fd::{AsRawFd as _, FromRawFd as _, OwnedFd},
+		unix::ffi::OsStrExt as _,
+	},
+};
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::{platform, Entries, Entry, Files};
+
+/// The number of submission queue entries of the ring. Entries are opened in batches of this size.
+const RING_SIZE: u32 = 256;
+
+impl Entries {
+	/// Opens all the remaining entries like [`Entries::open_all`], except that the `openat` calls
+	/// for each batch of entries are submitted to an `io_uring` together.
+	///
+	/// If `io_uring` is not available, such as because the kernel is too old or because it is disabled by a seccomp filter,
+	/// this falls back to [`Entries::open_all`].
+	pub(crate) fn open_all_io_uring(self) -> io::Result<Files> {
+		let Ok(mut ring) = IoUring::new(RING_SIZE) else {
+			return self.open_all();
+		};
+
+		let has_main_file = self.main_file.is_some();
+		let entries: Vec<_> = self.main_file.into_iter().chain(self.dropins).collect();
+
+		let mut files = Vec::with_capacity(entries.len());
+		for batch in entries.chunks(RING_SIZE as usize) {
+			files.extend(open_batch(&mut ring, batch)?);
+		}
+
+		// The result of the main file is taken before the files that no longer exist are filtered out,
+		// so that a dropin does not become the main file if the main file was removed since it was found.
+		let mut files = entries.into_iter().zip(files).map(|(entry, file)| file.map(|(file, metadata)| (entry.path, file, metadata)));
+		let main_file = if has_main_file { files.next().flatten() } else { None };
+		let dropins: Vec<_> = files.flatten().collect();
+
+		Ok(Files {
+			main_file,
+			dropins: dropins.into_iter(),
+			observer: None,
+			includes: self.includes,
+		})
+	}
+}
+
+/// Opens the given entries, returning `None` for each entry that no longer exists or is no longer a regular file.
+///
+/// The files are returned with the metadata of the opened file.
+fn open_batch(ring: &mut IoUring, entries: &[Entry]) -> io::Result<Vec<Option<(File, fs::Metadata)>>> {
+	// Entries that need `openat2` or custom `OpenOptions` cannot be opened with `IORING_OP_OPENAT`, so they are opened normally.
+	let paths: Vec<Option<(i32, CString)>> =
+		entries.iter()
+		.map(|entry| {
+			if entry.dir.is_contained() || entry.open_options.is_some() {
+				return Ok(None);
+			}
+
+			let (dirfd, path) = match entry.dir.fd() {
+				Some(dirfd) => (dirfd.as_raw_fd(), entry.file_name.as_bytes()),
+				None => (libc::AT_FDCWD, entry.path.as_os_str().as_bytes()),
+			};
+			let path = CString::new(path).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+			Ok(Some((dirfd, path)))
+		})
+		.collect::<io::Result<_>>()?;
+
+	let sqes: Vec<_> =
+		paths.iter().enumerate()
+		.filter_map(|(i, path)| {
+			let (dirfd, path) = path.as_ref()?;
+			let sqe =
+				opcode::OpenAt::new(types::Fd(*dirfd), path.as_ptr())
+				.flags(libc::O_RDONLY | libc::O_CLOEXEC | platform::NONBLOCKING_OPEN_FLAGS)
+				.build()
+				.user_data(i as u64);
+			Some(sqe)
+		})
+		.collect();
+	let (opened, paths) = submit(ring, sqes, paths)?;
+
+	let mut fds: Vec<Option<OwnedFd>> = entries.iter().map(|_| None).collect();
+	let mut errors = vec![];
+	for (i, result) in opened {
+		match result {
+			// SAFETY: A non-negative result of IORING_OP_OPENAT is a new fd that is owned by us.
+			Ok(fd) => fds[i] = Some(unsafe { OwnedFd::from_raw_fd(fd) }),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => errors.push(err),
+		}
+	}
+	// The fds are closed when they are dropped, so it is fine to return early now that all submissions have completed.
+	if let Some(err) = errors.into_iter().next() {
+		return Err(err);
+	}
+
+	let mut result = Vec::with_capacity(entries.len());
+	for ((entry, path), fd) in entries.iter().zip(&paths).zip(fds) {
+		if path.is_none() {
+			result.push(entry.open_file_if_still_a_file()?);
+			continue;
+		}
+
+		let Some(fd) = fd else {
+			result.push(None);
+			continue;
+		};
+
+		let file = File::from(fd);
+		let metadata = file.metadata()?;
+		if !metadata.is_file() {
+			result.push(None);
+			continue;
+		}
+
+		if let Some((dev, ino)) = platform::file_id(&metadata) {
+			entry.verify_identity_of(dev, ino)?;
+		}
+		platform::set_blocking(&file)?;
+
+		result.push(Some((file, metadata)));
+	}
+
+	Ok(result)
+}
+
+/// The user data and the result of each completion queue entry.
+type Completions = Vec<(usize, io::Result<i32>)>;
+
+/// Submits the given submission queue entries and waits for all of them to complete.
+///
+/// `buffers` are the buffers that the entries reference. They are returned once the kernel no longer references them.
+/// If the ring fails after some of the entries were submitted, the completions of the submitted entries are still waited for
+/// before the error is returned. If that also fails, `buffers` are leaked, since the kernel may still write to them.
+///
+/// Returns the user data and the result of each completion queue entry.
+fn submit<I, B>(ring: &mut IoUring, sqes: I, buffers: B) -> io::Result<(Completions, B)> where I: IntoIterator<Item = io_uring::squeue::Entry> {
+	let mut num_pushed = 0;
+	for sqe in sqes {
+		// SAFETY: The buffers referenced by the entry are owned by `buffers`, which is only dropped by the caller
+		// after all the submissions have completed, or is leaked.
+		unsafe { ring.submission().push(&sqe) }.map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+		num_pushed += 1;
+	}
+
+	let mut result = Vec::with_capacity(num_pushed);
+
+	// The number of entries that the kernel has consumed from the submission queue.
+	let mut num_submitted = 0;
+
+	// The error that the ring failed with while entries were in flight, if any.
+	let mut error = None;
+
+	loop {
+		result.extend(ring.completion().map(|cqe| {
+			#[allow(clippy::cast_possible_truncation)] // The user data is always an index into the batch.
+			let i = cqe.user_data() as usize;
+			let result = if cqe.result() < 0 { Err(io::Error::from_raw_os_error(-cqe.result())) } else { Ok(cqe.result()) };
+			(i, result)
+		}));
+
+		let num_expected = if error.is_some() { num_submitted } else { num_pushed };
+		if result.len() >= num_expected {
+			break;
+		}
+
+		match ring.submit_and_wait(num_expected - result.len()) {
+			Ok(n) => num_submitted += n,
+
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+
+			// Nothing is in flight, so the kernel does not reference the buffers. The caller drops the ring,
+			// so the entries that are still in the submission queue are never submitted.
+			Err(err) if result.len() >= num_submitted => return Err(error.unwrap_or(err)),
+
+			// Keep waiting for the in-flight entries to complete before returning the error.
+			Err(err) if error.is_none() => error = Some(err),
+
+			// The ring is unusable, so the kernel may still write to the buffers of the in-flight entries.
+			Err(err) => {
+				std::mem::forget(buffers);
+				return Err(error.unwrap_or(err));
+			},
+		}
+	}
+
+	match error {
+		Some(err) => Err(err),
+		None => Ok((result, buffers)),
+	}
+}